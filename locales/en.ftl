@@ -0,0 +1,159 @@
+# Portal daemon: English strings.
+# A copy of this file (or just some of its keys) in <config dir>/locales/en.ftl
+# overrides the built-in text without rebuilding.
+
+wizard_title = 🔧 --- PORTAL SETUP WIZARD ---
+scan_msg = 🔍 Scanning networks...
+scan_fail = ❌ No networks found.
+enter_ip_manual = Enter Lighthouse IP Manually
+select_net = Select Network:
+selected_net_log = ✅ Selected Network:
+enter_ip_prompt = Enter Lighthouse IP
+sleep_mins_prompt = Minutes to sleep without light?
+grace_sec_prompt = Grace period (sec) before sleep?
+wakeup_sec_prompt = Wait (sec) after waking up?
+scan_int_prompt = Scan interval (sec)?
+ping_count_prompt = Pings per check?
+ping_timeout_prompt = Ping reply timeout (ms)?
+ping_size_prompt = Ping payload size (bytes)?
+probe_prompt = How to detect power loss?
+sensor_prompt = Sensor (gpio:17, gpiod:gpiochip0:17, serial:/dev/ttyUSB0)?
+lighthouse_key_prompt = Signing key (the same as on the lighthouse device, empty - unsigned)
+mdns_scan = 🔎 Looking for lighthouses (mDNS)...
+mdns_select = Lighthouse device
+mdns_none = None, probe
+probe_recommended = recommended
+preset_prompt = What kind of machine is this?
+preset_custom = Custom (plain defaults)
+preset_laptop = Laptop: AC adapter probe, short sleeps
+preset_homeserver = Home server / NAS: waits for backups and disk activity
+preset_sbc = Off-grid SBC: powers off, spares the SD card
+settings_saved = ✅ Settings saved to { $config }!
+save_failed = ❌ Could not save settings:
+summary_title = 📋 Summary
+summary_preset = Preset:
+summary_probe = Probe:
+summary_sleep = Sleep:
+summary_grace = Grace period:
+summary_interval = Check every:
+summary_next = 👉 Next:
+not_a_number = Please enter a whole number
+out_of_range = Allowed range:
+daemon_start = 👻 Portal Daemon: START
+handoff_resumed = ♻️  Resumed after in-place upgrade
+daemon_net = 📡 Network:
+daemon_interval = ⏱ Interval:
+daemon_link = 🔌 Link:
+iface_watch = 🔌 Watching link:
+profile_applied = 🗺  Profile:
+gateway_probe = 🔀 Lighthouse unreachable on the same network, trying the current gateway:
+gateway_adopted = ✅ Gateway answers, using it as the lighthouse:
+mdns_probe = 🔎 Lighthouse unreachable, mDNS finds it at another address:
+mdns_adopted = ✅ Lighthouse answers at its new address:
+router_rebooting = 🔁 Router is silent but was up on SNMP: probably rebooting, grace
+profile_unknown = ❓ Unknown network, sleep disabled:
+iface_missing = ⚠️  Interface not found (yet), watching anyway:
+probe_auto = 🔎 Probe chosen automatically:
+hotspot_warn = 📱 Lighthouse is behind a phone hotspot/tether, sleep is suspended (notify only):
+daemon_tz = 🕒 Timezone:
+user_mode = 👤 User mode: sleeping via logind, no RTC alarm - wake the machine with the lid, a key or Wake-on-LAN.
+dry_run_mode = 🧪 Dry run: every check runs as usual, but the machine never sleeps and hooks are not run.
+freebsd_mode = 😈 FreeBSD: sleeping via acpiconf, no RTC alarm - sleep_minutes, wake_times and resleep_minutes have no effect; wake the machine with Wake-on-LAN, a key or the BIOS on power restore.
+daemon_quiet = 🤫 Quiet hours:
+quiet_invalid = ⚠️  Ignoring invalid quiet_hours entry:
+sleep_skipped_quiet = 🤫 Sleep skipped due to schedule (quiet hours)
+outage_loaded = 📅 Outage schedule windows:
+outage_scheduled = 📅 Scheduled outage, waking at restoration:
+wake_target = ⏰ Waking at the configured time:
+outage_unscheduled = 📅 Not in the outage schedule, extra grace:
+hook_abort = 🪝 Pre-sleep hook failed, sleep aborted.
+sleep_postponed = ⏳ Sleep postponed:
+conn_lost = ⚠️  Connection lost. Waiting
+conn_restored = ✅ Connection restored.
+no_light_sleep = 🌑 No light. Sleeping
+waking_up = ☀️  Woke up. Waiting
+wake_light_back = ☀️  Woke up, the lighthouse already answers - back to monitoring.
+tui_next_check = Next check in
+tui_sleep_check = No light! Sleep check in
+tui_paused = Paused, next check in
+tui_unknown_net = Unknown network, next check in
+tui_hint = [p] pause/resume  [c] check now  [q] quit
+tui_bye = 👋 Stopped by user.
+early_wake = ⏰ Woke up early by
+booted_after_off = ⏻ Booted after the portal shutdown, powered off for
+give_up = 🏳️  Still no light after this many wakes in a row, powering off without a wake alarm:
+notify_title = ⚡ Power lost
+notify_sleep_at = The computer will sleep at
+notify_cancel = Cancel sleep
+remote_lost = ⚡ Power lost. Sleep unless it returns within
+remote_sleep_in = 💤 Going to sleep in
+remote_pause = ⏸ Pause
+sms_sleeping = no power, sleeping
+sms_battery = battery critical, hibernating at
+remote_woke = ☀️  Woke up
+email_outage = ⚡ Still no power after
+selftest_online = 🟢 portal daemon online on
+status_degraded = ⚠️  Notifier failing:
+rtc_drift_warn = ⏰ RTC clock is off (check the CMOS battery):
+remote_rtc_drift = ⏰ RTC clock drift, check the CMOS battery:
+probe_stale = ⏳ No successful probe for
+secret_failed = ❌ Secret not resolved, left empty:
+secret_inline = ⚠️  config.json is world-readable and holds secrets in plain text (store them with `portal_daemon secret NAME` or chmod 600 the config):
+read_only_no_run = ❌ read_only_root: no writable /run, status and pause will not work:
+already_running = ❌ Portal daemon is already running, not starting a second copy. PID:
+ctrl_title = 🎮 --- PORTAL CONTROL ---
+ctrl_action = Action?
+ctrl_pause = ⏸  PAUSE (Disable sleep for X mins)
+ctrl_resume = ▶️  RESUME (Enable sleep mode)
+ctrl_kill = 🛑  KILL Process
+ctrl_exit = ❌  Exit
+pause_prompt = Pause for how many MINUTES?
+ctrl_pause_until = ⏰ PAUSE UNTIL (Disable sleep until a time)
+pause_until_prompt = Pause until (23:30, tomorrow 07:00, 2026-03-29 07:00)
+pause_until_activated = ✅ Pause activated until
+bad_moment = ❌ Expected a future time: 23:30, tomorrow 07:00, 2026-03-29 07:00
+pause_activated = ✅ Pause activated for
+pause_removed = ✅ Pause removed.
+process_killed = 💀 Process stopped.
+fleet_sending = 🛰  Sending to all portal daemons...
+fleet_no_token = ❌ fleet_token is not set in the config.
+fleet_none = ❌ No daemons answered.
+fleet_summary = 📋 Succeeded:
+fleet_aligned = 🛰  Waking together with the fleet leader at
+no_rights = ❌ Needs root or the { $group } group
+not_running = 💤 Portal daemon is not running.
+why_phase = 🔎 State:
+why_decision = 🧭 Last decision:
+why_probe = 📡 Last probe:
+why_last_ok = ✅ Last success:
+why_counters = 📊 Counters:
+why_config = 🛑 Config problem:
+why_blocked = 🛡  Last blocked by:
+status_running = 🟢 Running
+status_sleep_at = 💤 Sleep at
+status_rtt = ⏱️  RTT
+status_ago = ago
+status_recent = 📝 Recent log:
+status_paused = ⏸️  Paused, left
+status_config = ⚙️  Config:
+status_no_config = ⚙️  Config: missing or invalid
+bad_span = ❌ Bad duration (use 30m, 24h, 7d):
+history_empty = 📭 No records for this period.
+history_outages = ⚡ Power outages:
+history_ongoing = now (ongoing)
+stats_outages = ⚡ Outages:
+stats_dark = 🌑 Total dark time:
+stats_avg = 📏 Average outage:
+stats_longest = ⏱  Longest outage:
+stats_sleeps = 💤 Sleep cycles:
+stats_header = period       outages       dark    longest  sleeps
+latency_header = time                           min ms  avg ms  max ms   loss
+latency_summary = 📈 Average
+latency_lost = lost
+dash_title = 📟 PORTAL DAEMON
+dash_hint = [p] pause 60 min  [r] resume  [s] sleep now  [c] check now  [q] quit
+dash_no_bus = ⚠️  D-Bus unavailable, view only:
+dash_events = 📜 Recent events:
+dash_sleep_confirm = 💤 Press [s] again to put the machine to sleep now
+dash_sent = ✅ Done:
+dash_failed = ❌ Failed:
@@ -0,0 +1,159 @@
+# Portal daemon: русские строки.
+# Копия файла (или только нужные ключи) в <каталог конфига>/locales/ru.ftl
+# заменяет встроенный текст без пересборки.
+
+wizard_title = 🔧 --- МАСТЕР НАСТРОЙКИ PORTAL ---
+scan_msg = 🔍 Сканирую сети...
+scan_fail = ❌ Сети не найдены.
+enter_ip_manual = Ввести IP Маяка вручную
+select_net = Выбери сеть:
+selected_net_log = ✅ Выбрана сеть:
+enter_ip_prompt = Введи IP Маяка
+sleep_mins_prompt = Сколько МИНУТ спать без света?
+grace_sec_prompt = Грейс-период (сек) перед сном?
+wakeup_sec_prompt = Ждать сек. после включения?
+scan_int_prompt = Интервал проверки (сек)?
+ping_count_prompt = Пингов за одну проверку?
+ping_timeout_prompt = Ждать ответа на пинг (мс)?
+ping_size_prompt = Размер данных пинга (байт)?
+probe_prompt = Как определять, что света нет?
+sensor_prompt = Датчик (gpio:17, gpiod:gpiochip0:17, serial:/dev/ttyUSB0)?
+lighthouse_key_prompt = Ключ подписи (тот же, что на устройстве-маяке; пусто - без подписи)
+mdns_scan = 🔎 Ищем маяки (mDNS)...
+mdns_select = Устройство-маяк
+mdns_none = Нет, пробовать
+probe_recommended = рекомендуется
+preset_prompt = Что это за машина?
+preset_custom = Своя настройка (обычные значения)
+preset_laptop = Ноутбук: проба по зарядке, короткий сон
+preset_homeserver = Домашний сервер / NAS: ждет бэкапы и работу дисков
+preset_sbc = Одноплатник на автономке: выключается, бережет SD-карту
+settings_saved = ✅ Настройки сохранены в { $config }!
+save_failed = ❌ Не удалось сохранить настройки:
+summary_title = 📋 Итог
+summary_preset = Пресет:
+summary_probe = Проба:
+summary_sleep = Сон:
+summary_grace = Ожидание связи:
+summary_interval = Проверка каждые:
+summary_next = 👉 Дальше:
+not_a_number = Введи целое число
+out_of_range = Допустимый диапазон:
+daemon_start = 👻 Portal Daemon: ЗАПУСК
+handoff_resumed = ♻️  Продолжаю после обновления на месте
+daemon_net = 📡 Сеть:
+daemon_interval = ⏱ Интервал:
+daemon_link = 🔌 Линк:
+iface_watch = 🔌 Слежу за линком:
+profile_applied = 🗺  Профиль:
+gateway_probe = 🔀 Маяк молчит, но сеть та же - пробую текущий шлюз:
+gateway_adopted = ✅ Шлюз отвечает, теперь маяк - он:
+mdns_probe = 🔎 Маяк не отвечает, mDNS нашел его по другому адресу:
+mdns_adopted = ✅ Маяк отвечает по новому адресу:
+router_rebooting = 🔁 Роутер молчит, хотя по SNMP был жив: похоже, перезагружается, grace
+profile_unknown = ❓ Чужая сеть, сон выключен:
+iface_missing = ⚠️  Интерфейса (пока) нет, слежу все равно:
+probe_auto = 🔎 Проба выбрана автоматически:
+hotspot_warn = 📱 Маяк за раздачей с телефона, сон отключен (только уведомления):
+daemon_tz = 🕒 Часовой пояс:
+user_mode = 👤 Режим пользователя: сон через logind, без будильника RTC - будите машину крышкой, кнопкой или Wake-on-LAN.
+dry_run_mode = 🧪 Пробный прогон: все проверки идут как обычно, но машина не засыпает и хуки не запускаются.
+freebsd_mode = 😈 FreeBSD: сон через acpiconf, без будильника RTC - sleep_minutes, wake_times и resleep_minutes не действуют; будите машину Wake-on-LAN, кнопкой или BIOS при возврате питания.
+daemon_quiet = 🤫 Тихие часы:
+quiet_invalid = ⚠️  Пропускаю неверную запись quiet_hours:
+sleep_skipped_quiet = 🤫 Сон пропущен по расписанию (тихие часы)
+outage_loaded = 📅 Окон в графике отключений:
+outage_scheduled = 📅 Отключение по графику, проснемся к включению:
+wake_target = ⏰ Проснемся к заданному времени:
+outage_unscheduled = 📅 Отключения нет в графике, доп. ожидание:
+hook_abort = 🪝 Pre-sleep хук упал, сон отменен.
+sleep_postponed = ⏳ Сон отложен:
+conn_lost = ⚠️  Потеря связи. Ждем
+conn_restored = ✅ Связь вернулась.
+no_light_sleep = 🌑 Света нет. Сон
+waking_up = ☀️  Проснулись. Ждем
+wake_light_back = ☀️  Проснулись, маяк уже отвечает - сразу к мониторингу.
+tui_next_check = Следующая проверка через
+tui_sleep_check = Света нет! Проверка перед сном через
+tui_paused = Пауза, проверка через
+tui_unknown_net = Чужая сеть, проверка через
+tui_hint = [p] пауза/снять  [c] проверить сейчас  [q] выход
+tui_bye = 👋 Остановлено пользователем.
+early_wake = ⏰ Проснулись раньше на
+booted_after_off = ⏻ Загрузка после выключения демоном, машина была выключена
+give_up = 🏳️  Света нет уже столько подъемов подряд, выключаемся без будильника:
+notify_title = ⚡ Пропал свет
+notify_sleep_at = Компьютер уснет в
+notify_cancel = Отменить сон
+remote_lost = ⚡ Пропал свет. Сон, если не вернется за
+remote_sleep_in = 💤 Сон через
+remote_pause = ⏸ Пауза
+sms_sleeping = нет света, сплю
+sms_battery = батарея на исходе, гибернация при
+remote_woke = ☀️  Проснулись
+email_outage = ⚡ Света все еще нет:
+selftest_online = 🟢 portal daemon в сети на
+status_degraded = ⚠️  Канал уведомлений не работает:
+rtc_drift_warn = ⏰ Часы RTC врут (проверь батарейку CMOS):
+remote_rtc_drift = ⏰ Часы RTC разошлись, проверь батарейку CMOS:
+probe_stale = ⏳ Нет удачной пробы уже
+secret_failed = ❌ Секрет не получен, поле пустое:
+secret_inline = ⚠️  config.json читают все, а секреты в нем открытым текстом (сохраните их через `portal_daemon secret ИМЯ` или сделайте конфигу chmod 600):
+read_only_no_run = ❌ read_only_root: /run недоступен для записи, статус и пауза не будут работать:
+already_running = ❌ Portal daemon уже запущен, вторую копию не стартую. PID:
+ctrl_title = 🎮 --- УПРАВЛЕНИЕ PORTAL ---
+ctrl_action = Действие?
+ctrl_pause = ⏸  Поставить на ПАУЗУ
+ctrl_resume = ▶️  Снять с паузы
+ctrl_kill = 🛑  Убить процесс (Kill)
+ctrl_exit = ❌  Выход
+pause_prompt = На сколько МИНУТ?
+ctrl_pause_until = ⏰ ПАУЗА ДО (Отключить сон до времени)
+pause_until_prompt = Пауза до (23:30, tomorrow 07:00, 2026-03-29 07:00)
+pause_until_activated = ✅ Пауза включена до
+bad_moment = ❌ Нужно время в будущем: 23:30, tomorrow 07:00, 2026-03-29 07:00
+pause_activated = ✅ Пауза активирована на
+pause_removed = ✅ Пауза снята.
+process_killed = 💀 Процесс остановлен.
+fleet_sending = 🛰  Рассылаю всем демонам portal...
+fleet_no_token = ❌ В конфиге не задан fleet_token.
+fleet_none = ❌ Ни один демон не ответил.
+fleet_summary = 📋 Успешно:
+fleet_aligned = 🛰  Проснемся вместе с лидером флота в
+no_rights = ❌ Нужен root или группа { $group }
+not_running = 💤 Portal daemon не запущен.
+why_phase = 🔎 Состояние:
+why_decision = 🧭 Последнее решение:
+why_probe = 📡 Последняя проверка:
+why_last_ok = ✅ Последний успех:
+why_counters = 📊 Счетчики:
+why_config = 🛑 Проблема с конфигом:
+why_blocked = 🛡  Последний запрет сна:
+status_running = 🟢 Работает
+status_sleep_at = 💤 Сон в
+status_rtt = ⏱️  RTT
+status_ago = назад
+status_recent = 📝 Последние записи журнала:
+status_paused = ⏸️  Пауза, осталось
+status_config = ⚙️  Конфиг:
+status_no_config = ⚙️  Конфиг: нет или битый
+bad_span = ❌ Неверный период (например 30m, 24h, 7d):
+history_empty = 📭 За этот период записей нет.
+history_outages = ⚡ Отключения света:
+history_ongoing = сейчас (продолжается)
+stats_outages = ⚡ Отключений:
+stats_dark = 🌑 Всего без света:
+stats_avg = 📏 В среднем:
+stats_longest = ⏱  Самое долгое:
+stats_sleeps = 💤 Циклов сна:
+stats_header = период      отключ.  без света  макс.     сон
+latency_header = время                          мин мс  сред мс макс мс потери
+latency_summary = 📈 В среднем
+latency_lost = потеряно
+dash_title = 📟 PORTAL DAEMON
+dash_hint = [p] пауза 60 мин  [r] снять  [s] уснуть сейчас  [c] проверить  [q] выход
+dash_no_bus = ⚠️  D-Bus недоступен, только просмотр:
+dash_events = 📜 Последние события:
+dash_sleep_confirm = 💤 Нажми [s] еще раз, чтобы усыпить машину сейчас
+dash_sent = ✅ Готово:
+dash_failed = ❌ Не вышло:
@@ -0,0 +1,159 @@
+# Portal daemon: українські рядки.
+# Копія файлу (або лише потрібні ключі) у <каталог конфігу>/locales/uk.ftl
+# замінює вбудований текст без перезбирання.
+
+wizard_title = 🔧 --- МАЙСТЕР НАЛАШТУВАННЯ PORTAL ---
+scan_msg = 🔍 Сканую мережі...
+scan_fail = ❌ Мереж не знайдено.
+enter_ip_manual = Ввести IP Маяка вручну
+select_net = Обери мережу:
+selected_net_log = ✅ Обрано мережу:
+enter_ip_prompt = Введи IP Маяка
+sleep_mins_prompt = Скільки ХВИЛИН спати без світла?
+grace_sec_prompt = Грейс-період (сек) перед сном?
+wakeup_sec_prompt = Чекати сек. після увімкнення?
+scan_int_prompt = Інтервал перевірки (сек)?
+ping_count_prompt = Пінгів за одну перевірку?
+ping_timeout_prompt = Чекати відповіді на пінг (мс)?
+ping_size_prompt = Розмір даних пінгу (байт)?
+probe_prompt = Як визначати, що світла немає?
+sensor_prompt = Датчик (gpio:17, gpiod:gpiochip0:17, serial:/dev/ttyUSB0)?
+lighthouse_key_prompt = Ключ підпису (той самий, що на пристрої-маяку; порожньо - без підпису)
+mdns_scan = 🔎 Шукаємо маяки (mDNS)...
+mdns_select = Пристрій-маяк
+mdns_none = Ні, пробувати
+probe_recommended = рекомендовано
+preset_prompt = Що це за машина?
+preset_custom = Власне налаштування (звичайні значення)
+preset_laptop = Ноутбук: проба за зарядкою, короткий сон
+preset_homeserver = Домашній сервер / NAS: чекає бекапи та роботу дисків
+preset_sbc = Одноплатник на автономці: вимикається, береже SD-картку
+settings_saved = ✅ Налаштування збережено в { $config }!
+save_failed = ❌ Не вдалося зберегти налаштування:
+summary_title = 📋 Підсумок
+summary_preset = Пресет:
+summary_probe = Проба:
+summary_sleep = Сон:
+summary_grace = Очікування зв'язку:
+summary_interval = Перевірка кожні:
+summary_next = 👉 Далі:
+not_a_number = Введи ціле число
+out_of_range = Допустимий діапазон:
+daemon_start = 👻 Portal Daemon: ЗАПУСК
+handoff_resumed = ♻️  Продовжую після оновлення на місці
+daemon_net = 📡 Мережа:
+daemon_interval = ⏱ Інтервал:
+daemon_link = 🔌 Лінк:
+iface_watch = 🔌 Стежу за лінком:
+profile_applied = 🗺  Профіль:
+gateway_probe = 🔀 Маяк мовчить, але мережа та сама - пробую поточний шлюз:
+gateway_adopted = ✅ Шлюз відповідає, тепер маяк - він:
+mdns_probe = 🔎 Маяк не відповідає, mDNS знайшов його за іншою адресою:
+mdns_adopted = ✅ Маяк відповідає за новою адресою:
+router_rebooting = 🔁 Роутер мовчить, хоча за SNMP був живий: схоже, перезавантажується, grace
+profile_unknown = ❓ Чужа мережа, сон вимкнено:
+iface_missing = ⚠️  Інтерфейсу (поки) немає, стежу все одно:
+probe_auto = 🔎 Пробу обрано автоматично:
+hotspot_warn = 📱 Маяк за роздачею з телефона, сон вимкнено (лише сповіщення):
+daemon_tz = 🕒 Часовий пояс:
+user_mode = 👤 Режим користувача: сон через logind, без будильника RTC - будіть машину кришкою, кнопкою або Wake-on-LAN.
+dry_run_mode = 🧪 Пробний прогін: усі перевірки йдуть як зазвичай, але машина не засинає і хуки не запускаються.
+freebsd_mode = 😈 FreeBSD: сон через acpiconf, без будильника RTC - sleep_minutes, wake_times і resleep_minutes не діють; будіть машину Wake-on-LAN, кнопкою або BIOS при поверненні живлення.
+daemon_quiet = 🤫 Тихі години:
+quiet_invalid = ⚠️  Пропускаю неправильний запис quiet_hours:
+sleep_skipped_quiet = 🤫 Сон пропущено за розкладом (тихі години)
+outage_loaded = 📅 Вікон у графіку відключень:
+outage_scheduled = 📅 Відключення за графіком, прокинемося до увімкнення:
+wake_target = ⏰ Прокинемося до заданого часу:
+outage_unscheduled = 📅 Відключення немає в графіку, дод. очікування:
+hook_abort = 🪝 Pre-sleep хук упав, сон скасовано.
+sleep_postponed = ⏳ Сон відкладено:
+conn_lost = ⚠️  Втрата зв'язку. Чекаємо
+conn_restored = ✅ Зв'язок повернувся.
+no_light_sleep = 🌑 Світла немає. Сон
+waking_up = ☀️  Прокинулися. Чекаємо
+wake_light_back = ☀️  Прокинулися, маяк уже відповідає - одразу до моніторингу.
+tui_next_check = Наступна перевірка через
+tui_sleep_check = Світла немає! Перевірка перед сном через
+tui_paused = Пауза, перевірка через
+tui_unknown_net = Чужа мережа, перевірка через
+tui_hint = [p] пауза/зняти  [c] перевірити зараз  [q] вихід
+tui_bye = 👋 Зупинено користувачем.
+early_wake = ⏰ Прокинулися раніше на
+booted_after_off = ⏻ Завантаження після вимкнення демоном, машина була вимкнена
+give_up = 🏳️  Світла немає вже стільки підйомів поспіль, вимикаємося без будильника:
+notify_title = ⚡ Зникло світло
+notify_sleep_at = Комп'ютер засне о
+notify_cancel = Скасувати сон
+remote_lost = ⚡ Зникло світло. Сон, якщо не повернеться за
+remote_sleep_in = 💤 Сон через
+remote_pause = ⏸ Пауза
+sms_sleeping = немає світла, сплю
+sms_battery = батарея сідає, гібернація при
+remote_woke = ☀️  Прокинулися
+email_outage = ⚡ Світла досі немає:
+selftest_online = 🟢 portal daemon у мережі на
+status_degraded = ⚠️  Канал сповіщень не працює:
+rtc_drift_warn = ⏰ Годинник RTC бреше (перевір батарейку CMOS):
+remote_rtc_drift = ⏰ Годинник RTC розійшовся, перевір батарейку CMOS:
+probe_stale = ⏳ Немає вдалої проби вже
+secret_failed = ❌ Секрет не отримано, поле порожнє:
+secret_inline = ⚠️  config.json читають усі, а секрети в ньому відкритим текстом (збережіть їх через `portal_daemon secret ІМʼЯ` або зробіть конфігу chmod 600):
+read_only_no_run = ❌ read_only_root: /run недоступний для запису, статус і пауза не працюватимуть:
+already_running = ❌ Portal daemon вже запущено, другу копію не стартую. PID:
+ctrl_title = 🎮 --- КЕРУВАННЯ PORTAL ---
+ctrl_action = Дія?
+ctrl_pause = ⏸  Поставити на ПАУЗУ
+ctrl_resume = ▶️  Зняти з паузи
+ctrl_kill = 🛑  Вбити процес (Kill)
+ctrl_exit = ❌  Вихід
+pause_prompt = На скільки ХВИЛИН?
+ctrl_pause_until = ⏰ ПАУЗА ДО (Вимкнути сон до часу)
+pause_until_prompt = Пауза до (23:30, tomorrow 07:00, 2026-03-29 07:00)
+pause_until_activated = ✅ Паузу увімкнено до
+bad_moment = ❌ Потрібен час у майбутньому: 23:30, tomorrow 07:00, 2026-03-29 07:00
+pause_activated = ✅ Паузу активовано на
+pause_removed = ✅ Паузу знято.
+process_killed = 💀 Процес зупинено.
+fleet_sending = 🛰  Розсилаю всім демонам portal...
+fleet_no_token = ❌ У конфігу не задано fleet_token.
+fleet_none = ❌ Жоден демон не відповів.
+fleet_summary = 📋 Успішно:
+fleet_aligned = 🛰  Прокинемося разом з лідером флоту о
+no_rights = ❌ Потрібен root або група { $group }
+not_running = 💤 Portal daemon не запущено.
+why_phase = 🔎 Стан:
+why_decision = 🧭 Останнє рішення:
+why_probe = 📡 Остання перевірка:
+why_last_ok = ✅ Останній успіх:
+why_counters = 📊 Лічильники:
+why_config = 🛑 Проблема з конфігом:
+why_blocked = 🛡  Остання заборона сну:
+status_running = 🟢 Працює
+status_sleep_at = 💤 Сон о
+status_rtt = ⏱️  RTT
+status_ago = тому
+status_recent = 📝 Останні записи журналу:
+status_paused = ⏸️  Пауза, залишилось
+status_config = ⚙️  Конфіг:
+status_no_config = ⚙️  Конфіг: немає або битий
+bad_span = ❌ Неправильний період (наприклад 30m, 24h, 7d):
+history_empty = 📭 За цей період записів немає.
+history_outages = ⚡ Відключення світла:
+history_ongoing = зараз (триває)
+stats_outages = ⚡ Відключень:
+stats_dark = 🌑 Усього без світла:
+stats_avg = 📏 У середньому:
+stats_longest = ⏱  Найдовше:
+stats_sleeps = 💤 Циклів сну:
+stats_header = період      відключ.  без світла макс.     сон
+latency_header = час                            мін мс  сер мс  макс мс втрати
+latency_summary = 📈 У середньому
+latency_lost = втрачено
+dash_title = 📟 PORTAL DAEMON
+dash_hint = [p] пауза 60 хв  [r] зняти  [s] заснути зараз  [c] перевірити  [q] вихід
+dash_no_bus = ⚠️  D-Bus недоступний, лише перегляд:
+dash_events = 📜 Останні події:
+dash_sleep_confirm = 💤 Натисни [s] ще раз, щоб приспати машину зараз
+dash_sent = ✅ Готово:
+dash_failed = ❌ Не вдалося:
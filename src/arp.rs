@@ -0,0 +1,117 @@
+// === ARP-ПРОБА ===
+// Маяк в той же проводной сети: ARP-запрос не зависит от файрвола маяка
+// (ICMP часто режут, на ARP обязан ответить любой живой IPv4-узел) и не
+// проходит через маршрутизацию. Пакетный сокет (AF_PACKET, SOCK_DGRAM -
+// Ethernet-заголовок добавляет ядро) требует root или CAP_NET_RAW;
+// не открылся - Err, и проба откатывается на ping.
+
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+use crate::{net, reactor};
+
+const ETH_P_ARP: u16 = 0x0806;
+const ARP_REQUEST: u16 = 1;
+const ARP_REPLY: u16 = 2;
+
+// RTT в миллисекундах; Ok(None) - ответа не было за timeout
+pub fn request(ip: Ipv4Addr, dev: &str, timeout: Duration) -> io::Result<Option<f64>> {
+    let name = CString::new(dev).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if ifindex == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mac = mac_of(dev).ok_or_else(|| io::Error::other(format!("{}: no MAC", dev)))?;
+    let Some(IpAddr::V4(src)) = net::route_src(IpAddr::V4(ip)) else {
+        return Ok(None);
+    };
+
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_PACKET,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            ETH_P_ARP.to_be() as i32,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let sock = unsafe { OwnedFd::from_raw_fd(fd) };
+    let fd = sock.as_raw_fd();
+    let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+    addr.sll_family = libc::AF_PACKET as u16;
+    addr.sll_protocol = ETH_P_ARP.to_be();
+    addr.sll_ifindex = ifindex as i32;
+    let addr_len = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+    let r = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+            addr_len,
+        )
+    };
+    if r != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Ethernet/IPv4: htype 1, ptype 0x0800, hlen 6, plen 4
+    let mut pkt = [0u8; 28];
+    pkt[..8].copy_from_slice(&[0, 1, 8, 0, 6, 4, 0, ARP_REQUEST as u8]);
+    pkt[8..14].copy_from_slice(&mac);
+    pkt[14..18].copy_from_slice(&src.octets());
+    pkt[24..28].copy_from_slice(&ip.octets());
+    addr.sll_halen = 6;
+    addr.sll_addr[..6].copy_from_slice(&[0xff; 6]);
+    let sent = Instant::now();
+    let n = unsafe {
+        libc::sendto(
+            fd,
+            pkt.as_ptr() as *const libc::c_void,
+            pkt.len(),
+            0,
+            &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+            addr_len,
+        )
+    };
+    // Интерфейс лежит - это "ответа нет", а не поломка пробы
+    if n < 0 {
+        return Ok(None);
+    }
+
+    let deadline = sent + timeout;
+    let mut buf = [0u8; 128];
+    while reactor::wait_readable(fd, deadline) {
+        let n = unsafe {
+            libc::recv(
+                fd,
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                libc::MSG_DONTWAIT,
+            )
+        };
+        if n < 28 {
+            continue;
+        }
+        // Ответ от маяка и нам: op 2, sender IP = маяк, target IP = мы
+        let op = u16::from_be_bytes([buf[6], buf[7]]);
+        if op == ARP_REPLY && buf[14..18] == ip.octets() && buf[24..28] == src.octets() {
+            return Ok(Some(sent.elapsed().as_secs_f64() * 1000.0));
+        }
+    }
+    Ok(None)
+}
+
+fn mac_of(dev: &str) -> Option<[u8; 6]> {
+    let text = fs::read_to_string(format!("/sys/class/net/{}/address", dev)).ok()?;
+    let mut mac = [0u8; 6];
+    let mut parts = text.trim().split(':');
+    for b in mac.iter_mut() {
+        *b = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    Some(mac)
+}
@@ -0,0 +1,347 @@
+// === МАЯЧОК ПО UDP (probe = "beacon") ===
+// Устройство на сети (ESP8266 в розетке, Pi без ИБП) раз в несколько секунд
+// шлет широковещательную датаграмму; пока они приходят не реже timeout_sec,
+// свет есть. Без ICMP, через VLAN - с ретранслятором широковещания.
+//   "PORTALB1 <host> <unix-время> <счетчик> <hmac>"
+// hmac - HMAC-SHA256(key, все до него через пробел) в hex. Устаревшие
+// (max_skew_sec) и повторы (время и счетчик не больше прошлых) не считаются.
+// Отправитель для Pi - `portal_daemon beacon`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{handoff, hostname, log, reactor, unix_now};
+
+const MAGIC: &str = "PORTALB1";
+// Пока слушатель моложе timeout_sec, отсутствие маячков еще ничего не значит
+static LISTENING_SINCE: AtomicU64 = AtomicU64::new(0);
+static LAST_BEACON: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BeaconConfig {
+    pub port: u16,
+    // Общий ключ подписи (лучше ссылкой: "file:...", см. secrets.rs)
+    pub key: String,
+    pub timeout_sec: u64,
+    // Только от этого хоста; пусто - от любого с верной подписью
+    pub sender: String,
+    pub max_skew_sec: u64,
+    // Отправитель: период и куда слать
+    pub interval_sec: u64,
+    pub target: String,
+}
+
+impl Default for BeaconConfig {
+    fn default() -> Self {
+        Self {
+            port: 47475,
+            key: String::new(),
+            timeout_sec: 15,
+            sender: String::new(),
+            max_skew_sec: 60,
+            interval_sec: 5,
+            target: "255.255.255.255".into(),
+        }
+    }
+}
+
+impl BeaconConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("port must be set".into());
+        }
+        if self.key.is_empty() {
+            return Err("key must be set".into());
+        }
+        if self.timeout_sec == 0 || self.interval_sec == 0 {
+            return Err("timeout_sec and interval_sec must be above 0".into());
+        }
+        if self.interval_sec >= self.timeout_sec {
+            return Err(format!(
+                "interval_sec = {} must be below timeout_sec = {}",
+                self.interval_sec, self.timeout_sec
+            ));
+        }
+        Ok(())
+    }
+}
+
+// --- ПОДПИСЬ ---
+pub fn sign(key: &str, host: &str, ts: u64, seq: u64) -> String {
+    let body = format!("{} {} {} {}", MAGIC, host, ts, seq);
+    format!("{} {}", body, mac_hex(key, &body))
+}
+
+// HMAC-SHA256 в hex; им же подписаны пробы lighthouse.rs
+pub fn mac_hex(key: &str, body: &str) -> String {
+    let mac = hmac_sha256(key.as_bytes(), body.as_bytes());
+    mac.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Сравнение без раннего выхода: время ответа не подсказывает подпись
+pub fn verify(key: &str, body: &str, mac: &str) -> bool {
+    let expected = mac_hex(key, body);
+    let diff = expected
+        .bytes()
+        .zip(mac.bytes())
+        .fold(expected.len() ^ mac.len(), |d, (a, b)| d | (a ^ b) as usize);
+    diff == 0
+}
+
+// Последний принятый маячок каждого отправителя: (время, счетчик)
+#[derive(Default)]
+struct Seen(HashMap<String, (u64, u64)>);
+
+impl Seen {
+    // Ok(host) - маячок настоящий и свежий
+    fn accept(&mut self, cfg: &BeaconConfig, msg: &str, now: u64) -> Result<String, &'static str> {
+        let (body, mac) = msg.trim().rsplit_once(' ').ok_or("malformed")?;
+        let p: Vec<&str> = body.split(' ').collect();
+        let [MAGIC, host, ts, seq] = p.as_slice() else {
+            return Err("malformed");
+        };
+        let ts: u64 = ts.parse().map_err(|_| "malformed")?;
+        let seq: u64 = seq.parse().map_err(|_| "malformed")?;
+        if !verify(&cfg.key, body, mac) {
+            return Err("bad signature");
+        }
+        if !cfg.sender.is_empty() && *host != cfg.sender {
+            return Err("unexpected sender");
+        }
+        if ts.abs_diff(now) > cfg.max_skew_sec {
+            return Err("stale");
+        }
+        let last = self.0.entry(host.to_string()).or_default();
+        if (ts, seq) <= *last {
+            return Err("replayed");
+        }
+        *last = (ts, seq);
+        Ok(host.to_string())
+    }
+}
+
+// --- СЛУШАТЕЛЬ В ДЕМОНЕ ---
+pub fn listen(cfg: &BeaconConfig) {
+    let inherited = handoff::inherit("beacon")
+        .map(UdpSocket::from)
+        .filter(|s| s.local_addr().is_ok_and(|a| a.port() == cfg.port));
+    let sock = match inherited.map_or_else(|| UdpSocket::bind(("0.0.0.0", cfg.port)), Ok) {
+        Ok(s) => s,
+        Err(e) => {
+            log::warn!("⚠️  Beacon listener on UDP {} failed: {}", cfg.port, e);
+            return;
+        }
+    };
+    sock.set_nonblocking(true).ok();
+    handoff::register("beacon", sock.as_raw_fd());
+    LISTENING_SINCE.store(unix_now(), Ordering::Relaxed);
+    log::info!("📡 Listening for beacons on UDP {}", cfg.port);
+    reactor::add(Listener {
+        sock,
+        cfg: cfg.clone(),
+        seen: Seen::default(),
+    });
+}
+
+struct Listener {
+    sock: UdpSocket,
+    cfg: BeaconConfig,
+    seen: Seen,
+}
+
+impl reactor::Source for Listener {
+    fn fd(&self) -> Option<RawFd> {
+        Some(self.sock.as_raw_fd())
+    }
+
+    fn ready(&mut self, _: bool, _: &mut reactor::Spawned) -> bool {
+        let mut buf = [0u8; 512];
+        let Ok((n, from)) = self.sock.recv_from(&mut buf) else {
+            return true;
+        };
+        let msg = String::from_utf8_lossy(&buf[..n]);
+        if !msg.starts_with(MAGIC) {
+            return true;
+        }
+        match self.seen.accept(&self.cfg, &msg, unix_now()) {
+            Ok(host) => {
+                log::debug!(host = host, from = from.ip(); "beacon");
+                LAST_BEACON.store(unix_now(), Ordering::Relaxed);
+            }
+            Err(why) => log::debug!(from = from.ip(); "beacon rejected: {}", why),
+        }
+        true
+    }
+}
+
+// Проба: свет есть, пока маячок был не позже timeout_sec назад. Слушатель
+// только запущен - ждем первый маячок до timeout_sec, а не тревожимся сразу.
+// Проба идет в потоке цикла: ждем в нем же, иначе маячок некому принять
+pub fn light(cfg: &BeaconConfig) -> Option<bool> {
+    let since = LISTENING_SINCE.load(Ordering::Relaxed);
+    if since == 0 {
+        return None;
+    }
+    let fresh =
+        || unix_now().saturating_sub(LAST_BEACON.load(Ordering::Relaxed)) <= cfg.timeout_sec;
+    let warmup_end = since + cfg.timeout_sec;
+    while !fresh() && unix_now() < warmup_end {
+        reactor::run_until(Instant::now() + Duration::from_millis(200));
+    }
+    Some(fresh())
+}
+
+// --- ОТПРАВИТЕЛЬ (`portal_daemon beacon`) ---
+pub fn send_forever(cfg: &BeaconConfig) -> Result<(), String> {
+    let sock = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+    sock.set_broadcast(true).map_err(|e| e.to_string())?;
+    let host = hostname();
+    let to = (cfg.target.as_str(), cfg.port);
+    println!(
+        "📡 Sending beacons as {} to {}:{} every {} sec",
+        host, cfg.target, cfg.port, cfg.interval_sec
+    );
+    let started = Instant::now();
+    for seq in 0u64.. {
+        let msg = sign(&cfg.key, &host, unix_now(), seq);
+        if let Err(e) = sock.send_to(msg.as_bytes(), to) {
+            eprintln!("⚠️  {}: {}", cfg.target, e);
+        }
+        // По сетке от старта: отправка не копит сдвиг
+        let next = Duration::from_secs(cfg.interval_sec * (seq + 1));
+        thread::sleep(next.saturating_sub(started.elapsed()));
+    }
+    Ok(())
+}
+
+// --- SHA-256 / HMAC (FIPS 180-4, RFC 2104): крипто-крейтов в зависимостях нет ---
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (x, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *x = x.wrapping_add(v);
+        }
+    }
+    let mut out = [0u8; 32];
+    for (i, v) in h.iter().enumerate() {
+        out[i * 4..i * 4 + 4].copy_from_slice(&v.to_be_bytes());
+    }
+    out
+}
+
+fn hmac_sha256(key: &[u8], msg: &[u8]) -> [u8; 32] {
+    let mut k = [0u8; 64];
+    if key.len() > 64 {
+        k[..32].copy_from_slice(&sha256(key));
+    } else {
+        k[..key.len()].copy_from_slice(key);
+    }
+    let pad = |x: u8| k.iter().map(|b| b ^ x).collect::<Vec<u8>>();
+    let mut inner = pad(0x36);
+    inner.extend_from_slice(msg);
+    let mut outer = pad(0x5c);
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(b: &[u8]) -> String {
+        b.iter().map(|x| format!("{:02x}", x)).collect()
+    }
+
+    #[test]
+    fn hmac_matches_rfc4231() {
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // RFC 4231, тест 2
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn accepts_only_fresh_signed_beacons() {
+        let cfg = BeaconConfig {
+            key: "s3cret".into(),
+            ..Default::default()
+        };
+        let mut seen = Seen::default();
+        let now = 1_791_936_000;
+        let b = sign("s3cret", "plug", now, 1);
+        assert_eq!(seen.accept(&cfg, &b, now + 2), Ok("plug".to_string()));
+        assert_eq!(seen.accept(&cfg, &b, now + 2), Err("replayed"));
+        let forged = sign("guess", "plug", now, 2);
+        assert_eq!(seen.accept(&cfg, &forged, now), Err("bad signature"));
+        let old = sign("s3cret", "plug", now - 600, 3);
+        assert_eq!(seen.accept(&cfg, &old, now), Err("stale"));
+        assert_eq!(
+            seen.accept(&cfg, &sign("s3cret", "plug", now, 2), now),
+            Ok("plug".to_string())
+        );
+    }
+}
@@ -0,0 +1,65 @@
+// === КАНАЛЫ УВЕДОМЛЕНИЙ: САМОПРОВЕРКА И ЗДОРОВЬЕ ===
+// Протухший токен Telegram или сменившийся пароль почты обычно всплывает
+// в первое же отключение - когда уже поздно. Поэтому при старте демона
+// (self_test_on_start) каждый настроенный канал получает тихое "online",
+// а результат - и всех последующих отправок - виден в `status` и /status.
+// SMS не проверяем: платно и громко.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::thread;
+
+use crate::{PortalConfig, email, hostname, log, notify, unix_now, webhook};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Channel {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checked_at: u64,
+}
+
+static CHANNELS: Mutex<BTreeMap<String, Channel>> = Mutex::new(BTreeMap::new());
+
+// Итог отправки по каналу ("ntfy", "telegram", "email", "webhook:<host>", "mqtt")
+pub fn record(name: &str, res: &Result<(), String>) {
+    if let Ok(mut m) = CHANNELS.lock() {
+        m.insert(
+            name.to_string(),
+            Channel {
+                ok: res.is_ok(),
+                error: res.as_ref().err().cloned(),
+                checked_at: unix_now(),
+            },
+        );
+    }
+}
+
+pub fn snapshot() -> BTreeMap<String, Channel> {
+    CHANNELS.lock().map(|m| m.clone()).unwrap_or_default()
+}
+
+// В фоне: старт демона не ждет чужих таймаутов
+pub fn self_test(cfg: &PortalConfig, online: &str) {
+    let message = format!("{} {}", online, hostname());
+    let notifications = cfg.notifications.clone();
+    let webhooks = cfg.webhooks.clone();
+    let mail = cfg.email.clone();
+    thread::spawn(move || {
+        notify::self_test(&notifications, &message);
+        webhook::self_test(&webhooks, &message);
+        if mail.enabled() {
+            email::self_test(&mail, &message);
+        }
+        let failed: Vec<String> = snapshot()
+            .into_iter()
+            .filter(|(_, c)| !c.ok)
+            .map(|(n, _)| n)
+            .collect();
+        if !failed.is_empty() {
+            log::warn!(channels = failed.join(","); "⚠️  Notification self-test failed");
+        }
+    });
+}
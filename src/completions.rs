@@ -0,0 +1,418 @@
+// === ДОПОЛНЕНИЕ И MAN ===
+// `completions <shell>` и `man` печатают скрипт дополнения и страницу
+// man(8), собранные из того же описания clap, что и --help: новый ключ или
+// подкоманда попадают туда сами. clap_complete и clap_mangen в
+// зависимостях нет - генераторы свои, под bash, zsh, fish и roff.
+// --install кладет все это в /usr/local/share (с --user - в ~/.local/share).
+
+use clap::Command;
+use std::fmt::Write;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+pub const ALL: [Shell; 3] = [Shell::Bash, Shell::Zsh, Shell::Fish];
+
+impl Shell {
+    pub fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+
+    // Куда кладут дополнения пакеты: share - /usr/local/share или ~/.local/share
+    pub fn install_path(self, share: &str) -> String {
+        match self {
+            Shell::Bash => format!("{}/bash-completion/completions/portal_daemon", share),
+            Shell::Zsh => format!("{}/zsh/site-functions/_portal_daemon", share),
+            Shell::Fish => format!("{}/fish/vendor_completions.d/portal_daemon.fish", share),
+        }
+    }
+}
+
+pub fn man_path(share: &str) -> String {
+    format!("{}/man/man8/portal_daemon.8", share)
+}
+
+pub fn generate(shell: Shell, mut cmd: Command) -> String {
+    // build() разносит global-ключи (--user, --log-level) по подкомандам
+    cmd.build();
+    let mut nodes = Vec::new();
+    walk(&cmd, &mut Vec::new(), &mut nodes);
+    match shell {
+        Shell::Bash => bash(&nodes),
+        Shell::Zsh => zsh(&nodes),
+        Shell::Fish => fish(&nodes),
+    }
+}
+
+// Команда и путь к ней: [] - сам portal_daemon, ["history", "events"]
+struct Node<'a> {
+    path: Vec<&'a str>,
+    cmd: &'a Command,
+}
+
+fn walk<'a>(cmd: &'a Command, path: &mut Vec<&'a str>, out: &mut Vec<Node<'a>>) {
+    out.push(Node {
+        path: path.clone(),
+        cmd,
+    });
+    for sub in subcommands(cmd) {
+        path.push(sub.get_name());
+        walk(sub, path, out);
+        path.pop();
+    }
+}
+
+fn subcommands(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands()
+        .filter(|c| !c.is_hide_set() && c.get_name() != "help")
+}
+
+fn args(cmd: &Command) -> impl Iterator<Item = &clap::Arg> {
+    cmd.get_arguments().filter(|a| !a.is_hide_set())
+}
+
+fn help(text: Option<&clap::builder::StyledStr>) -> String {
+    text.map(|h| h.to_string()).unwrap_or_default()
+}
+
+fn takes_value(a: &clap::Arg) -> bool {
+    a.get_action().takes_values()
+}
+
+fn values(a: &clap::Arg) -> Vec<String> {
+    a.get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| v.get_name().to_string())
+        .collect()
+}
+
+fn flags(a: &clap::Arg) -> Vec<String> {
+    let mut f: Vec<String> = a
+        .get_short()
+        .map(|s| format!("-{}", s))
+        .into_iter()
+        .collect();
+    f.extend(a.get_long().map(|l| format!("--{}", l)));
+    f
+}
+
+// --- BASH ---
+// Путь подкоманды так же, как его собирает скрипт: "", "/history/events"
+fn key(n: &Node) -> String {
+    n.path.iter().map(|p| format!("/{}", p)).collect()
+}
+
+fn bash(nodes: &[Node]) -> String {
+    let mut s = String::from(
+        "# bash completion for portal_daemon\n\
+         _portal_daemon() {\n\
+         \x20   local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n\
+         \x20   local path=\"\" w\n\
+         \x20   for w in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n\
+         \x20       case \"$path/$w\" in\n",
+    );
+    for n in nodes.iter().skip(1) {
+        let _ = writeln!(s, "            {}) path=\"$path/$w\" ;;", key(n));
+    }
+    s.push_str("        esac\n    done\n    case \"$path $prev\" in\n");
+    for n in nodes {
+        for a in args(n.cmd).filter(|a| takes_value(a) && !a.is_positional()) {
+            let pattern: Vec<String> = flags(a)
+                .iter()
+                .map(|f| format!("\"{} {}\"", key(n), f))
+                .collect();
+            let _ = writeln!(
+                s,
+                "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;",
+                pattern.join("|"),
+                values(a).join(" ")
+            );
+        }
+    }
+    s.push_str("    esac\n    local words\n    case \"$path\" in\n");
+    for n in nodes {
+        let mut words: Vec<String> = subcommands(n.cmd)
+            .map(|c| c.get_name().to_string())
+            .collect();
+        for a in args(n.cmd) {
+            if a.is_positional() {
+                words.extend(values(a));
+            } else {
+                words.extend(flags(a));
+            }
+        }
+        let _ = writeln!(
+            s,
+            "        \"{}\") words=\"{}\" ;;",
+            key(n),
+            words.join(" ")
+        );
+    }
+    s.push_str(
+        "    esac\n\
+         \x20   COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n\
+         }\n\
+         complete -F _portal_daemon portal_daemon\n",
+    );
+    s
+}
+
+// --- ZSH ---
+fn zsh(nodes: &[Node]) -> String {
+    let quote = |t: &str| {
+        t.replace('\'', "'\\''")
+            .replace('[', "\\[")
+            .replace(']', "\\]")
+            .replace(':', "\\:")
+    };
+    let mut s = String::from("#compdef portal_daemon\n");
+    for n in nodes {
+        let func = std::iter::once("_portal_daemon")
+            .chain(n.path.iter().copied())
+            .collect::<Vec<_>>()
+            .join("__")
+            .replace('-', "_");
+        let _ = writeln!(s, "\n{}() {{\n    _arguments -C \\", func);
+        for a in args(n.cmd) {
+            let h = quote(&help(a.get_help()));
+            let vals = values(a);
+            let action = if vals.is_empty() {
+                String::new()
+            } else {
+                format!("({})", vals.join(" "))
+            };
+            let name = a.get_id().as_str().to_string();
+            if a.is_positional() {
+                let _ = writeln!(s, "        ':{}:{}' \\", name, action);
+                continue;
+            }
+            for f in flags(a) {
+                if takes_value(a) {
+                    // --recent [SPAN]: значение необязательно
+                    let optional = a.get_num_args().is_some_and(|r| r.min_values() == 0);
+                    let sep = match (f.starts_with("--"), optional) {
+                        (true, true) => "=-",
+                        (true, false) => "=",
+                        (false, _) => "+",
+                    };
+                    let _ = writeln!(s, "        '{}{}[{}]:{}:{}' \\", f, sep, h, name, action);
+                } else {
+                    let _ = writeln!(s, "        '{}[{}]' \\", f, h);
+                }
+            }
+        }
+        let subs: Vec<&Command> = subcommands(n.cmd).collect();
+        if subs.is_empty() {
+            s.push_str("        && return\n}\n");
+            continue;
+        }
+        let list: Vec<String> = subs
+            .iter()
+            .map(|c| format!("{}\\:\"{}\"", c.get_name(), quote(&help(c.get_about()))))
+            .collect();
+        let _ = writeln!(
+            s,
+            "        '1: :(({}))' \\\n        '*:: :->args' && return",
+            list.join(" ")
+        );
+        s.push_str("    [[ $state == args ]] || return 1\n    case $words[1] in\n");
+        for c in subs {
+            let _ = writeln!(
+                s,
+                "        {}) {}__{} ;;",
+                c.get_name(),
+                func,
+                c.get_name().replace('-', "_")
+            );
+        }
+        s.push_str("    esac\n}\n");
+    }
+    s.push_str("\n_portal_daemon \"$@\"\n");
+    s
+}
+
+// --- FISH ---
+fn fish(nodes: &[Node]) -> String {
+    let quote = |t: &str| format!("'{}'", t.replace('\\', "\\\\").replace('\'', "\\'"));
+    let mut s = String::from("# fish completion for portal_daemon\ncomplete -c portal_daemon -f\n");
+    for n in nodes {
+        // Условие "мы внутри этой подкоманды, но не глубже"
+        let mut cond: Vec<String> = match n.path.as_slice() {
+            [] => vec!["__fish_use_subcommand".into()],
+            path => path
+                .iter()
+                .map(|p| format!("__fish_seen_subcommand_from {}", p))
+                .collect(),
+        };
+        let subs: Vec<&str> = subcommands(n.cmd).map(|c| c.get_name()).collect();
+        if !n.path.is_empty() && !subs.is_empty() {
+            cond.push(format!(
+                "not __fish_seen_subcommand_from {}",
+                subs.join(" ")
+            ));
+        }
+        let cond = cond.join("; and ");
+        for c in subcommands(n.cmd) {
+            let _ = writeln!(
+                s,
+                "complete -c portal_daemon -n \"{}\" -a {} -d {}",
+                cond,
+                c.get_name(),
+                quote(&help(c.get_about()))
+            );
+        }
+        for a in args(n.cmd) {
+            let vals = values(a);
+            if a.is_positional() {
+                if !vals.is_empty() {
+                    let _ = writeln!(
+                        s,
+                        "complete -c portal_daemon -n \"{}\" -a {}",
+                        cond,
+                        quote(&vals.join(" "))
+                    );
+                }
+                continue;
+            }
+            let mut line = format!("complete -c portal_daemon -n \"{}\"", cond);
+            if let Some(c) = a.get_short() {
+                let _ = write!(line, " -s {}", c);
+            }
+            if let Some(l) = a.get_long() {
+                let _ = write!(line, " -l {}", l);
+            }
+            if takes_value(a) {
+                line.push_str(" -r");
+                if !vals.is_empty() {
+                    let _ = write!(line, " -a {}", quote(&vals.join(" ")));
+                }
+            }
+            let _ = writeln!(s, "{} -d {}", line, quote(&help(a.get_help())));
+        }
+    }
+    s
+}
+
+// --- MAN ---
+pub fn man(mut cmd: Command) -> String {
+    cmd.build();
+    let mut nodes = Vec::new();
+    walk(&cmd, &mut Vec::new(), &mut nodes);
+    let version = cmd.get_version().unwrap_or_default();
+    let mut s = format!(
+        ".TH PORTAL_DAEMON 8 \"\" \"portal_daemon {}\" \"System Manager's Manual\"\n\
+         .SH NAME\nportal_daemon \\- {}\n\
+         .SH SYNOPSIS\n\\fBportal_daemon\\fR [\\fIOPTIONS\\fR] [\\fICOMMAND\\fR]\n",
+        version,
+        roff(&help(cmd.get_about()))
+    );
+    s.push_str(".SH OPTIONS\n");
+    options(&mut s, &cmd, true);
+    s.push_str(".SH COMMANDS\n");
+    for n in nodes.iter().skip(1) {
+        let _ = writeln!(
+            s,
+            ".TP\n\\fBportal_daemon {}\\fR\n{}",
+            n.path.join(" "),
+            roff(&help(n.cmd.get_about()))
+        );
+        if args(n.cmd).any(|a| !shared(a)) {
+            s.push_str(".RS\n");
+            options(&mut s, n.cmd, false);
+            s.push_str(".RE\n");
+        }
+    }
+    s.push_str(
+        ".SH FILES\n\
+         .TP\n/etc/portal_daemon/config.json\nSettings written by \\fB\\-\\-configure\\fR.\n\
+         .TP\n/etc/portal_daemon/locales/\nOverrides for the built-in strings (\\fIen.ftl\\fR, \\fIuk.ftl\\fR, \\fIru.ftl\\fR).\n\
+         .TP\n/run/portal_daemon/state.json\nLive state read by \\fBstatus\\fR and \\fBwhy\\fR.\n\
+         .TP\n/var/lib/portal_daemon/\nEvent and latency history.\n\
+         .SH EXIT STATUS\n\
+         Installer and wizard failures exit with \\fIsysexits.h\\fR codes; an invalid \
+         config at daemon start exits with 78 (EX_CONFIG).\n",
+    );
+    s
+}
+
+// Общие ключи (--user, -h) у подкоманд не повторяем - они в OPTIONS
+fn shared(a: &clap::Arg) -> bool {
+    a.is_global_set() || a.get_id() == "help"
+}
+
+fn options(s: &mut String, cmd: &Command, root: bool) {
+    for a in args(cmd).filter(|a| root || !shared(a)) {
+        let mut head = flags(a)
+            .iter()
+            .map(|f| format!("\\fB{}\\fR", roff(f)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if a.is_positional() || takes_value(a) {
+            let name = a
+                .get_value_names()
+                .and_then(|v| v.first())
+                .map_or(a.get_id().as_str().to_uppercase(), |v| v.to_string());
+            let _ = write!(head, " \\fI{}\\fR", roff(&name));
+        }
+        let mut body = roff(&help(a.get_help()));
+        let vals = values(a);
+        if !vals.is_empty() {
+            let _ = write!(body, " [possible values: {}]", roff(&vals.join(", ")));
+        }
+        let _ = writeln!(s, ".TP\n{}\n{}", head.trim_start(), body);
+    }
+}
+
+// Экранирование roff: обратный слеш, дефис, точка или апостроф в начале строки
+fn roff(t: &str) -> String {
+    let t = t.replace('\\', "\\e").replace('-', "\\-");
+    t.lines()
+        .map(|l| {
+            if l.starts_with('.') || l.starts_with('\'') {
+                format!("\\&{}", l)
+            } else {
+                l.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn every_visible_subcommand_is_covered() {
+        let cmd = crate::Args::command();
+        let names: Vec<String> = subcommands(&cmd)
+            .map(|c| c.get_name().to_string())
+            .collect();
+        assert!(names.iter().any(|n| n == "completions"));
+        for shell in ALL {
+            let script = generate(shell, cmd.clone());
+            for n in &names {
+                assert!(script.contains(n.as_str()), "{:?} misses {}", shell, n);
+            }
+            assert!(
+                !script.contains("bench"),
+                "{:?} shows a hidden command",
+                shell
+            );
+        }
+        let page = man(cmd);
+        assert!(page.contains("\\fBportal_daemon history events\\fR"));
+        assert!(page.contains("\\fB\\-\\-since\\fR \\fISINCE\\fR"));
+        assert!(!page.contains("inject"));
+    }
+}
@@ -0,0 +1,173 @@
+// === CURL: СЕКРЕТЫ МИМО ARGV ===
+// argv любого процесса читает кто угодно через ps и /proc/<pid>/cmdline.
+// URL с токеном, заголовки и пароли отдаем curl конфигом (-K) через pipe:
+// в argv остается только "-K /dev/fd/N", stdin свободен под тело письма.
+// Job - тот же запуск без ожидания: опросы ntfy/Telegram в цикле демона.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ChildStdout, Command, Output, Stdio};
+
+use crate::reactor;
+
+#[derive(Default, Debug, PartialEq)]
+pub struct Config(String);
+
+impl Config {
+    // key = "value"; в кавычках curl понимает \\ \" \n \r \t
+    pub fn set(&mut self, key: &str, value: &str) -> &mut Self {
+        let mut quoted = String::with_capacity(value.len());
+        for ch in value.chars() {
+            match ch {
+                '\\' => quoted.push_str("\\\\"),
+                '"' => quoted.push_str("\\\""),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                c => quoted.push(c),
+            }
+        }
+        self.0.push_str(&format!("{} = \"{}\"\n", key, quoted));
+        self
+    }
+
+    pub fn url(&mut self, url: &str) -> &mut Self {
+        self.set("url", url)
+    }
+
+    pub fn header(&mut self, header: &str) -> &mut Self {
+        self.set("header", header)
+    }
+}
+
+fn spawn(c: &mut Command, cfg: &Config, stdin: Stdio, stderr: Stdio) -> io::Result<Child> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (r, w) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    // Конфиг в сотню-другую байтов целиком ложится в буфер pipe
+    File::from(w).write_all(cfg.0.as_bytes())?;
+    let fd = r.as_raw_fd();
+    // O_CLOEXEC снимаем только в потомке: параллельные запуски его не унаследуют
+    unsafe {
+        c.pre_exec(move || {
+            if libc::fcntl(fd, libc::F_SETFD, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    c.args(["-K", &format!("/dev/fd/{}", fd)])
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(stderr)
+        .spawn()
+}
+
+// Запустить c с конфигом cfg; input - в stdin. stdout и stderr собираются
+pub fn output(c: &mut Command, cfg: &Config, input: Option<&[u8]>) -> io::Result<Output> {
+    let stdin = if input.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+    let mut child = spawn(c, cfg, stdin, Stdio::piped())?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input).ok();
+    }
+    child.wait_with_output()
+}
+
+// Запрос, который не ждут: stdout читает цикл демона, когда poll скажет
+pub struct Job {
+    child: Child,
+    out: ChildStdout,
+    buf: Vec<u8>,
+}
+
+impl Job {
+    pub fn start(c: &mut Command, cfg: &Config) -> io::Result<Self> {
+        let mut child = spawn(c, cfg, Stdio::null(), Stdio::null())?;
+        let out = child.stdout.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        Ok(Self {
+            child,
+            out,
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.out.as_raw_fd()
+    }
+
+    // Одно чтение. None - curl еще работает; Some - закончил, тело только при успехе
+    pub fn read(&mut self) -> Option<Option<String>> {
+        let mut chunk = [0u8; 4096];
+        match self.out.read(&mut chunk) {
+            Ok(n) if n > 0 => {
+                self.buf.extend_from_slice(&chunk[..n]);
+                None
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => None,
+            _ => {
+                let ok = self.child.wait().is_ok_and(|s| s.success());
+                Some(ok.then(|| String::from_utf8_lossy(&self.buf).to_string()))
+            }
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        // Уже закончился - kill вернет ошибку, wait отдаст сохраненный статус
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+// Сам по себе Job - ответ, результат которого не нужен
+impl reactor::Source for Job {
+    fn fd(&self) -> Option<RawFd> {
+        Some(Job::fd(self))
+    }
+
+    fn ready(&mut self, _: bool, _: &mut reactor::Spawned) -> bool {
+        self.read().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_config_through_a_pipe() {
+        let mut cfg = Config::default();
+        cfg.url("https://api.telegram.org/bot1:x/getMe")
+            .header("X-Note: \"a\\b\"\n");
+        // Вместо curl - sh: $1 = -K, $2 = /dev/fd/N
+        let mut sh = Command::new("sh");
+        sh.args(["-c", "cat \"$2\"; cat", "sh"]);
+        let out = output(&mut sh, &cfg, Some(b"body")).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&out.stdout),
+            "url = \"https://api.telegram.org/bot1:x/getMe\"\n\
+             header = \"X-Note: \\\"a\\\\b\\\"\\n\"\nbody"
+        );
+        // Job: тело по частям, пока curl не закрыл stdout
+        let mut sh = Command::new("sh");
+        sh.args(["-c", "cat \"$2\"", "sh"]);
+        let mut job = Job::start(&mut sh, &cfg).unwrap();
+        let body = loop {
+            if let Some(body) = job.read() {
+                break body;
+            }
+        };
+        assert!(body.unwrap().starts_with("url = "));
+        let mut fail = Command::new("false");
+        assert_eq!(Job::start(&mut fail, &cfg).unwrap().read(), Some(None));
+    }
+}
@@ -0,0 +1,278 @@
+// === ПАНЕЛЬ (portal_daemon tui) ===
+// Живая картина демона на одном экране: маяк и последняя проба, RTT
+// полоской, отсчет до сна (grace) или до конца паузы, последние события.
+// Клавиши шлют команды демону по D-Bus - тем же методам, что у апплетов.
+// Нет шины (dbus_service выключен) - показываем то же из /run, но без
+// управления. ratatui в зависимостях нет: ANSI поверх режима ввода tui.rs.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::time::{Duration, Instant};
+
+use crate::error::PortalError;
+use crate::{
+    Language, Locales, QUICK_PAUSE_MINUTES, StatusReport, dbus, history, hm, local_tz, mmss, state,
+    tui, unix_now,
+};
+
+// Точек в полоске RTT (по одной на пробу)
+const SPARK_POINTS: usize = 48;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const EVENTS_SHOWN: usize = 8;
+// События читаются из файла истории - не на каждом кадре
+const EVENTS_REFRESH: Duration = Duration::from_secs(10);
+// Второе [s] в течение стольких секунд - усыпить
+const CONFIRM_SEC: u64 = 5;
+
+struct Dashboard {
+    t: Locales,
+    tz: crate::schedule::TimeZone,
+    bus: Result<dbus::Client, String>,
+    report: Option<StatusReport>,
+    // RTT по пробам; None - проба не прошла
+    rtt: Vec<Option<f64>>,
+    last_probe_at: u64,
+    events: Vec<history::EventRecord>,
+    events_read: Option<Instant>,
+    message: String,
+    sleep_armed_at: Option<u64>,
+}
+
+pub fn run(lang: Language) -> Result<(), PortalError> {
+    if !tui::enable() {
+        return Err(PortalError::Usage("tui needs an interactive terminal"));
+    }
+    let mut d = Dashboard::new(Locales::new(lang));
+    tui::full_screen(true);
+    loop {
+        d.refresh();
+        d.draw();
+        // Кадр раз в секунду - отсчеты идут по секундам
+        let Some(key) = tui::key(Duration::from_secs(1)) else {
+            continue;
+        };
+        if !d.on_key(key.to_ascii_lowercase()) {
+            break;
+        }
+    }
+    tui::full_screen(false);
+    tui::restore();
+    Ok(())
+}
+
+impl Dashboard {
+    fn new(t: Locales) -> Self {
+        // Полоска сразу с историей: средние по агрегатам последнего часа
+        let rtt = history::read_latency(unix_now().saturating_sub(3600))
+            .iter()
+            .map(|b| (b.probes > b.lost).then_some(b.avg_ms))
+            .collect();
+        Dashboard {
+            t,
+            tz: local_tz(),
+            bus: dbus::Client::daemon(),
+            report: None,
+            rtt,
+            last_probe_at: 0,
+            events: Vec::new(),
+            events_read: None,
+            message: String::new(),
+            sleep_armed_at: None,
+        }
+    }
+
+    fn refresh(&mut self) {
+        // Демон перезапускался - соединение переоткрываем
+        if self.bus.is_err() {
+            self.bus = dbus::Client::daemon();
+        }
+        let report = match &mut self.bus {
+            Ok(c) => match c.daemon_status() {
+                Ok(json) => serde_json::from_str(&json).ok(),
+                Err(e) => {
+                    self.bus = Err(e);
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+        let report = report.unwrap_or_else(crate::status_report);
+        if let Some(st) = &report.daemon
+            && st.last_probe_at != self.last_probe_at
+        {
+            self.last_probe_at = st.last_probe_at;
+            self.rtt.push(st.last_rtt_ms.filter(|_| st.last_probe_ok));
+            let extra = self.rtt.len().saturating_sub(SPARK_POINTS);
+            self.rtt.drain(..extra);
+        }
+        self.report = Some(report);
+        if self
+            .events_read
+            .is_none_or(|at| at.elapsed() >= EVENTS_REFRESH)
+        {
+            let mut events = history::read_events(unix_now().saturating_sub(86400));
+            events.drain(..events.len().saturating_sub(EVENTS_SHOWN));
+            self.events = events;
+            self.events_read = Some(Instant::now());
+        }
+    }
+
+    // false - выход
+    fn on_key(&mut self, key: u8) -> bool {
+        let now = unix_now();
+        let armed = self
+            .sleep_armed_at
+            .take()
+            .is_some_and(|at| now <= at + CONFIRM_SEC);
+        let (member, minutes) = match key {
+            b'q' | 0x1b => return false,
+            b'p' => ("Pause", Some(QUICK_PAUSE_MINUTES as u32)),
+            b'r' => ("Resume", None),
+            b'c' => ("ProbeNow", None),
+            b's' if armed => ("SleepNow", None),
+            b's' => {
+                self.sleep_armed_at = Some(now);
+                self.message = self.t.dash_sleep_confirm.clone();
+                return true;
+            }
+            _ => return true,
+        };
+        let res = match &mut self.bus {
+            Ok(c) => c.daemon_call(member, minutes),
+            Err(e) => Err(e.clone()),
+        };
+        self.message = match res {
+            Ok(()) => format!("{} {}", self.t.dash_sent, member),
+            Err(e) => format!("{} {}: {}", self.t.dash_failed, member, e),
+        };
+        // Событие паузы или сна - сразу в список
+        self.events_read = None;
+        true
+    }
+
+    fn draw(&self) {
+        let t = &self.t;
+        let now = unix_now();
+        let at = |ts: u64| {
+            let l = self.tz.to_local(ts as i64);
+            format!("{:02}:{:02}:{:02}", l.hour, l.minute, l.second)
+        };
+        let mut lines = vec![format!("{}  {}", t.dash_title, at(now)), "─".repeat(60)];
+        if let Err(e) = &self.bus {
+            lines.push(format!("{} {}", t.dash_no_bus, e));
+        }
+        let report = self.report.as_ref();
+        match report.and_then(|r| r.daemon.as_ref()) {
+            Some(st) => {
+                lines.push(format!(
+                    "{} (pid {}): {:?}",
+                    t.status_running, st.pid, st.phase
+                ));
+                let mark = if st.last_probe_ok { "✅" } else { "❌" };
+                lines.push(format!(
+                    "{} {} {} {} ({}s {})",
+                    t.why_probe,
+                    st.lighthouse_ip,
+                    mark,
+                    at(st.last_probe_at),
+                    now.saturating_sub(st.last_probe_at),
+                    t.status_ago
+                ));
+                if st.last_ok_at > 0 {
+                    lines.push(format!(
+                        "{} {} ({} {})",
+                        t.why_last_ok,
+                        at(st.last_ok_at),
+                        hm(now.saturating_sub(st.last_ok_at)),
+                        t.status_ago
+                    ));
+                }
+                let rtt = st
+                    .last_rtt_ms
+                    .map_or("-".to_string(), |ms| format!("{:.1} ms", ms));
+                lines.push(format!(
+                    "{} {:<10} {}",
+                    t.status_rtt,
+                    rtt,
+                    sparkline(&self.rtt)
+                ));
+                if st.phase == state::Phase::Grace && st.sleep_at > 0 {
+                    lines.push(format!(
+                        "{} {} (⏳ {})",
+                        t.status_sleep_at,
+                        at(st.sleep_at),
+                        mmss(st.sleep_at.saturating_sub(now))
+                    ));
+                }
+                if !st.decision.is_empty() {
+                    lines.push(format!("{} {}", t.why_decision, st.decision));
+                }
+                if let Some(b) = &st.last_blocked {
+                    lines.push(format!("{} {}", t.why_blocked, b));
+                }
+            }
+            None => lines.push(t.not_running.clone()),
+        }
+        if let Some(until) = report.and_then(|r| r.pause_until)
+            && until > now
+        {
+            lines.push(format!(
+                "{} {} (-> {})",
+                t.status_paused,
+                mmss(until - now),
+                at(until)
+            ));
+        }
+        lines.push(String::new());
+        lines.push(t.dash_events.clone());
+        if self.events.is_empty() {
+            lines.push(format!("  {}", t.history_empty));
+        }
+        for e in &self.events {
+            lines.push(format!("  {}  {} {}", at(e.ts), e.event, e.detail));
+        }
+        lines.push(String::new());
+        lines.push(self.message.clone());
+        lines.push(t.dash_hint.clone());
+
+        // С начала экрана, каждую строку дочищаем - без мигания от \x1b[2J
+        let mut frame = String::from("\x1b[H");
+        for l in &lines {
+            let _ = writeln!(frame, "{}\x1b[K", l);
+        }
+        frame.push_str("\x1b[J");
+        let mut out = std::io::stdout();
+        out.write_all(frame.as_bytes()).ok();
+        out.flush().ok();
+    }
+}
+
+// Своя шкала от минимума до максимума окна; потерянная проба - точка
+fn sparkline(points: &[Option<f64>]) -> String {
+    let ok = points.iter().flatten();
+    let min = ok.clone().copied().fold(f64::INFINITY, f64::min);
+    let max = ok.copied().fold(0.0, f64::max);
+    points
+        .iter()
+        .map(|p| match p {
+            None => '·',
+            Some(_) if max <= min => BARS[0],
+            Some(ms) => {
+                let level = (ms - min) / (max - min) * (BARS.len() - 1) as f64;
+                BARS[level.round() as usize]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparkline_scales_to_window() {
+        assert_eq!(sparkline(&[Some(1.0), Some(8.0), None, Some(4.5)]), "▁█·▅");
+        assert_eq!(sparkline(&[Some(3.0), Some(3.0)]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
@@ -0,0 +1,974 @@
+// === D-BUS СЕРВИС ua.portal.Daemon1 ===
+// Для апплетов и прочих программ: методы Pause/Resume/SleepNow/ProbeNow/GetStatus и
+// сигналы ConnectionLost/SleepingIn/WokeUp вместо опроса файлов в /run.
+// Протокол D-Bus реализован минимально (только нужные типы), без libdbus:
+// одно соединение с системной шиной, авторизация EXTERNAL по uid.
+// Кто может звать методы, решает политика шины (ставится в --install).
+// С --user сервис живет на сессионной шине пользователя.
+// Тем же кодом - клиент для чужих сервисов: свойства NetworkManager.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::events::{Event, Subscriber};
+use crate::{
+    PAUSE_MINUTES_RANGE, clear_pause, log, paths, reactor, request_sleep_now, set_pause,
+    status_json, tui,
+};
+
+pub const BUS_NAME: &str = "ua.portal.Daemon1";
+const OBJECT_PATH: &str = "/ua/portal/Daemon1";
+const SYSTEM_BUS: &str = "unix:path=/run/dbus/system_bus_socket";
+// Клиентский вызов: занятый сервис не должен вешать мастер настройки
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const POLICY_FILE: &str = "/etc/dbus-1/system.d/ua.portal.Daemon1.conf";
+
+const INTROSPECT_XML: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="ua.portal.Daemon1">
+    <method name="Pause"><arg name="minutes" type="u" direction="in"/></method>
+    <method name="Resume"/>
+    <method name="SleepNow"/>
+    <method name="ProbeNow"/>
+    <method name="GetStatus"><arg name="json" type="s" direction="out"/></method>
+    <signal name="ConnectionLost"><arg name="grace_sec" type="t"/></signal>
+    <signal name="SleepingIn"><arg name="seconds" type="t"/></signal>
+    <signal name="WokeUp"/>
+  </interface>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect"><arg name="xml" type="s" direction="out"/></method>
+  </interface>
+  <interface name="org.freedesktop.DBus.Peer">
+    <method name="Ping"/>
+  </interface>
+</node>
+"#;
+
+// Политика системной шины: имя держит root или пользователь run_as,
+// управляют они и portal-admins, статус и сигналы доступны всем
+pub fn policy_xml(group: &str, user: &str) -> String {
+    format!(
+        r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-BUS Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <policy user="root">
+    <allow own="{name}"/>
+    <allow send_destination="{name}"/>
+  </policy>
+  <policy user="{user}">
+    <allow own="{name}"/>
+    <allow send_destination="{name}"/>
+  </policy>
+  <policy group="{group}">
+    <allow send_destination="{name}"/>
+  </policy>
+  <policy context="default">
+    <allow send_destination="{name}" send_interface="{name}" send_member="GetStatus"/>
+    <allow send_destination="{name}" send_interface="org.freedesktop.DBus.Introspectable"/>
+    <allow send_destination="{name}" send_interface="org.freedesktop.DBus.Peer"/>
+  </policy>
+</busconfig>
+"#,
+        name = BUS_NAME,
+        group = group,
+        user = user
+    )
+}
+
+// --- СООБЩЕНИЯ ---
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+const SIGNAL: u8 = 4;
+const NO_REPLY_EXPECTED: u8 = 0x1;
+// Предел спецификации: длиннее шина не пропустит, а от чужого пира это
+// просто мусор в длине - не выделять под него гигабайты
+const MAX_MESSAGE_LEN: usize = 128 * 1024 * 1024;
+
+// Значение поля заголовка (только типы, которые встречаются в заголовке)
+enum Field<'a> {
+    Str(u8, char, &'a str),
+    U32(u8, u32),
+}
+
+#[derive(Default)]
+struct Message {
+    kind: u8,
+    flags: u8,
+    serial: u32,
+    path: String,
+    interface: String,
+    member: String,
+    sender: String,
+    signature: String,
+    reply_serial: u32,
+    body: Vec<u8>,
+    big_endian: bool,
+}
+
+// Запись little-endian с выравниванием от начала буфера
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn align(&mut self, n: usize) {
+        while !self.buf.len().is_multiple_of(n) {
+            self.buf.push(0);
+        }
+    }
+
+    fn byte(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.align(4);
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.align(8);
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn str(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn sig(&mut self, s: &str) {
+        self.buf.push(s.len() as u8);
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+    }
+}
+
+fn encode(kind: u8, serial: u32, fields: &[Field], signature: &str, body: &[u8]) -> Vec<u8> {
+    let mut w = Writer::default();
+    for b in [b'l', kind, 0, 1] {
+        w.byte(b);
+    }
+    w.u32(body.len() as u32);
+    w.u32(serial);
+    // a(yv): длина массива без выравнивания перед первым элементом
+    let len_at = w.buf.len();
+    w.u32(0);
+    w.align(8);
+    let start = w.buf.len();
+    let signature_field = Field::Str(8, 'g', signature);
+    let all = fields
+        .iter()
+        .chain((!signature.is_empty()).then_some(&signature_field));
+    for f in all {
+        w.align(8);
+        match f {
+            Field::Str(code, t, v) => {
+                w.byte(*code);
+                w.sig(&t.to_string());
+                if *t == 'g' {
+                    w.sig(v);
+                } else {
+                    w.str(v);
+                }
+            }
+            Field::U32(code, v) => {
+                w.byte(*code);
+                w.sig("u");
+                w.u32(*v);
+            }
+        }
+    }
+    let len = (w.buf.len() - start) as u32;
+    w.buf[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+    w.align(8);
+    w.buf.extend_from_slice(body);
+    w.buf
+}
+
+// Чтение с учетом порядка байт отправителя
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big: bool,
+}
+
+impl Reader<'_> {
+    fn align(&mut self, n: usize) {
+        self.pos = self.pos.div_ceil(n) * n;
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let b = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.align(4);
+        let b: [u8; 4] = self.data.get(self.pos..self.pos + 4)?.try_into().ok()?;
+        self.pos += 4;
+        Some(if self.big {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<String> {
+        let s = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len + 1;
+        Some(String::from_utf8_lossy(s).into_owned())
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    fn sig(&mut self) -> Option<String> {
+        let len = self.byte()? as usize;
+        self.bytes(len)
+    }
+
+    // Только то, что отдают нужные нам свойства: s, o, u, b, as, ao и ay
+    // (SSID точки доступа - байты, не строка)
+    fn value(&mut self, sig: &str) -> Option<Value> {
+        match sig {
+            "s" | "o" => self.str().map(Value::Str),
+            "ay" => {
+                let len = self.u32()? as usize;
+                let b = self.data.get(self.pos..self.pos + len)?;
+                self.pos += len;
+                Some(Value::Str(String::from_utf8_lossy(b).into_owned()))
+            }
+            "u" | "b" => self.u32().map(Value::U32),
+            "as" | "ao" => {
+                let len = self.u32()? as usize;
+                self.align(4);
+                let end = self.pos + len;
+                let mut items = Vec::new();
+                while self.pos < end {
+                    items.push(self.str()?);
+                }
+                Some(Value::List(items))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Str(String),
+    U32(u32),
+    List(Vec<String>),
+}
+
+impl Value {
+    pub fn into_str(self) -> Option<String> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Value::U32(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn into_list(self) -> Vec<String> {
+        match self {
+            Value::List(l) => l,
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn num(big: bool, b: &[u8]) -> u32 {
+    let b: [u8; 4] = b.try_into().unwrap();
+    if big {
+        u32::from_be_bytes(b)
+    } else {
+        u32::from_le_bytes(b)
+    }
+}
+
+// Длина всего сообщения по его первым 16 байтам; None - не видно 16 байт
+// или длина больше MAX_MESSAGE_LEN
+fn message_len(fixed: &[u8]) -> Option<usize> {
+    let fixed = fixed.get(..16)?;
+    let big = fixed[0] == b'B';
+    let body_len = num(big, &fixed[4..8]) as usize;
+    let fields_len = num(big, &fixed[12..16]) as usize;
+    Some(16 + fields_len.div_ceil(8) * 8 + body_len).filter(|&l| l <= MAX_MESSAGE_LEN)
+}
+
+fn read_message(r: &mut impl Read) -> Option<Message> {
+    let mut fixed = [0u8; 16];
+    r.read_exact(&mut fixed).ok()?;
+    let big = fixed[0] == b'B';
+    let num = |b: &[u8]| num(big, b);
+    let fields_len = num(&fixed[12..16]) as usize;
+    let mut data = fixed.to_vec();
+    data.resize(message_len(&fixed)?, 0);
+    r.read_exact(&mut data[16..]).ok()?;
+
+    let mut m = Message {
+        kind: fixed[1],
+        flags: fixed[2],
+        serial: num(&fixed[8..12]),
+        big_endian: big,
+        ..Default::default()
+    };
+    let mut rd = Reader {
+        data: &data,
+        pos: 16,
+        big,
+    };
+    let end = 16 + fields_len;
+    while rd.pos < end {
+        rd.align(8);
+        let code = rd.byte()?;
+        let t = rd.sig()?;
+        match (code, t.as_str()) {
+            (1, "o") => m.path = rd.str()?,
+            (2, "s") => m.interface = rd.str()?,
+            (3, "s") => m.member = rd.str()?,
+            (5, "u") => m.reply_serial = rd.u32()?,
+            (7, "s") => m.sender = rd.str()?,
+            (8, "g") => m.signature = rd.sig()?,
+            (_, "s" | "o") => {
+                rd.str()?;
+            }
+            (_, "g") => {
+                rd.sig()?;
+            }
+            (_, "u") => {
+                rd.u32()?;
+            }
+            _ => return None,
+        }
+    }
+    m.body = data[16 + fields_len.div_ceil(8) * 8..].to_vec();
+    Some(m)
+}
+
+// --- СОЕДИНЕНИЕ ---
+struct Conn {
+    out: Mutex<UnixStream>,
+    serial: AtomicU32,
+}
+
+impl Conn {
+    fn send(&self, kind: u8, fields: &[Field], signature: &str, body: &[u8]) -> u32 {
+        let serial = self.serial.fetch_add(1, Ordering::Relaxed);
+        let msg = encode(kind, serial, fields, signature, body);
+        if let Ok(mut s) = self.out.lock() {
+            s.write_all(&msg).ok();
+        }
+        serial
+    }
+
+    fn call_bus(&self, member: &str, signature: &str, body: &[u8]) -> u32 {
+        self.send(
+            METHOD_CALL,
+            &[
+                Field::Str(1, 'o', "/org/freedesktop/DBus"),
+                Field::Str(2, 's', "org.freedesktop.DBus"),
+                Field::Str(3, 's', member),
+                Field::Str(6, 's', "org.freedesktop.DBus"),
+            ],
+            signature,
+            body,
+        )
+    }
+
+    fn reply(&self, to: &Message, signature: &str, body: &[u8]) {
+        if to.flags & NO_REPLY_EXPECTED != 0 {
+            return;
+        }
+        self.send(
+            METHOD_RETURN,
+            &[Field::U32(5, to.serial), Field::Str(6, 's', &to.sender)],
+            signature,
+            body,
+        );
+    }
+
+    fn error(&self, to: &Message, name: &str, text: &str) {
+        if to.flags & NO_REPLY_EXPECTED != 0 {
+            return;
+        }
+        let mut w = Writer::default();
+        w.str(text);
+        self.send(
+            ERROR,
+            &[
+                Field::Str(4, 's', name),
+                Field::U32(5, to.serial),
+                Field::Str(6, 's', &to.sender),
+            ],
+            "s",
+            &w.buf,
+        );
+    }
+
+    fn signal(&self, member: &str, signature: &str, body: &[u8]) {
+        self.send(
+            SIGNAL,
+            &[
+                Field::Str(1, 'o', OBJECT_PATH),
+                Field::Str(2, 's', BUS_NAME),
+                Field::Str(3, 's', member),
+            ],
+            signature,
+            body,
+        );
+    }
+}
+
+fn system_address() -> String {
+    std::env::var("DBUS_SYSTEM_BUS_ADDRESS").unwrap_or_else(|_| SYSTEM_BUS.to_string())
+}
+
+fn service_address() -> Result<String, String> {
+    if !paths::user() {
+        return Ok(system_address());
+    }
+    std::env::var("DBUS_SESSION_BUS_ADDRESS")
+        .or_else(|_| std::env::var("XDG_RUNTIME_DIR").map(|d| format!("unix:path={}/bus", d)))
+        .map_err(|_| "no session bus (DBUS_SESSION_BUS_ADDRESS is not set)".to_string())
+}
+
+// Авторизация и Hello - дальше можно звать и принимать вызовы
+fn open(address: &str) -> Result<(Arc<Conn>, BufReader<UnixStream>), String> {
+    let path = address
+        .split(';')
+        .find_map(|a| a.strip_prefix("unix:path="))
+        .map(|p| p.split(',').next().unwrap_or(p))
+        .ok_or_else(|| format!("unsupported address {}", address))?;
+    let stream = UnixStream::connect(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let mut out = stream;
+
+    let uid = unsafe { libc::getuid() }.to_string();
+    let hex: String = uid.bytes().map(|b| format!("{:02x}", b)).collect();
+    write!(out, "\0AUTH EXTERNAL {}\r\n", hex).map_err(|e| e.to_string())?;
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    if !line.starts_with("OK ") {
+        return Err(format!("auth rejected: {}", line.trim()));
+    }
+    out.write_all(b"BEGIN\r\n").map_err(|e| e.to_string())?;
+
+    let conn = Arc::new(Conn {
+        out: Mutex::new(out),
+        serial: AtomicU32::new(1),
+    });
+    let hello = conn.call_bus("Hello", "", &[]);
+    wait_reply(&mut reader, hello)?;
+    Ok((conn, reader))
+}
+
+fn connect(address: &str) -> Result<(Arc<Conn>, BufReader<UnixStream>), String> {
+    let (conn, mut reader) = open(address)?;
+    // DO_NOT_QUEUE: второй демон не должен тихо встать в очередь за первым
+    let mut w = Writer::default();
+    w.str(BUS_NAME);
+    w.u32(4);
+    let req = conn.call_bus("RequestName", "su", &w.buf);
+    let reply = wait_reply(&mut reader, req)?;
+    let mut rd = Reader {
+        data: &reply.body,
+        pos: 0,
+        big: reply.big_endian,
+    };
+    match rd.u32() {
+        Some(1) | Some(4) => Ok((conn, reader)),
+        _ => Err(format!("{} is owned by another process", BUS_NAME)),
+    }
+}
+
+fn wait_reply(reader: &mut impl Read, serial: u32) -> Result<Message, String> {
+    loop {
+        let m = read_message(reader).ok_or("connection closed")?;
+        if m.reply_serial != serial {
+            continue;
+        }
+        if m.kind == ERROR {
+            let mut rd = Reader {
+                data: &m.body,
+                pos: 0,
+                big: m.big_endian,
+            };
+            return Err(rd.str().unwrap_or_else(|| "error".into()));
+        }
+        return Ok(m);
+    }
+}
+
+fn reply_value(reply: &Message, member: &str) -> Result<Value, String> {
+    let mut rd = Reader {
+        data: &reply.body,
+        pos: 0,
+        big: reply.big_endian,
+    };
+    let mut sig = reply.signature.clone();
+    // Properties.Get: значение завернуто в variant
+    if sig == "v" {
+        sig = rd.sig().ok_or("truncated reply")?;
+    }
+    rd.value(&sig)
+        .ok_or_else(|| format!("{}: unsupported reply type '{}'", member, sig))
+}
+
+// --- КЛИЕНТ ---
+pub struct Client {
+    conn: Arc<Conn>,
+    reader: BufReader<UnixStream>,
+}
+
+impl Client {
+    pub fn system() -> Result<Self, String> {
+        Self::at(&system_address())
+    }
+
+    // Шина, на которой живет наш сервис (с --user - сессионная)
+    pub fn daemon() -> Result<Self, String> {
+        Self::at(&service_address()?)
+    }
+
+    fn at(address: &str) -> Result<Self, String> {
+        let (conn, reader) = open(address)?;
+        reader.get_ref().set_read_timeout(Some(CALL_TIMEOUT)).ok();
+        Ok(Self { conn, reader })
+    }
+
+    // Метод нашего сервиса: Pause с минутами, остальные без аргументов
+    pub fn daemon_call(&mut self, member: &str, minutes: Option<u32>) -> Result<(), String> {
+        let mut w = Writer::default();
+        if let Some(m) = minutes {
+            w.u32(m);
+        }
+        let serial = self.conn.send(
+            METHOD_CALL,
+            &[
+                Field::Str(1, 'o', OBJECT_PATH),
+                Field::Str(2, 's', BUS_NAME),
+                Field::Str(3, 's', member),
+                Field::Str(6, 's', BUS_NAME),
+            ],
+            if minutes.is_some() { "u" } else { "" },
+            &w.buf,
+        );
+        wait_reply(&mut self.reader, serial).map(|_| ())
+    }
+
+    // JSON как у `status --json`
+    pub fn daemon_status(&mut self) -> Result<String, String> {
+        self.call(BUS_NAME, OBJECT_PATH, BUS_NAME, "GetStatus", &[])?
+            .into_str()
+            .ok_or_else(|| "GetStatus: unexpected reply".to_string())
+    }
+
+    // Метод с одним строковым аргументом (или без) и одним значением в ответе
+    pub fn call(
+        &mut self,
+        dest: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        args: &[&str],
+    ) -> Result<Value, String> {
+        let mut w = Writer::default();
+        for a in args {
+            w.str(a);
+        }
+        let serial = self.conn.send(
+            METHOD_CALL,
+            &[
+                Field::Str(1, 'o', path),
+                Field::Str(2, 's', interface),
+                Field::Str(3, 's', member),
+                Field::Str(6, 's', dest),
+            ],
+            &"s".repeat(args.len()),
+            &w.buf,
+        );
+        let reply = wait_reply(&mut self.reader, serial)?;
+        reply_value(&reply, member)
+    }
+
+    // Метод с одним bool-аргументом, ответ не нужен (login1.Suspend и т.п.)
+    pub fn call_flag(
+        &mut self,
+        dest: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        flag: bool,
+    ) -> Result<(), String> {
+        let mut w = Writer::default();
+        w.u32(flag as u32);
+        let serial = self.conn.send(
+            METHOD_CALL,
+            &[
+                Field::Str(1, 'o', path),
+                Field::Str(2, 's', interface),
+                Field::Str(3, 's', member),
+                Field::Str(6, 's', dest),
+            ],
+            "b",
+            &w.buf,
+        );
+        wait_reply(&mut self.reader, serial).map(|_| ())
+    }
+
+    pub fn property(
+        &mut self,
+        dest: &str,
+        path: &str,
+        interface: &str,
+        name: &str,
+    ) -> Result<Value, String> {
+        self.call(
+            dest,
+            path,
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            &[interface, name],
+        )
+    }
+}
+
+fn handle(conn: &Conn, m: &Message) {
+    let body_u32 = || {
+        Reader {
+            data: &m.body,
+            pos: 0,
+            big: m.big_endian,
+        }
+        .u32()
+    };
+    let ok_or_fail = |r: std::io::Result<()>| match r {
+        Ok(()) => conn.reply(m, "", &[]),
+        Err(e) => conn.error(m, "org.freedesktop.DBus.Error.AccessDenied", &e.to_string()),
+    };
+    match (m.interface.as_str(), m.member.as_str()) {
+        (BUS_NAME, "Pause") if m.signature == "u" => match body_u32() {
+            Some(mins) if PAUSE_MINUTES_RANGE.contains(&(mins as u64)) => {
+                log::info!("🔌 D-Bus: pause {} min from {}", mins, m.sender);
+                ok_or_fail(set_pause(mins as u64));
+            }
+            _ => conn.error(
+                m,
+                "org.freedesktop.DBus.Error.InvalidArgs",
+                &format!(
+                    "minutes must be {}..={}",
+                    PAUSE_MINUTES_RANGE.start(),
+                    PAUSE_MINUTES_RANGE.end()
+                ),
+            ),
+        },
+        (BUS_NAME, "Resume") => {
+            log::info!("🔌 D-Bus: resume from {}", m.sender);
+            ok_or_fail(clear_pause());
+        }
+        (BUS_NAME, "SleepNow") => {
+            log::info!("🔌 D-Bus: sleep now from {}", m.sender);
+            request_sleep_now();
+            conn.reply(m, "", &[]);
+        }
+        (BUS_NAME, "ProbeNow") => {
+            log::info!("🔌 D-Bus: probe now from {}", m.sender);
+            tui::check_now();
+            conn.reply(m, "", &[]);
+        }
+        (BUS_NAME, "GetStatus") => {
+            let mut w = Writer::default();
+            w.str(&status_json());
+            conn.reply(m, "s", &w.buf);
+        }
+        ("org.freedesktop.DBus.Introspectable", "Introspect") => {
+            let mut w = Writer::default();
+            w.str(INTROSPECT_XML);
+            conn.reply(m, "s", &w.buf);
+        }
+        ("org.freedesktop.DBus.Peer", "Ping") => conn.reply(m, "", &[]),
+        _ => conn.error(
+            m,
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("No such method {}.{}", m.interface, m.member),
+        ),
+    }
+}
+
+// Подписчик шины событий: рассылает сигналы
+pub struct Signals {
+    conn: Arc<Conn>,
+}
+
+impl Subscriber for Signals {
+    fn on_event(&mut self, e: &Event) {
+        let mut w = Writer::default();
+        match e {
+            Event::ConnectionLost { grace_sec } => {
+                w.u64(*grace_sec);
+                self.conn.signal("ConnectionLost", "t", &w.buf);
+                // Если свет не вернется, уснем по окончании grace
+                self.conn.signal("SleepingIn", "t", &w.buf);
+            }
+            Event::GraceReminder { remaining_sec } => {
+                w.u64(*remaining_sec);
+                self.conn.signal("SleepingIn", "t", &w.buf);
+            }
+            Event::SleepRequested { .. } => {
+                w.u64(0);
+                self.conn.signal("SleepingIn", "t", &w.buf);
+            }
+            Event::Woke { .. } => self.conn.signal("WokeUp", "", &[]),
+            _ => {}
+        }
+    }
+}
+
+// Регистрирует сервис; на вызовы отвечает цикл демона (reactor.rs).
+// Нет шины (сервер без D-Bus) - просто работаем без нее.
+pub fn serve() -> Option<Signals> {
+    let (conn, reader) = match service_address().and_then(|a| connect(&a)) {
+        Ok(c) => c,
+        Err(e) => {
+            log::warn!("⚠️  D-Bus service unavailable: {}", e);
+            return None;
+        }
+    };
+    log::info!("🔌 D-Bus service {} registered", BUS_NAME);
+    // Что BufReader успел прочитать за RequestName - уже наши вызовы
+    let mut service = Service {
+        buf: reader.buffer().to_vec(),
+        stream: reader.into_inner(),
+        conn: Arc::clone(&conn),
+    };
+    if !service.dispatch() {
+        log::warn!("⚠️  D-Bus connection closed");
+        return None;
+    }
+    reactor::add(service);
+    Some(Signals { conn })
+}
+
+// Сокет остается блокирующим (он общий с записью), но poll сказал, что
+// данные есть: одно чтение не ждет. Сообщение по частям копится в buf
+struct Service {
+    stream: UnixStream,
+    buf: Vec<u8>,
+    conn: Arc<Conn>,
+}
+
+impl Service {
+    // false - в длине мусор: такое соединение уже не разобрать
+    fn dispatch(&mut self) -> bool {
+        loop {
+            let len = match message_len(&self.buf) {
+                Some(len) if len <= self.buf.len() => len,
+                None if self.buf.len() >= 16 => return false,
+                _ => return true,
+            };
+            let raw: Vec<u8> = self.buf.drain(..len).collect();
+            let Some(m) = read_message(&mut raw.as_slice()) else {
+                continue;
+            };
+            if m.kind == METHOD_CALL && (m.path == OBJECT_PATH || m.path == "/") {
+                handle(&self.conn, &m);
+            } else if m.kind == METHOD_CALL {
+                self.conn.error(
+                    &m,
+                    "org.freedesktop.DBus.Error.UnknownObject",
+                    &format!("No such object {}", m.path),
+                );
+            }
+        }
+    }
+}
+
+impl reactor::Source for Service {
+    fn fd(&self) -> Option<RawFd> {
+        Some(self.stream.as_raw_fd())
+    }
+
+    fn ready(&mut self, _: bool, _: &mut reactor::Spawned) -> bool {
+        let mut chunk = [0u8; 4096];
+        match self.stream.read(&mut chunk) {
+            Ok(0) | Err(_) => {
+                log::warn!("⚠️  D-Bus connection closed");
+                false
+            }
+            Ok(n) => {
+                self.buf.extend_from_slice(&chunk[..n]);
+                let ok = self.dispatch();
+                if !ok {
+                    log::warn!("⚠️  D-Bus connection closed: malformed message");
+                }
+                ok
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(member: &str, signature: &str, body: &[u8]) -> Vec<u8> {
+        encode(
+            METHOD_CALL,
+            7,
+            &[
+                Field::Str(1, 'o', OBJECT_PATH),
+                Field::Str(2, 's', BUS_NAME),
+                Field::Str(3, 's', member),
+                Field::Str(7, 's', ":1.42"),
+            ],
+            signature,
+            body,
+        )
+    }
+
+    #[test]
+    fn encoded_message_reads_back() {
+        let mut w = Writer::default();
+        w.u32(30);
+        let raw = call("Pause", "u", &w.buf);
+        assert_eq!(message_len(&raw), Some(raw.len()));
+        let m = read_message(&mut raw.as_slice()).unwrap();
+        assert_eq!((m.kind, m.serial, m.big_endian), (METHOD_CALL, 7, false));
+        assert_eq!(m.path, OBJECT_PATH);
+        assert_eq!(m.interface, BUS_NAME);
+        assert_eq!(m.member, "Pause");
+        assert_eq!(m.sender, ":1.42");
+        assert_eq!(m.signature, "u");
+        let mut rd = Reader {
+            data: &m.body,
+            pos: 0,
+            big: false,
+        };
+        assert_eq!(rd.u32(), Some(30));
+
+        // Тело с массивом строк: ao свойства NetworkManager
+        let mut w = Writer::default();
+        w.u32(0);
+        let at = w.buf.len();
+        w.str("/a");
+        w.str("/bc");
+        let len = (w.buf.len() - at) as u32;
+        w.buf[..4].copy_from_slice(&len.to_le_bytes());
+        let mut rd = Reader {
+            data: &w.buf,
+            pos: 0,
+            big: false,
+        };
+        let items = rd.value("ao").unwrap().into_list();
+        assert_eq!(items, ["/a", "/bc"]);
+    }
+
+    #[test]
+    fn unwraps_property_variants() {
+        let reply = |signature: &str, body: Vec<u8>| {
+            let raw = encode(METHOD_RETURN, 3, &[], signature, &body);
+            read_message(&mut raw.as_slice()).unwrap()
+        };
+        // Ssid точки доступа: variant с ay, байты не обязаны быть UTF-8
+        let mut body = vec![2, b'a', b'y', 0];
+        body.extend(5u32.to_le_bytes());
+        body.extend(b"Home\xff");
+        assert_eq!(
+            reply_value(&reply("v", body), "Get"),
+            Ok(Value::Str("Home\u{fffd}".into()))
+        );
+        // DeviceType: variant с u, выровненным на 4
+        let mut w = Writer::default();
+        w.sig("u");
+        w.u32(8);
+        assert_eq!(reply_value(&reply("v", w.buf), "Get"), Ok(Value::U32(8)));
+        // GetDeviceByIpIface: o без variant
+        let mut w = Writer::default();
+        w.str("/org/freedesktop/NetworkManager/Devices/3");
+        assert_eq!(
+            reply_value(&reply("o", w.buf), "GetDeviceByIpIface"),
+            Ok(Value::Str(
+                "/org/freedesktop/NetworkManager/Devices/3".into()
+            ))
+        );
+        let mut w = Writer::default();
+        w.sig("a{sv}");
+        assert_eq!(
+            reply_value(&reply("v", w.buf), "Get"),
+            Err("Get: unsupported reply type 'a{sv}'".into())
+        );
+        assert_eq!(
+            reply_value(&reply("v", Vec::new()), "Get"),
+            Err("truncated reply".into())
+        );
+    }
+
+    #[test]
+    fn reads_big_endian_messages() {
+        let mut raw = vec![b'B', METHOD_CALL, 0, 1, 0, 0, 0, 4, 0, 0, 0, 9, 0, 0, 0, 23];
+        // member "Pause"
+        raw.extend([3, 1, b's', 0, 0, 0, 0, 5]);
+        raw.extend(b"Pause\0");
+        raw.extend([0, 0]);
+        // signature "u"
+        raw.extend([8, 1, b'g', 0, 1, b'u', 0]);
+        raw.push(0);
+        raw.extend(30u32.to_be_bytes());
+        assert_eq!(message_len(&raw), Some(raw.len()));
+        let m = read_message(&mut raw.as_slice()).unwrap();
+        assert!(m.big_endian);
+        assert_eq!((m.serial, m.member.as_str()), (9, "Pause"));
+        assert_eq!(m.signature, "u");
+        let mut rd = Reader {
+            data: &m.body,
+            pos: 0,
+            big: true,
+        };
+        assert_eq!(rd.u32(), Some(30));
+    }
+
+    #[test]
+    fn rejects_truncated_and_oversized_messages() {
+        let raw = call("Resume", "", &[]);
+        for cut in [0, 10, 16, raw.len() - 1] {
+            assert!(read_message(&mut &raw[..cut]).is_none(), "cut at {}", cut);
+        }
+        assert_eq!(message_len(&raw[..15]), None);
+        // Длина тела 4 ГБ: ни выделять, ни ждать
+        let mut huge = raw.clone();
+        huge[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(message_len(&huge), None);
+        assert!(read_message(&mut huge.as_slice()).is_none());
+        // Длина поля заголовка больше самого заголовка
+        let mut bad = raw.clone();
+        bad[12..16].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(read_message(&mut bad.as_slice()).is_none());
+        // Строка длиннее данных
+        let mut rd = Reader {
+            data: &[0xff, 0xff, 0, 0, b'a'],
+            pos: 0,
+            big: false,
+        };
+        assert!(rd.str().is_none());
+    }
+}
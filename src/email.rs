@@ -0,0 +1,381 @@
+// === ПОЧТА (SMTP) ===
+// Для сервера/NAS без рабочего стола и телефона под рукой: письмо, когда
+// пропал свет, и еще одно, если отключение затянулось дольше
+// prolonged_outage_min. Отправляет curl (smtps:// - TLS сразу, smtp:// -
+// STARTTLS обязателен), тема и текст - шаблоны с {{host}}, {{message}},
+// {{time}}, {{event}}, {{duration_min}}, {{suppressed}}.
+// Мигающий линк не должен заваливать ящик: писем не чаще min_interval_min,
+// пропущенные считаются и упоминаются в следующем.
+
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use crate::events::{Event, Subscriber};
+use crate::schedule::TimeZone;
+use crate::{channels, curl, hostname, log, unix_now};
+
+const RETRIES: u32 = 3;
+// Интернет часто пропадает вместе со светом: повторы через 1, 2, 4 минуты
+const RETRY_SEC: u64 = 60;
+const DEFAULT_SUBJECT: &str = "[portal_daemon] {{host}}: {{message}}";
+const DEFAULT_BODY: &str = "{{message}}\n\nHost: {{host}}\nTime: {{time}}\n";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EmailConfig {
+    // "smtps://smtp.example.com:465" или "smtp://smtp.example.com:587"; пусто - выключено
+    pub smtp_url: String,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    pub prolonged_outage_min: u64,
+    pub min_interval_min: u64,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            smtp_url: String::new(),
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            to: Vec::new(),
+            subject: String::new(),
+            body: String::new(),
+            prolonged_outage_min: 60,
+            min_interval_min: 30,
+        }
+    }
+}
+
+impl EmailConfig {
+    pub fn enabled(&self) -> bool {
+        !self.smtp_url.is_empty() && !self.to.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.smtp_url.starts_with("smtp://") && !self.smtp_url.starts_with("smtps://") {
+            return Err(format!("smtp_url '{}' is not smtp(s)://", self.smtp_url));
+        }
+        if self.from.is_empty() {
+            return Err("from is empty".into());
+        }
+        Ok(())
+    }
+}
+
+pub struct EmailText {
+    pub lost: String,
+    pub outage: String,
+}
+
+pub struct Email {
+    cfg: EmailConfig,
+    text: EmailText,
+    tz: TimeZone,
+    // Начало текущего отключения (UNIX) и отправлено ли письмо "затянулось"
+    outage_since: Option<u64>,
+    prolonged_sent: bool,
+    last_sent: u64,
+    suppressed: u32,
+}
+
+impl Email {
+    pub fn new(cfg: &EmailConfig, text: EmailText, tz: TimeZone) -> Self {
+        Self {
+            cfg: cfg.clone(),
+            text,
+            tz,
+            outage_since: None,
+            prolonged_sent: false,
+            last_sent: 0,
+            suppressed: 0,
+        }
+    }
+
+    // Лимит - только для connection_lost: "затянулось" и так одно на отключение
+    fn send(&mut self, event: &str, message: String, duration_min: u64, limited: bool) {
+        let now = unix_now();
+        if limited && self.last_sent > 0 && now < self.last_sent + self.cfg.min_interval_min * 60 {
+            self.suppressed += 1;
+            log::debug!(event = event, suppressed = self.suppressed; "email rate-limited");
+            return;
+        }
+        let vars = [
+            ("event", event.to_string()),
+            ("host", hostname()),
+            ("message", message),
+            ("time", self.tz.to_local(now as i64).to_string()),
+            ("duration_min", duration_min.to_string()),
+            ("suppressed", self.suppressed.to_string()),
+        ];
+        let tpl =
+            |t: &str, default: &'static str| render(if t.is_empty() { default } else { t }, &vars);
+        let subject = tpl(&self.cfg.subject, DEFAULT_SUBJECT);
+        let mut body = tpl(&self.cfg.body, DEFAULT_BODY);
+        if self.suppressed > 0 {
+            body += &format!("\n({} earlier alerts suppressed)\n", self.suppressed);
+        }
+        self.last_sent = now;
+        self.suppressed = 0;
+        let mail = message_text(&self.cfg, &subject, &body, now);
+        let cfg = self.cfg.clone();
+        // Цикл демона ждать не должен; уснем - повтор после подъема
+        thread::spawn(move || {
+            let res = deliver(&cfg, &mail, RETRIES);
+            channels::record("email", &res);
+        });
+    }
+}
+
+// Самопроверка при старте: одно письмо, одна попытка
+pub fn self_test(cfg: &EmailConfig, message: &str) {
+    let now = unix_now();
+    let subject = format!("[portal_daemon] {}", message);
+    let mail = message_text(cfg, &subject, &format!("{}\n", message), now);
+    channels::record("email", &deliver(cfg, &mail, 0));
+}
+
+impl Subscriber for Email {
+    fn on_event(&mut self, e: &Event) {
+        let now = unix_now();
+        match e {
+            // Повторный ConnectionLost после подъема в темноте - то же отключение
+            Event::ConnectionLost { grace_sec } if self.outage_since.is_none() => {
+                self.outage_since = Some(now);
+                let msg = format!("{} {} sec", self.text.lost, grace_sec);
+                self.send("connection_lost", msg, 0, true);
+                return;
+            }
+            // Маяк ответил - свет есть, отключение кончилось
+            Event::Probe(r) if r.ok => self.outage_since = None,
+            _ => {}
+        }
+        if self.outage_since.is_none() {
+            self.prolonged_sent = false;
+        }
+        // Проверяем на любом событии: пробы в grace, подъемы между снами
+        if let Some(since) = self.outage_since
+            && !self.prolonged_sent
+            && now >= since + self.cfg.prolonged_outage_min * 60
+        {
+            self.prolonged_sent = true;
+            let mins = (now - since) / 60;
+            let msg = format!("{} {} min", self.text.outage, mins);
+            self.send("prolonged_outage", msg, mins, false);
+        }
+    }
+}
+
+fn render(tpl: &str, vars: &[(&str, String)]) -> String {
+    let mut out = tpl.to_string();
+    for (k, v) in vars {
+        out = out.replace(&format!("{{{{{}}}}}", k), v);
+    }
+    out
+}
+
+// RFC 5322 с темой в RFC 2047 (UTF-8, base64)
+fn message_text(cfg: &EmailConfig, subject: &str, body: &str, now: u64) -> String {
+    let subject = subject.replace(['\r', '\n'], " ");
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: =?UTF-8?B?{}?=\r\nDate: {}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n{}",
+        cfg.from,
+        cfg.to.join(", "),
+        base64(subject.as_bytes()),
+        rfc2822(now),
+        body.replace("\r\n", "\n").replace('\n', "\r\n")
+    )
+}
+
+fn rfc2822(ts: u64) -> String {
+    const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let l = TimeZone::utc().to_local(ts as i64);
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} +0000",
+        DAYS[l.weekday as usize % 7],
+        l.day,
+        MONTHS[(l.month as usize + 11) % 12],
+        l.year,
+        l.hour,
+        l.minute,
+        l.second
+    )
+}
+
+fn base64(data: &[u8]) -> String {
+    const ABC: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for c in data.chunks(3) {
+        let n = (c[0] as u32) << 16
+            | (*c.get(1).unwrap_or(&0) as u32) << 8
+            | *c.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= c.len() {
+                out.push(ABC[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn deliver(cfg: &EmailConfig, mail: &str, retries: u32) -> Result<(), String> {
+    let mut delay = RETRY_SEC;
+    let mut last = String::new();
+    // Пароль и URL (в нем бывает user:pass@) - конфигом curl, не в argv
+    let mut secrets = curl::Config::default();
+    secrets.url(&cfg.smtp_url);
+    if !cfg.username.is_empty() {
+        secrets.set("user", &format!("{}:{}", cfg.username, cfg.password));
+    }
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            thread::sleep(Duration::from_secs(delay));
+            delay *= 2;
+        }
+        let mut c = Command::new("curl");
+        c.args(["-fsS", "--max-time", "60"])
+            .args(["--ssl-reqd", "--mail-from", &cfg.from]);
+        for rcpt in &cfg.to {
+            c.args(["--mail-rcpt", rcpt]);
+        }
+        c.args(["--upload-file", "-"]);
+        match curl::output(&mut c, &secrets, Some(mail.as_bytes())) {
+            Ok(o) if o.status.success() => {
+                log::info!(to = cfg.to.join(","); "📧 Email sent");
+                return Ok(());
+            }
+            Ok(o) => {
+                last = String::from_utf8_lossy(&o.stderr).trim().to_string();
+                log::debug!(attempt = attempt + 1; "email failed: {}", last);
+            }
+            Err(e) => {
+                log::warn!("⚠️  Email needs curl: {}", e);
+                return Err(format!("curl: {}", e));
+            }
+        }
+    }
+    log::warn!("⚠️  Email failed after {} attempts", retries + 1);
+    Err(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::ProbeResult;
+
+    fn cfg() -> EmailConfig {
+        EmailConfig {
+            smtp_url: "smtps://smtp.example.com:465".into(),
+            from: "ups@example.com".into(),
+            to: vec!["a@example.com".into(), "b@example.com".into()],
+            ..EmailConfig::default()
+        }
+    }
+
+    #[test]
+    fn base64_matches_rfc4648() {
+        let b = |s: &str| base64(s.as_bytes());
+        assert_eq!(b(""), "");
+        assert_eq!(b("f"), "Zg==");
+        assert_eq!(b("fo"), "Zm8=");
+        assert_eq!(b("foo"), "Zm9v");
+        assert_eq!(b("foob"), "Zm9vYg==");
+        assert_eq!(b("foobar"), "Zm9vYmFy");
+        assert_eq!(b("Свет пропал"), "0KHQstC10YIg0L/RgNC+0L/QsNC7");
+    }
+
+    #[test]
+    fn message_has_encoded_subject_and_crlf_lines() {
+        let mail = message_text(
+            &cfg(),
+            "Свет пропал\r\nBcc: x@evil",
+            "one\ntwo\r\nthree\n",
+            951_782_400 + 3723,
+        );
+        let (head, body) = mail.split_once("\r\n\r\n").unwrap();
+        let headers: Vec<&str> = head.split("\r\n").collect();
+        assert_eq!(headers[0], "From: ups@example.com");
+        assert_eq!(headers[1], "To: a@example.com, b@example.com");
+        // Перевод строки в теме не становится новым заголовком
+        assert_eq!(
+            headers[2],
+            format!(
+                "Subject: =?UTF-8?B?{}?=",
+                base64("Свет пропал  Bcc: x@evil".as_bytes())
+            )
+        );
+        assert_eq!(headers[3], "Date: Tue, 29 Feb 2000 01:02:03 +0000");
+        assert!(!headers.iter().any(|h| h.starts_with("Bcc")));
+        assert_eq!(body, "one\r\ntwo\r\nthree\r\n");
+    }
+
+    #[test]
+    fn templates_and_validation() {
+        let vars = [
+            ("host", "nas".to_string()),
+            ("duration_min", "75".to_string()),
+        ];
+        assert_eq!(
+            render("{{host}}: {{duration_min}} min, {{unknown}}", &vars),
+            "nas: 75 min, {{unknown}}"
+        );
+
+        assert_eq!(cfg().validate(), Ok(()));
+        let http = EmailConfig {
+            smtp_url: "https://smtp.example.com".into(),
+            ..cfg()
+        };
+        assert!(http.validate().is_err());
+        let anonymous = EmailConfig {
+            from: String::new(),
+            ..cfg()
+        };
+        assert_eq!(anonymous.validate(), Err("from is empty".into()));
+        assert!(
+            !EmailConfig {
+                to: vec![],
+                ..cfg()
+            }
+            .enabled()
+        );
+    }
+
+    #[test]
+    fn flapping_link_is_rate_limited() {
+        let text = EmailText {
+            lost: "lost".into(),
+            outage: "outage".into(),
+        };
+        let mut email = Email::new(&cfg(), text, TimeZone::utc());
+        // Письмо только что ушло - следующие в пределах min_interval_min копятся
+        email.last_sent = unix_now();
+        for _ in 0..3 {
+            email.on_event(&Event::ConnectionLost { grace_sec: 30 });
+            email.on_event(&Event::Probe(ProbeResult {
+                ok: true,
+                ..ProbeResult::default()
+            }));
+        }
+        assert_eq!(email.suppressed, 3);
+        assert_eq!(email.outage_since, None);
+        assert!(!email.prolonged_sent);
+
+        // Повторный ConnectionLost в том же отключении - не новое письмо
+        email.on_event(&Event::ConnectionLost { grace_sec: 30 });
+        email.on_event(&Event::ConnectionLost { grace_sec: 30 });
+        assert_eq!(email.suppressed, 4);
+        assert!(email.outage_since.is_some());
+    }
+}
@@ -0,0 +1,105 @@
+// === ОШИБКИ ===
+// Установка, удаление и мастер: вместо паники с backtrace - одна понятная
+// строка и код выхода из sysexits.h, по которому скрипт провижининга
+// поймет, что случилось. Display - вручную: thiserror в зависимостях нет.
+// Команды не выходят сами: ошибка доходит до main, и выход только там.
+
+use std::io;
+
+use crate::{EX_CANTCREAT, EX_CONFIG, EX_IOERR, EX_NOPERM, EX_SOFTWARE, EX_UNAVAILABLE, EX_USAGE};
+
+#[derive(Debug)]
+pub enum PortalError {
+    // Нужен root: "Install", "Uninstall"...
+    NotRoot(&'static str),
+    // Не тот способ запуска
+    Usage(&'static str),
+    // Файл или каталог не записать (только для чтения, нет прав)
+    Write { path: String, source: io::Error },
+    // Внешняя программа не запустилась - обычно ее нет в PATH
+    Tool { name: String, source: io::Error },
+    // Проверка отклонила то, что мы сгенерировали (visudo)
+    Rejected { what: &'static str, detail: String },
+    // Терминал пропал посреди вопроса мастера
+    Prompt(dialoguer::Error),
+    // Мастер не смог сохранить конфиг (сводка с причиной уже выведена)
+    NotSaved(String),
+    // Конфиг не прочитать или он не прошел проверку
+    Config(String),
+    // Сокет не открыть, сеть отвалилась (маяк, маячок)
+    Io(String),
+    // Команда уже все сказала на языке пользователя, остался только код:
+    // status - 3 (демон не запущен), ctl --all - 2 (ответили не все)...
+    Reported(i32),
+}
+
+impl PortalError {
+    pub fn write(path: impl Into<String>, source: io::Error) -> Self {
+        PortalError::Write {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            PortalError::NotRoot(_) => EX_NOPERM,
+            PortalError::Usage(_) => EX_USAGE,
+            PortalError::Write { .. } => EX_CANTCREAT,
+            PortalError::Tool { .. } => EX_UNAVAILABLE,
+            PortalError::Rejected { .. } => EX_SOFTWARE,
+            PortalError::Prompt(_) | PortalError::Io(_) => EX_IOERR,
+            PortalError::NotSaved(_) => EX_CANTCREAT,
+            PortalError::Config(_) => EX_CONFIG,
+            PortalError::Reported(code) => *code,
+        }
+    }
+
+    pub fn exit(self) -> ! {
+        if !matches!(self, PortalError::Reported(_)) {
+            eprintln!("❌ Error: {}", self);
+        }
+        std::process::exit(self.exit_code());
+    }
+}
+
+impl std::fmt::Display for PortalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PortalError::NotRoot(what) => write!(f, "{} must be run as root (sudo/doas)!", what),
+            PortalError::Usage(msg) => f.write_str(msg),
+            PortalError::Write { path, source } => write!(f, "cannot write {}: {}", path, source),
+            PortalError::Tool { name, source } if source.kind() == io::ErrorKind::NotFound => {
+                write!(f, "{} not found - install it or add it to PATH", name)
+            }
+            PortalError::Tool { name, source } => write!(f, "cannot run {}: {}", name, source),
+            PortalError::Rejected { what, detail } => write!(f, "{} rejected: {}", what, detail),
+            PortalError::Prompt(e) => write!(f, "cannot read the answer: {}", e),
+            PortalError::NotSaved(path) => write!(f, "config was not saved to {}", path),
+            PortalError::Config(msg) | PortalError::Io(msg) => f.write_str(msg),
+            PortalError::Reported(code) => write!(f, "exit status {}", code),
+        }
+    }
+}
+
+impl std::error::Error for PortalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PortalError::Write { source, .. } | PortalError::Tool { source, .. } => Some(source),
+            PortalError::Prompt(e) => Some(e),
+            PortalError::NotRoot(_)
+            | PortalError::Usage(_)
+            | PortalError::Rejected { .. }
+            | PortalError::NotSaved(_)
+            | PortalError::Config(_)
+            | PortalError::Io(_)
+            | PortalError::Reported(_) => None,
+        }
+    }
+}
+
+impl From<dialoguer::Error> for PortalError {
+    fn from(e: dialoguer::Error) -> Self {
+        PortalError::Prompt(e)
+    }
+}
@@ -0,0 +1,118 @@
+// === ШИНА СОБЫТИЙ ДЕМОНА ===
+// Цикл демона только сообщает, что произошло; состояние, счетчики, история
+// задержек и прочие потребители подписываются на шину сами. Каждое событие
+// получают все подписчики по порядку подписки. Потоку достаточно подписать
+// mpsc::Sender<Event> и читать из своего Receiver.
+
+use std::sync::mpsc::Sender;
+
+use crate::probe::ProbeResult;
+use crate::state::Phase;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Probe(ProbeResult),
+    // Смена фазы или новое решение в той же фазе
+    StateChanged {
+        phase: Phase,
+        reason: String,
+    },
+    // Свет пропал: повторная проверка через grace_sec
+    ConnectionLost {
+        grace_sec: u64,
+    },
+    // Напоминание по ходу grace: до сна осталось remaining_sec
+    GraceReminder {
+        remaining_sec: u64,
+    },
+    ConnectionRestored,
+    // Защита не дала уснуть: "process", "inhibitor", "quiet_hours", ...
+    SleepBlocked {
+        guard: &'static str,
+        reason: String,
+    },
+    SleepRequested {
+        seconds: u64,
+        mode: String,
+    },
+    // Заряд на пороге low_battery_percent: спим в disk
+    BatteryLow {
+        percent: u8,
+    },
+    // early_by_sec > 0 - проснулись раньше срока (cause - почему, если известно)
+    Woke {
+        slept_sec: u64,
+        early_by_sec: u64,
+        cause: Option<String>,
+        rearmed: bool,
+    },
+    // Столько подъемов подряд без света (give_up_after_wakes): выключаемся
+    // без будильника
+    GaveUp {
+        dark_wakes: u32,
+    },
+    // Часы RTC разошлись с системными (before_sleep) или подъем пришелся
+    // не на время будильника (after_wake): drift_sec = RTC - ожидание
+    RtcDrift {
+        drift_sec: i64,
+        after_wake: bool,
+    },
+    // Успешной пробы не было дольше probe_age_alert_sec (раз за эпизод)
+    ProbeStale {
+        age_sec: u64,
+    },
+    // Демон стартовал не с тем конфигом, что лежит в /etc
+    ConfigInvalid {
+        message: String,
+        restored: bool,
+    },
+}
+
+impl Event {
+    // Фаза, в которую событие переводит демон
+    pub fn phase(&self) -> Option<Phase> {
+        match self {
+            Event::StateChanged { phase, .. } => Some(*phase),
+            Event::ConnectionLost { .. } => Some(Phase::Grace),
+            Event::SleepRequested { .. } | Event::GaveUp { .. } => Some(Phase::Sleeping),
+            _ => None,
+        }
+    }
+}
+
+pub trait Subscriber {
+    fn on_event(&mut self, e: &Event);
+}
+
+// Для потоков: событие уходит в канал, отвалившийся получатель не мешает
+impl Subscriber for Sender<Event> {
+    fn on_event(&mut self, e: &Event) {
+        self.send(e.clone()).ok();
+    }
+}
+
+#[derive(Default)]
+pub struct Bus {
+    subscribers: Vec<Box<dyn Subscriber>>,
+    phase: Phase,
+}
+
+impl Bus {
+    pub fn subscribe(&mut self, s: impl Subscriber + 'static) {
+        self.subscribers.push(Box::new(s));
+    }
+
+    pub fn emit(&mut self, e: Event) {
+        if let Some(p) = e.phase() {
+            self.phase = p;
+        }
+        for s in &mut self.subscribers {
+            s.on_event(&e);
+        }
+    }
+
+    // Текущая фаза по последним событиям
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+}
@@ -0,0 +1,449 @@
+// === ФЛОТ: ОБЩАЯ ПАУЗА ДЛЯ ВСЕХ ДЕМОНОВ В СЕТИ ===
+// Протокол - одна UDP-датаграмма в каждую сторону:
+//   запрос: "PORTAL2 <команда> <unix-время> <nonce> <hmac>", команда -
+//           "PAUSE <минуты>" | "RESUME" | "PAUSE_UNTIL <unix-время>" |
+//           "WAKE_AT <unix-время> <период, сек>" (объявление лидера)
+//   ответ:  "PORTAL2 OK <host> <детали>"     | "PORTAL2 ERR <host> <причина>"
+// hmac - HMAC-SHA256(fleet_token, все до него через пробел), как у маячка:
+// сам токен в сеть не уходит. Старше MAX_SKEW_SEC и повторы не принимаются.
+// Демоны находятся по списку fleet_peers и широковещательным запросом.
+//
+// Общие пробуждения: роутер после отключения поднимается не сразу, и если
+// каждая машина просыпается в свое время, пробы идут всю ночь вразнобой.
+// Лидер, засыпая, объявляет, когда проснется и с каким периодом; фолловеры
+// спят до ближайшего слота этой сетки плюс свой fleet_wake_offset_sec.
+// Объявление доходит только до бодрствующих - сетка сходится с первого
+// общего окна (обычно grace в начале отключения), дальше держится сама:
+// фолловер просыпается, пока лидер еще не уснул и объявляет снова.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{
+    PAUSE_MINUTES_RANGE, PortalConfig, beacon, clear_pause, handoff, hostname, lighthouse, log,
+    reactor, set_pause, set_pause_until, unix_now,
+};
+
+const MAGIC: &str = "PORTAL2";
+// Часы флота сверены по NTP (на этом держится и PAUSE_UNTIL)
+const MAX_SKEW_SEC: u64 = 60;
+const REPLY_WAIT: Duration = Duration::from_secs(2);
+// Короче не спим ради выравнивания: слот вот-вот - берем следующий
+const MIN_ALIGNED_SLEEP_SEC: u64 = 60;
+// Лидер не объявлялся столько своих периодов - сетка устарела
+const LEADER_MISSED_PERIODS: u64 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Standalone,
+    Leader,
+    Follower,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LeaderSchedule {
+    wake_at: u64,
+    period: u64,
+}
+
+// Последнее объявление лидера (пишет поток слушателя)
+static LEADER: Mutex<Option<LeaderSchedule>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy)]
+pub enum FleetAction {
+    Pause(u64),
+    // Абсолютное время (UNIX), одинаковое для всех хостов с NTP
+    PauseUntil(u64),
+    Resume,
+}
+
+impl FleetAction {
+    fn command(&self) -> String {
+        match self {
+            FleetAction::Pause(m) => format!("PAUSE {}", m),
+            FleetAction::PauseUntil(ts) => format!("PAUSE_UNTIL {}", ts),
+            FleetAction::Resume => "RESUME".into(),
+        }
+    }
+}
+
+fn sign(key: &str, command: &str, ts: u64, nonce: &str) -> String {
+    let body = format!("{} {} {} {}", MAGIC, command, ts, nonce);
+    format!("{} {}", body, beacon::mac_hex(key, &body))
+}
+
+// Принятые запросы за окно свежести: (время, nonce). Один запрос приходит
+// дважды - напрямую и широковещательно, второй молча отбрасываем
+#[derive(Default)]
+struct Seen(HashSet<(u64, String)>);
+
+impl Seen {
+    // Ok(команда и аргументы) - запрос подписан нашим ключом и свежий
+    fn accept<'a>(
+        &mut self,
+        key: &str,
+        msg: &'a str,
+        now: u64,
+    ) -> Result<Vec<&'a str>, &'static str> {
+        let (body, mac) = msg.trim().rsplit_once(' ').ok_or("malformed")?;
+        let p: Vec<&str> = body.split(' ').collect();
+        let [MAGIC, command @ .., ts, nonce] = p.as_slice() else {
+            return Err("malformed");
+        };
+        let ts: u64 = ts.parse().map_err(|_| "malformed")?;
+        if command.is_empty() || nonce.is_empty() {
+            return Err("malformed");
+        }
+        if !beacon::verify(key, body, mac) {
+            return Err("bad-signature");
+        }
+        if ts.abs_diff(now) > MAX_SKEW_SEC {
+            return Err("stale");
+        }
+        self.0.retain(|(t, _)| t.abs_diff(now) <= MAX_SKEW_SEC);
+        if !self.0.insert((ts, nonce.to_string())) {
+            return Err("replayed");
+        }
+        Ok(command.to_vec())
+    }
+}
+
+pub fn listener_enabled(cfg: &PortalConfig) -> bool {
+    cfg.fleet_port != 0 && !cfg.fleet_token.is_empty()
+}
+
+// Источник цикла демона: принимает команды от `ctl --all`
+pub fn listen(cfg: &PortalConfig) {
+    let port = cfg.fleet_port;
+    // После обновления на месте - тот же сокет, если порт не сменился
+    let inherited = handoff::inherit("fleet")
+        .map(UdpSocket::from)
+        .filter(|s| s.local_addr().is_ok_and(|a| a.port() == port));
+    let sock = match inherited.map_or_else(|| UdpSocket::bind(("0.0.0.0", port)), Ok) {
+        Ok(s) => s,
+        Err(e) => {
+            log::warn!("⚠️  Fleet listener on UDP {} failed: {}", port, e);
+            return;
+        }
+    };
+    sock.set_nonblocking(true).ok();
+    handoff::register("fleet", sock.as_raw_fd());
+    log::info!("🛰  Fleet control listening on UDP {}", port);
+    reactor::add(Listener {
+        sock,
+        token: cfg.fleet_token.clone(),
+        host: hostname(),
+        seen: Seen::default(),
+    });
+}
+
+struct Listener {
+    sock: UdpSocket,
+    token: String,
+    host: String,
+    seen: Seen,
+}
+
+impl reactor::Source for Listener {
+    fn fd(&self) -> Option<RawFd> {
+        Some(self.sock.as_raw_fd())
+    }
+
+    fn ready(&mut self, _: bool, _: &mut reactor::Spawned) -> bool {
+        let mut buf = [0u8; 512];
+        let Ok((n, from)) = self.sock.recv_from(&mut buf) else {
+            return true;
+        };
+        let msg = String::from_utf8_lossy(&buf[..n]);
+        let reply = match handle_request(&msg, &self.token, &mut self.seen) {
+            Some(Ok(detail)) => {
+                log::info!("🛰  Fleet command from {}: {}", from.ip(), detail);
+                format!("{} OK {} {}", MAGIC, self.host, detail)
+            }
+            Some(Err(reason)) => format!("{} ERR {} {}", MAGIC, self.host, reason),
+            // Чужие пакеты (в т.ч. наши же ответы) молча игнорируем
+            None => return true,
+        };
+        self.sock.send_to(reply.as_bytes(), from).ok();
+        true
+    }
+}
+
+fn handle_request(msg: &str, key: &str, seen: &mut Seen) -> Option<Result<String, String>> {
+    let mut p = msg.split_whitespace();
+    if p.next()? != MAGIC {
+        return None;
+    }
+    if matches!(p.next()?, "OK" | "ERR") {
+        return None;
+    }
+    let command = match seen.accept(key, msg, unix_now()) {
+        Ok(c) => c,
+        Err("replayed") => return None,
+        Err(reason) => return Some(Err(reason.into())),
+    };
+    let [cmd, args @ ..] = command.as_slice() else {
+        return Some(Err("bad-command".into()));
+    };
+    match (*cmd, args) {
+        ("PAUSE", [m]) => match m.parse::<u64>() {
+            Ok(mins) if PAUSE_MINUTES_RANGE.contains(&mins) => Some(
+                set_pause(mins)
+                    .map(|_| format!("paused {} min", mins))
+                    .map_err(|e| e.to_string()),
+            ),
+            _ => Some(Err("bad-minutes".into())),
+        },
+        ("PAUSE_UNTIL", [ts]) => match ts.parse::<u64>() {
+            Ok(ts) if ts > unix_now() => Some(
+                set_pause_until(ts)
+                    .map(|_| format!("paused until {}", ts))
+                    .map_err(|e| e.to_string()),
+            ),
+            _ => Some(Err("bad-time".into())),
+        },
+        ("RESUME", []) => Some(
+            clear_pause()
+                .map(|_| "resumed".to_string())
+                .map_err(|e| e.to_string()),
+        ),
+        ("WAKE_AT", [at, period]) => match (at.parse::<u64>(), period.parse::<u64>()) {
+            (Ok(wake_at), Ok(period)) => {
+                if let Ok(mut l) = LEADER.lock() {
+                    *l = Some(LeaderSchedule { wake_at, period });
+                }
+                Some(Ok(format!(
+                    "leader wakes at {} every {} sec",
+                    wake_at, period
+                )))
+            }
+            _ => Some(Err("bad-schedule".into())),
+        },
+        _ => Some(Err("bad-command".into())),
+    }
+}
+
+pub struct HostReport {
+    pub name: String,
+    pub addr: Option<SocketAddr>,
+    pub result: Result<String, String>,
+}
+
+// Рассылает команду по списку и широковещательно, собирает ответы
+pub fn broadcast(cfg: &PortalConfig, action: FleetAction) -> Vec<HostReport> {
+    let mut reports = Vec::new();
+    let sock = match UdpSocket::bind("0.0.0.0:0") {
+        Ok(s) => s,
+        Err(e) => {
+            reports.push(HostReport {
+                name: "local".into(),
+                addr: None,
+                result: Err(e.to_string()),
+            });
+            return reports;
+        }
+    };
+    let payload = sign(
+        &cfg.fleet_token,
+        &action.command(),
+        unix_now(),
+        &lighthouse::nonce(),
+    );
+    let mut expected = send_all(&sock, cfg, &payload, &mut reports);
+
+    let deadline = Instant::now() + REPLY_WAIT;
+    // Хост может ответить дважды: на прямой и на широковещательный запрос
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut buf = [0u8; 512];
+    while let Some(left) = deadline.checked_duration_since(Instant::now())
+        && !left.is_zero()
+    {
+        sock.set_read_timeout(Some(left)).ok();
+        let Ok((n, from)) = sock.recv_from(&mut buf) else {
+            break;
+        };
+        let msg = String::from_utf8_lossy(&buf[..n]).to_string();
+        let mut p = msg.splitn(4, ' ');
+        if p.next() != Some(MAGIC) {
+            continue;
+        }
+        let status = p.next().unwrap_or("");
+        let host = p.next().unwrap_or("?").to_string();
+        let detail = p.next().unwrap_or("").to_string();
+        expected.remove(&from);
+        if !seen.insert(host.clone()) {
+            continue;
+        }
+        reports.push(HostReport {
+            name: host,
+            addr: Some(from),
+            result: if status == "OK" {
+                Ok(detail)
+            } else {
+                Err(detail)
+            },
+        });
+    }
+
+    for (addr, name) in expected {
+        reports.push(HostReport {
+            name,
+            addr: Some(addr),
+            result: Err("no response".into()),
+        });
+    }
+    reports
+}
+
+// Рассылает команду по fleet_peers и широковещательно; адрес -> имя из
+// конфига, чтобы потом сообщить о молчащих хостах
+fn send_all(
+    sock: &UdpSocket,
+    cfg: &PortalConfig,
+    payload: &str,
+    reports: &mut Vec<HostReport>,
+) -> HashMap<SocketAddr, String> {
+    sock.set_broadcast(true).ok();
+    let mut expected = HashMap::new();
+    for peer in &cfg.fleet_peers {
+        let target = if peer.contains(':') {
+            peer.clone()
+        } else {
+            format!("{}:{}", peer, cfg.fleet_port)
+        };
+        match target.to_socket_addrs().ok().and_then(|mut a| a.next()) {
+            Some(addr) => {
+                sock.send_to(payload.as_bytes(), addr).ok();
+                expected.insert(addr, peer.clone());
+            }
+            None => reports.push(HostReport {
+                name: peer.clone(),
+                addr: None,
+                result: Err("cannot resolve".into()),
+            }),
+        }
+    }
+    sock.send_to(payload.as_bytes(), ("255.255.255.255", cfg.fleet_port))
+        .ok();
+    expected
+}
+
+// Лидер перед сном: ответов не ждем - через секунду машина уже спит.
+// Подписано, как и команды: чужое объявление сетку фолловеров не сдвинет
+pub fn announce_wake(cfg: &PortalConfig, at: u64, period: u64) {
+    let Ok(sock) = UdpSocket::bind("0.0.0.0:0") else {
+        return;
+    };
+    let mut unresolved = Vec::new();
+    // Проснусь в at, дальше каждые period секунд
+    let payload = sign(
+        &cfg.fleet_token,
+        &format!("WAKE_AT {} {}", at, period),
+        unix_now(),
+        &lighthouse::nonce(),
+    );
+    send_all(&sock, cfg, &payload, &mut unresolved);
+    for r in unresolved {
+        log::debug!(peer = r.name; "fleet peer cannot be resolved");
+    }
+}
+
+// Сон фолловера до ближайшего слота сетки лидера (wake_at + k * period)
+// со сдвигом offset; None - объявлений не было или они устарели
+pub fn aligned_sleep(now: u64, offset: u64) -> Option<u64> {
+    let l = (*LEADER.lock().ok()?)?;
+    let earliest = now + MIN_ALIGNED_SLEEP_SEC;
+    let mut slot = l.wake_at + offset;
+    if slot < earliest {
+        if l.period == 0 {
+            return None;
+        }
+        let missed = (earliest - slot).div_ceil(l.period);
+        if missed > LEADER_MISSED_PERIODS {
+            return None;
+        }
+        slot += missed * l.period;
+    }
+    Some(slot - now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_only_fresh_signed_requests() {
+        let now = 1_791_936_000;
+        let mut seen = Seen::default();
+        let req = sign("s3cret", "PAUSE 30", now, "ab12");
+        assert!(!req.contains("s3cret"));
+        assert_eq!(
+            seen.accept("s3cret", &req, now + 5),
+            Ok(vec!["PAUSE", "30"])
+        );
+        assert_eq!(seen.accept("s3cret", &req, now + 5), Err("replayed"));
+        // Другой ключ, подмена команды, старый запрос
+        let forged = sign("guess", "RESUME", now, "cd34");
+        assert_eq!(seen.accept("s3cret", &forged, now), Err("bad-signature"));
+        let tampered = req.replace("PAUSE 30", "PAUSE 9999");
+        assert_eq!(seen.accept("s3cret", &tampered, now), Err("bad-signature"));
+        let old = sign("s3cret", "RESUME", now - 600, "ef56");
+        assert_eq!(seen.accept("s3cret", &old, now), Err("stale"));
+        assert_eq!(
+            seen.accept("s3cret", &format!("{} RESUME", MAGIC), now),
+            Err("malformed")
+        );
+        // Окно свежести прошло - старые nonce забываются
+        let later = sign("s3cret", "RESUME", now + 300, "ab12");
+        assert_eq!(seen.accept("s3cret", &later, now + 300), Ok(vec!["RESUME"]));
+        assert_eq!(seen.0.len(), 1);
+    }
+
+    #[test]
+    fn spoofed_leader_announcement_keeps_the_grid() {
+        let now = unix_now();
+        let mut seen = Seen::default();
+        let spoofed = sign("guess", "WAKE_AT 1 60", now, "ab12");
+        assert_eq!(
+            handle_request(&spoofed, "s3cret", &mut seen),
+            Some(Err("bad-signature".into()))
+        );
+        assert_eq!(aligned_sleep(now, 0), None);
+        let real = sign(
+            "s3cret",
+            &format!("WAKE_AT {} 1800", now + 600),
+            now,
+            "cd34",
+        );
+        assert!(matches!(
+            handle_request(&real, "s3cret", &mut seen),
+            Some(Ok(_))
+        ));
+        assert_eq!(aligned_sleep(now, 30), Some(630));
+        // Перехваченное объявление повторно ничего не сдвигает
+        assert_eq!(handle_request(&real, "s3cret", &mut seen), None);
+    }
+
+    #[test]
+    fn pause_minutes_are_bounded() {
+        let now = unix_now();
+        let mut seen = Seen::default();
+        for (mins, nonce) in [
+            ("0", "ab12"),
+            ("10081", "cd34"),
+            (&u64::MAX.to_string(), "ef56"),
+        ] {
+            let req = sign("s3cret", &format!("PAUSE {}", mins), now, nonce);
+            assert_eq!(
+                handle_request(&req, "s3cret", &mut seen),
+                Some(Err("bad-minutes".into()))
+            );
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// === FREEBSD ===
+// Тот же демон на FreeBSD (NAS на ZFS): вместо /proc/net/route -
+// route -n get default, вместо rtcwake - acpiconf (zzz как запасной),
+// служба - rc.d через daemon(8). Будильника RTC FreeBSD из userland не
+// ставит: машина спит до WoL, кнопки или возврата питания (BIOS), и
+// sleep_minutes/wake_times/resleep_minutes ничего не делают.
+// Только-Linux модули (netlink, ARP через AF_PACKET) собираются заглушками
+// из freebsd/; чтения /proc и /sys там просто ничего не находят: защиты по
+// диску и температуре, носители линка и GPIO-датчик молчат. Питание
+// ноутбука - hw.acpi.acline, источник ping - адрес интерфейса (-S).
+// Остальной код проверяется и в сборке под Linux.
+
+use std::net::IpAddr;
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::watchdog;
+
+// Замены модулей, которые есть только на Linux
+#[cfg(not(target_os = "linux"))]
+pub mod arp;
+#[cfg(not(target_os = "linux"))]
+pub mod netlink;
+
+pub const RC_SCRIPT: &str = "/usr/local/etc/rc.d/portal";
+// Сколько ждать, пока acpiconf в самом деле усыпит систему
+const SLEEP_WAIT_SEC: u64 = 30;
+
+pub fn is() -> bool {
+    cfg!(target_os = "freebsd")
+}
+
+// Маршруты по умолчанию IPv4 и IPv6: (интерфейс, шлюз)
+pub fn default_routes() -> Vec<(String, IpAddr)> {
+    ["-inet", "-inet6"]
+        .into_iter()
+        .filter_map(|family| {
+            let out = Command::new("route")
+                .args(["-n", "get", family, "default"])
+                .output()
+                .ok()?;
+            parse_route_get(&String::from_utf8_lossy(&out.stdout))
+        })
+        .collect()
+}
+
+// "    gateway: 192.168.1.1" / "  interface: em0"
+fn parse_route_get(text: &str) -> Option<(String, IpAddr)> {
+    let field = |name: &str| {
+        text.lines().find_map(|l| {
+            let (k, v) = l.split_once(':')?;
+            (k.trim() == name).then(|| v.trim().to_string())
+        })
+    };
+    let gateway = field("gateway")?;
+    // fe80::1%em0 - link-local, маяком не задать
+    let gateway = gateway.parse::<IpAddr>().ok()?;
+    Some((field("interface")?, gateway))
+}
+
+// hw.acpi.acline: 1 - от сети, 0 - от батареи; нет sysctl - нет ACPI-адаптера
+pub fn acline() -> Option<bool> {
+    let out = Command::new("sysctl")
+        .args(["-n", "hw.acpi.acline"])
+        .output()
+        .ok()?;
+    match String::from_utf8_lossy(&out.stdout).trim() {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
+// true - система спала и проснулась
+pub fn sleep(mode: &str) -> bool {
+    let cmd: &[&str] = match mode {
+        "disk" => &["acpiconf", "-s", "4"],
+        "off" => &["shutdown", "-p", "now"],
+        _ => &["acpiconf", "-s", "3"],
+    };
+    let run = |cmd: &[&str]| {
+        Command::new(cmd[0])
+            .args(&cmd[1..])
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+    let (wall, mono) = (SystemTime::now(), Instant::now());
+    if !run(cmd) && (mode == "off" || !run(&["zzz"])) {
+        return false;
+    }
+    // acpiconf только просит сон: ждем, пока настенные часы не уйдут
+    // вперед монотонных (во сне те стоят)
+    for _ in 0..SLEEP_WAIT_SEC {
+        watchdog::sleep(Duration::from_secs(1));
+        let slept = wall.elapsed().unwrap_or_default();
+        if slept > mono.elapsed() + Duration::from_secs(5) {
+            return true;
+        }
+    }
+    false
+}
+
+// rc.d: daemon(8) перезапускает упавший демон и пишет pid ребенка -
+// reload шлет ему USR2 (обновление на месте)
+pub fn rc_script(binary: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+
+# PROVIDE: portal
+# REQUIRE: NETWORKING
+# KEYWORD: shutdown
+
+. /etc/rc.subr
+
+name="portal"
+rcvar="portal_enable"
+pidfile="/var/run/${{name}}.pid"
+child_pidfile="/var/run/${{name}}_daemon.pid"
+command="/usr/sbin/daemon"
+command_args="-r -R 5 -P ${{pidfile}} -p ${{child_pidfile}} -S -T ${{name}} {}"
+extra_commands="reload"
+reload_cmd="${{name}}_reload"
+
+portal_reload()
+{{
+    kill -USR2 $(cat ${{child_pidfile}})
+}}
+
+load_rc_config $name
+: ${{portal_enable:="NO"}}
+
+run_rc_command "$1"
+"#,
+        binary
+    )
+}
@@ -0,0 +1,11 @@
+// === ARP НА FREEBSD: НЕТ ===
+// Пакетного сокета (AF_PACKET) нет, ARP там - через bpf(4). Err - и проба
+// откатывается на ping, как при нехватке прав на Linux.
+
+use std::io;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+pub fn request(_: Ipv4Addr, _: &str, _: Duration) -> io::Result<Option<f64>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
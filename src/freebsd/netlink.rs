@@ -0,0 +1,14 @@
+// === NETLINK НА FREEBSD: НЕТ ===
+// netlink - только Linux. Шлюзы FreeBSD берет из route get (freebsd.rs),
+// а линк проверяется перед каждой пробой, без событий от ядра.
+
+use std::io;
+use std::os::fd::OwnedFd;
+
+pub fn link_monitor() -> io::Result<OwnedFd> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+pub fn link_events(_: &OwnedFd, _: &mut [u8]) -> io::Result<Vec<u32>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
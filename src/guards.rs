@@ -0,0 +1,170 @@
+// === ЗАЩИТА ОТ СНА ===
+// Проверки прямо перед сном: если хоть одна находит причину, сон
+// откладывается и перепроверяется через inhibit_recheck_sec.
+
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::probe;
+use crate::{PortalConfig, inject, inverter, net, reactor};
+
+pub struct Blocker {
+    // Имя для счетчиков: "guard.<guard>"
+    pub guard: &'static str,
+    pub reason: String,
+}
+
+// Причина отложить сон или None
+pub fn sleep_blocker(cfg: &PortalConfig) -> Option<Blocker> {
+    if inject::take(inject::Fault::GuardBlock) {
+        return Some(Blocker {
+            guard: "injected",
+            reason: "injected by --inject guard-block".into(),
+        });
+    }
+    if cfg.inverter.enabled
+        && let Some(why) = inverter::reserve_left(cfg)
+    {
+        return Some(Blocker {
+            guard: "inverter",
+            reason: why,
+        });
+    }
+    if let Some(p) = running_inhibitor(&cfg.inhibit_processes) {
+        return Some(Blocker {
+            guard: "process",
+            reason: format!("process '{}' is running", p),
+        });
+    }
+    if cfg.respect_inhibitors
+        && let Some(lock) = blocking_inhibitor(&cfg.ignore_inhibitors)
+    {
+        return Some(Blocker {
+            guard: "inhibitor",
+            reason: format!("inhibitor lock by {}", lock),
+        });
+    }
+    if cfg.hotspot_guard
+        && cfg.probe.network()
+        && let Some(why) = hotspot(cfg)
+    {
+        return Some(Blocker {
+            guard: "hotspot",
+            reason: why,
+        });
+    }
+    if cfg.disk_io_threshold_mbps > 0.0 {
+        let rate = disk_io_mbps(cfg.disk_io_sample_sec);
+        if rate >= cfg.disk_io_threshold_mbps {
+            return Some(Blocker {
+                guard: "disk_io",
+                reason: format!("disk I/O {:.1} MB/s", rate),
+            });
+        }
+    }
+    None
+}
+
+// Маяк за раздачей с телефона: свет по нему не определить, только уведомляем
+pub fn hotspot(cfg: &PortalConfig) -> Option<String> {
+    let dev = probe::egress_dev(cfg)?;
+    net::tethered(&dev)
+}
+
+// Суммарный read+write по целым дискам (не разделам), МБ/с за окно
+pub fn disk_io_mbps(sample_sec: u64) -> f64 {
+    let started = Instant::now();
+    let a = disk_sectors();
+    reactor::pause(started + Duration::from_secs(sample_sec.max(1)));
+    let b = disk_sectors();
+    let secs = started.elapsed().as_secs_f64();
+    // Сектор в /proc/diskstats всегда 512 байт
+    (b.saturating_sub(a) as f64 * 512.0) / 1_000_000.0 / secs
+}
+
+fn disk_sectors() -> u64 {
+    let data = fs::read_to_string("/proc/diskstats").unwrap_or_default();
+    data.lines()
+        .filter_map(|l| {
+            let f: Vec<&str> = l.split_whitespace().collect();
+            let name = *f.get(2)?;
+            let whole = Path::new("/sys/block").join(name).exists();
+            if !whole || name.starts_with("loop") || name.starts_with("ram") {
+                return None;
+            }
+            let read: u64 = f.get(5)?.parse().ok()?;
+            let written: u64 = f.get(9)?.parse().ok()?;
+            Some(read + written)
+        })
+        .sum()
+}
+
+// Блокирующие (mode=block) локи logind на sleep/idle от других программ.
+// Без D-Bus/logind (OpenRC без elogind) просто ничего не находим.
+pub fn blocking_inhibitor(ignore: &[String]) -> Option<String> {
+    let out = reactor::output(Command::new("busctl").args([
+        "--json=short",
+        "call",
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+        "ListInhibitors",
+    ]))
+    .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let v: Value = serde_json::from_slice(&out.stdout).ok()?;
+    // {"type":"a(ssssuu)","data":[[[what, who, why, mode, uid, pid], ...]]}
+    let locks = v.get("data")?.get(0)?.as_array()?;
+    locks.iter().find_map(|l| {
+        let f = l.as_array()?;
+        let what = f.first()?.as_str()?;
+        let who = f.get(1)?.as_str()?;
+        let why = f.get(2)?.as_str().unwrap_or("");
+        let mode = f.get(3)?.as_str()?;
+        let relevant = what.split(':').any(|w| w == "sleep" || w == "idle");
+        if mode != "block" || !relevant || ignore.iter().any(|i| i == who) {
+            return None;
+        }
+        Some(format!("{} ({})", who, why))
+    })
+}
+
+// Имя первого запущенного процесса из списка (по comm или argv[0])
+pub fn running_inhibitor(names: &[String]) -> Option<String> {
+    if names.is_empty() {
+        return None;
+    }
+    let me = std::process::id().to_string();
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let pid = entry.file_name();
+        let pid = pid.to_string_lossy();
+        if !pid.bytes().all(|b| b.is_ascii_digit()) || pid == me {
+            continue;
+        }
+        let dir = entry.path();
+        let comm = fs::read_to_string(dir.join("comm")).unwrap_or_default();
+        let argv0 = fs::read(dir.join("cmdline"))
+            .ok()
+            .and_then(|c| {
+                let first = c.split(|b| *b == 0).next()?.to_vec();
+                let s = String::from_utf8_lossy(&first).to_string();
+                Path::new(&s)
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+            })
+            .unwrap_or_default();
+        // comm обрезается ядром до 15 символов
+        if let Some(n) = names.iter().find(|n| {
+            let n = n.as_str();
+            comm.trim() == n || argv0 == n || (n.len() > 15 && comm.trim() == &n[..15])
+        }) {
+            return Some(n.clone());
+        }
+    }
+    None
+}
@@ -0,0 +1,171 @@
+// === ОБНОВЛЕНИЕ НА МЕСТЕ (SIGUSR2) ===
+// После обновления пакета или self-update: `systemctl reload portal` (или
+// kill -USR2). Старый процесс в безопасной точке цикла сохраняет состояние в
+// /run/portal_daemon/handoff.json и делает exec нового бинарника с теми же
+// аргументами - PID тот же, systemd и OpenRC ничего не замечают.
+// Новый процесс продолжает с того же места: идущий grace досчитывается, а не
+// начинается заново (и без повторных уведомлений), время старта сохраняется,
+// сокеты fleet и HTTP наследуются открытыми - команды не теряются.
+// Пауза и счетчики и так лежат в файлах.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{log, paths, state, store, tui, unix_now};
+
+const HANDOFF_FILE: &str = "handoff.json";
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+// Открытые слушающие сокеты: (имя, fd) - их отдаем новому процессу
+static SOCKETS: Mutex<Vec<(String, RawFd)>> = Mutex::new(Vec::new());
+// Унаследованные, но еще не подобранные
+static INHERITED: Mutex<Vec<(String, RawFd)>> = Mutex::new(Vec::new());
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Handoff {
+    // Файл наш, только если PID совпадает: exec его не меняет
+    pub pid: u32,
+    pub started_at: u64,
+    // Конец идущего grace (UNIX); 0 - grace не шел
+    pub grace_until: u64,
+    pub sockets: Vec<(String, RawFd)>,
+}
+
+impl Handoff {
+    pub fn grace_left(&self) -> Option<u64> {
+        let now = unix_now();
+        (self.grace_until > now).then(|| self.grace_until - now)
+    }
+}
+
+pub fn listen_sigusr2() {
+    unsafe { libc::signal(libc::SIGUSR2, on_usr2 as *const () as libc::sighandler_t) };
+}
+
+extern "C" fn on_usr2(_: libc::c_int) {
+    REQUESTED.store(true, Ordering::Relaxed);
+    // Прервать ожидание: цикл дойдет до requested() сразу
+    tui::check_now();
+}
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+}
+
+// При старте: состояние от предыдущего процесса, если это exec после обновления
+pub fn take() -> Option<Handoff> {
+    let data = fs::read_to_string(paths::run(HANDOFF_FILE)).ok()?;
+    fs::remove_file(paths::run(HANDOFF_FILE)).ok();
+    let h: Handoff = serde_json::from_str(&data).ok()?;
+    // Остался от процесса, который так и не сделал exec - дескрипторы не наши
+    if h.pid != std::process::id() {
+        return None;
+    }
+    if let Ok(mut inherited) = INHERITED.lock() {
+        *inherited = h.sockets.clone();
+    }
+    Some(h)
+}
+
+// Унаследованный сокет по имени; владение переходит вызывающему
+pub fn inherit(name: &str) -> Option<OwnedFd> {
+    let mut inherited = INHERITED.lock().ok()?;
+    let i = inherited.iter().position(|(n, _)| n == name)?;
+    let (_, fd) = inherited.remove(i);
+    set_cloexec(fd, true);
+    Some(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+// Новый конфиг сокет больше не использует - закрыть
+pub fn close_unclaimed() {
+    if let Ok(mut inherited) = INHERITED.lock() {
+        for (_, fd) in inherited.drain(..) {
+            drop(unsafe { OwnedFd::from_raw_fd(fd) });
+        }
+    }
+}
+
+pub fn register(name: &str, fd: RawFd) {
+    if let Ok(mut s) = SOCKETS.lock() {
+        s.push((name.to_string(), fd));
+    }
+}
+
+// Из безопасной точки цикла. Возвращается, только если обновиться не вышло -
+// тогда старый процесс просто работает дальше
+pub fn exec(grace_left: Option<u64>) {
+    REQUESTED.store(false, Ordering::Relaxed);
+    let exe = match std::env::current_exe() {
+        Ok(p) => p,
+        Err(e) => {
+            log::error!("❌ Upgrade aborted: cannot find executable ({})", e);
+            return;
+        }
+    };
+    // Пакет заменил файл: /proc/self/exe указывает на "... (deleted)"
+    let path = exe.to_string_lossy();
+    let exe = path.strip_suffix(" (deleted)").unwrap_or(&path).to_string();
+    // Битый или не того формата бинарник не должен получить управление
+    let runs = Command::new(&exe)
+        .arg("--version")
+        .output()
+        .is_ok_and(|o| o.status.success());
+    if !runs {
+        log::error!("❌ Upgrade aborted: {} --version failed", exe);
+        return;
+    }
+
+    let sockets = SOCKETS.lock().map(|s| s.clone()).unwrap_or_default();
+    let h = Handoff {
+        pid: std::process::id(),
+        started_at: state::read_state().map_or(0, |s| s.started_at),
+        grace_until: grace_left.map_or(0, |l| unix_now() + l),
+        sockets: sockets.clone(),
+    };
+    let Ok(json) = serde_json::to_string(&h) else {
+        return;
+    };
+    if let Err(e) = fs::write(paths::run(HANDOFF_FILE), json) {
+        log::error!(
+            "❌ Upgrade aborted: cannot write {}: {}",
+            paths::run(HANDOFF_FILE),
+            e
+        );
+        return;
+    }
+    for (_, fd) in &sockets {
+        set_cloexec(*fd, false);
+    }
+    log::info!(exe = exe; "♻️  Upgrading in place");
+    // Буфер истории "memory" живет в памяти этого процесса
+    store::sync();
+    tui::restore();
+    let err = Command::new(&exe).args(std::env::args_os().skip(1)).exec();
+    // exec вернулся - значит, не вышло; работаем как раньше
+    tui::reenter();
+    for (_, fd) in &sockets {
+        set_cloexec(*fd, true);
+    }
+    fs::remove_file(paths::run(HANDOFF_FILE)).ok();
+    log::error!("❌ Upgrade failed: exec {}: {}", exe, err);
+}
+
+fn set_cloexec(fd: RawFd, on: bool) {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags >= 0 {
+            let flags = if on {
+                flags | libc::FD_CLOEXEC
+            } else {
+                flags & !libc::FD_CLOEXEC
+            };
+            libc::fcntl(fd, libc::F_SETFD, flags);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+// === ИСТОРИЯ (/var/lib/portal_daemon) ===
+// Задержка до маяка пишется не каждой пробой, а агрегатами за bucket_sec:
+// тренд RTT полезен для диагностики просадок и умирающих роутеров, а
+// файл при этом растет на пару сотен строк в сутки.
+// События (свет пропал/вернулся, сон, подъем, пауза) пишутся по одному в
+// events.jsonl - по ним `history events` восстанавливает, когда не было света.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::events::{Event, Subscriber};
+use crate::state::Phase;
+use crate::store::{self, Table};
+use crate::unix_now;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LatencyBucket {
+    pub ts: u64,
+    pub probes: u32,
+    pub lost: u32,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+pub struct LatencyRecorder {
+    bucket_sec: u64,
+    cur: LatencyBucket,
+    sum: f64,
+}
+
+impl LatencyRecorder {
+    pub fn new(bucket_sec: u64, retention_days: u64) -> Self {
+        compact(Table::Latency, retention_days);
+        Self {
+            bucket_sec: bucket_sec.max(10),
+            cur: LatencyBucket::default(),
+            sum: 0.0,
+        }
+    }
+
+    fn record(&mut self, rtt_ms: Option<f64>) {
+        let now = unix_now();
+        if self.cur.probes > 0 && now >= self.cur.ts + self.bucket_sec {
+            self.flush();
+        }
+        if self.cur.probes == 0 {
+            self.cur.ts = now - now % self.bucket_sec;
+        }
+        self.cur.probes += 1;
+        match rtt_ms {
+            Some(ms) => {
+                let ok = self.cur.probes - self.cur.lost;
+                if ok == 1 || ms < self.cur.min_ms {
+                    self.cur.min_ms = ms;
+                }
+                if ms > self.cur.max_ms {
+                    self.cur.max_ms = ms;
+                }
+                self.sum += ms;
+                self.cur.avg_ms = self.sum / ok as f64;
+            }
+            None => self.cur.lost += 1,
+        }
+    }
+
+    // Сбросить незаконченный агрегат (например, перед сном)
+    fn flush(&mut self) {
+        if self.cur.probes == 0 {
+            return;
+        }
+        append_line(Table::Latency, self.cur.ts, &self.cur);
+        self.cur = LatencyBucket::default();
+        self.sum = 0.0;
+    }
+}
+
+// Подписывается только при ping-пробе: у остальных RTT нет
+impl Subscriber for LatencyRecorder {
+    fn on_event(&mut self, e: &Event) {
+        match e {
+            Event::Probe(r) => self.record(r.rtt_ms),
+            // Перед сном сбрасываем неполный агрегат, чтобы не потерять
+            Event::SleepRequested { .. } | Event::GaveUp { .. } => self.flush(),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventRecord {
+    pub ts: u64,
+    // connection_lost, connection_restored, sleep, wake, pause, resume, ...
+    pub event: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+}
+
+pub struct EventRecorder {
+    phase: Phase,
+}
+
+impl EventRecorder {
+    pub fn new(retention_days: u64) -> Self {
+        compact(Table::Events, retention_days);
+        Self {
+            phase: Phase::default(),
+        }
+    }
+
+    fn record(&self, event: &str, detail: String) {
+        let r = EventRecord {
+            ts: unix_now(),
+            event: event.into(),
+            detail,
+        };
+        append_line(Table::Events, r.ts, &r);
+    }
+}
+
+impl Subscriber for EventRecorder {
+    fn on_event(&mut self, e: &Event) {
+        let prev = self.phase;
+        if let Some(p) = e.phase() {
+            self.phase = p;
+        }
+        match e {
+            Event::ConnectionLost { grace_sec } => {
+                self.record("connection_lost", format!("grace {} sec", grace_sec))
+            }
+            Event::ConnectionRestored => self.record("connection_restored", String::new()),
+            Event::SleepRequested { seconds, mode } => {
+                self.record("sleep", format!("{} min, {}", seconds.div_ceil(60), mode));
+                // Сон может кончиться и без подъема: отложенное - на диск
+                store::sync();
+            }
+            Event::Woke {
+                slept_sec,
+                early_by_sec,
+                cause,
+                ..
+            } => {
+                let mut detail = format!("slept {} min", slept_sec / 60);
+                if *early_by_sec > 0 {
+                    detail += &format!(
+                        ", {} min early ({})",
+                        early_by_sec.div_ceil(60),
+                        cause.as_deref().unwrap_or("unknown cause")
+                    );
+                }
+                self.record("wake", detail)
+            }
+            Event::SleepBlocked { guard, reason } => {
+                self.record("sleep_blocked", format!("{}: {}", guard, reason))
+            }
+            Event::BatteryLow { percent } => self.record("battery_low", format!("{}%", percent)),
+            Event::GaveUp { dark_wakes } => {
+                self.record("gave_up", format!("{} wakes without light", dark_wakes));
+                store::sync();
+            }
+            Event::RtcDrift {
+                drift_sec,
+                after_wake,
+            } => {
+                let when = if *after_wake {
+                    "after wake"
+                } else {
+                    "before sleep"
+                };
+                self.record("rtc_drift", format!("{:+} sec {}", drift_sec, when))
+            }
+            Event::ConfigInvalid { message, .. } => self.record("config_invalid", message.clone()),
+            Event::StateChanged { phase, reason } if *phase != prev => match (prev, phase) {
+                (_, Phase::Paused) => self.record("pause", String::new()),
+                (Phase::Paused, _) => self.record("resume", String::new()),
+                // Проснулись, а маяк отвечает - свет вернулся, пока спали
+                (Phase::Sleeping, Phase::Monitoring) => {
+                    self.record("connection_restored", reason.clone())
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+pub fn read_events(since: u64) -> Vec<EventRecord> {
+    read_lines(Table::Events, since, |r: &EventRecord| r.ts)
+}
+
+// Отрезки без света: от первого connection_lost до connection_restored.
+// Незакрытый отрезок (света нет до сих пор) - с концом None
+pub fn outages(events: &[EventRecord]) -> Vec<(u64, Option<u64>)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for e in events {
+        match e.event.as_str() {
+            "connection_lost" if start.is_none() => start = Some(e.ts),
+            "connection_restored" => {
+                if let Some(s) = start.take() {
+                    out.push((s, Some(e.ts)));
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        out.push((s, None));
+    }
+    out
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OutageStats {
+    pub outages: u32,
+    pub dark_sec: u64,
+    pub longest_sec: u64,
+    pub sleeps: u32,
+}
+
+impl OutageStats {
+    pub fn avg_sec(&self) -> u64 {
+        self.dark_sec / self.outages.max(1) as u64
+    }
+}
+
+// Итог и разбивка по группам (key - день/неделя/месяц от метки времени).
+// Отключение целиком идет в группу, где оно началось; незакрытое - до now
+pub fn stats(
+    events: &[EventRecord],
+    now: u64,
+    key: impl Fn(u64) -> String,
+) -> (OutageStats, Vec<(String, OutageStats)>) {
+    let mut total = OutageStats::default();
+    let mut groups: BTreeMap<String, OutageStats> = BTreeMap::new();
+    for (start, end) in outages(events) {
+        let secs = end.unwrap_or(now).saturating_sub(start);
+        for s in [&mut total, groups.entry(key(start)).or_default()] {
+            s.outages += 1;
+            s.dark_sec += secs;
+            s.longest_sec = s.longest_sec.max(secs);
+        }
+    }
+    for e in events.iter().filter(|e| e.event == "sleep") {
+        total.sleeps += 1;
+        groups.entry(key(e.ts)).or_default().sleeps += 1;
+    }
+    (total, groups.into_iter().collect())
+}
+
+pub fn read_latency(since: u64) -> Vec<LatencyBucket> {
+    read_lines(Table::Latency, since, |b: &LatencyBucket| b.ts)
+}
+
+fn append_line<T: Serialize>(t: Table, ts: u64, item: &T) {
+    if let Ok(line) = serde_json::to_string(item) {
+        store::with(|s| s.append(t, ts, &line));
+    }
+}
+
+fn read_lines<T: for<'de> Deserialize<'de>>(t: Table, since: u64, ts: fn(&T) -> u64) -> Vec<T> {
+    store::with(|s| s.read(t, since))
+        .iter()
+        .filter_map(|l| serde_json::from_str::<T>(l).ok())
+        .filter(|x| ts(x) >= since)
+        .collect()
+}
+
+// Выкидывает записи старше retention_days
+fn compact(t: Table, retention_days: u64) {
+    if retention_days == 0 {
+        return;
+    }
+    let cutoff = unix_now().saturating_sub(retention_days * 86400);
+    store::with(|s| s.compact(t, cutoff));
+}
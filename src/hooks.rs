@@ -0,0 +1,82 @@
+// === ХУКИ ВОКРУГ СНА ===
+// Команды из pre_sleep_hooks / post_wake_hooks выполняются через `sh -c`
+// по очереди, каждая со своим таймаутом. Вывод идет в лог демона.
+// Пока хук идет, цикл событий обслуживает HTTP, D-Bus и команды чатов.
+
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::{log, reactor};
+
+#[derive(Debug, Clone, Copy)]
+pub enum HookStage {
+    PreSleep,
+    PostWake,
+}
+
+impl HookStage {
+    fn name(&self) -> &'static str {
+        match self {
+            HookStage::PreSleep => "pre-sleep",
+            HookStage::PostWake => "post-wake",
+        }
+    }
+}
+
+// true - все хуки завершились успешно
+pub fn run_hooks(stage: HookStage, hooks: &[String], timeout_sec: u64, sleep_sec: u64) -> bool {
+    if crate::dry_run() {
+        for cmd in hooks {
+            log::info!("🧪 [{}] would run: {}", stage.name(), cmd);
+        }
+        return true;
+    }
+    let mut ok = true;
+    for cmd in hooks {
+        let started = Instant::now();
+        log::info!("🪝 [{}] $ {}", stage.name(), cmd);
+        let child = Command::new("sh")
+            .args(["-c", cmd])
+            .env("PORTAL_EVENT", stage.name())
+            .env("PORTAL_SLEEP_SECONDS", sleep_sec.to_string())
+            .spawn();
+        let mut child = match child {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("❌ [{}] cannot start hook: {}", stage.name(), e);
+                ok = false;
+                continue;
+            }
+        };
+
+        let deadline = started + Duration::from_secs(timeout_sec.max(1));
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(s)) => break Some(s),
+                Ok(None) if Instant::now() < deadline => {
+                    crate::watchdog::pet();
+                    reactor::pause(Instant::now() + Duration::from_millis(100))
+                }
+                Ok(None) => {
+                    child.kill().ok();
+                    child.wait().ok();
+                    break None;
+                }
+                Err(_) => break None,
+            }
+        };
+        let took = started.elapsed().as_secs_f32();
+        match status {
+            Some(s) if s.success() => log::info!(hook = cmd; "   ✅ done in {:.1}s", took),
+            Some(s) => {
+                ok = false;
+                log::error!(hook = cmd; "   ❌ exit {} after {:.1}s", s.code().unwrap_or(-1), took);
+            }
+            None => {
+                ok = false;
+                log::warn!(hook = cmd; "   ⏱  killed after {}s timeout", timeout_sec);
+            }
+        }
+    }
+    ok
+}
@@ -0,0 +1,180 @@
+// === HTTP: /healthz И /status ===
+// Для систем мониторинга (Uptime Kuma, blackbox_exporter, curl в скрипте):
+// жив ли демон и что он делает, без разбора журнала и без D-Bus.
+//   GET /healthz - 200 "ok" или 503, если цикл демона опаздывает с pet()
+//   GET /status  - тот же JSON, что `status --json`
+//   GET /recent  - последние строки журнала из памяти (JSON-массив)
+//   GET /metrics - формат Prometheus: главное - возраст последней удачной пробы
+// Слушаем только 127.0.0.1; по соединению на запрос, Connection: close.
+// Отвечает цикл демона (reactor.rs): пока идет проба, запрос ждет секунды,
+// а вставший намертво цикл не ответит совсем - для мониторинга это тот же 503.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
+
+use crate::{handoff, log, reactor, status_json, status_report, watchdog};
+
+// Сколько цикл может молчать сверх обещанного (проба, защиты, хуки)
+const STALL_SLACK_SEC: u64 = 120;
+
+pub fn listen(port: u16) {
+    let inherited = handoff::inherit("http")
+        .map(TcpListener::from)
+        .filter(|l| l.local_addr().is_ok_and(|a| a.port() == port));
+    let listener = match inherited.map_or_else(|| TcpListener::bind(("127.0.0.1", port)), Ok) {
+        Ok(l) => l,
+        Err(e) => {
+            log::warn!("⚠️  HTTP endpoint on 127.0.0.1:{} failed: {}", port, e);
+            return;
+        }
+    };
+    listener.set_nonblocking(true).ok();
+    handoff::register("http", listener.as_raw_fd());
+    log::info!("🩺 Health endpoint on http://127.0.0.1:{}/healthz", port);
+    reactor::add(Listener(listener));
+}
+
+struct Listener(TcpListener);
+
+impl reactor::Source for Listener {
+    fn fd(&self) -> Option<RawFd> {
+        Some(self.0.as_raw_fd())
+    }
+
+    fn ready(&mut self, _: bool, spawned: &mut reactor::Spawned) -> bool {
+        if let Ok((stream, _)) = self.0.accept() {
+            stream.set_nonblocking(true).ok();
+            spawned.push(Box::new(Conn {
+                stream,
+                buf: Vec::new(),
+                until: Instant::now() + Duration::from_secs(2),
+            }));
+        }
+        true
+    }
+}
+
+// Соединение копит запрос между вызовами: медленный клиент не держит цикл
+struct Conn {
+    stream: TcpStream,
+    buf: Vec<u8>,
+    until: Instant,
+}
+
+impl reactor::Source for Conn {
+    fn fd(&self) -> Option<RawFd> {
+        Some(self.stream.as_raw_fd())
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        Some(self.until)
+    }
+
+    fn ready(&mut self, readable: bool, _: &mut reactor::Spawned) -> bool {
+        let mut chunk = [0u8; 2048];
+        let eof = match readable.then(|| self.stream.read(&mut chunk)) {
+            Some(Ok(0)) | None => true,
+            Some(Ok(n)) => {
+                self.buf.extend_from_slice(&chunk[..n]);
+                false
+            }
+            Some(Err(e)) => e.kind() != ErrorKind::WouldBlock,
+        };
+        let complete = self.buf.len() >= 2048 || self.buf.windows(4).any(|w| w == b"\r\n\r\n");
+        if !eof && !complete {
+            return true;
+        }
+        // Ответ в пару сотен байтов целиком ложится в буфер сокета
+        self.stream.set_nonblocking(false).ok();
+        self.stream
+            .set_write_timeout(Some(Duration::from_secs(2)))
+            .ok();
+        self.stream.write_all(&reply(&self.buf)).ok();
+        false
+    }
+}
+
+fn reply(buf: &[u8]) -> Vec<u8> {
+    let req = String::from_utf8_lossy(buf);
+    let mut line = req.lines().next().unwrap_or("").split_whitespace();
+    let method = line.next().unwrap_or("");
+    let path = line.next().unwrap_or("").split('?').next().unwrap_or("");
+
+    let (status, kind, body) = match (method, path) {
+        ("GET" | "HEAD", "/healthz") => match watchdog::overdue() {
+            late if late > STALL_SLACK_SEC => (
+                "503 Service Unavailable",
+                "text/plain",
+                format!("stalled {} sec\n", late),
+            ),
+            _ => ("200 OK", "text/plain", "ok\n".to_string()),
+        },
+        ("GET" | "HEAD", "/status") => ("200 OK", "application/json", status_json() + "\n"),
+        ("GET" | "HEAD", "/metrics") => ("200 OK", "text/plain; version=0.0.4", metrics()),
+        ("GET" | "HEAD", "/recent") => {
+            let body = serde_json::to_string(&log::recent()).unwrap_or_default();
+            ("200 OK", "application/json", body + "\n")
+        }
+        ("GET" | "HEAD", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+    let mut reply = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n",
+        status,
+        kind,
+        body.len()
+    );
+    if status.starts_with("405") {
+        reply.push_str("Allow: GET, HEAD\r\n");
+    }
+    reply.push_str("\r\n");
+    if method != "HEAD" {
+        reply.push_str(&body);
+    }
+    reply.into_bytes()
+}
+
+fn metrics() -> String {
+    let r = status_report();
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, v: f64| {
+        out.push_str(&format!(
+            "# HELP {0} {1}\n# TYPE {0} gauge\n{0} {2}\n",
+            name, help, v
+        ));
+    };
+    let Some(st) = &r.daemon else {
+        return out;
+    };
+    if let Some(age) = r.last_ok_age_sec {
+        gauge(
+            "portal_last_success_age_seconds",
+            "Seconds since the last successful probe",
+            age as f64,
+        );
+    }
+    gauge(
+        "portal_probe_ok",
+        "Whether the last probe succeeded",
+        st.last_probe_ok as u8 as f64,
+    );
+    if let Some(rtt) = st.last_rtt_ms {
+        gauge(
+            "portal_rtt_milliseconds",
+            "Round-trip time of the last successful probe",
+            rtt,
+        );
+    }
+    gauge(
+        "portal_paused",
+        "Whether sleep is paused by the user",
+        r.pause_until.is_some() as u8 as f64,
+    );
+    out
+}
@@ -0,0 +1,209 @@
+// === ICMP ECHO БЕЗ ПРОЦЕССА ping ===
+// На одноплатнике fork+exec ping (а с ним и sh у хуков) каждые 30 секунд
+// заметен и по CPU, и по числу пробуждений. Эхо шлем сами: сначала
+// непривилегированный сокет (SOCK_DGRAM + IPPROTO_ICMP, разрешается через
+// net.ipv4.ping_group_range), потом raw (root или CAP_NET_RAW). Не открылся
+// ни один - Err, и проба откатывается на /bin/ping.
+// Буферы на стеке, сокет живет одну пробу (или одну серию): в цикле
+// ожидания ни одной аллокации.
+
+use std::io;
+use std::mem;
+use std::net::IpAddr;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{net, reactor};
+
+const ECHO_REQUEST: u8 = 8;
+const ECHO_REPLY: u8 = 0;
+const ECHO_REQUEST_V6: u8 = 128;
+const ECHO_REPLY_V6: u8 = 129;
+
+// Данных в эхо-запросе: до MTU 1500 вместе с заголовками IPv4 и ICMP
+pub const MAX_PAYLOAD: usize = 1472;
+const PAYLOAD: &[u8] = b"portal_d";
+
+static SEQ: AtomicU16 = AtomicU16::new(0);
+
+// Серия из count эхо разом, ответы ждем вместе: серия стоит один timeout,
+// а не count. RTT в миллисекундах по порядку запросов, None - ответа не
+// было за timeout. dev - прибить пакет к интерфейсу (SO_BINDTODEVICE), как
+// ping -I; size - байт данных (как ping -s), не больше MAX_PAYLOAD
+pub fn echo(
+    ip: IpAddr,
+    dev: Option<&str>,
+    timeout: Duration,
+    size: usize,
+    count: usize,
+) -> io::Result<Vec<Option<f64>>> {
+    let v6 = ip.is_ipv6();
+    let (sock, raw) = open(v6)?;
+    let fd = sock.as_raw_fd();
+    if let Some(d) = dev {
+        bind_to_device(fd, d, ip)?;
+    }
+
+    // У DGRAM-сокета идентификатор подставит ядро и отфильтрует чужие
+    // ответы само; raw видит весь ICMP машины - сверяем и id, и seq
+    let id = (std::process::id() as u16).to_be_bytes();
+    let first = SEQ.fetch_add(count as u16, Ordering::Relaxed);
+    let mut out = [0u8; 8 + MAX_PAYLOAD];
+    let pkt = &mut out[..8 + size.min(MAX_PAYLOAD)];
+    pkt[0] = if v6 { ECHO_REQUEST_V6 } else { ECHO_REQUEST };
+    pkt[4..6].copy_from_slice(&id);
+    for (b, p) in pkt[8..].iter_mut().zip(PAYLOAD.iter().cycle()) {
+        *b = *p;
+    }
+
+    let (addr, len) = sockaddr(ip);
+    let mut sent = vec![None; count];
+    let mut rtt = vec![None; count];
+    for (k, at) in sent.iter_mut().enumerate() {
+        pkt[6..8].copy_from_slice(&first.wrapping_add(k as u16).to_be_bytes());
+        // ICMPv6 считает ядро (псевдозаголовок ему виднее)
+        if !v6 {
+            pkt[2..4].fill(0);
+            let sum = checksum(pkt);
+            pkt[2..4].copy_from_slice(&sum.to_be_bytes());
+        }
+        let n = unsafe {
+            libc::sendto(
+                fd,
+                pkt.as_ptr() as *const libc::c_void,
+                pkt.len(),
+                0,
+                &addr as *const libc::sockaddr_storage as *const libc::sockaddr,
+                len,
+            )
+        };
+        // Нет маршрута, интерфейс лежит - это "ответа нет", а не поломка пробы
+        if n >= 0 {
+            *at = Some(Instant::now());
+        }
+    }
+
+    let reply = if v6 { ECHO_REPLY_V6 } else { ECHO_REPLY };
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 1500];
+    let mut left = sent.iter().flatten().count();
+    // Ждем поллом цикла событий: источники демона тем временем обслуживаются
+    while left > 0 && reactor::wait_readable(fd, deadline) {
+        let mut from: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut from_len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        let n = unsafe {
+            libc::recvfrom(
+                fd,
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                libc::MSG_DONTWAIT,
+                &mut from as *mut libc::sockaddr_storage as *mut libc::sockaddr,
+                &mut from_len,
+            )
+        };
+        if n <= 0 {
+            continue;
+        }
+        let mut msg = &buf[..n as usize];
+        // raw IPv4 отдает пакет вместе с IP-заголовком
+        if raw && !v6 {
+            let ihl = (msg[0] & 0x0f) as usize * 4;
+            msg = msg.get(ihl..).unwrap_or_default();
+        }
+        if msg.len() < 8 || msg[0] != reply || (raw && msg[4..6] != id) {
+            continue;
+        }
+        let k = u16::from_be_bytes([msg[6], msg[7]]).wrapping_sub(first) as usize;
+        let Some(Some(at)) = sent.get(k) else {
+            continue;
+        };
+        if rtt[k].is_some()
+            || net::sockaddr_ip(&from as *const libc::sockaddr_storage as *const libc::sockaddr)
+                != Some(ip)
+        {
+            continue;
+        }
+        rtt[k] = Some(at.elapsed().as_secs_f64() * 1000.0);
+        left -= 1;
+    }
+    Ok(rtt)
+}
+
+#[cfg(target_os = "linux")]
+fn bind_to_device(fd: RawFd, dev: &str, _: IpAddr) -> io::Result<()> {
+    let r = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            dev.as_ptr() as *const libc::c_void,
+            dev.len() as libc::socklen_t,
+        )
+    };
+    if r != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// SO_BINDTODEVICE нет: источник - адрес интерфейса, маршрут выберет ядро
+#[cfg(not(target_os = "linux"))]
+fn bind_to_device(fd: RawFd, dev: &str, to: IpAddr) -> io::Result<()> {
+    let src = net::dev_addr(dev, to).ok_or(io::ErrorKind::AddrNotAvailable)?;
+    let (addr, len) = sockaddr(src);
+    let r = unsafe { libc::bind(fd, &addr as *const _ as *const libc::sockaddr, len) };
+    if r != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// (сокет, raw ли он)
+fn open(v6: bool) -> io::Result<(OwnedFd, bool)> {
+    let (domain, proto) = if v6 {
+        (libc::AF_INET6, libc::IPPROTO_ICMPV6)
+    } else {
+        (libc::AF_INET, libc::IPPROTO_ICMP)
+    };
+    let mut err = None;
+    for (kind, raw) in [(libc::SOCK_DGRAM, false), (libc::SOCK_RAW, true)] {
+        let fd = unsafe { libc::socket(domain, kind | libc::SOCK_CLOEXEC, proto) };
+        if fd >= 0 {
+            return Ok((unsafe { OwnedFd::from_raw_fd(fd) }, raw));
+        }
+        err = Some(io::Error::last_os_error());
+    }
+    Err(err.unwrap_or_else(|| io::Error::from(io::ErrorKind::Unsupported)))
+}
+
+fn sockaddr(ip: IpAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut ss: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match ip {
+        IpAddr::V4(v4) => {
+            let sin = unsafe { &mut *(&mut ss as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_addr.s_addr = u32::from_ne_bytes(v4.octets());
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        IpAddr::V6(v6) => {
+            let sin6 = unsafe { &mut *(&mut ss as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_addr.s6_addr = v6.octets();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (ss, len as libc::socklen_t)
+}
+
+// RFC 1071: дополнение до единицы суммы 16-битных слов
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
@@ -0,0 +1,103 @@
+// === ВНЕДРЕНИЕ ОТКАЗОВ (ДЛЯ ПРОВЕРКИ НА ЖИВОЙ МАШИНЕ) ===
+// Скрытый флаг --inject (или PORTAL_INJECT): проверить защиты, откаты и
+// уведомления от начала до конца, не выдергивая роутер из розетки.
+//   probe-fail[=N]   N проб подряд "света нет" (без N - всегда)
+//   rtcwake-fail[=N] N вызовов rtcwake падают, машина не засыпает
+//   guard-block[=N]  N проверок защит подряд откладывают сон
+//   battery=PCT      заряд батареи PCT% (откат на disk, SMS)
+// Отказы детерминированные: счетчик уменьшается на каждом срабатывании.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::log;
+
+// 0 - выключено, u64::MAX - без ограничения
+static PROBE_FAIL: AtomicU64 = AtomicU64::new(0);
+static RTCWAKE_FAIL: AtomicU64 = AtomicU64::new(0);
+static GUARD_BLOCK: AtomicU64 = AtomicU64::new(0);
+// u64::MAX - не подменять
+static BATTERY: AtomicU64 = AtomicU64::new(u64::MAX);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Fault {
+    ProbeFail,
+    RtcwakeFail,
+    GuardBlock,
+}
+
+impl Fault {
+    fn counter(self) -> &'static AtomicU64 {
+        match self {
+            Fault::ProbeFail => &PROBE_FAIL,
+            Fault::RtcwakeFail => &RTCWAKE_FAIL,
+            Fault::GuardBlock => &GUARD_BLOCK,
+        }
+    }
+}
+
+// "probe-fail=3,rtcwake-fail" - из флагов и переменной окружения
+pub fn configure(specs: &[String]) -> Result<(), String> {
+    let env = std::env::var("PORTAL_INJECT").unwrap_or_default();
+    let all: Vec<&str> = specs
+        .iter()
+        .map(String::as_str)
+        .chain([env.as_str()])
+        .flat_map(|s| s.split(','))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    for spec in &all {
+        let (name, value) = match spec.split_once('=') {
+            Some((n, v)) => (n, Some(v)),
+            None => (*spec, None),
+        };
+        let count = || match value {
+            None => Ok(u64::MAX),
+            Some(v) => v
+                .parse::<u64>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("bad count in '{}'", spec)),
+        };
+        match name {
+            "probe-fail" => PROBE_FAIL.store(count()?, Ordering::Relaxed),
+            "rtcwake-fail" => RTCWAKE_FAIL.store(count()?, Ordering::Relaxed),
+            "guard-block" => GUARD_BLOCK.store(count()?, Ordering::Relaxed),
+            "battery" => {
+                let pct = value
+                    .and_then(|v| v.parse::<u8>().ok())
+                    .filter(|p| *p <= 100)
+                    .ok_or_else(|| format!("'{}' needs battery=0..100", spec))?;
+                BATTERY.store(pct as u64, Ordering::Relaxed);
+            }
+            _ => return Err(format!("unknown fault '{}'", name)),
+        }
+    }
+    if !all.is_empty() {
+        log::warn!(faults = all.join(","); "🧪 Fault injection active");
+    }
+    Ok(())
+}
+
+// true - этот вызов должен отказать
+pub fn take(f: Fault) -> bool {
+    let c = f.counter();
+    let left = c
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| match n {
+            0 => None,
+            u64::MAX => Some(n),
+            n => Some(n - 1),
+        })
+        .is_ok();
+    if left {
+        log::warn!(fault = format!("{:?}", f); "🧪 Injected failure");
+    }
+    left
+}
+
+pub fn battery() -> Option<u8> {
+    match BATTERY.load(Ordering::Relaxed) {
+        u64::MAX => None,
+        p => Some(p as u8),
+    }
+}
@@ -0,0 +1,136 @@
+// === ИНВЕРТОРНЫЙ ДОМ ===
+// Дом на инверторе с аккумуляторами: пропала сеть - это норма, машина
+// работает от батареи часами. Спать стоит не по потере маяка, а когда
+// запас кончается: пока заряд (и оставшееся время) выше порога, сон
+// откладывается защитой "inverter". Ночью солнца нет - порог обычно выше.
+// Заряд - из NUT (battery.charge / battery.runtime, так отдают себя и
+// многие инверторы) или из батареи самой машины (sysfs).
+
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::{TimeWindow, TimeZone};
+use crate::{PortalConfig, log, power, probe, unix_now};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    // nut_address / nut_ups из общего конфига
+    #[default]
+    Nut,
+    Battery,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct InverterConfig {
+    pub enabled: bool,
+    pub source: Source,
+    // Спим, когда заряд опустился до порога (или время работы - до своего;
+    // 0 - время не учитывать)
+    pub day_min_percent: u8,
+    pub night_min_percent: u8,
+    pub day_min_runtime_min: u64,
+    pub night_min_runtime_min: u64,
+    // Когда действуют ночные пороги, формат quiet_hours
+    pub night: String,
+}
+
+impl Default for InverterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source: Source::Nut,
+            day_min_percent: 30,
+            night_min_percent: 50,
+            day_min_runtime_min: 0,
+            night_min_runtime_min: 0,
+            night: "22:00-07:00".into(),
+        }
+    }
+}
+
+impl InverterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, v) in [
+            ("day_min_percent", self.day_min_percent),
+            ("night_min_percent", self.night_min_percent),
+        ] {
+            if v > 100 {
+                return Err(format!("{} = {} is above 100", name, v));
+            }
+        }
+        TimeWindow::parse(&self.night).ok_or_else(|| format!("invalid night '{}'", self.night))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Reading {
+    percent: Option<u8>,
+    runtime_min: Option<u64>,
+}
+
+fn read(cfg: &PortalConfig) -> Result<Reading, String> {
+    match cfg.inverter.source {
+        Source::Nut => {
+            let var = |name| probe::nut_var(&cfg.nut_address, &cfg.nut_ups, name);
+            let percent = var("battery.charge")?.parse::<f64>().ok().map(|p| p as u8);
+            // battery.runtime есть не у всех, в секундах
+            let runtime_min = var("battery.runtime")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(|s| s as u64 / 60);
+            Ok(Reading {
+                percent,
+                runtime_min,
+            })
+        }
+        Source::Battery => Ok(Reading {
+            percent: power::battery_percent(),
+            runtime_min: power::battery_runtime_min(),
+        }),
+    }
+}
+
+// Почему спать рано (запас еще есть) или None - пора спать, либо заряд
+// не узнать: тогда решает обычная логика по маяку
+pub fn reserve_left(cfg: &PortalConfig) -> Option<String> {
+    let inv = &cfg.inverter;
+    let r = match read(cfg) {
+        Ok(r) => r,
+        Err(e) => {
+            log::warn!(source = format!("{:?}", inv.source); "⚠️  Inverter battery unknown: {}", e);
+            return None;
+        }
+    };
+    let tz = TimeZone::resolve(cfg.timezone.as_deref()).unwrap_or_else(|_| TimeZone::utc());
+    let night =
+        TimeWindow::parse(&inv.night).is_some_and(|w| w.contains(&tz.to_local(unix_now() as i64)));
+    let (min_pct, min_runtime, label) = if night {
+        (inv.night_min_percent, inv.night_min_runtime_min, "night")
+    } else {
+        (inv.day_min_percent, inv.day_min_runtime_min, "day")
+    };
+    let pct_ok = r.percent.map(|p| p > min_pct);
+    let runtime_ok = r
+        .runtime_min
+        .filter(|_| min_runtime > 0)
+        .map(|m| m > min_runtime);
+    match (pct_ok, runtime_ok) {
+        (None, None) => None,
+        (Some(false), _) | (_, Some(false)) => None,
+        _ => Some(match (r.percent, r.runtime_min) {
+            (Some(p), Some(m)) => format!(
+                "inverter battery {}% ({} min left), {} threshold {}%",
+                p, m, label, min_pct
+            ),
+            (Some(p), None) => format!("inverter battery {}%, {} threshold {}%", p, label, min_pct),
+            (None, m) => format!(
+                "inverter runtime {} min, {} threshold {} min",
+                m.unwrap_or_default(),
+                label,
+                min_runtime
+            ),
+        }),
+    }
+}
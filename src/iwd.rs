@@ -0,0 +1,73 @@
+// === IWD (D-BUS) ===
+// Arch, Alpine, postmarketOS часто живут без NetworkManager: Wi-Fi ведет iwd.
+// Его объекты - /net/connman/iwd/<адаптер>/<устройство>; ObjectManager
+// отдает a{oa{sa{sv}}}, который наш D-Bus не разбирает, поэтому дерево
+// обходим через Introspect. Шлюз iwd не хранит (DHCP может быть и у
+// networkd/dhcpcd) - берем маршрут по умолчанию из ядра.
+
+use crate::dbus::Client;
+use crate::net::{self, Connection};
+
+const IWD: &str = "net.connman.iwd";
+const IWD_PATH: &str = "/net/connman/iwd";
+const DEVICE: &str = "net.connman.iwd.Device";
+const STATION: &str = "net.connman.iwd.Station";
+const NETWORK: &str = "net.connman.iwd.Network";
+
+pub fn connections() -> Result<Vec<Connection>, String> {
+    let mut c = Client::system()?;
+    let mut out = Vec::new();
+    for adapter in children(&mut c, IWD_PATH)? {
+        for dev in children(&mut c, &adapter)? {
+            // Не станция (точка доступа, ad-hoc) или не подключена
+            let Ok(state) = c.property(IWD, &dev, STATION, "State") else {
+                continue;
+            };
+            if state.into_str().as_deref() != Some("connected") {
+                continue;
+            }
+            let Some(network) = c
+                .property(IWD, &dev, STATION, "ConnectedNetwork")?
+                .into_str()
+            else {
+                continue;
+            };
+            let name = c
+                .property(IWD, &network, NETWORK, "Name")?
+                .into_str()
+                .unwrap_or_default();
+            let device = c
+                .property(IWD, &dev, DEVICE, "Name")?
+                .into_str()
+                .unwrap_or_default();
+            let gateway = net::default_gateway(&device).unwrap_or_default();
+            out.push(Connection {
+                name,
+                device,
+                gateway,
+            });
+        }
+    }
+    Ok(out)
+}
+
+// Дочерние объекты: <node name="0"/> в XML Introspect
+fn children(c: &mut Client, path: &str) -> Result<Vec<String>, String> {
+    let xml = c
+        .call(
+            IWD,
+            path,
+            "org.freedesktop.DBus.Introspectable",
+            "Introspect",
+            &[],
+        )?
+        .into_str()
+        .unwrap_or_default();
+    Ok(xml
+        .split("<node name=\"")
+        .skip(1)
+        .filter_map(|s| s.split('"').next())
+        .filter(|n| !n.is_empty())
+        .map(|n| format!("{}/{}", path, n))
+        .collect())
+}
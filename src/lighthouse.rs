@@ -0,0 +1,402 @@
+// === МАЯК (portal_daemon lighthouse) ===
+// Вторая сторона проб в том же бинарнике: запускается на машине без ИБП
+// (Pi в обычной розетке). Пока она отвечает - в доме есть свет.
+//   UDP   "PORTALH1 PING <nonce> <ts> <hmac>"
+//      -> "PORTALH1 PONG <host> <nonce> <ts> <hmac>"              (probe = "udp")
+//   HTTP  GET /health?nonce=..&ts=..&mac=.. -> та же строка PONG  (probe = "http")
+//   маячки beacon.rs, если задан beacon.key                       (probe = "beacon")
+// HTTP и UDP слушают один порт lighthouse.port; демон спрашивает lighthouse_ip.
+// hmac - как у маячков; nonce случайный у каждой пробы, поэтому записанный
+// ответ не повторить, а без ключа "свет есть" не подделать. Ключ пуст -
+// без подписей: "PING <nonce>" -> "PONG <host> <nonce>", GET /health -> "ok".
+// Себя маяк объявляет по mDNS как <host>._portal._udp.local (lighthouse.mdns).
+
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::os::fd::AsRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{PortalConfig, beacon, hostname, mdns, reactor, unix_now};
+
+const MAGIC: &str = "PORTALH1";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LighthouseConfig {
+    pub port: u16,
+    // Где слушает `portal_daemon lighthouse`
+    pub bind: String,
+    // Общий ключ подписи проб; пусто - тот же, что beacon.key
+    pub key: String,
+    pub max_skew_sec: u64,
+    // Сервер: объявлять себя по mDNS (mdns.rs)
+    pub mdns: bool,
+    // Демон: имя маяка в mDNS; адрес сменился - ищем по нему
+    pub name: String,
+}
+
+impl Default for LighthouseConfig {
+    fn default() -> Self {
+        Self {
+            port: 47476,
+            bind: "0.0.0.0".into(),
+            key: String::new(),
+            max_skew_sec: 60,
+            mdns: true,
+            name: String::new(),
+        }
+    }
+}
+
+impl LighthouseConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("port must be set".into());
+        }
+        Ok(())
+    }
+}
+
+// Ключ проб: свой или от маячков (одно устройство - один секрет)
+pub fn key(cfg: &PortalConfig) -> &str {
+    match cfg.lighthouse.key.as_str() {
+        "" => &cfg.beacon.key,
+        k => k,
+    }
+}
+
+// --- СЕРВЕР ---
+pub fn serve(cfg: &PortalConfig) -> Result<(), String> {
+    let lh = &cfg.lighthouse;
+    let addr = (lh.bind.as_str(), lh.port);
+    let tcp = TcpListener::bind(addr).map_err(|e| format!("TCP {}:{}: {}", lh.bind, lh.port, e))?;
+    let udp = UdpSocket::bind(addr).map_err(|e| format!("UDP {}:{}: {}", lh.bind, lh.port, e))?;
+    let host = hostname();
+    println!(
+        "🗼 Lighthouse {} on {}:{}: http://…/health, UDP ping",
+        host, lh.bind, lh.port
+    );
+    let server = Server {
+        key: key(cfg).to_string(),
+        max_skew_sec: lh.max_skew_sec,
+        host,
+    };
+    if server.key.is_empty() {
+        println!("⚠️  No lighthouse.key or beacon.key: answers are unsigned");
+    }
+    if lh.mdns {
+        match mdns::spawn_responder(&server.host, lh.port) {
+            Ok(()) => println!("🔎 Advertised as {}.{}", server.host, mdns::SERVICE),
+            Err(e) => println!("⚠️  {}: not advertised", e),
+        }
+    }
+    let s = server.clone();
+    thread::spawn(move || {
+        for stream in tcp.incoming().flatten() {
+            s.answer_http(stream);
+        }
+    });
+    let s = server.clone();
+    thread::spawn(move || {
+        let mut buf = [0u8; 256];
+        loop {
+            let Ok((n, from)) = udp.recv_from(&mut buf) else {
+                continue;
+            };
+            let msg = String::from_utf8_lossy(&buf[..n]);
+            let Some(fields) = msg
+                .trim()
+                .strip_prefix(MAGIC)
+                .and_then(|m| m.strip_prefix(" PING "))
+            else {
+                continue;
+            };
+            let fields: Vec<&str> = fields.split(' ').collect();
+            if let Ok(reply) = s.pong(&fields, unix_now()) {
+                udp.send_to(reply.as_bytes(), from).ok();
+            }
+        }
+    });
+    if cfg.beacon.key.is_empty() {
+        println!("📡 beacon.key is not set: no beacons");
+        loop {
+            thread::park();
+        }
+    }
+    cfg.beacon
+        .validate()
+        .map_err(|e| format!("beacon: {}", e))?;
+    beacon::send_forever(&cfg.beacon)
+}
+
+#[derive(Clone)]
+struct Server {
+    key: String,
+    max_skew_sec: u64,
+    host: String,
+}
+
+impl Server {
+    // fields - nonce [ts hmac]; с ключом неподписанный запрос не отвечаем:
+    // иначе любой в сети собирает ответы маяка впрок
+    fn pong(&self, fields: &[&str], now: u64) -> Result<String, &'static str> {
+        match fields {
+            [nonce] if self.key.is_empty() && valid_nonce(nonce) => {
+                Ok(format!("{} PONG {} {}", MAGIC, self.host, nonce))
+            }
+            [nonce, ts, mac] if !self.key.is_empty() && valid_nonce(nonce) => {
+                let body = format!("{} PING {} {}", MAGIC, nonce, ts);
+                if !beacon::verify(&self.key, &body, mac) {
+                    return Err("bad signature");
+                }
+                let ts: u64 = ts.parse().map_err(|_| "malformed")?;
+                if ts.abs_diff(now) > self.max_skew_sec {
+                    return Err("stale");
+                }
+                let body = format!("{} PONG {} {} {}", MAGIC, self.host, nonce, now);
+                Ok(format!("{} {}", body, beacon::mac_hex(&self.key, &body)))
+            }
+            _ => Err("malformed"),
+        }
+    }
+
+    fn answer_http(&self, mut stream: TcpStream) {
+        stream.set_read_timeout(Some(Duration::from_secs(2))).ok();
+        stream.set_write_timeout(Some(Duration::from_secs(2))).ok();
+        let mut buf = [0u8; 1024];
+        let mut len = 0;
+        while len < buf.len() && !buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+            match stream.read(&mut buf[len..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => len += n,
+            }
+        }
+        let req = String::from_utf8_lossy(&buf[..len]);
+        let mut line = req.lines().next().unwrap_or("").split_whitespace();
+        let (method, target) = (line.next().unwrap_or(""), line.next().unwrap_or(""));
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let (status, body) = match (method, path) {
+            ("GET" | "HEAD", "/health") => {
+                let param = |name: &str| {
+                    query
+                        .split('&')
+                        .find_map(|kv| kv.strip_prefix(name)?.strip_prefix('='))
+                };
+                let fields: Vec<&str> = [param("nonce"), param("ts"), param("mac")]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                match self.pong(&fields, unix_now()) {
+                    Ok(pong) => ("200 OK", pong + "\n"),
+                    // Без ключа и без nonce (curl, Uptime Kuma) - просто "ok"
+                    Err(_) if fields.is_empty() && self.key.is_empty() => {
+                        ("200 OK", format!("ok {}\n", self.host))
+                    }
+                    Err(why) => ("401 Unauthorized", format!("{}\n", why)),
+                }
+            }
+            _ => ("404 Not Found", "not found\n".to_string()),
+        };
+        let mut reply = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+            status,
+            body.len()
+        );
+        if method != "HEAD" {
+            reply.push_str(&body);
+        }
+        stream.write_all(reply.as_bytes()).ok();
+    }
+}
+
+fn valid_nonce(n: &str) -> bool {
+    !n.is_empty() && n.len() <= 64 && n.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+// 128 бит из getrandom: предсказуемый nonce дал бы собрать ответы заранее
+pub fn nonce() -> String {
+    let mut r = [0u8; 16];
+    let n = unsafe { libc::getrandom(r.as_mut_ptr() as *mut libc::c_void, r.len(), 0) };
+    if n != r.len() as isize {
+        let t = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        r = (t ^ ((std::process::id() as u128) << 64)).to_le_bytes();
+    }
+    r.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// --- ПРОБЫ ДЕМОНА: Ok(RTT, мс) ---
+// Запрос: поля после "PING"; ответ проверяется против того же nonce
+struct Challenge<'a> {
+    key: &'a str,
+    max_skew_sec: u64,
+    nonce: String,
+}
+
+impl<'a> Challenge<'a> {
+    fn new(cfg: &'a PortalConfig) -> Self {
+        Self {
+            key: key(cfg),
+            max_skew_sec: cfg.lighthouse.max_skew_sec,
+            nonce: nonce(),
+        }
+    }
+
+    fn fields(&self, now: u64) -> Vec<String> {
+        if self.key.is_empty() {
+            return vec![self.nonce.clone()];
+        }
+        let body = format!("{} PING {} {}", MAGIC, self.nonce, now);
+        vec![
+            self.nonce.clone(),
+            now.to_string(),
+            beacon::mac_hex(self.key, &body),
+        ]
+    }
+
+    fn check(&self, reply: &str, now: u64) -> Result<(), &'static str> {
+        let reply = reply.trim();
+        let (body, mac) = if self.key.is_empty() {
+            (reply, "")
+        } else {
+            reply.rsplit_once(' ').ok_or("malformed")?
+        };
+        let p: Vec<&str> = body.split(' ').collect();
+        let (nonce, ts) = match p.as_slice() {
+            [MAGIC, "PONG", _host, nonce] if self.key.is_empty() => (*nonce, None),
+            [MAGIC, "PONG", _host, nonce, ts] if !self.key.is_empty() => (*nonce, Some(*ts)),
+            _ => return Err("malformed"),
+        };
+        if let Some(ts) = ts {
+            if !beacon::verify(self.key, body, mac) {
+                return Err("bad signature");
+            }
+            let ts: u64 = ts.parse().map_err(|_| "malformed")?;
+            if ts.abs_diff(now) > self.max_skew_sec {
+                return Err("stale");
+            }
+        }
+        if nonce != self.nonce {
+            return Err("not our nonce");
+        }
+        Ok(())
+    }
+}
+
+pub fn http_probe(cfg: &PortalConfig, timeout: Duration) -> Result<f64, String> {
+    let (host, port) = (cfg.lighthouse_ip.as_str(), cfg.lighthouse.port);
+    let started = Instant::now();
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("no address")?;
+    let end = started + timeout;
+    let mut s = reactor::connect(addr, end).map_err(|e| e.to_string())?;
+    let ch = Challenge::new(cfg);
+    let f = ch.fields(unix_now());
+    let query = match f.as_slice() {
+        [n, t, m] => format!("nonce={}&ts={}&mac={}", n, t, m),
+        _ => format!("nonce={}", ch.nonce),
+    };
+    write!(
+        s,
+        "GET /health?{} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        query, host
+    )
+    .map_err(|e| e.to_string())?;
+    // Запрос в одну строку влезет в буфер сокета, ответ ждем с таймаутом
+    let (mut resp, mut chunk) = (Vec::new(), [0u8; 1024]);
+    while resp.len() < 4096 {
+        match reactor::read_with(s.as_raw_fd(), end, || s.read(&mut chunk)) {
+            Ok(0) => break,
+            Ok(n) => resp.extend_from_slice(&chunk[..n]),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    let resp = String::from_utf8_lossy(&resp);
+    let code = resp.split_whitespace().nth(1).unwrap_or("");
+    if code != "200" {
+        return Err(format!("HTTP {}", code));
+    }
+    let body = resp.split_once("\r\n\r\n").map_or("", |(_, b)| b);
+    ch.check(body, unix_now())?;
+    Ok(started.elapsed().as_secs_f64() * 1000.0)
+}
+
+pub fn udp_probe(cfg: &PortalConfig, timeout: Duration) -> Result<f64, String> {
+    let (host, port) = (cfg.lighthouse_ip.as_str(), cfg.lighthouse.port);
+    let sock = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+    sock.connect((host, port)).map_err(|e| e.to_string())?;
+    let ch = Challenge::new(cfg);
+    let started = Instant::now();
+    let msg = format!("{} PING {}", MAGIC, ch.fields(unix_now()).join(" "));
+    sock.send(msg.as_bytes()).map_err(|e| e.to_string())?;
+    sock.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let mut buf = [0u8; 256];
+    let mut last = "timeout";
+    // Опоздавший ответ на прошлую пробу или подделка - ждем дальше свой
+    let end = started + timeout;
+    while let Ok(n) = reactor::read_with(sock.as_raw_fd(), end, || sock.recv(&mut buf)) {
+        match ch.check(&String::from_utf8_lossy(&buf[..n]), unix_now()) {
+            Ok(()) => return Ok(started.elapsed().as_secs_f64() * 1000.0),
+            Err(why) => last = why,
+        }
+    }
+    Err(last.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(key: &str) -> Server {
+        Server {
+            key: key.into(),
+            max_skew_sec: 60,
+            host: "pi".into(),
+        }
+    }
+
+    #[test]
+    fn unsigned_pong_echoes_the_nonce() {
+        assert_eq!(
+            server("").pong(&["1a2b"], 0).as_deref(),
+            Ok("PORTALH1 PONG pi 1a2b")
+        );
+        assert!(server("").pong(&[""], 0).is_err());
+        assert!(server("").pong(&["a", "b"], 0).is_err());
+    }
+
+    #[test]
+    fn signed_challenge_round_trip() {
+        let mut cfg = PortalConfig::default();
+        cfg.lighthouse.key = "k".into();
+        let ch = Challenge::new(&cfg);
+        assert_eq!(ch.nonce.len(), 32);
+        let now = 1_000_000;
+        let f = ch.fields(now);
+        let f: Vec<&str> = f.iter().map(String::as_str).collect();
+        let pong = server("k").pong(&f, now + 5).unwrap();
+        assert_eq!(ch.check(&pong, now + 6), Ok(()));
+        // Чужой ключ, старый запрос, неподписанный запрос - без ответа
+        assert_eq!(server("x").pong(&f, now), Err("bad signature"));
+        assert_eq!(server("k").pong(&f, now + 61), Err("stale"));
+        assert!(server("k").pong(&f[..1], now).is_err());
+        // Ответ на чужой nonce, подделка и ответ без подписи не засчитываются
+        let other = Challenge::new(&cfg);
+        assert_eq!(other.check(&pong, now), Err("not our nonce"));
+        assert_eq!(
+            ch.check(&pong.replace(" pi ", " evil "), now),
+            Err("bad signature")
+        );
+        assert!(
+            ch.check(&format!("PORTALH1 PONG pi {}", ch.nonce), now)
+                .is_err()
+        );
+    }
+}
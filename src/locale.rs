@@ -0,0 +1,267 @@
+// === ЛОКАЛИЗАЦИЯ ===
+// Строки демона, мастера и status лежат в locales/<код>.ftl (формат
+// Fluent) и вшиваются в бинарник при сборке. Файл с тем же именем в
+// <каталог конфига>/locales/ заменяет отдельные ключи - поправить перевод
+// можно без пересборки. Крейта fluent в зависимостях нет: разбираем то
+// подмножество, что нужно словарю - комментарии, "ключ = значение",
+// многострочные значения с отступом и плейсхолдеры { $var } / { "текст" }.
+
+use std::collections::HashMap;
+use std::fs;
+
+use crate::paths;
+
+const EMBEDDED: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("ru", include_str!("../locales/ru.ftl")),
+    ("uk", include_str!("../locales/uk.ftl")),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Var(String),
+}
+
+type Pattern = Vec<Piece>;
+
+// Словарь одного языка: встроенный, поверх - правки из /etc
+pub struct Bundle {
+    messages: HashMap<String, Pattern>,
+}
+
+impl Bundle {
+    pub fn load(code: &str) -> Self {
+        let builtin = EMBEDDED
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map_or("", |(_, text)| text);
+        let mut messages = parse(builtin).unwrap_or_else(|e| panic!("locales/{}.ftl: {}", code, e));
+        let path = format!("{}/{}.ftl", paths::config("locales"), code);
+        if let Ok(text) = fs::read_to_string(&path) {
+            match parse(&text) {
+                Ok(over) => messages.extend(over),
+                Err(e) => eprintln!("⚠️  {}: {}, using built-in strings.", path, e),
+            }
+        }
+        Bundle { messages }
+    }
+
+    // Все сообщения с подставленными переменными; неизвестная остается
+    // как {$name} - так же поступает Fluent
+    pub fn format_all(&self, args: &[(&str, &str)]) -> HashMap<String, String> {
+        self.messages
+            .iter()
+            .map(|(key, pattern)| (key.clone(), format(pattern, args)))
+            .collect()
+    }
+}
+
+fn format(pattern: &Pattern, args: &[(&str, &str)]) -> String {
+    let mut out = String::new();
+    for piece in pattern {
+        match piece {
+            Piece::Text(s) => out.push_str(s),
+            Piece::Var(name) => match args.iter().find(|(k, _)| k == name) {
+                Some((_, v)) => out.push_str(v),
+                None => {
+                    out.push_str("{$");
+                    out.push_str(name);
+                    out.push('}');
+                }
+            },
+        }
+    }
+    out
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn parse(text: &str) -> Result<HashMap<String, Pattern>, String> {
+    let mut messages = HashMap::new();
+    let lines: Vec<&str> = text.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line_no = i;
+        let err = |msg: &str| format!("line {}: {}", line_no, msg);
+        if line.starts_with(' ') {
+            return Err(err("indented line outside a message"));
+        }
+        let Some((key, first)) = line.split_once('=') else {
+            return Err(err("expected \"key = value\""));
+        };
+        let key = key.trim_end();
+        if key.starts_with('-') {
+            return Err(err("terms are not supported"));
+        }
+        if !is_ident(key) {
+            return Err(err("bad message key"));
+        }
+        // Продолжение - строки с отступом (и пустые между ними)
+        let start = i;
+        while i < lines.len() && (lines[i].starts_with(' ') || lines[i].trim().is_empty()) {
+            i += 1;
+        }
+        let mut rest: Vec<&str> = lines[start..i].to_vec();
+        while rest.last().is_some_and(|l| l.trim().is_empty()) {
+            rest.pop();
+        }
+        if rest.iter().any(|l| l.trim_start().starts_with('.')) {
+            return Err(err("attributes are not supported"));
+        }
+        let indent = rest
+            .iter()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.len() - l.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let mut value: Vec<&str> = Vec::new();
+        let first = first.trim();
+        if !first.is_empty() {
+            value.push(first);
+        }
+        value.extend(
+            rest.iter()
+                .map(|l| l.get(indent..).unwrap_or("").trim_end()),
+        );
+        if value.is_empty() {
+            return Err(err("message has no value"));
+        }
+        let pattern = parse_pattern(&value.join("\n")).map_err(|e| err(&e))?;
+        messages.insert(key.to_string(), pattern);
+    }
+    Ok(messages)
+}
+
+fn parse_pattern(s: &str) -> Result<Pattern, String> {
+    let mut pattern = Vec::new();
+    let mut text = String::new();
+    let mut rest = s;
+    while let Some(open) = rest.find(['{', '}']) {
+        text.push_str(&rest[..open]);
+        if rest[open..].starts_with('}') {
+            return Err("unbalanced }".into());
+        }
+        let body = &rest[open + 1..];
+        if let Some(lit) = body.trim_start().strip_prefix('"') {
+            // "..." - строка может содержать } - ищем закрывающую кавычку
+            let end = literal_end(lit).ok_or("unterminated string literal")?;
+            text.push_str(&unescape(&lit[..end])?);
+            let after = lit[end + 1..].trim_start();
+            rest = after.strip_prefix('}').ok_or("expected } after string")?;
+            continue;
+        }
+        let close = body.find('}').ok_or("unterminated placeable")?;
+        let inner = body[..close].trim();
+        let name = inner.strip_prefix('$').filter(|n| is_ident(n));
+        let name = name.ok_or_else(|| format!("unsupported placeable {{ {} }}", inner))?;
+        if !text.is_empty() {
+            pattern.push(Piece::Text(std::mem::take(&mut text)));
+        }
+        pattern.push(Piece::Var(name.to_string()));
+        rest = &body[close + 1..];
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        pattern.push(Piece::Text(text));
+    }
+    Ok(pattern)
+}
+
+// Позиция закрывающей кавычки с учетом \" и \\
+fn literal_end(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Escape-последовательности Fluent: \" \\ \uXXXX \UXXXXXX
+fn unescape(s: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let digits = match chars.next() {
+            Some(c @ ('"' | '\\')) => {
+                out.push(c);
+                continue;
+            }
+            Some('u') => 4,
+            Some('U') => 6,
+            _ => return Err("bad escape in string literal".into()),
+        };
+        let hex: String = chars.by_ref().take(digits).collect();
+        let c = u32::from_str_radix(&hex, 16)
+            .ok()
+            .filter(|_| hex.len() == digits)
+            .and_then(char::from_u32)
+            .ok_or("bad \\u escape in string literal")?;
+        out.push(c);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one(text: &str, args: &[(&str, &str)]) -> String {
+        let messages = parse(text).unwrap();
+        format(&messages["k"], args)
+    }
+
+    #[test]
+    fn plain_and_variables() {
+        assert_eq!(
+            one("# c\nk = Saved to { $path }!\n", &[("path", "/x")]),
+            "Saved to /x!"
+        );
+        assert_eq!(one("k = { $missing } left", &[]), "{$missing} left");
+        assert_eq!(one("k = {\"  \"}indent", &[]), "  indent");
+        assert_eq!(one("k = {\"\\u000A\"}title {\"}\"}", &[]), "\ntitle }");
+    }
+
+    #[test]
+    fn multiline_values() {
+        let text = "k =\n    first\n      second\n\n    third\n\nother = x\n";
+        assert_eq!(one(text, &[]), "first\n  second\n\nthird");
+        assert_eq!(one("k = a\n  b\n", &[]), "a\nb");
+    }
+
+    #[test]
+    fn rejects_what_we_cannot_render() {
+        assert!(parse("-term = x").is_err());
+        assert!(parse("k = x\n  .attr = y").is_err());
+        assert!(parse("k = { NUMBER($n) }").is_err());
+        assert!(parse("k = { $n").is_err());
+        assert!(parse("just text").is_err());
+    }
+
+    #[test]
+    fn embedded_files_cover_every_key() {
+        for lang in crate::Language::ALL {
+            let t = crate::Locales::new(lang);
+            assert!(!t.settings_saved.contains("{$"));
+            assert!(!t.no_rights.contains("{$"));
+        }
+    }
+}
@@ -0,0 +1,484 @@
+// === ЖУРНАЛ ДЕМОНА ===
+// Без крейта tracing: уровень, метка времени и поля key=value в одной строке,
+// чтобы после аварии строки сводились по времени и grep'ались по полям.
+// Уровень берется из --log-level, иначе из RUST_LOG ("debug",
+// "portal_daemon=debug,warn"), иначе info. Куда писать - log_target:
+// journald (родной протокол: приоритет и поля отдельными полями журнала),
+// syslog (/dev/log, для OpenRC) или stdout. На stdout под journald время
+// пишет сам журнал, а мы ставим префикс <N> - приоритет разложится по уровням.
+// Последние log_buffer_lines строк демон держит в памяти (HTTP /recent) и
+// дублирует в /run (tmpfs) для `status --recent`: под OpenRC/runit без
+// постоянного журнала это единственный способ узнать, что было час назад.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Write as _};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write as _;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::events::{Event, Subscriber};
+use crate::schedule::TimeZone;
+use crate::{paths, state, tui, unix_now};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn parse(s: &str) -> Option<Level> {
+        Some(match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Level::Off,
+            "error" => Level::Error,
+            "warn" | "warning" => Level::Warn,
+            "info" => Level::Info,
+            "debug" => Level::Debug,
+            "trace" => Level::Trace,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Level::Off => "OFF",
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    // sd-daemon(3): <3> err, <4> warning, <6> info, <7> debug
+    fn syslog(self) -> u8 {
+        match self {
+            Level::Off | Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogTarget {
+    // journald под systemd, syslog под OpenRC, иначе stdout
+    #[default]
+    Auto,
+    Stdout,
+    Journald,
+    Syslog,
+}
+
+const RECENT_FILE: &str = "recent.jsonl";
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+const SYSLOG_SOCKET: &str = "/dev/log";
+// LOG_DAEMON
+const SYSLOG_FACILITY: u8 = 3;
+
+enum Sink {
+    Stdout,
+    Journald(UnixDatagram),
+    Syslog(UnixDatagram),
+}
+
+static SINK: OnceLock<Sink> = OnceLock::new();
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static TZ: OnceLock<TimeZone> = OnceLock::new();
+
+pub fn init(cli: Option<Level>) {
+    let level = cli
+        .or_else(|| {
+            std::env::var("RUST_LOG")
+                .ok()
+                .and_then(|v| from_rust_log(&v))
+        })
+        .unwrap_or(Level::Info);
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+// Директивы без цели - общий уровень, "portal_daemon=..." - наш (важнее)
+fn from_rust_log(v: &str) -> Option<Level> {
+    let mut global = None;
+    for d in v.split(',') {
+        match d.split_once('=') {
+            Some((target, l)) if target.trim() == env!("CARGO_PKG_NAME") => {
+                return Level::parse(l);
+            }
+            Some(_) => {}
+            None => global = Level::parse(d).or(global),
+        }
+    }
+    global
+}
+
+// Выбор вывода по конфигу; недоступный сокет - остаемся на stdout
+pub fn set_target(target: LogTarget) {
+    let target = match target {
+        LogTarget::Auto if std::env::var_os("JOURNAL_STREAM").is_some() => LogTarget::Journald,
+        LogTarget::Auto if std::env::var_os("RC_SVCNAME").is_some() => LogTarget::Syslog,
+        LogTarget::Auto => LogTarget::Stdout,
+        t => t,
+    };
+    let socket = |path: &str| {
+        let s = UnixDatagram::unbound().ok()?;
+        s.connect(path).ok()?;
+        Some(s)
+    };
+    let sink = match target {
+        LogTarget::Journald => socket(JOURNAL_SOCKET).map(Sink::Journald),
+        LogTarget::Syslog => socket(SYSLOG_SOCKET).map(Sink::Syslog),
+        _ => None,
+    };
+    let failed = sink.is_none() && target != LogTarget::Stdout;
+    SINK.set(sink.unwrap_or(Sink::Stdout)).ok();
+    if failed {
+        warn!("⚠️  Cannot log to {:?}, using stdout", target);
+    }
+}
+
+// Время в строках - в поясе из конфига, как и все остальное у демона
+pub fn set_timezone(tz: TimeZone) {
+    TZ.set(tz).ok();
+}
+
+pub fn enabled(level: Level) -> bool {
+    level != Level::Off && level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    // До set_timezone (ошибки конфига при старте) - UTC, но пояс не фиксируем
+    let l = match TZ.get() {
+        Some(tz) => tz.to_local(now.as_secs() as i64),
+        None => TimeZone::utc().to_local(now.as_secs() as i64),
+    };
+    let sign = if l.offset < 0 { '-' } else { '+' };
+    let off = l.offset.unsigned_abs();
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}{}{:02}:{:02}",
+        l.year,
+        l.month,
+        l.day,
+        l.hour,
+        l.minute,
+        l.second,
+        now.subsec_millis(),
+        sign,
+        off / 3600,
+        off % 3600 / 60
+    )
+}
+
+pub fn write(level: Level, fields: &[(&str, &dyn Display)], msg: fmt::Arguments) {
+    let msg = msg.to_string();
+    remember(level, fields, &msg);
+    let sent = match SINK.get() {
+        Some(Sink::Journald(s)) => s.send(&journal_entry(level, fields, &msg)).is_ok(),
+        Some(Sink::Syslog(s)) => {
+            let pri = SYSLOG_FACILITY * 8 + level.syslog();
+            let line = format!(
+                "<{}>{}[{}]: {}",
+                pri,
+                env!("CARGO_PKG_NAME"),
+                std::process::id(),
+                with_fields(&msg, fields)
+            );
+            s.send(line.as_bytes()).is_ok()
+        }
+        _ => false,
+    };
+    if !sent {
+        write_stdout(level, fields, &msg);
+    }
+}
+
+fn with_fields(msg: &str, fields: &[(&str, &dyn Display)]) -> String {
+    let mut line = msg.to_string();
+    for (k, v) in fields {
+        let v = v.to_string();
+        if v.is_empty() || v.contains(char::is_whitespace) || v.contains('"') {
+            write!(line, " {}={:?}", k, v).ok();
+        } else {
+            write!(line, " {}={}", k, v).ok();
+        }
+    }
+    line
+}
+
+fn write_stdout(level: Level, fields: &[(&str, &dyn Display)], msg: &str) {
+    let mut line = String::new();
+    if std::env::var_os("JOURNAL_STREAM").is_some() {
+        write!(line, "<{}>", level.syslog()).ok();
+    } else {
+        // Строка отсчета в терминале перерисуется следом
+        if tui::active() {
+            line.push_str("\r\x1b[2K");
+        }
+        write!(line, "{} {:<5} ", timestamp(), level.name()).ok();
+    }
+    line.push_str(&with_fields(msg, fields));
+    match level {
+        Level::Error | Level::Warn => eprintln!("{}", line),
+        _ => println!("{}", line),
+    }
+}
+
+// --- Последние строки в памяти ---
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Recent {
+    pub ts: u64,
+    pub level: String,
+    pub message: String,
+}
+
+struct Ring {
+    cap: usize,
+    lines: VecDeque<Recent>,
+    // Файл в /run растет дописыванием и переписывается из памяти раз в cap строк
+    file: Option<File>,
+    appended: usize,
+}
+
+impl Ring {
+    fn rewrite(&mut self) {
+        self.appended = 0;
+        let file = paths::run(RECENT_FILE);
+        let tmp = format!("{}.tmp", file);
+        let mut body = String::new();
+        for r in &self.lines {
+            if let Ok(l) = serde_json::to_string(r) {
+                body.push_str(&l);
+                body.push('\n');
+            }
+        }
+        self.file = fs::write(&tmp, body)
+            .and_then(|_| fs::rename(&tmp, &file))
+            .and_then(|_| OpenOptions::new().append(true).open(&file))
+            .ok();
+    }
+}
+
+static RING: Mutex<Option<Ring>> = Mutex::new(None);
+
+// Только у демона; 0 - не держать. Строки прошлого процесса (рестарт,
+// обновление на месте) подхватываются из /run
+pub fn keep_recent(cap: usize) {
+    if cap == 0 {
+        return;
+    }
+    if !Path::new(paths::run_dir()).exists() {
+        state::prepare_run_dir();
+    }
+    let mut lines: VecDeque<Recent> = read_recent(0).into();
+    while lines.len() > cap {
+        lines.pop_front();
+    }
+    let mut ring = Ring {
+        cap,
+        lines,
+        file: None,
+        appended: 0,
+    };
+    ring.rewrite();
+    if let Ok(mut g) = RING.lock() {
+        *g = Some(ring);
+    }
+}
+
+fn remember(level: Level, fields: &[(&str, &dyn Display)], msg: &str) {
+    let Ok(mut g) = RING.lock() else {
+        return;
+    };
+    let Some(ring) = g.as_mut() else {
+        return;
+    };
+    let r = Recent {
+        ts: unix_now(),
+        level: level.name().to_string(),
+        message: with_fields(msg, fields),
+    };
+    if ring.lines.len() == ring.cap {
+        ring.lines.pop_front();
+    }
+    ring.appended += 1;
+    if ring.appended >= ring.cap {
+        ring.lines.push_back(r);
+        ring.rewrite();
+        return;
+    }
+    if let (Some(f), Ok(l)) = (ring.file.as_mut(), serde_json::to_string(&r)) {
+        writeln!(f, "{}", l).ok();
+    }
+    ring.lines.push_back(r);
+}
+
+// Из памяти этого процесса (HTTP /recent)
+pub fn recent() -> Vec<Recent> {
+    RING.lock()
+        .ok()
+        .and_then(|g| g.as_ref().map(|r| r.lines.iter().cloned().collect()))
+        .unwrap_or_default()
+}
+
+// Из файла демона (`status --recent` в другом процессе)
+pub fn read_recent(since: u64) -> Vec<Recent> {
+    fs::read_to_string(paths::run(RECENT_FILE))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| serde_json::from_str::<Recent>(l).ok())
+        .filter(|r| r.ts >= since)
+        .collect()
+}
+
+// Родной протокол journald: KEY=value построчно, многострочное значение -
+// KEY\n<длина u64 LE><значение>\n. Наши поля идут как IP=, RTT_MS=, STATE=...
+fn journal_entry(level: Level, fields: &[(&str, &dyn Display)], msg: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut field = |k: &str, v: &str| {
+        if v.contains('\n') {
+            buf.extend_from_slice(k.as_bytes());
+            buf.push(b'\n');
+            buf.extend_from_slice(&(v.len() as u64).to_le_bytes());
+            buf.extend_from_slice(v.as_bytes());
+            buf.push(b'\n');
+        } else {
+            buf.extend_from_slice(format!("{}={}\n", k, v).as_bytes());
+        }
+    };
+    field("MESSAGE", msg);
+    field("PRIORITY", &level.syslog().to_string());
+    field("SYSLOG_IDENTIFIER", env!("CARGO_PKG_NAME"));
+    for (k, v) in fields {
+        let key: String = k
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        field(&key, &v.to_string());
+    }
+    buf
+}
+
+// log_at!(Level::Info, ip = cfg.lighthouse_ip, rtt_ms = rtt; "probe {}", x)
+macro_rules! log_at {
+    ($lvl:expr, $($k:ident = $v:expr),+ ; $($arg:tt)+) => {
+        if $crate::log::enabled($lvl) {
+            $crate::log::write(
+                $lvl,
+                &[$((stringify!($k), &$v as &dyn std::fmt::Display)),+],
+                format_args!($($arg)+),
+            )
+        }
+    };
+    ($lvl:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($lvl) {
+            $crate::log::write($lvl, &[], format_args!($($arg)+))
+        }
+    };
+}
+
+macro_rules! error {
+    ($($t:tt)+) => { $crate::log::log_at!($crate::log::Level::Error, $($t)+) };
+}
+
+// warn занят встроенным атрибутом - экспортируем под ним через as
+macro_rules! warning {
+    ($($t:tt)+) => { $crate::log::log_at!($crate::log::Level::Warn, $($t)+) };
+}
+
+macro_rules! info {
+    ($($t:tt)+) => { $crate::log::log_at!($crate::log::Level::Info, $($t)+) };
+}
+
+macro_rules! debug {
+    ($($t:tt)+) => { $crate::log::log_at!($crate::log::Level::Debug, $($t)+) };
+}
+
+pub(crate) use {debug, error, info, log_at, warning as warn};
+
+// Каждое событие шины - строка с полями; пробы и прочая мелочь - на debug
+pub struct EventLog {
+    target: String,
+}
+
+impl EventLog {
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.to_string(),
+        }
+    }
+}
+
+impl Subscriber for EventLog {
+    fn on_event(&mut self, e: &Event) {
+        match e {
+            // Проба - каждый цикл: без debug даже строку RTT не собираем
+            Event::Probe(r) if enabled(Level::Debug) => {
+                let rtt = r.rtt_ms.map(|v| format!("{:.1}", v)).unwrap_or_default();
+                debug!(ip = self.target, ok = r.ok, rtt_ms = rtt; "probe");
+            }
+            Event::Probe(_) => {}
+            Event::StateChanged { phase, reason } => {
+                let phase = format!("{:?}", phase).to_lowercase();
+                info!(state = phase; "{}", reason);
+            }
+            Event::ConnectionLost { grace_sec } => {
+                debug!(ip = self.target, grace_sec = grace_sec; "connection lost")
+            }
+            Event::GraceReminder { remaining_sec } => {
+                debug!(remaining_sec = remaining_sec; "grace reminder")
+            }
+            Event::ConnectionRestored => debug!(ip = self.target; "connection restored"),
+            Event::SleepBlocked { guard, reason } => {
+                debug!(guard = guard, reason = reason; "sleep blocked")
+            }
+            Event::SleepRequested { seconds, mode } => {
+                debug!(seconds = seconds, mode = mode; "sleep requested")
+            }
+            Event::BatteryLow { percent } => debug!(percent = percent; "battery low"),
+            Event::Woke {
+                slept_sec,
+                early_by_sec,
+                cause,
+                rearmed,
+            } => {
+                let cause = cause.clone().unwrap_or_default();
+                debug!(
+                    slept_sec = slept_sec,
+                    early_by_sec = early_by_sec,
+                    cause = cause,
+                    rearmed = rearmed;
+                    "woke up"
+                )
+            }
+            Event::GaveUp { dark_wakes } => debug!(dark_wakes = dark_wakes; "gave up"),
+            Event::RtcDrift {
+                drift_sec,
+                after_wake,
+            } => debug!(drift_sec = drift_sec, after_wake = after_wake; "rtc drift"),
+            Event::ProbeStale { age_sec } => debug!(age_sec = age_sec; "probe stale"),
+            Event::ConfigInvalid { message, restored } => {
+                debug!(restored = restored, message = message; "config invalid")
+            }
+        }
+    }
+}
@@ -1,112 +1,1012 @@
-use clap::Parser;
-use dialoguer::{Input, Select, theme::ColorfulTheme};
+use clap::{CommandFactory, Parser, Subcommand};
+use dialoguer::{Confirm, Input, Password, Select, theme::ColorfulTheme};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
-use std::io::Write; // Нужно для записи файлов
+use std::io::{IsTerminal, Write};
+use std::net::IpAddr;
+use std::ops::RangeInclusive;
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
-use std::process::Command;
-use std::thread;
-use std::time::{Duration, SystemTime};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use error::PortalError;
+
+#[cfg(target_os = "linux")]
+mod arp;
+mod beacon;
+mod channels;
+mod completions;
+mod curl;
+mod dashboard;
+mod dbus;
+mod email;
+mod error;
+mod events;
+mod fleet;
+mod freebsd;
+mod guards;
+mod handoff;
+mod history;
+mod hooks;
+mod http;
+mod icmp;
+mod inject;
+mod inverter;
+mod iwd;
+mod lighthouse;
+mod locale;
+mod log;
+mod mdns;
+mod mqtt;
+mod net;
+#[cfg(target_os = "linux")]
+mod netlink;
+mod nm;
+mod notify;
+mod outages;
+mod paths;
+mod power;
+mod presets;
+mod privsep;
+mod probe;
+mod profiles;
+mod reactor;
+mod rtc;
+mod schedule;
+mod secrets;
+mod sensor;
+mod sms;
+mod snmp;
+mod state;
+mod statusbar;
+mod store;
+mod system;
+mod thermal;
+mod timers;
+mod tui;
+mod watchdog;
+mod webhook;
+mod wol;
+
+#[cfg(not(target_os = "linux"))]
+use freebsd::{arp, netlink};
 
 // --- КОНФИГУРАЦИЯ И ПУТИ ---
-const CONFIG_DIR: &str = "/etc/portal_daemon";
-const CONFIG_FILE: &str = "/etc/portal_daemon/config.json";
-const PAUSE_FILE: &str = "/tmp/portal.pause";
+// Каталоги - в paths: системные или, с --user, XDG
+const CONFIG_FILE: &str = "config.json";
+// Последний конфиг, с которым демон успешно стартовал
+const CONFIG_BACKUP: &str = "config.json.good";
+// /run/portal_daemon: состояние читают все, паузу ставят root и portal-admins
+const STATE_FILE: &str = "state.json";
+const PAUSE_FILE: &str = "pause";
 
 // Для установки
 const BINARY_DEST: &str = "/usr/local/bin/portal_daemon";
+// Рядом с бинарником: дополнение оболочек и man
+const SHARE_DIR: &str = "/usr/local/share";
 const GROUP_NAME: &str = "portal-admins";
+// Системный пользователь для run_as: создает --install
+const RUN_AS_USER: &str = "portal";
+// Что из них создали мы сами, а не нашли готовым, - удаляет только это.
+// В каталоге конфига: каталог состояния после run_as принадлежит portal
+const CREATED_ACCOUNTS: &str = "accounts.created";
 const DOAS_CONF: &str = "/etc/doas.conf";
 const SUDOERS_FILE: &str = "/etc/sudoers.d/portal-daemon";
+const SYSTEMD_UNIT: &str = "/etc/systemd/system/portal.service";
+const OPENRC_SCRIPT: &str = "/etc/init.d/portal";
+const S6_SOURCES: &str = "/etc/s6/sv";
+const DINIT_SERVICE: &str = "/etc/dinit.d/portal";
+
+// Допустимые диапазоны для визарда и меню
+const SLEEP_MINUTES_RANGE: RangeInclusive<u64> = 1..=1440;
+const GRACE_SEC_RANGE: RangeInclusive<u64> = 0..=3600;
+const WAKEUP_SEC_RANGE: RangeInclusive<u64> = 0..=600;
+const SCAN_INTERVAL_RANGE: RangeInclusive<u64> = 5..=3600;
+const PAUSE_MINUTES_RANGE: RangeInclusive<u64> = 1..=10080;
+const PING_ATTEMPTS_RANGE: RangeInclusive<u64> = 1..=10;
+const PING_COUNT_RANGE: RangeInclusive<u64> = 1..=20;
+const PING_TIMEOUT_MS_RANGE: RangeInclusive<u64> = 100..=30000;
+const PING_SIZE_RANGE: RangeInclusive<u64> = 8..=icmp::MAX_PAYLOAD as u64;
+const BAD_CYCLES_RANGE: RangeInclusive<u64> = 1..=60;
+// Предупреждения - в минутах; 0 - выключено
+const PROBE_AGE_ALERT_RANGE: RangeInclusive<u64> = 60..=7 * 86400;
+const FLEET_WAKE_OFFSET_RANGE: RangeInclusive<u64> = 0..=3600;
+const SLEEP_MODES: [&str; 5] = ["mem", "disk", "freeze", "standby", "off"];
+// Проснулись раньше будильника больше чем на это - ранний подъем
+const EARLY_WAKE_TOLERANCE_SEC: u64 = 60;
+// Цель wake_times ближе этого - спим до следующей
+const MIN_WAKE_AHEAD_SEC: i64 = 120;
+// Столько ждем остановки системы после rtcwake -m off
+const SHUTDOWN_WAIT: Duration = Duration::from_secs(300);
+// Столько раз подряд доспать, потом сдаться (сон не держится - будит железо)
+const MAX_REARMS: u32 = 5;
+// Сколько ждать, пока logind в самом деле усыпит систему
+const LOGIND_SLEEP_WAIT_SEC: u64 = 30;
+// Пауза по клавише "p" и кнопке "Отменить сон" в уведомлении
+const QUICK_PAUSE_MINUTES: u64 = 60;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 enum Language {
     En,
     Ru,
+    Uk,
+}
+
+impl Language {
+    // Порядок пунктов в мастере
+    const ALL: [Language; 3] = [Language::En, Language::Uk, Language::Ru];
+
+    // Имя файла в locales/
+    fn code(self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::Ru => "ru",
+            Language::Uk => "uk",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Language::En => "English",
+            Language::Ru => "Русский",
+            Language::Uk => "Українська",
+        }
+    }
+
+    // "uk_UA.UTF-8", "ru_RU@euro", "C" - по коду языка до "_", "." и "@"
+    fn from_locale(locale: &str) -> Option<Language> {
+        let code = locale.split(['_', '.', '@']).next()?;
+        Language::ALL
+            .into_iter()
+            .find(|l| l.code().eq_ignore_ascii_case(code))
+    }
+
+    // Язык, пока конфига нет: как у setlocale, первая непустая из
+    // LC_ALL, LC_MESSAGES, LANG; незнакомый - английский
+    fn from_env() -> Language {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|v| !v.is_empty())
+            .and_then(|v| Language::from_locale(&v))
+            .unwrap_or(Language::En)
+    }
 }
 
+// Что делать, если конфиг не читается при старте демона
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum InvalidConfigPolicy {
+    // Выйти с EX_CONFIG: юнит уходит в failed и не перезапускается
+    Fail,
+    // Работать на настройках по умолчанию, но громко об этом сообщить
+    Notify,
+    // Восстановить config.json из config.json.good; если его нет - как Fail
+    #[default]
+    LastGood,
+}
+
+// sysexits.h: ошибка конфигурации
+const EX_CONFIG: i32 = 78;
+// sysexits.h: не удалось создать файл (мастер не сохранил конфиг)
+const EX_CANTCREAT: i32 = 73;
+// sysexits.h: не так запущены
+const EX_USAGE: i32 = 64;
+// sysexits.h: нужной программы нет
+const EX_UNAVAILABLE: i32 = 69;
+// sysexits.h: внутренняя ошибка (наше же правило отклонено)
+const EX_SOFTWARE: i32 = 70;
+// sysexits.h: ошибка ввода-вывода (терминал пропал)
+const EX_IOERR: i32 = 74;
+// sysexits.h: не хватает прав
+const EX_NOPERM: i32 = 77;
+// sysexits.h: временно нельзя (уже запущен другой демон)
+const EX_TEMPFAIL: i32 = 75;
+
+// Версия схемы config.json. Поле переименовали или поменяли смысл - версия +1
+// и шаг в MIGRATIONS; просто новое поле с умолчанием миграции не требует
+const CONFIG_VERSION: u32 = 1;
+
+type Migration = fn(&mut serde_json::Map<String, serde_json::Value>);
+
+// MIGRATIONS[n] переводит конфиг версии n в n + 1 - еще до разбора по типам
+const MIGRATIONS: [Migration; CONFIG_VERSION as usize] = [
+    // 0 -> 1: появилось само поле version, остальные ключи как были
+    |_| {},
+];
+
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 struct PortalConfig {
+    // Схема, по которой записан файл; нет поля - версия 0
+    version: u32,
     language: Language,
     lighthouse_ip: String,
     target_ssid: String,
+    // Откуда мастер берет список сетей: "auto", "networkmanager", "iwd", "kernel"
+    network_backend: net::Backend,
+    // Свои маяк и тайминги для других сетей (офис, родители); в чужой сети не спим
+    profiles: Vec<profiles::Profile>,
+    // Через сколько разбудить RTC. На FreeBSD и с --user будильника нет:
+    // sleep_minutes, wake_times и resleep_minutes там не действуют
     sleep_minutes: u64,
+    // Просыпаться к времени на часах, а не через sleep_minutes:
+    // ["mon-fri 07:00", "every 30m", "cron 30 6 * * 1-5"] - к ближайшей из целей
+    wake_times: Vec<String>,
+    // Проснулись, а света нет: следующие сны по этой лестнице, минут
+    // ([15, 30, 60, 120]), последняя ступень повторяется. Пусто - sleep_minutes
+    resleep_minutes: Vec<u64>,
+    // Столько подъемов подряд без света - выключиться совсем; 0 - никогда
+    give_up_after_wakes: u32,
     grace_period_sec: u64,
     wakeup_wait_sec: u64,
     scan_interval_sec: u64,
+    // Случайная добавка к циклу проб: много демонов в одной сети не пингуют
+    // роутер одновременно (0 и 0 - без нее)
+    scan_jitter_min_sec: u64,
+    scan_jitter_max_sec: u64,
+    // Пол и потолок адаптивного интервала: после сбоев и потерь - чаще,
+    // после долгой стабильности - реже (оба 0 - всегда scan_interval_sec)
+    scan_interval_min_sec: u64,
+    scan_interval_max_sec: u64,
+    // Чем проверять свет: "ping" (маяк), "arp" (маяк в той же проводной сети),
+    // "power_supply" (AC ноутбука), "nut" (ИБП), "auto" (выбрать при старте)
+    probe: probe::ProbeKind,
+    // Попыток пинга на одну проверку (по Wi-Fi пакеты теряются и со светом)
+    ping_attempts: u32,
+    // Пингов за цикл (серия, а не повторы до первого ответа). Свет пропал,
+    // когда потери выше max_loss_percent или RTT выше max_rtt_ms (0 - не
+    // смотреть) держатся bad_cycles циклов подряд
+    ping_count: u32,
+    // Ожидание ответа на один пинг (мосты PLC, Wi-Fi-репитеры отвечают
+    // медленно) и байт данных в нем, как ping -W и -s
+    ping_timeout_ms: u64,
+    ping_size: usize,
+    max_loss_percent: u8,
+    max_rtt_ms: f64,
+    bad_cycles: u32,
+    // Предупредить (журнал, телефон), если удачной пробы нет дольше стольких
+    // секунд - независимо от сна; 0 - не предупреждать
+    probe_age_alert_sec: u64,
+    // Сетевая карта, чей линк тоже проверять: нет carrier - света нет сразу
+    interface: Option<String>,
+    nut_address: String,
+    nut_ups: String,
+    // probe = "sensor": GPIO или последовательный порт (sensor.rs)
+    sensor: sensor::SensorConfig,
+    // probe = "beacon": подписанные UDP-маячки от устройства на сети (beacon.rs)
+    beacon: beacon::BeaconConfig,
+    // probe = "http"/"udp" и сервер `portal_daemon lighthouse` (lighthouse.rs)
+    lighthouse: lighthouse::LighthouseConfig,
+    // probe = "snmp": sysUpTime/ifOperStatus роутера (snmp.rs)
+    snmp: snmp::SnmpConfig,
+    // Режим rtcwake; при заряде <= low_battery_percent - всегда "disk"
+    sleep_mode: String,
+    low_battery_percent: u8,
+    // Вне suspend_temp_min_c..max_c - не suspend, а temp_fallback_mode ("off":
+    // выключение, включит RTC или плата питания). temp_sensor - файл датчика
+    temp_sensor: Option<String>,
+    suspend_temp_min_c: Option<f64>,
+    suspend_temp_max_c: Option<f64>,
+    temp_fallback_mode: String,
+    // IANA-имя ("Europe/Kyiv") или POSIX TZ; по умолчанию $TZ / /etc/localtime
+    timezone: Option<String>,
+    // Окна, когда усыплять нельзя: ["mon-fri 08:00-18:00", "22:00-07:00"]
+    quiet_hours: Vec<String>,
+    // Общая пауза по сети (`ctl --all`): слушаем UDP, только если задан токен
+    fleet_port: u16,
+    fleet_token: String,
+    fleet_peers: Vec<String>,
+    // Общие пробуждения: "leader" объявляет свою сетку при засыпании,
+    // "follower" просыпается по ней через fleet_wake_offset_sec
+    fleet_role: fleet::Role,
+    fleet_wake_offset_sec: u64,
+    outage_schedule: outages::OutageScheduleConfig,
+    // Дом на инверторе: без сети не спим, пока заряд батареи выше порога
+    inverter: inverter::InverterConfig,
+    // Команды до сна и после пробуждения (sync, пауза торрентов, NFS...)
+    pre_sleep_hooks: Vec<String>,
+    post_wake_hooks: Vec<String>,
+    hook_timeout_sec: u64,
+    // Неудачный pre-sleep хук отменяет сон
+    abort_sleep_on_hook_failure: bool,
+    // RTT до маяка пишется агрегатами за latency_bucket_sec
+    latency_bucket_sec: u64,
+    latency_retention_days: u64,
+    // Журнал событий (events.jsonl) для `history events`
+    event_retention_days: u64,
+    // Где хранить историю: "jsonl", "memory" (на диск раз в history_sync_sec), "sqlite"
+    history_backend: store::Backend,
+    history_sync_sec: u64,
+    // Пока работает любой из процессов (ffmpeg, rsync, borg) - не спим
+    inhibit_processes: Vec<String>,
+    inhibit_recheck_sec: u64,
+    // Уважать block-локи logind (systemd-inhibit), кроме перечисленных "who"
+    respect_inhibitors: bool,
+    ignore_inhibitors: Vec<String>,
+    // Не спать, пока диски пишут/читают быстрее порога (0 - выключено)
+    disk_io_threshold_mbps: f64,
+    disk_io_sample_sec: u64,
+    // Для внешнего/аппаратного watchdog: файл с меткой времени и /dev/watchdog
+    heartbeat_file: Option<String>,
+    watchdog_device: Option<String>,
+    heartbeat_interval_sec: u64,
+    on_invalid_config: InvalidConfigPolicy,
+    // Сервис ua.portal.Daemon1 на системной шине для апплетов
+    dbus_service: bool,
+    // Доп. аргументы rtcwake ("-d", "rtc1"); -s/-t/--date отключают наш расчет.
+    // С run_as помощник root пропускает из них только -d
+    rtcwake_args: Vec<String>,
+    // Часы RTC: "auto" (по /etc/adjtime), "utc", "local"
+    rtc_clock: rtc::RtcClock,
+    // Уведомление с кнопкой отмены всем вошедшим в графическую сессию
+    desktop_notify: bool,
+    // ntfy / Telegram с напоминаниями по ходу grace и кнопкой паузы
+    notifications: notify::NotifyConfig,
+    // Проснулись раньше срока без света - доспать остаток, если он не меньше rearm_min_sec
+    rearm_on_early_wake: bool,
+    rearm_min_sec: u64,
+    // Маршрут к маяку через VPN: "physical" (пинг мимо VPN) или "warn"
+    vpn_probe: probe::VpnProbe,
+    // Маяк за раздачей с телефона (USB/Bluetooth/hotspot) - не спать, только уведомлять
+    hotspot_guard: bool,
+    // Куда писать журнал: "auto", "stdout", "journald", "syslog"
+    log_target: log::LogTarget,
+    // Сколько последних строк журнала держать для `status --recent` и /recent
+    log_buffer_lines: usize,
+    // GET /healthz и /status (JSON) на 127.0.0.1:http_port; 0 - выключено
+    http_port: u16,
+    // Состояние и пробы в MQTT-брокер для умного дома
+    mqtt: mqtt::MqttConfig,
+    // Свои HTTP-адреса на пропажу света, сон и подъем (шаблон тела, повторы)
+    webhooks: Vec<webhook::WebhookConfig>,
+    // Письма о пропаже света и затянувшемся отключении
+    email: email::EmailConfig,
+    // Кого будить магическим пакетом, когда свет вернулся (по порядку)
+    wake_on_lan: Vec<wol::WolTarget>,
+    // Сетевые карты, которым перед сном включается Wake-on-LAN ("eth0")
+    wol_interfaces: Vec<String>,
+    // Корень только для чтения: ничего не писать в /etc и /var/lib, состояние -
+    // в /run, история - только в памяти (и наружу через MQTT/вебхуки)
+    read_only_root: bool,
+    // Пользователь, от которого демон работает после старта (root остается
+    // у помощника для rtcwake); None - весь цикл от root
+    run_as: Option<String>,
+    // Расхождение RTC больше стольких секунд - предупредить (0 - не проверять)
+    rtc_drift_alert_sec: u64,
+    // При старте - тихое "online" во все каналы: сломанный токен виден сразу
+    self_test_on_start: bool,
+    // Насколько ядру можно сдвинуть наши таймеры ради общих пробуждений; 0 - как есть
+    timer_slack_ms: u64,
 }
 
 impl Default for PortalConfig {
     fn default() -> Self {
         Self {
-            language: Language::En,
+            version: CONFIG_VERSION,
+            language: Language::from_env(),
             lighthouse_ip: "192.168.1.1".to_string(),
             target_ssid: "Unknown".to_string(),
+            network_backend: net::Backend::Auto,
+            profiles: Vec::new(),
             sleep_minutes: 60,
+            wake_times: Vec::new(),
+            resleep_minutes: Vec::new(),
+            give_up_after_wakes: 0,
             grace_period_sec: 300,
             wakeup_wait_sec: 30,
             scan_interval_sec: 60,
+            scan_jitter_min_sec: 0,
+            scan_jitter_max_sec: 0,
+            scan_interval_min_sec: 0,
+            scan_interval_max_sec: 0,
+            probe: probe::ProbeKind::Ping,
+            ping_attempts: 1,
+            ping_count: 1,
+            ping_timeout_ms: 2000,
+            ping_size: 8,
+            max_loss_percent: 50,
+            max_rtt_ms: 0.0,
+            bad_cycles: 1,
+            probe_age_alert_sec: 0,
+            interface: None,
+            nut_address: "127.0.0.1:3493".into(),
+            nut_ups: "ups".into(),
+            sensor: sensor::SensorConfig::default(),
+            beacon: beacon::BeaconConfig::default(),
+            lighthouse: lighthouse::LighthouseConfig::default(),
+            snmp: snmp::SnmpConfig::default(),
+            sleep_mode: "mem".into(),
+            low_battery_percent: 20,
+            temp_sensor: None,
+            suspend_temp_min_c: None,
+            suspend_temp_max_c: None,
+            temp_fallback_mode: "off".into(),
+            timezone: None,
+            quiet_hours: Vec::new(),
+            fleet_port: 47474,
+            fleet_token: String::new(),
+            fleet_peers: Vec::new(),
+            fleet_role: fleet::Role::Standalone,
+            fleet_wake_offset_sec: 30,
+            outage_schedule: Default::default(),
+            inverter: inverter::InverterConfig::default(),
+            pre_sleep_hooks: Vec::new(),
+            post_wake_hooks: Vec::new(),
+            hook_timeout_sec: 30,
+            abort_sleep_on_hook_failure: false,
+            latency_bucket_sec: 300,
+            latency_retention_days: 30,
+            event_retention_days: 365,
+            history_backend: store::Backend::Jsonl,
+            history_sync_sec: 3600,
+            inhibit_processes: Vec::new(),
+            inhibit_recheck_sec: 300,
+            respect_inhibitors: true,
+            ignore_inhibitors: Vec::new(),
+            disk_io_threshold_mbps: 0.0,
+            disk_io_sample_sec: 3,
+            heartbeat_file: None,
+            watchdog_device: None,
+            heartbeat_interval_sec: 10,
+            on_invalid_config: InvalidConfigPolicy::LastGood,
+            dbus_service: true,
+            rtcwake_args: Vec::new(),
+            rtc_clock: rtc::RtcClock::Auto,
+            desktop_notify: true,
+            notifications: Default::default(),
+            rearm_on_early_wake: true,
+            rearm_min_sec: 120,
+            vpn_probe: probe::VpnProbe::Physical,
+            hotspot_guard: true,
+            log_target: log::LogTarget::Auto,
+            log_buffer_lines: 500,
+            http_port: 0,
+            mqtt: Default::default(),
+            webhooks: Vec::new(),
+            email: Default::default(),
+            wake_on_lan: Vec::new(),
+            wol_interfaces: Vec::new(),
+            read_only_root: false,
+            run_as: None,
+            rtc_drift_alert_sec: 120,
+            self_test_on_start: true,
+            timer_slack_ms: 200,
+        }
+    }
+}
+
+impl PortalConfig {
+    // Смысловые проверки поверх serde: конфиг, который парсится, но усыпит
+    // машину не так, как задумано, тоже считается битым
+    fn validate(&self) -> Result<(), String> {
+        let check = |name: &str, v: u64, range: RangeInclusive<u64>| {
+            if range.contains(&v) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{} = {} is outside {}..={}",
+                    name,
+                    v,
+                    range.start(),
+                    range.end()
+                ))
+            }
+        };
+        check("sleep_minutes", self.sleep_minutes, SLEEP_MINUTES_RANGE)?;
+        for &m in &self.resleep_minutes {
+            check("resleep_minutes", m, SLEEP_MINUTES_RANGE)?;
+        }
+        check("grace_period_sec", self.grace_period_sec, GRACE_SEC_RANGE)?;
+        check("wakeup_wait_sec", self.wakeup_wait_sec, WAKEUP_SEC_RANGE)?;
+        check(
+            "scan_interval_sec",
+            self.scan_interval_sec,
+            SCAN_INTERVAL_RANGE,
+        )?;
+        check(
+            "ping_attempts",
+            self.ping_attempts as u64,
+            PING_ATTEMPTS_RANGE,
+        )?;
+        // 0 у границы - ее нет: timers::Adaptive берет 1 сек и scan_interval_sec
+        if self.scan_interval_min_sec != 0 {
+            check(
+                "scan_interval_min_sec",
+                self.scan_interval_min_sec,
+                *SCAN_INTERVAL_RANGE.start()..=self.scan_interval_sec,
+            )?;
+        }
+        if self.scan_interval_max_sec != 0 {
+            check(
+                "scan_interval_max_sec",
+                self.scan_interval_max_sec,
+                self.scan_interval_sec..=*SCAN_INTERVAL_RANGE.end(),
+            )?;
+        }
+        check(
+            "scan_jitter_max_sec",
+            self.scan_jitter_max_sec,
+            0..=self.scan_interval_sec,
+        )?;
+        check(
+            "scan_jitter_min_sec",
+            self.scan_jitter_min_sec,
+            0..=self.scan_jitter_max_sec,
+        )?;
+        check("ping_count", self.ping_count as u64, PING_COUNT_RANGE)?;
+        check(
+            "ping_timeout_ms",
+            self.ping_timeout_ms,
+            PING_TIMEOUT_MS_RANGE,
+        )?;
+        check("ping_size", self.ping_size as u64, PING_SIZE_RANGE)?;
+        check("bad_cycles", self.bad_cycles as u64, BAD_CYCLES_RANGE)?;
+        check("max_loss_percent", self.max_loss_percent as u64, 0..=100)?;
+        if self.probe_age_alert_sec != 0 {
+            check(
+                "probe_age_alert_sec",
+                self.probe_age_alert_sec,
+                PROBE_AGE_ALERT_RANGE,
+            )?;
+        }
+        if !(self.max_rtt_ms >= 0.0 && self.max_rtt_ms.is_finite()) {
+            return Err(format!(
+                "max_rtt_ms = {} is not a duration",
+                self.max_rtt_ms
+            ));
+        }
+        check(
+            "fleet_wake_offset_sec",
+            self.fleet_wake_offset_sec,
+            FLEET_WAKE_OFFSET_RANGE,
+        )?;
+        if self.fleet_role != fleet::Role::Standalone && !fleet::listener_enabled(self) {
+            return Err(format!(
+                "fleet_role = {:?} needs fleet_token and fleet_port",
+                self.fleet_role
+            ));
+        }
+        if matches!(
+            self.probe,
+            probe::ProbeKind::Ping | probe::ProbeKind::Arp | probe::ProbeKind::Auto
+        ) && self.lighthouse_ip.parse::<IpAddr>().is_err()
+        {
+            return Err(format!(
+                "lighthouse_ip '{}' is not an IP",
+                self.lighthouse_ip
+            ));
+        }
+        for (name, mode) in [
+            ("sleep_mode", &self.sleep_mode),
+            ("temp_fallback_mode", &self.temp_fallback_mode),
+        ] {
+            if !SLEEP_MODES.contains(&mode.as_str()) {
+                return Err(format!("unknown {} '{}'", name, mode));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.suspend_temp_min_c, self.suspend_temp_max_c)
+            && min >= max
+        {
+            return Err(format!(
+                "suspend_temp_min_c = {} is not below suspend_temp_max_c = {}",
+                min, max
+            ));
+        }
+        if let Some(tz) = &self.timezone {
+            schedule::TimeZone::resolve(Some(tz)).map_err(|e| format!("timezone: {}", e))?;
+        }
+        for q in &self.quiet_hours {
+            schedule::TimeWindow::parse(q).ok_or_else(|| format!("invalid quiet_hours '{}'", q))?;
+        }
+        for w in &self.wake_times {
+            schedule::WakeTime::parse(w).ok_or_else(|| format!("invalid wake_times '{}'", w))?;
+        }
+        for (i, p) in self.profiles.iter().enumerate() {
+            p.validate().map_err(|e| format!("profiles: {}", e))?;
+            if self.profiles[..i].iter().any(|q| q.ssid == p.ssid) {
+                return Err(format!("profiles: duplicate ssid '{}'", p.ssid));
+            }
+        }
+        for w in &self.webhooks {
+            w.validate().map_err(|e| format!("webhooks: {}", e))?;
+        }
+        if self.email.enabled() {
+            self.email.validate().map_err(|e| format!("email: {}", e))?;
+        }
+        if self.uses_probe(probe::ProbeKind::Sensor) {
+            self.sensor
+                .validate()
+                .map_err(|e| format!("sensor: {}", e))?;
+        }
+        if self.uses_probe(probe::ProbeKind::Beacon) {
+            self.beacon
+                .validate()
+                .map_err(|e| format!("beacon: {}", e))?;
+        }
+        if self.uses_probe(probe::ProbeKind::Http) || self.uses_probe(probe::ProbeKind::Udp) {
+            self.lighthouse
+                .validate()
+                .map_err(|e| format!("lighthouse: {}", e))?;
+        }
+        if self.uses_probe(probe::ProbeKind::Snmp) {
+            self.snmp.validate().map_err(|e| format!("snmp: {}", e))?;
+            check(
+                "snmp.reboot_grace_sec",
+                self.snmp.reboot_grace_sec,
+                GRACE_SEC_RANGE,
+            )?;
+        }
+        if self.inverter.enabled {
+            self.inverter
+                .validate()
+                .map_err(|e| format!("inverter: {}", e))?;
+        }
+        for w in &self.wake_on_lan {
+            w.validate().map_err(|e| format!("wake_on_lan: {}", e))?;
+        }
+        if let Some(i) = &self.interface
+            && (i.is_empty() || i.contains('/'))
+        {
+            return Err(format!("invalid interface '{}'", i));
+        }
+        if let Some(i) = self
+            .wol_interfaces
+            .iter()
+            .find(|i| i.is_empty() || i.contains('/'))
+        {
+            return Err(format!("invalid wol_interfaces entry '{}'", i));
+        }
+        if self.read_only_root
+            && (self.history_backend != store::Backend::Memory || self.history_sync_sec != 0)
+        {
+            return Err(
+                "read_only_root needs history_backend = \"memory\" and history_sync_sec = 0".into(),
+            );
+        }
+        Ok(())
+    }
+
+    // Проба основная или у одного из профилей
+    fn uses_probe(&self, kind: probe::ProbeKind) -> bool {
+        self.probe == kind || self.profiles.iter().any(|p| p.probe == Some(kind))
+    }
+
+    // Поля, где бывают секреты; у вебхуков - заголовки авторизации
+    fn secret_fields(&mut self) -> Vec<(String, &mut String)> {
+        let n = &mut self.notifications;
+        let mut fields = vec![
+            ("ntfy_token".to_string(), &mut n.ntfy_token),
+            ("telegram_bot_token".to_string(), &mut n.telegram_bot_token),
+            ("email.password".to_string(), &mut self.email.password),
+            ("mqtt.password".to_string(), &mut self.mqtt.password),
+            ("fleet_token".to_string(), &mut self.fleet_token),
+            ("beacon.key".to_string(), &mut self.beacon.key),
+            ("lighthouse.key".to_string(), &mut self.lighthouse.key),
+        ];
+        // "public" у всех одинаковый - не секрет
+        if self.snmp.community != "public" {
+            fields.push(("snmp.community".to_string(), &mut self.snmp.community));
+        }
+        for (i, w) in self.webhooks.iter_mut().enumerate() {
+            for (k, v) in w.headers.iter_mut() {
+                fields.push((format!("webhooks[{}].{}", i, k), v));
+            }
+        }
+        fields
+    }
+
+    // env:/file:/systemd-creds: -> сами секреты; ошибки - по одной на поле
+    fn resolve_secrets(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (field, value) in self.secret_fields() {
+            secrets::resolve_in(&field, value, &mut errors);
         }
+        errors
+    }
+
+    // Секреты открытым текстом; из заголовков вебхуков - только похожие на них
+    fn inline_secrets(&mut self) -> Vec<String> {
+        self.secret_fields()
+            .into_iter()
+            .filter(|(field, value)| {
+                let header = field
+                    .strip_prefix("webhooks[")
+                    .and_then(|f| f.split_once('.'));
+                secrets::is_inline(value)
+                    && header.is_none_or(|(_, h)| {
+                        let h = h.to_ascii_lowercase();
+                        ["auth", "token", "key", "secret"]
+                            .iter()
+                            .any(|w| h.contains(w))
+                    })
+            })
+            .map(|(field, _)| field)
+            .collect()
     }
 }
 
+// Демон стартовал не с тем конфигом, что был в /etc
+struct ConfigIssue {
+    message: String,
+    restored: bool,
+}
+
 // --- АРГУМЕНТЫ ---
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Install the binary, service, permissions, completions and man page (root or --user)
     #[arg(long)]
     install: bool,
+    /// Run the setup wizard and save the config
     #[arg(long)]
     configure: bool,
+    /// With --configure: print the summary as JSON and exit instead of starting the daemon
+    #[arg(long, requires = "configure")]
+    json: bool,
+    /// With --configure: start from settings for this kind of machine
+    #[arg(long, value_enum, requires = "configure")]
+    preset: Option<presets::Preset>,
+    /// Control menu: pause, resume or stop the running daemon
     #[arg(long)]
     off: bool,
+    /// Check everything and log what would be done, but never sleep; with --install only list files and commands
+    #[arg(long)]
+    dry_run: bool,
+    /// Run as a regular user: config and state in XDG dirs, sleep via logind
+    #[arg(long, global = true)]
+    user: bool,
+    /// Log verbosity (overrides RUST_LOG)
+    #[arg(long, global = true, value_enum)]
+    log_level: Option<log::Level>,
+    /// Inject failures for testing: probe-fail[=N], rtcwake-fail[=N], guard-block[=N], battery=PCT
+    #[arg(long, hide = true)]
+    inject: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Cmd>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Show whether the daemon runs, what it is doing and the config summary
+    Status {
+        /// Machine-readable output
+        #[arg(long)]
+        json: bool,
+        /// Also show the daemon's recent log lines (default: last hour)
+        #[arg(long, value_name = "SPAN", num_args = 0..=1, default_missing_value = "1h")]
+        recent: Option<String>,
+        /// Keep printing a Waybar JSON line on every change (text, tooltip, class)
+        #[arg(long, conflicts_with_all = ["json", "recent"])]
+        waybar: bool,
+    },
+    /// Disable sleep on this host (no menu, for scripts and hotkeys)
+    Pause(PauseArgs),
+    /// Re-enable sleep on this host
+    Resume,
+    /// Explain the daemon's current state and its last decision (any user)
+    Why,
+    /// Query recorded history (any user)
+    History {
+        #[command(subcommand)]
+        kind: HistoryKind,
+    },
+    /// Outage statistics from the event history: dark time, outages, sleep cycles
+    Stats {
+        /// How far back: 7d, 30d, 365d
+        #[arg(long, default_value = "30d")]
+        since: String,
+        /// Group by day, week or month
+        #[arg(long, value_enum, default_value = "day")]
+        by: StatsPeriod,
+    },
+    /// Live dashboard: lighthouse, RTT, countdowns, recent events and control keys
+    Tui,
+    /// Control the daemon on this host or, with --all, every daemon on the LAN
+    Ctl {
+        #[arg(long)]
+        all: bool,
+        #[command(subcommand)]
+        action: CtlAction,
+    },
+    /// Print a shell completion script (installed by --install)
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+    /// Print the man page (roff) to stdout (installed by --install)
+    Man,
+    /// Broadcast signed heartbeats for probe = "beacon" (run on a mains-powered device)
+    Beacon,
+    /// Answer http/udp probes and broadcast beacons (run on a mains-powered device)
+    Lighthouse,
+    /// Store a secret read from stdin in a 0600 file and print its file: reference
+    Secret {
+        /// File name in the secrets directory, e.g. telegram
+        name: String,
+    },
+    /// Remove what --install set up: binary, service, sudo/doas rules, group
+    Uninstall {
+        /// Also delete the config and recorded history
+        #[arg(long)]
+        purge: bool,
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Measure the cost of an idle monitoring cycle
+    #[command(hide = true)]
+    Bench {
+        #[arg(long, default_value_t = 20)]
+        cycles: u32,
+    },
+    /// Root helper of a daemon that dropped privileges (run_as)
+    #[command(hide = true)]
+    Privhelper {
+        #[arg(long)]
+        uid: u32,
+        #[arg(long)]
+        parent: u32,
+        #[arg(long, default_value = "rtc0")]
+        rtc: String,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct PauseArgs {
+    /// Minutes from now
+    #[arg(
+        value_parser = clap::value_parser!(u64).range(1..=10080),
+        required_unless_present = "until",
+        conflicts_with = "until"
+    )]
+    minutes: Option<u64>,
+    /// Until a local time: "23:30", "tomorrow 07:00", "2026-03-29 07:00"
+    #[arg(long)]
+    until: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryKind {
+    /// Connection losses, sleeps, wakes and pauses, with power outages summed up
+    Events {
+        /// How far back: 90m, 24h, 7d
+        #[arg(long, default_value = "24h")]
+        since: String,
+    },
+    /// Lighthouse round-trip time trend
+    Latency {
+        /// How far back: 90m, 24h, 7d
+        #[arg(long, default_value = "24h")]
+        since: String,
+    },
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum StatsPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Subcommand, Debug)]
+enum CtlAction {
+    /// Disable sleep for N minutes or until a time
+    Pause(PauseArgs),
+    /// Re-enable sleep
+    Resume,
 }
 
 fn main() {
-    let args = Args::parse();
+    // Единственное место выхода с ошибкой: команды возвращают PortalError
+    if let Err(e) = run(Args::parse()) {
+        e.exit();
+    }
+}
+
+fn run(args: Args) -> Result<(), PortalError> {
+    if let Err(e) = paths::init(args.user) {
+        eprintln!("❌ --user: {}", e);
+        return Err(PortalError::Reported(2));
+    }
+    log::init(args.log_level);
+    DRY_RUN.store(args.dry_run, Ordering::Relaxed);
 
-    // 1. Установка (требует root)
+    // 1. Установка (требует root, кроме --user)
     if args.install {
-        run_system_install();
-        return;
+        return if paths::user() {
+            run_user_install()
+        } else {
+            run_system_install()
+        };
     }
 
     // Загружаем конфиг (если есть), чтобы знать язык для меню
-    let mut temp_lang = Language::En;
-    if let Ok(cfg) = load_config_safe() {
-        temp_lang = cfg.language;
+    let temp_lang = load_config_safe().map_or_else(|_| Language::from_env(), |cfg| cfg.language);
+
+    match args.command {
+        Some(Cmd::Status { waybar: true, .. }) => {
+            statusbar::run(temp_lang);
+            return Ok(());
+        }
+        Some(Cmd::Status { json, recent, .. }) => {
+            return run_status(temp_lang, json, recent.as_deref());
+        }
+        Some(Cmd::Pause(p)) => return run_ctl(temp_lang, false, CtlAction::Pause(p)),
+        Some(Cmd::Resume) => return run_ctl(temp_lang, false, CtlAction::Resume),
+        Some(Cmd::Why) => return run_why(temp_lang),
+        Some(Cmd::History { kind }) => return run_history(temp_lang, kind),
+        Some(Cmd::Stats { since, by }) => return run_stats(temp_lang, &since, by),
+        Some(Cmd::Tui) => return dashboard::run(temp_lang),
+        Some(Cmd::Ctl { all, action }) => return run_ctl(temp_lang, all, action),
+        Some(Cmd::Uninstall { purge, dry_run }) => {
+            if dry_run {
+                DRY_RUN.store(true, Ordering::Relaxed);
+            }
+            return run_uninstall(purge);
+        }
+        Some(Cmd::Completions { shell }) => {
+            print!("{}", completions::generate(shell, Args::command()));
+            return Ok(());
+        }
+        Some(Cmd::Man) => {
+            print!("{}", completions::man(Args::command()));
+            return Ok(());
+        }
+        Some(Cmd::Beacon) => return run_beacon(),
+        Some(Cmd::Lighthouse) => return run_lighthouse(),
+        Some(Cmd::Secret { name }) => return run_secret(&name),
+        Some(Cmd::Bench { cycles }) => {
+            run_bench(cycles);
+            return Ok(());
+        }
+        Some(Cmd::Privhelper { uid, parent, rtc }) => privsep::serve(uid, parent, rtc),
+        None => {}
     }
 
     // 2. Меню управления (выключить/пауза)
     if args.off {
-        run_control_menu(temp_lang);
-        return;
+        return run_control_menu(temp_lang);
     }
 
     // 3. Логика загрузки конфига или визарда
     // Если конфига нет ИЛИ явно попросили --configure
-    let config = if args.configure || !Path::new(CONFIG_FILE).exists() {
-        // Проверяем права, так как писать будем в /etc
-        if !is_root() {
-            println!(
-                "⚠️  Config setup requires ROOT permissions to write to {}.",
-                CONFIG_FILE
-            );
-            println!("⚠️  Please run with sudo/doas.");
-            std::process::exit(1);
-        }
-        run_interactive_wizard()
-    } else {
-        load_config_safe().unwrap_or_default()
-    };
+    let (config, config_issue) =
+        if args.configure || !Path::new(&paths::config(CONFIG_FILE)).exists() {
+            // Проверяем права, так как писать будем в /etc
+            if !paths::user() && !is_root() {
+                println!(
+                    "⚠️  Config setup requires ROOT permissions to write to {}.",
+                    paths::config(CONFIG_FILE)
+                );
+                println!("⚠️  Please run with sudo/doas.");
+                return Err(PortalError::Reported(1));
+            }
+            let config = run_interactive_wizard(args.json, args.preset)?;
+            // Скрипту провижининга нужна сводка, а не демон на переднем плане
+            if args.json {
+                return Ok(());
+            }
+            (config, None)
+        } else {
+            startup_config()?
+        };
 
     // 4. Запуск демона
-    run_daemon(config);
+    if let Err(e) = inject::configure(&args.inject) {
+        eprintln!("❌ --inject: {}", e);
+        return Err(PortalError::Reported(2));
+    }
+    run_daemon(config, config_issue)
 }
 
 // --- СЛОВАРЬ (LOCALIZATION) ---
+// Ключи locales/*.ftl совпадают с именами полей
+#[derive(Deserialize)]
 struct Locales {
     wizard_title: String,
     scan_msg: String,
@@ -119,15 +1019,95 @@ struct Locales {
     grace_sec_prompt: String,
     wakeup_sec_prompt: String,
     scan_int_prompt: String,
+    ping_count_prompt: String,
+    ping_timeout_prompt: String,
+    ping_size_prompt: String,
+    probe_prompt: String,
+    sensor_prompt: String,
+    lighthouse_key_prompt: String,
+    mdns_scan: String,
+    mdns_select: String,
+    mdns_none: String,
+    probe_recommended: String,
+    preset_prompt: String,
+    preset_custom: String,
+    preset_laptop: String,
+    preset_homeserver: String,
+    preset_sbc: String,
     settings_saved: String,
+    save_failed: String,
+    summary_title: String,
+    summary_preset: String,
+    summary_probe: String,
+    summary_sleep: String,
+    summary_grace: String,
+    summary_interval: String,
+    summary_next: String,
+    not_a_number: String,
+    out_of_range: String,
 
     daemon_start: String,
+    handoff_resumed: String,
     daemon_net: String,
     daemon_interval: String,
+    daemon_link: String,
+    iface_watch: String,
+    profile_applied: String,
+    gateway_probe: String,
+    gateway_adopted: String,
+    mdns_probe: String,
+    mdns_adopted: String,
+    router_rebooting: String,
+    profile_unknown: String,
+    iface_missing: String,
+    probe_auto: String,
+    hotspot_warn: String,
+    daemon_tz: String,
+    user_mode: String,
+    dry_run_mode: String,
+    freebsd_mode: String,
+    daemon_quiet: String,
+    quiet_invalid: String,
+    sleep_skipped_quiet: String,
+    outage_loaded: String,
+    outage_scheduled: String,
+    wake_target: String,
+    outage_unscheduled: String,
+    hook_abort: String,
+    sleep_postponed: String,
     conn_lost: String,
     conn_restored: String,
     no_light_sleep: String,
     waking_up: String,
+    wake_light_back: String,
+    tui_next_check: String,
+    tui_sleep_check: String,
+    tui_paused: String,
+    tui_unknown_net: String,
+    tui_hint: String,
+    tui_bye: String,
+    early_wake: String,
+    booted_after_off: String,
+    give_up: String,
+    notify_title: String,
+    notify_sleep_at: String,
+    notify_cancel: String,
+    remote_lost: String,
+    remote_sleep_in: String,
+    remote_pause: String,
+    sms_sleeping: String,
+    sms_battery: String,
+    remote_woke: String,
+    email_outage: String,
+    selftest_online: String,
+    status_degraded: String,
+    rtc_drift_warn: String,
+    remote_rtc_drift: String,
+    probe_stale: String,
+    secret_failed: String,
+    secret_inline: String,
+    read_only_no_run: String,
+    already_running: String,
 
     ctrl_title: String,
     ctrl_action: String,
@@ -136,508 +1116,3287 @@ struct Locales {
     ctrl_kill: String,
     ctrl_exit: String,
     pause_prompt: String,
+    ctrl_pause_until: String,
+    pause_until_prompt: String,
+    pause_until_activated: String,
+    bad_moment: String,
     pause_activated: String,
     pause_removed: String,
     process_killed: String,
+
+    fleet_sending: String,
+    fleet_no_token: String,
+    fleet_none: String,
+    fleet_summary: String,
+    fleet_aligned: String,
+
+    no_rights: String,
+    not_running: String,
+    why_phase: String,
+    why_decision: String,
+    why_probe: String,
+    why_last_ok: String,
+    why_counters: String,
+    why_config: String,
+    why_blocked: String,
+    status_running: String,
+    status_sleep_at: String,
+    status_rtt: String,
+    status_ago: String,
+    status_recent: String,
+    status_paused: String,
+    status_config: String,
+    status_no_config: String,
+    bad_span: String,
+    history_empty: String,
+    history_outages: String,
+    history_ongoing: String,
+    stats_outages: String,
+    stats_dark: String,
+    stats_avg: String,
+    stats_longest: String,
+    stats_sleeps: String,
+    stats_header: String,
+    latency_header: String,
+    latency_summary: String,
+    latency_lost: String,
+    dash_title: String,
+    dash_hint: String,
+    dash_no_bus: String,
+    dash_events: String,
+    dash_sleep_confirm: String,
+    dash_sent: String,
+    dash_failed: String,
 }
 
 impl Locales {
     fn new(lang: Language) -> Self {
-        match lang {
-            Language::En => Locales {
-                wizard_title: "\n🔧 --- PORTAL SETUP WIZARD ---".into(),
-                scan_msg: "🔍 Scanning networks...".into(),
-                scan_fail: "❌ No networks found.".into(),
-                enter_ip_manual: "Enter Lighthouse IP Manually".into(),
-                select_net: "Select Network:".into(),
-                selected_net_log: "✅ Selected Network:".into(),
-                enter_ip_prompt: "Enter Lighthouse IP".into(),
-                sleep_mins_prompt: "Minutes to sleep without light?".into(),
-                grace_sec_prompt: "Grace period (sec) before sleep?".into(),
-                wakeup_sec_prompt: "Wait (sec) after waking up?".into(),
-                scan_int_prompt: "Scan interval (sec)?".into(),
-                settings_saved: format!("✅ Settings saved to {}!", CONFIG_FILE),
-
-                daemon_start: "👻 Portal Daemon: START".into(),
-                daemon_net: "📡 Network:".into(),
-                daemon_interval: "⏱ Interval:".into(),
-                conn_lost: "⚠️  Connection lost. Waiting".into(),
-                conn_restored: "✅ Connection restored.".into(),
-                no_light_sleep: "🌑 No light. Sleeping".into(),
-                waking_up: "☀️  Woke up. Waiting".into(),
-
-                ctrl_title: "\n🎮 --- PORTAL CONTROL ---".into(),
-                ctrl_action: "Action?".into(),
-                ctrl_pause: "⏸  PAUSE (Disable sleep for X mins)".into(),
-                ctrl_resume: "▶️  RESUME (Enable sleep mode)".into(),
-                ctrl_kill: "🛑  KILL Process".into(),
-                ctrl_exit: "❌  Exit".into(),
-                pause_prompt: "Pause for how many MINUTES?".into(),
-                pause_activated: "✅ Pause activated for".into(),
-                pause_removed: "✅ Pause removed.".into(),
-                process_killed: "💀 Process stopped.".into(),
-            },
-            Language::Ru => Locales {
-                wizard_title: "\n🔧 --- МАСТЕР НАСТРОЙКИ PORTAL ---".into(),
-                scan_msg: "🔍 Сканирую сети...".into(),
-                scan_fail: "❌ Сети не найдены.".into(),
-                enter_ip_manual: "Ввести IP Маяка вручную".into(),
-                select_net: "Выбери сеть:".into(),
-                selected_net_log: "✅ Выбрана сеть:".into(),
-                enter_ip_prompt: "Введи IP Маяка".into(),
-                sleep_mins_prompt: "Сколько МИНУТ спать без света?".into(),
-                grace_sec_prompt: "Грейс-период (сек) перед сном?".into(),
-                wakeup_sec_prompt: "Ждать сек. после включения?".into(),
-                scan_int_prompt: "Интервал проверки (сек)?".into(),
-                settings_saved: format!("✅ Настройки сохранены в {}!", CONFIG_FILE),
-
-                daemon_start: "👻 Portal Daemon: ЗАПУСК".into(),
-                daemon_net: "📡 Сеть:".into(),
-                daemon_interval: "⏱ Интервал:".into(),
-                conn_lost: "⚠️  Потеря связи. Ждем".into(),
-                conn_restored: "✅ Связь вернулась.".into(),
-                no_light_sleep: "🌑 Света нет. Сон".into(),
-                waking_up: "☀️  Проснулись. Ждем".into(),
-
-                ctrl_title: "\n🎮 --- УПРАВЛЕНИЕ PORTAL ---".into(),
-                ctrl_action: "Действие?".into(),
-                ctrl_pause: "⏸  Поставить на ПАУЗУ".into(),
-                ctrl_resume: "▶️  Снять с паузы".into(),
-                ctrl_kill: "🛑  Убить процесс (Kill)".into(),
-                ctrl_exit: "❌  Выход".into(),
-                pause_prompt: "На сколько МИНУТ?".into(),
-                pause_activated: "✅ Пауза активирована на".into(),
-                pause_removed: "✅ Пауза снята.".into(),
-                process_killed: "💀 Процесс остановлен.".into(),
-            },
-        }
+        let config = paths::config(CONFIG_FILE);
+        let args = [("config", config.as_str()), ("group", GROUP_NAME)];
+        let messages = locale::Bundle::load(lang.code()).format_all(&args);
+        serde_json::to_value(messages)
+            .and_then(serde_json::from_value)
+            .unwrap_or_else(|e| panic!("locales/{}.ftl: {}", lang.code(), e))
     }
 }
 
 // === МЕНЮ УПРАВЛЕНИЯ ===
-fn run_control_menu(lang: Language) {
+fn run_control_menu(lang: Language) -> Result<(), PortalError> {
     let t = Locales::new(lang);
-    println!("{}", t.ctrl_title);
+    println!("\n{}", t.ctrl_title);
 
-    let selections = vec![&t.ctrl_pause, &t.ctrl_resume, &t.ctrl_kill, &t.ctrl_exit];
+    let selections = vec![
+        &t.ctrl_pause,
+        &t.ctrl_pause_until,
+        &t.ctrl_resume,
+        &t.ctrl_kill,
+        &t.ctrl_exit,
+    ];
     let selection = Select::with_theme(&ColorfulTheme::default())
         .with_prompt(&t.ctrl_action)
         .default(0)
         .items(&selections)
-        .interact()
-        .unwrap();
+        .interact()?;
 
     match selection {
         0 => {
-            let mins: u64 = Input::with_theme(&ColorfulTheme::default())
-                .with_prompt(&t.pause_prompt)
-                .default(60)
-                .interact_text()
-                .unwrap();
-            let end = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                + (mins * 60);
-            fs::write(PAUSE_FILE, end.to_string()).ok();
-            println!("{} {} min.", t.pause_activated, mins);
+            let mins = prompt_number(&t, &t.pause_prompt, 60, PAUSE_MINUTES_RANGE)?;
+            apply_local(&t, fleet::FleetAction::Pause(mins))?;
         }
         1 => {
-            fs::remove_file(PAUSE_FILE).ok();
-            println!("{}", t.pause_removed);
+            let tz = local_tz();
+            let input: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(&t.pause_until_prompt)
+                .default("07:00".into())
+                .validate_with(|v: &String| -> Result<(), String> {
+                    match schedule::parse_moment(v, &tz, unix_now() as i64) {
+                        Some(_) => Ok(()),
+                        None => Err(t.bad_moment.clone()),
+                    }
+                })
+                .interact_text()?;
+            // validate_with уже проверил: None тут не бывает
+            if let Some(until) = schedule::parse_moment(&input, &tz, unix_now() as i64) {
+                apply_local(&t, fleet::FleetAction::PauseUntil(until as u64))?;
+            }
         }
-        2 => {
+        2 => apply_local(&t, fleet::FleetAction::Resume)?,
+        3 => {
             Command::new("pkill")
                 .args(["-f", "portal_daemon"])
                 .status()
                 .ok();
-            fs::remove_file(PAUSE_FILE).ok();
+            clear_pause().ok();
             println!("{}", t.process_killed);
         }
         _ => {}
     }
+    Ok(())
 }
 
-// === МАСТЕР НАСТРОЙКИ ===
-fn run_interactive_wizard() -> PortalConfig {
-    // Создаем директорию конфига, если нет
-    if !Path::new(CONFIG_DIR).exists() {
-        println!("📂 Creating config directory: {}", CONFIG_DIR);
-        fs::create_dir_all(CONFIG_DIR).expect("Failed to create config dir");
+// Пауза/снятие на этом хосте; без прав на /run/portal_daemon - понятная ошибка
+fn apply_local(t: &Locales, action: fleet::FleetAction) -> Result<(), PortalError> {
+    let res = match action {
+        fleet::FleetAction::Pause(mins) => set_pause(mins).map(|_| {
+            println!("{} {} min.", t.pause_activated, mins);
+        }),
+        fleet::FleetAction::PauseUntil(ts) => set_pause_until(ts).map(|_| {
+            println!(
+                "{} {}",
+                t.pause_until_activated,
+                local_tz().to_local(ts as i64)
+            );
+        }),
+        fleet::FleetAction::Resume => clear_pause().map(|_| println!("{}", t.pause_removed)),
+    };
+    res.map_err(|e| {
+        eprintln!("{} ({})", t.no_rights, e);
+        PortalError::Reported(1)
+    })
+}
+
+// Минуты или --until -> команда; неразборчивое время - ошибка использования
+fn pause_action(t: &Locales, p: &PauseArgs) -> Result<fleet::FleetAction, PortalError> {
+    if let Some(mins) = p.minutes {
+        return Ok(fleet::FleetAction::Pause(mins));
+    }
+    let until = p.until.as_deref().unwrap_or_default();
+    match schedule::parse_moment(until, &local_tz(), unix_now() as i64) {
+        Some(ts) => Ok(fleet::FleetAction::PauseUntil(ts as u64)),
+        None => {
+            eprintln!("{} '{}'", t.bad_moment, until);
+            Err(PortalError::Reported(2))
+        }
     }
+}
 
-    let langs = &["English (Default)", "Русский"];
-    let lang_sel = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("Select Language / Выберите язык")
-        .default(0)
-        .items(&langs[..])
-        .interact()
-        .unwrap();
+// Пояс из конфига (как у демона), иначе системный
+fn local_tz() -> schedule::TimeZone {
+    let name = load_config_safe().ok().and_then(|c| c.timezone);
+    schedule::TimeZone::resolve(name.as_deref()).unwrap_or_else(|_| schedule::TimeZone::utc())
+}
 
-    let lang = if lang_sel == 1 {
-        Language::Ru
-    } else {
-        Language::En
-    };
-    let t = Locales::new(lang);
+// === ЧТЕНИЕ СОСТОЯНИЯ ===
+// Краткая сводка конфига для status (без токенов и прочих секретов)
+#[derive(Serialize, Deserialize)]
+struct ConfigSummary {
+    target_ssid: String,
+    lighthouse_ip: String,
+    probe: probe::ProbeKind,
+    sleep_minutes: u64,
+    grace_period_sec: u64,
+    scan_interval_sec: u64,
+}
 
-    println!("{}", t.wizard_title);
+impl ConfigSummary {
+    fn of(c: &PortalConfig) -> Self {
+        Self {
+            target_ssid: c.target_ssid.clone(),
+            lighthouse_ip: c.lighthouse_ip.clone(),
+            probe: c.probe,
+            sleep_minutes: c.sleep_minutes,
+            grace_period_sec: c.grace_period_sec,
+            scan_interval_sec: c.scan_interval_sec,
+        }
+    }
+}
 
-    let mut final_ip = String::new();
-    let mut final_ssid = "Manual".to_string();
+#[derive(Serialize, Deserialize)]
+struct StatusReport {
+    running: bool,
+    // Сколько секунд нет удачной пробы - главное число для мониторинга
+    last_ok_age_sec: Option<u64>,
+    daemon: Option<state::DaemonState>,
+    pause_until: Option<u64>,
+    pause_remaining_sec: u64,
+    config: Option<ConfigSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recent: Option<Vec<log::Recent>>,
+}
 
-    println!("{}", t.scan_msg);
-    let networks = scan_networks();
+fn status_report() -> StatusReport {
+    let daemon = state::read_state().filter(state::daemon_alive);
+    let now = unix_now();
+    let pause_until = pause_until().filter(|&u| u > now);
+    let config = load_config_safe().ok().map(|c| ConfigSummary::of(&c));
+    StatusReport {
+        running: daemon.is_some(),
+        last_ok_age_sec: daemon.as_ref().map(last_ok_age),
+        daemon,
+        pause_until,
+        pause_remaining_sec: pause_until.map_or(0, |u| u - now),
+        config,
+        recent: None,
+    }
+}
 
-    if networks.is_empty() {
-        println!("{}", t.scan_fail);
-        final_ip = Input::with_theme(&ColorfulTheme::default())
-            .with_prompt(&t.enter_ip_manual)
-            .default("192.168.1.1".into())
-            .interact_text()
-            .unwrap();
+// Удачных проб с запуска еще не было - считаем от запуска
+fn last_ok_age(st: &state::DaemonState) -> u64 {
+    let since = if st.last_ok_at > 0 {
+        st.last_ok_at
     } else {
-        let mut options: Vec<String> = networks
-            .iter()
-            .map(|n| format!("{} (GW: {})", n.ssid, n.gateway))
-            .collect();
-        options.push(t.enter_ip_manual.clone());
-
-        let sel = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt(&t.select_net)
-            .default(0)
-            .items(&options)
-            .interact()
-            .unwrap();
-        if sel < networks.len() {
-            final_ip = networks[sel].gateway.clone();
-            final_ssid = networks[sel].ssid.clone();
-            println!(
-                "{} {} -> Target IP: {}",
-                t.selected_net_log, final_ssid, final_ip
-            );
-        } else {
-            final_ip = Input::with_theme(&ColorfulTheme::default())
-                .with_prompt(&t.enter_ip_prompt)
-                .interact_text()
-                .unwrap();
-        }
-    }
-
-    let sleep_minutes: u64 = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt(&t.sleep_mins_prompt)
-        .default(60)
-        .interact_text()
-        .unwrap();
-    let grace_period_sec: u64 = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt(&t.grace_sec_prompt)
-        .default(300)
-        .interact_text()
-        .unwrap();
-    let wakeup_wait_sec: u64 = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt(&t.wakeup_sec_prompt)
-        .default(30)
-        .interact_text()
-        .unwrap();
-    let scan_interval_sec: u64 = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt(&t.scan_int_prompt)
-        .default(60)
-        .interact_text()
-        .unwrap();
-
-    let config = PortalConfig {
-        language: lang,
-        lighthouse_ip: final_ip,
-        target_ssid: final_ssid,
-        sleep_minutes,
-        grace_period_sec,
-        wakeup_wait_sec,
-        scan_interval_sec,
+        st.started_at
     };
-
-    let json = serde_json::to_string_pretty(&config).expect("Fail json");
-    fs::write(CONFIG_FILE, json).expect("Fail write");
-    println!("{}\n", t.settings_saved);
-    config
+    unix_now().saturating_sub(since)
 }
 
-// === ДЕМОН ===
-fn run_daemon(cfg: PortalConfig) {
-    let t = Locales::new(cfg.language);
-    let sleep_seconds = cfg.sleep_minutes * 60;
-
-    println!("{}", t.daemon_start);
-    println!("{} {}", t.daemon_net, cfg.target_ssid);
-    println!("{} {} sec", t.daemon_interval, cfg.scan_interval_sec);
+// Тот же отчет, что `status --json` (для D-Bus GetStatus и HTTP /status)
+fn status_json() -> String {
+    serde_json::to_string(&status_report()).unwrap_or_default()
+}
 
-    loop {
-        if check_pause() {
-            thread::sleep(Duration::from_secs(cfg.scan_interval_sec));
-            continue;
+fn run_status(lang: Language, json: bool, recent: Option<&str>) -> Result<(), PortalError> {
+    let t = Locales::new(lang);
+    let mut report = status_report();
+    // Журнал нужнее всего, когда демон упал - читаем и без живого процесса
+    if let Some(span) = recent {
+        let Some(span) = schedule::parse_span(span) else {
+            eprintln!("{} '{}'", t.bad_span, span);
+            return Err(PortalError::Reported(1));
+        };
+        let mut lines = log::read_recent(unix_now().saturating_sub(span));
+        // В файле до двух буферов: старая половина из памяти демона уже ушла
+        let cap = load_config_safe().unwrap_or_default().log_buffer_lines;
+        lines.drain(..lines.len().saturating_sub(cap));
+        report.recent = Some(lines);
+    }
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).unwrap_or_default()
+        );
+    } else {
+        let tz = local_tz();
+        let at = |ts: u64| tz.to_local(ts as i64).to_string();
+        match &report.daemon {
+            Some(st) => {
+                println!("{} (pid {}): {:?}", t.status_running, st.pid, st.phase);
+                if st.phase == state::Phase::Grace && st.sleep_at > 0 {
+                    println!("{} {}", t.status_sleep_at, at(st.sleep_at));
+                }
+                if st.last_ok_at > 0 {
+                    println!(
+                        "{} {} ({} {})",
+                        t.why_last_ok,
+                        at(st.last_ok_at),
+                        hm(last_ok_age(st)),
+                        t.status_ago
+                    );
+                    if let Some(b) = &st.last_blocked {
+                        println!("{} {}", t.why_blocked, b);
+                    }
+                }
+                if let Some(rtt) = st.last_rtt_ms {
+                    println!("{} {:.1} ms", t.status_rtt, rtt);
+                }
+                if let Some(c) = &st.probe_choice {
+                    println!("{} {}", t.probe_auto, c);
+                }
+                if let Some(w) = &st.config_warning {
+                    println!("{} {}", t.why_config, w);
+                }
+                for (name, c) in st.channels.iter().filter(|(_, c)| !c.ok) {
+                    let err = c.error.as_deref().unwrap_or("-");
+                    println!("{} {} ({})", t.status_degraded, name, err);
+                }
+            }
+            None => println!("{}", t.not_running),
         }
-
-        if check_ping(&cfg.lighthouse_ip) {
-            thread::sleep(Duration::from_secs(cfg.scan_interval_sec));
-        } else {
-            println!("{} {} sec...", t.conn_lost, cfg.grace_period_sec);
-            thread::sleep(Duration::from_secs(cfg.grace_period_sec));
-            if check_pause() {
-                continue;
+        if let Some(until) = report.pause_until {
+            let left = report.pause_remaining_sec;
+            println!(
+                "{} {}h {:02}m (-> {})",
+                t.status_paused,
+                left / 3600,
+                left % 3600 / 60,
+                at(until)
+            );
+        }
+        match &report.config {
+            Some(c) => println!(
+                "{} {} -> {} ({:?}), sleep {} min, grace {} sec, every {} sec",
+                t.status_config,
+                c.target_ssid,
+                c.lighthouse_ip,
+                c.probe,
+                c.sleep_minutes,
+                c.grace_period_sec,
+                c.scan_interval_sec
+            ),
+            None => println!("{}", t.status_no_config),
+        }
+        if let Some(lines) = &report.recent {
+            println!("\n{}", t.status_recent);
+            if lines.is_empty() {
+                println!("{}", t.history_empty);
             }
-
-            if check_ping(&cfg.lighthouse_ip) {
-                println!("{}", t.conn_restored);
-            } else {
-                println!("{} {} min.", t.no_light_sleep, cfg.sleep_minutes);
-                enter_hibernation(sleep_seconds);
-                println!("{} {} sec...", t.waking_up, cfg.wakeup_wait_sec);
-                thread::sleep(Duration::from_secs(cfg.wakeup_wait_sec));
+            for r in lines {
+                println!("{} {:<5} {}", at(r.ts), r.level, r.message);
             }
         }
     }
+    // 3 - как у LSB status: демон не запущен
+    if !report.running {
+        return Err(PortalError::Reported(3));
+    }
+    Ok(())
 }
 
-// === УТИЛИТЫ ===
-fn load_config_safe() -> Result<PortalConfig, ()> {
-    if let Ok(d) = fs::read_to_string(CONFIG_FILE) {
-        if let Ok(c) = serde_json::from_str(&d) {
-            return Ok(c);
+fn run_why(lang: Language) -> Result<(), PortalError> {
+    let t = Locales::new(lang);
+    let Some(st) = state::read_state().filter(state::daemon_alive) else {
+        println!("{}", t.not_running);
+        return Err(PortalError::Reported(3));
+    };
+    let tz = local_tz();
+    let at = |ts: u64| {
+        if ts == 0 {
+            "-".to_string()
+        } else {
+            tz.to_local(ts as i64).to_string()
         }
+    };
+    println!("{} {:?} (pid {})", t.why_phase, st.phase, st.pid);
+    if let Some(w) = &st.config_warning {
+        println!("{} {}", t.why_config, w);
     }
-    Err(())
-}
-
-fn check_pause() -> bool {
-    if Path::new(PAUSE_FILE).exists() {
-        if let Ok(c) = fs::read_to_string(PAUSE_FILE) {
-            if let Ok(end) = c.trim().parse::<u64>() {
-                let now = SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                if now < end {
-                    return true;
-                } else {
-                    fs::remove_file(PAUSE_FILE).ok();
-                    return false;
-                }
-            }
+    println!(
+        "{} {} @ {}",
+        t.why_decision,
+        st.decision,
+        at(st.decision_at)
+    );
+    println!(
+        "{} {} {} @ {}",
+        t.why_probe,
+        st.lighthouse_ip,
+        if st.last_probe_ok { "OK" } else { "FAIL" },
+        at(st.last_probe_at)
+    );
+    println!("{} {}", t.why_last_ok, at(st.last_ok_at));
+    if let Some(b) = &st.last_blocked {
+        println!("{} {}", t.why_blocked, b);
+    }
+    if !st.counters.is_empty() {
+        println!("{}", t.why_counters);
+        for (k, v) in &st.counters {
+            println!("   {:<28} {}", k, v);
         }
-        fs::remove_file(PAUSE_FILE).ok();
     }
-    false
+    Ok(())
 }
 
-fn scan_networks() -> Vec<NetworkInfo> {
-    let mut r = Vec::new();
-    let o = Command::new("nmcli")
-        .args(["-t", "-f", "NAME,DEVICE", "connection", "show", "--active"])
-        .output()
-        .ok();
-    if let Some(out) = o {
-        for l in String::from_utf8_lossy(&out.stdout).lines() {
-            let p: Vec<&str> = l.split(':').collect();
-            if p.len() >= 2 {
-                let (s, d) = (p[0], p[1]);
-                if d == "lo" || s.is_empty() {
-                    continue;
-                }
-                if let Some(gw) = get_gateway_for_device(d) {
-                    r.push(NetworkInfo {
-                        ssid: s.to_string(),
-                        device: d.to_string(),
-                        gateway: gw,
-                    });
-                }
-            }
-        }
-    }
-    r
+// История в том же хранилище, что у демона (буфер "memory" отсюда не виден)
+fn open_history() {
+    let cfg = load_config_safe().unwrap_or_default();
+    store::open(cfg.history_backend, cfg.history_sync_sec);
 }
 
-fn get_gateway_for_device(dev: &str) -> Option<String> {
-    let o = Command::new("nmcli")
-        .args(["-t", "dev", "show", dev])
-        .output()
-        .ok()?;
-    for l in String::from_utf8_lossy(&o.stdout).lines() {
-        if l.starts_with("IP4.GATEWAY:") {
-            let p: Vec<&str> = l.split(':').collect();
-            if p.len() >= 2 {
-                let gw = p[1].trim();
-                if !gw.is_empty() && gw != "--" {
-                    return Some(gw.to_string());
-                }
+fn run_history(lang: Language, kind: HistoryKind) -> Result<(), PortalError> {
+    let t = Locales::new(lang);
+    open_history();
+    let tz = local_tz();
+    let since_ts = |since: &str| match schedule::parse_span(since) {
+        Some(span) => Ok(unix_now().saturating_sub(span)),
+        None => {
+            eprintln!("{} '{}'", t.bad_span, since);
+            Err(PortalError::Reported(1))
+        }
+    };
+    match kind {
+        HistoryKind::Events { since } => {
+            let rows = history::read_events(since_ts(&since)?);
+            if rows.is_empty() {
+                println!("{}", t.history_empty);
+                return Ok(());
+            }
+            for r in &rows {
+                println!("{}  {:<20} {}", tz.to_local(r.ts as i64), r.event, r.detail);
             }
+            let outages = history::outages(&rows);
+            if outages.is_empty() {
+                return Ok(());
+            }
+            println!("\n{}", t.history_outages);
+            for (start, end) in outages {
+                let till = end.unwrap_or_else(unix_now);
+                println!(
+                    "   {} → {}  ({})",
+                    tz.to_local(start as i64),
+                    end.map(|e| tz.to_local(e as i64).to_string())
+                        .unwrap_or_else(|| t.history_ongoing.clone()),
+                    hm(till.saturating_sub(start))
+                );
+            }
+        }
+        HistoryKind::Latency { since } => {
+            let rows = history::read_latency(since_ts(&since)?);
+            if rows.is_empty() {
+                println!("{}", t.history_empty);
+                return Ok(());
+            }
+            println!("{}", t.latency_header);
+            let (mut probes, mut lost, mut sum, mut max) = (0u32, 0u32, 0.0, 0.0f64);
+            for r in &rows {
+                let ok = r.probes - r.lost;
+                println!(
+                    "{}  {:>7.1} {:>7.1} {:>7.1}  {:>5.1}%",
+                    tz.to_local(r.ts as i64),
+                    r.min_ms,
+                    r.avg_ms,
+                    r.max_ms,
+                    r.lost as f64 * 100.0 / r.probes.max(1) as f64
+                );
+                probes += r.probes;
+                lost += r.lost;
+                sum += r.avg_ms * ok as f64;
+                max = max.max(r.max_ms);
+            }
+            let ok = (probes - lost).max(1);
+            println!(
+                "{} {:.1} ms, max {:.1} ms, {} {}/{}",
+                t.latency_summary,
+                sum / ok as f64,
+                max,
+                t.latency_lost,
+                lost,
+                probes
+            );
         }
     }
-    None
+    Ok(())
 }
 
-struct NetworkInfo {
-    ssid: String,
-    device: String,
-    gateway: String,
+// === МАЯЧОК ===
+// Ключ, порт и период - из секции beacon того же config.json, что у демонов
+fn run_beacon() -> Result<(), PortalError> {
+    let mut cfg = load_config_safe().map_err(PortalError::Config)?;
+    for e in cfg.resolve_secrets() {
+        eprintln!("❌ {}", e);
+    }
+    cfg.beacon
+        .validate()
+        .map_err(|e| PortalError::Config(format!("beacon: {}", e)))?;
+    beacon::send_forever(&cfg.beacon).map_err(PortalError::Io)
 }
 
-fn check_ping(ip: &str) -> bool {
-    Command::new("ping")
-        .args(["-c", "1", "-W", "2", ip])
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
+// Маяку config.json не обязателен: без него - порт по умолчанию и без маячков
+fn run_lighthouse() -> Result<(), PortalError> {
+    let path = paths::config(CONFIG_FILE);
+    let mut cfg = if Path::new(&path).exists() {
+        load_config_from(&path).map_err(PortalError::Config)?
+    } else {
+        PortalConfig::default()
+    };
+    for e in cfg.resolve_secrets() {
+        eprintln!("❌ {}", e);
+    }
+    cfg.lighthouse
+        .validate()
+        .map_err(|e| PortalError::Config(format!("lighthouse: {}", e)))?;
+    lighthouse::serve(&cfg).map_err(PortalError::Io)
 }
 
-fn enter_hibernation(seconds: u64) {
-    let priv_cmd = if Path::new(DOAS_CONF).exists() {
-        "doas"
+// === СЕКРЕТЫ ===
+// С терминала - без эха, из пайпа - как есть: `pass tg | portal_daemon secret tg`
+fn run_secret(name: &str) -> Result<(), PortalError> {
+    if !paths::user() && !is_root() {
+        return Err(PortalError::NotRoot("Storing a secret"));
+    }
+    let value = if std::io::stdin().is_terminal() {
+        Password::new()
+            .with_prompt(format!("Secret '{}'", name))
+            .interact()?
     } else {
-        "sudo"
+        std::io::read_to_string(std::io::stdin())
+            .map_err(dialoguer::Error::IO)?
+            .trim_end_matches(['\r', '\n'])
+            .to_string()
     };
-
-    let status_result = Command::new(priv_cmd)
-        .args(["rtcwake", "-m", "mem", "-s", &seconds.to_string()])
-        .status();
-
-    if let Ok(s) = status_result {
-        if s.success() {
-            println!("✅ Sleep OK.");
-            return;
-        }
+    if value.is_empty() {
+        return Err(PortalError::Usage("empty secret, nothing stored"));
     }
-    eprintln!("❌ Error: rtcwake failed.");
-    thread::sleep(Duration::from_secs(60));
+    println!("{}", secrets::store(name, &value)?);
+    Ok(())
+}
+
+// === КОМАНДЫ CTL ===
+// Цена цикла мониторинга без самого ожидания: проба и проверка паузы.
+// CPU дочерних процессов не ноль - значит, в цикле что-то запускается
+fn run_bench(cycles: u32) {
+    let cfg = load_config_safe().unwrap_or_default();
+    let cycles = cycles.max(1);
+    let usage = |who| {
+        let mut ru: libc::rusage = unsafe { std::mem::zeroed() };
+        unsafe { libc::getrusage(who, &mut ru) };
+        let ms = |tv: libc::timeval| tv.tv_sec as f64 * 1000.0 + tv.tv_usec as f64 / 1000.0;
+        (
+            ms(ru.ru_utime) + ms(ru.ru_stime),
+            ru.ru_nvcsw + ru.ru_nivcsw,
+        )
+    };
+    let (cpu0, sw0) = usage(libc::RUSAGE_SELF);
+    let (child0, _) = usage(libc::RUSAGE_CHILDREN);
+    let start = Instant::now();
+    let mut ok = 0;
+    for _ in 0..cycles {
+        watchdog::pet();
+        check_pause();
+        ok += probe::run(&cfg).ok as u32;
+    }
+    let wall = start.elapsed().as_secs_f64() * 1000.0;
+    let (cpu1, sw1) = usage(libc::RUSAGE_SELF);
+    let (child1, _) = usage(libc::RUSAGE_CHILDREN);
+    let n = cycles as f64;
+    println!(
+        "{} cycles, probe {:?} -> {}, {} ok",
+        cycles, cfg.probe, cfg.lighthouse_ip, ok
+    );
+    println!("wall      {:8.3} ms/cycle", wall / n);
+    println!("cpu       {:8.3} ms/cycle", (cpu1 - cpu0) / n);
+    println!("children  {:8.3} ms/cycle", (child1 - child0) / n);
+    println!("switches  {:8.1} /cycle", (sw1 - sw0) as f64 / n);
+}
+
+fn run_stats(lang: Language, since: &str, by: StatsPeriod) -> Result<(), PortalError> {
+    let t = Locales::new(lang);
+    open_history();
+    let tz = local_tz();
+    let Some(span) = schedule::parse_span(since) else {
+        eprintln!("{} '{}'", t.bad_span, since);
+        return Err(PortalError::Reported(1));
+    };
+    let now = unix_now();
+    let events = history::read_events(now.saturating_sub(span));
+    let key = |ts: u64| {
+        let l = tz.to_local(ts as i64);
+        match by {
+            StatsPeriod::Day => format!("{:04}-{:02}-{:02}", l.year, l.month, l.day),
+            // Неделя - по дате ее понедельника
+            StatsPeriod::Week => {
+                let (y, m, d) = schedule::civil_from_days(l.days() - l.weekday as i64);
+                format!("{:04}-{:02}-{:02}", y, m, d)
+            }
+            StatsPeriod::Month => format!("{:04}-{:02}", l.year, l.month),
+        }
+    };
+    let (total, groups) = history::stats(&events, now, key);
+    if total.outages == 0 && total.sleeps == 0 {
+        println!("{}", t.history_empty);
+        return Ok(());
+    }
+    println!("{} {}", t.stats_outages, total.outages);
+    println!("{} {}", t.stats_dark, hm(total.dark_sec));
+    println!("{} {}", t.stats_avg, hm(total.avg_sec()));
+    println!("{} {}", t.stats_longest, hm(total.longest_sec));
+    println!("{} {}", t.stats_sleeps, total.sleeps);
+    println!("\n{}", t.stats_header);
+    for (period, s) in groups {
+        println!(
+            "{:<12} {:>7} {:>10} {:>10} {:>7}",
+            period,
+            s.outages,
+            hm(s.dark_sec),
+            hm(s.longest_sec),
+            s.sleeps
+        );
+    }
+    Ok(())
+}
+
+// 5h 03m
+fn hm(sec: u64) -> String {
+    let mins = sec / 60;
+    format!("{}h {:02}m", mins / 60, mins % 60)
+}
+
+// Отсчет: мм:сс, от часа - как hm
+fn mmss(sec: u64) -> String {
+    if sec >= 3600 {
+        return hm(sec);
+    }
+    format!("{:02}:{:02}", sec / 60, sec % 60)
+}
+
+fn run_ctl(lang: Language, all: bool, action: CtlAction) -> Result<(), PortalError> {
+    let t = Locales::new(lang);
+    let action = match action {
+        CtlAction::Pause(p) => pause_action(&t, &p)?,
+        CtlAction::Resume => fleet::FleetAction::Resume,
+    };
+
+    if !all {
+        return apply_local(&t, action);
+    }
+
+    let mut cfg = load_config_safe().unwrap_or_default();
+    for e in cfg.resolve_secrets() {
+        eprintln!("{} {}", t.secret_failed, e);
+    }
+    if cfg.fleet_token.is_empty() {
+        eprintln!("{}", t.fleet_no_token);
+        return Err(PortalError::Reported(1));
+    }
+    println!("{}", t.fleet_sending);
+    let reports = fleet::broadcast(&cfg, action);
+    if reports.is_empty() {
+        println!("{}", t.fleet_none);
+        return Err(PortalError::Reported(1));
+    }
+    let mut failed = 0;
+    for r in &reports {
+        let addr = r.addr.map(|a| a.ip().to_string()).unwrap_or_default();
+        match &r.result {
+            Ok(d) => println!("  ✅ {} ({}): {}", r.name, addr, d),
+            Err(e) => {
+                failed += 1;
+                println!("  ❌ {} ({}): {}", r.name, addr, e);
+            }
+        }
+    }
+    println!(
+        "{} {}/{}",
+        t.fleet_summary,
+        reports.len() - failed,
+        reports.len()
+    );
+    if failed > 0 {
+        return Err(PortalError::Reported(2));
+    }
+    Ok(())
+}
+
+// === МАСТЕР НАСТРОЙКИ ===
+fn run_interactive_wizard(
+    json: bool,
+    preset: Option<presets::Preset>,
+) -> Result<PortalConfig, PortalError> {
+    // Ход мастера - в stderr (туда же пишет dialoguer), в stdout только итог:
+    // его разбирают скрипты. Директорию создаем заранее; не вышло - скажет сохранение
+    if !Path::new(paths::config_dir()).exists() {
+        eprintln!("📂 Creating config directory: {}", paths::config_dir());
+        fs::create_dir_all(paths::config_dir()).ok();
+    }
+
+    // По умолчанию - язык из окружения (LANG)
+    let detected = Language::from_env();
+    let langs: Vec<&str> = Language::ALL.iter().map(|l| l.label()).collect();
+    let lang_sel = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select Language / Оберіть мову / Выберите язык")
+        .default(
+            Language::ALL
+                .iter()
+                .position(|l| *l == detected)
+                .unwrap_or(0),
+        )
+        .items(&langs)
+        .interact()?;
+    let lang = Language::ALL[lang_sel];
+    let t = Locales::new(lang);
+
+    eprintln!("\n{}", t.wizard_title);
+
+    // Первый и главный выбор; дальше значения пресета - ответы по умолчанию
+    let preset = match preset {
+        Some(p) => Some(p),
+        None => {
+            let mut items = vec![t.preset_custom.clone()];
+            items.extend(presets::ALL.iter().map(|p| match p {
+                presets::Preset::Laptop => t.preset_laptop.clone(),
+                presets::Preset::Homeserver => t.preset_homeserver.clone(),
+                presets::Preset::SbcOffgrid => t.preset_sbc.clone(),
+            }));
+            let sel = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(&t.preset_prompt)
+                .default(0)
+                .items(&items)
+                .interact()?;
+            sel.checked_sub(1).map(|i| presets::ALL[i])
+        }
+    };
+    let mut base = PortalConfig::default();
+    if let Some(p) = preset {
+        p.apply(&mut base);
+    }
+    // После --install есть пользователь portal - новый конфиг сразу без root
+    if !paths::user() && privsep::user_exists(RUN_AS_USER) {
+        base.run_as = Some(RUN_AS_USER.to_string());
+    }
+
+    let mut final_ip: String;
+    let mut final_ssid = "Manual".to_string();
+
+    // Переопределение бэкенда переживает повторную настройку
+    let network_backend = load_config_safe()
+        .map(|c| c.network_backend)
+        .unwrap_or_default();
+    eprintln!("{}", t.scan_msg);
+    let networks = scan_networks(network_backend);
+
+    if networks.is_empty() {
+        eprintln!("{}", t.scan_fail);
+        final_ip = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(&t.enter_ip_manual)
+            .default("192.168.1.1".into())
+            .interact_text()?;
+    } else {
+        let mut options: Vec<String> = networks
+            .iter()
+            .map(|n| format!("{} ({}, GW: {})", n.ssid, link_label(&n.device), n.gateway))
+            .collect();
+        options.push(t.enter_ip_manual.clone());
+
+        let sel = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(&t.select_net)
+            .default(0)
+            .items(&options)
+            .interact()?;
+        if sel < networks.len() {
+            final_ip = networks[sel].gateway.clone();
+            final_ssid = networks[sel].ssid.clone();
+            eprintln!(
+                "{} {} -> Target IP: {}",
+                t.selected_net_log, final_ssid, final_ip
+            );
+        } else {
+            final_ip = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(&t.enter_ip_prompt)
+                .interact_text()?;
+        }
+    }
+
+    // Маяк с `portal_daemon lighthouse` в сети - предлагаем его вместо шлюза
+    let mut lighthouse = base.lighthouse.clone();
+    eprintln!("{}", t.mdns_scan);
+    let found = mdns::discover(Duration::from_secs(2));
+    if !found.is_empty() {
+        let mut options: Vec<String> = found
+            .iter()
+            .map(|f| format!("{} ({}:{})", f.name, f.ip, f.port))
+            .collect();
+        options.push(format!("{} {}", t.mdns_none, final_ip));
+        let sel = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(&t.mdns_select)
+            .default(0)
+            .items(&options)
+            .interact()?;
+        if let Some(f) = found.get(sel) {
+            final_ip = f.ip.clone();
+            lighthouse.name = f.name.clone();
+            lighthouse.port = f.port;
+        }
+    }
+
+    let sleep_minutes = prompt_number(
+        &t,
+        &t.sleep_mins_prompt,
+        base.sleep_minutes,
+        SLEEP_MINUTES_RANGE,
+    )?;
+    let grace_period_sec = prompt_number(
+        &t,
+        &t.grace_sec_prompt,
+        base.grace_period_sec,
+        GRACE_SEC_RANGE,
+    )?;
+    let wakeup_wait_sec = prompt_number(
+        &t,
+        &t.wakeup_sec_prompt,
+        base.wakeup_wait_sec,
+        WAKEUP_SEC_RANGE,
+    )?;
+    let scan_interval_sec = prompt_number(
+        &t,
+        &t.scan_int_prompt,
+        base.scan_interval_sec,
+        SCAN_INTERVAL_RANGE,
+    )?;
+
+    // Рекомендуем то, что выбрал бы auto; "auto" - пересматривать при каждом старте
+    let detected = probe::detect(&PortalConfig {
+        lighthouse_ip: final_ip.clone(),
+        ..Default::default()
+    });
+    let kinds = [
+        probe::ProbeKind::Ping,
+        probe::ProbeKind::Arp,
+        probe::ProbeKind::Nut,
+        probe::ProbeKind::PowerSupply,
+        probe::ProbeKind::Sensor,
+        probe::ProbeKind::Beacon,
+        probe::ProbeKind::Http,
+        probe::ProbeKind::Udp,
+        probe::ProbeKind::Snmp,
+        probe::ProbeKind::Auto,
+    ];
+    let items: Vec<String> = kinds
+        .iter()
+        .map(|k| {
+            if *k == detected.kind {
+                format!("{:?} - {} ({})", k, t.probe_recommended, detected.reason)
+            } else {
+                format!("{:?}", k)
+            }
+        })
+        .collect();
+    let preferred = if lighthouse.name.is_empty() {
+        preset.and_then(|p| p.probe()).unwrap_or(detected.kind)
+    } else {
+        probe::ProbeKind::Udp
+    };
+    let sel = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(&t.probe_prompt)
+        .default(kinds.iter().position(|k| *k == preferred).unwrap_or(0))
+        .items(&items)
+        .interact()?;
+    let probe = kinds[sel];
+    let ping_attempts = if probe == detected.kind {
+        detected.attempts
+    } else {
+        1
+    };
+    let mut sensor = base.sensor.clone();
+    if probe == probe::ProbeKind::Sensor {
+        let current = if sensor.source.is_empty() {
+            "gpio:17".to_string()
+        } else {
+            sensor.source.clone()
+        };
+        sensor.source = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(&t.sensor_prompt)
+            .default(current)
+            .validate_with(|v: &String| {
+                sensor::SensorConfig {
+                    source: v.trim().to_string(),
+                    ..base.sensor.clone()
+                }
+                .validate()
+            })
+            .interact_text()?
+            .trim()
+            .to_string();
+    }
+    let mut beacon = base.beacon.clone();
+    if matches!(
+        probe,
+        probe::ProbeKind::Beacon | probe::ProbeKind::Http | probe::ProbeKind::Udp
+    ) {
+        let (name, slot) = match probe {
+            probe::ProbeKind::Beacon => ("beacon", &mut beacon.key),
+            _ => ("lighthouse", &mut lighthouse.key),
+        };
+        let key = Password::with_theme(&ColorfulTheme::default())
+            .with_prompt(&t.lighthouse_key_prompt)
+            .allow_empty_password(true)
+            .interact()?;
+        // Ключ - в хранилище секретов, в конфиг - только ссылка
+        if !key.is_empty() {
+            *slot = secrets::store(name, &key)?;
+        }
+    }
+    // Параметры пинга - только сетевым пробам (auto может выбрать ping)
+    let (mut ping_count, mut ping_timeout_ms, mut ping_size) =
+        (base.ping_count, base.ping_timeout_ms, base.ping_size);
+    if probe.network() || probe == probe::ProbeKind::Auto {
+        ping_count = prompt_number(
+            &t,
+            &t.ping_count_prompt,
+            ping_count as u64,
+            PING_COUNT_RANGE,
+        )? as u32;
+        ping_timeout_ms = prompt_number(
+            &t,
+            &t.ping_timeout_prompt,
+            ping_timeout_ms,
+            PING_TIMEOUT_MS_RANGE,
+        )?;
+        ping_size =
+            prompt_number(&t, &t.ping_size_prompt, ping_size as u64, PING_SIZE_RANGE)? as usize;
+    }
+
+    let config = PortalConfig {
+        language: lang,
+        lighthouse_ip: final_ip,
+        probe,
+        ping_attempts,
+        ping_count,
+        ping_timeout_ms,
+        ping_size,
+        sensor,
+        beacon,
+        lighthouse,
+        target_ssid: final_ssid,
+        network_backend,
+        sleep_minutes,
+        grace_period_sec,
+        wakeup_wait_sec,
+        scan_interval_sec,
+        ..base
+    };
+
+    let saved = save_config(&config);
+    let summary = WizardSummary {
+        saved: saved.is_ok(),
+        error: saved.err(),
+        config_file: paths::config(CONFIG_FILE),
+        preset,
+        config: ConfigSummary::of(&config),
+        next_steps: next_steps(),
+    };
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&summary).unwrap_or_default()
+        );
+    } else {
+        print_summary(&t, &summary);
+    }
+    if !summary.saved {
+        return Err(PortalError::NotSaved(paths::config(CONFIG_FILE)));
+    }
+    Ok(config)
+}
+
+#[derive(Serialize)]
+struct WizardSummary {
+    saved: bool,
+    error: Option<String>,
+    config_file: String,
+    preset: Option<presets::Preset>,
+    config: ConfigSummary,
+    next_steps: Vec<String>,
+}
+
+fn save_config(cfg: &PortalConfig) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(cfg).map_err(|e| e.to_string())?;
+    fs::write(paths::config(CONFIG_FILE), &json)
+        .map_err(|e| format!("{}: {}", paths::config(CONFIG_FILE), e))?;
+    fs::write(paths::config(CONFIG_BACKUP), json).ok();
+    Ok(())
+}
+
+// Служба еще не стоит - --install; стоит - перезапустить с новым конфигом
+fn next_steps() -> Vec<String> {
+    if paths::user() {
+        let restart = if Path::new(&user_install_paths().1).exists() {
+            "systemctl --user restart portal"
+        } else {
+            "portal_daemon --user --install"
+        };
+        return vec![restart.into(), "portal_daemon --user status".into()];
+    }
+    let restart = Init::installed().map_or("portal_daemon --install", Init::restart_cmd);
+    vec![restart.into(), "portal_daemon status".into()]
+}
+
+fn print_summary(t: &Locales, s: &WizardSummary) {
+    match &s.error {
+        None => println!("\n{}", t.settings_saved),
+        Some(e) => {
+            eprintln!("\n{} {}", t.save_failed, e);
+            return;
+        }
+    }
+    let c = &s.config;
+    println!("{}", t.summary_title);
+    println!("  📡 {} -> {}", c.target_ssid, c.lighthouse_ip);
+    if let Some(p) = s.preset {
+        println!("  {} {}", t.summary_preset, p.name());
+    }
+    println!("  {} {:?}", t.summary_probe, c.probe);
+    println!("  {} {} min", t.summary_sleep, c.sleep_minutes);
+    println!("  {} {} sec", t.summary_grace, c.grace_period_sec);
+    println!("  {} {} sec", t.summary_interval, c.scan_interval_sec);
+    println!("{}", t.summary_next);
+    for step in &s.next_steps {
+        println!("  {}", step);
+    }
+    println!();
+}
+
+// Ввод числа с проверкой диапазона: dialoguer сам переспрашивает при ошибке
+fn prompt_number(
+    t: &Locales,
+    prompt: &str,
+    default: u64,
+    range: RangeInclusive<u64>,
+) -> Result<u64, PortalError> {
+    let input: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("{} [{}-{}]", prompt, range.start(), range.end()))
+        .default(default.to_string())
+        .validate_with(|v: &String| -> Result<(), String> {
+            match v.trim().parse::<u64>() {
+                Ok(n) if range.contains(&n) => Ok(()),
+                Ok(_) => Err(format!(
+                    "{} {}-{}",
+                    t.out_of_range,
+                    range.start(),
+                    range.end()
+                )),
+                Err(_) => Err(t.not_a_number.clone()),
+            }
+        })
+        .interact_text()?;
+    Ok(input.trim().parse().unwrap_or(default))
+}
+
+// === ДЕМОН ===
+fn run_daemon(mut cfg: PortalConfig, config_issue: Option<ConfigIssue>) -> Result<(), PortalError> {
+    let t = Locales::new(cfg.language);
+    let tz = schedule::TimeZone::resolve(cfg.timezone.as_deref()).unwrap_or_else(|e| {
+        eprintln!("⚠️  {}, falling back to UTC.", e);
+        schedule::TimeZone::utc()
+    });
+    log::set_timezone(tz.clone());
+    log::set_target(cfg.log_target);
+    log::keep_recent(cfg.log_buffer_lines);
+    timers::set_slack(cfg.timer_slack_ms);
+    let world_readable =
+        fs::metadata(paths::config(CONFIG_FILE)).is_ok_and(|m| m.permissions().mode() & 0o004 != 0);
+    let inline = cfg.inline_secrets();
+    if world_readable && !inline.is_empty() {
+        log::warn!("{} {}", t.secret_inline, inline.join(", "));
+    }
+    for e in cfg.resolve_secrets() {
+        log::error!("{} {}", t.secret_failed, e);
+    }
+    state::set_read_only(cfg.read_only_root);
+    if cfg.read_only_root
+        && let Err(e) = state::check_run_dir()
+    {
+        log::error!("{} {}", t.read_only_no_run, e);
+    }
+    if let Err(pid) = state::lock_instance() {
+        let pid = pid.map_or("?".to_string(), |p| p.to_string());
+        log::error!("{} {}", t.already_running, pid);
+        return Err(PortalError::Reported(EX_TEMPFAIL));
+    }
+    // Все, что нужно от root, уже сделано: каталоги, блокировка, секреты
+    if let Some(user) = &cfg.run_as
+        && !paths::user()
+    {
+        match privsep::drop_to(user, &rtc::device(&cfg.rtcwake_args)) {
+            Ok(true) => log::info!(user = user; "🔒 Dropped root, running as {}", user),
+            Ok(false) => {}
+            Err(e) => log::warn!("⚠️  Cannot switch to user '{}': {}, staying root.", user, e),
+        }
+    }
+
+    log::info!("{}", t.daemon_start);
+    log::info!("{} {}", t.daemon_net, cfg.target_ssid);
+    log::info!("{} {} sec", t.daemon_interval, cfg.scan_interval_sec);
+    let probe_choice = (cfg.probe == probe::ProbeKind::Auto).then(|| {
+        let c = probe::detect(&cfg);
+        cfg.probe = c.kind;
+        cfg.ping_attempts = cfg.ping_attempts.max(c.attempts);
+        let text = format!("{:?} x{} ({})", c.kind, cfg.ping_attempts, c.reason);
+        log::info!("{} {}", t.probe_auto, text);
+        text
+    });
+    if cfg.probe.network()
+        && let Some(dev) = net::route_dev(&cfg.lighthouse_ip)
+    {
+        log::info!("{} {}", t.daemon_link, link_label(&dev));
+    }
+    let profiles = (!cfg.profiles.is_empty()).then(|| profiles::Switcher::new(&cfg));
+    if let Some(iface) = &cfg.interface {
+        if net::if_index(iface).is_some() {
+            log::info!("{} {}", t.iface_watch, link_label(iface));
+        } else {
+            log::warn!("{} {}", t.iface_missing, iface);
+        }
+        probe::watch_interface(iface);
+    }
+    if cfg.hotspot_guard
+        && cfg.probe.network()
+        && let Some(why) = guards::hotspot(&cfg)
+    {
+        log::warn!(guard = "hotspot"; "{} {}", t.hotspot_warn, why);
+    }
+
+    log::info!(
+        "{} {} ({})",
+        t.daemon_tz,
+        tz.name,
+        tz.to_local(unix_now() as i64)
+    );
+    if paths::user() {
+        log::info!("{}", t.user_mode);
+    } else if freebsd::is() {
+        log::info!("{}", t.freebsd_mode);
+    }
+    if dry_run() {
+        log::warn!("{}", t.dry_run_mode);
+    }
+
+    if cfg.probe == probe::ProbeKind::PowerSupply && power::ac_online().is_none() {
+        log::warn!("⚠️  No AC adapter in /sys/class/power_supply, probing with ping.");
+    }
+
+    // exec после `systemctl reload`: продолжаем с того места, где был старый процесс
+    let resumed = handoff::take();
+    if let Some(h) = &resumed {
+        log::info!(grace_left_sec = h.grace_left().unwrap_or(0); "{}", t.handoff_resumed);
+    }
+    let powered_off = state::take_poweroff(power::boot_time());
+    if fleet::listener_enabled(&cfg) {
+        fleet::listen(&cfg);
+    }
+    if cfg.uses_probe(probe::ProbeKind::Beacon) {
+        beacon::listen(&cfg.beacon);
+    }
+    if (cfg.uses_probe(probe::ProbeKind::Http) || cfg.uses_probe(probe::ProbeKind::Udp))
+        && lighthouse::key(&cfg).is_empty()
+    {
+        log::warn!(
+            "⚠️  No lighthouse.key: any host on the LAN can answer http/udp probes for the lighthouse."
+        );
+    }
+    if cfg.http_port != 0 {
+        http::listen(cfg.http_port);
+    }
+    handoff::close_unclaimed();
+
+    let mut quiet = Vec::new();
+    for q in &cfg.quiet_hours {
+        match schedule::TimeWindow::parse(q) {
+            Some(w) => quiet.push(w),
+            None => log::warn!("{} '{}'", t.quiet_invalid, q),
+        }
+    }
+    if !quiet.is_empty() {
+        let list: Vec<&str> = quiet.iter().map(|w| w.source.as_str()).collect();
+        log::info!("{} {}", t.daemon_quiet, list.join(", "));
+    }
+
+    let outages = outages::OutageSchedule::new(&cfg.outage_schedule);
+    if outages.enabled() {
+        log::info!("{} {}", t.outage_loaded, outages.window_count());
+    }
+
+    watchdog::init(
+        cfg.heartbeat_file.as_deref(),
+        cfg.watchdog_device.as_deref(),
+        cfg.heartbeat_interval_sec,
+    );
+    let mut bus = events::Bus::default();
+    bus.subscribe(log::EventLog::new(&cfg.lighthouse_ip));
+    let mut writer = state::StateWriter::new(&cfg.lighthouse_ip);
+    if let Some(h) = &resumed {
+        writer.resume(h.started_at, h.grace_until);
+    }
+    if let Some(c) = probe_choice {
+        writer.probe_choice(c);
+    }
+    bus.subscribe(writer);
+    store::open(cfg.history_backend, cfg.history_sync_sec);
+    // RTT раньше событий: перед сном агрегат сбрасывается до store::sync()
+    if cfg.probe.network() {
+        bus.subscribe(history::LatencyRecorder::new(
+            cfg.latency_bucket_sec,
+            cfg.latency_retention_days,
+        ));
+    }
+    bus.subscribe(history::EventRecorder::new(cfg.event_retention_days));
+    if cfg.desktop_notify {
+        let text = notify::DesktopText {
+            title: t.notify_title.clone(),
+            sleep_at: t.notify_sleep_at.clone(),
+            cancel: t.notify_cancel.clone(),
+        };
+        bus.subscribe(notify::Desktop::new(text, tz.clone()));
+    }
+    if cfg.notifications.enabled() {
+        let text = notify::RemoteText {
+            lost: t.remote_lost.clone(),
+            sleep_in: t.remote_sleep_in.clone(),
+            sleeping: t.no_light_sleep.clone(),
+            woke: t.remote_woke.clone(),
+            rtc_drift: t.remote_rtc_drift.clone(),
+            stale: t.probe_stale.clone(),
+            pause: t.remote_pause.clone(),
+        };
+        bus.subscribe(notify::Remote::new(cfg.notifications.clone(), text));
+        notify::listen_commands(&cfg.notifications);
+    }
+    if cfg.notifications.sms() {
+        let text = sms::SmsText {
+            sleeping: t.sms_sleeping.clone(),
+            battery: t.sms_battery.clone(),
+        };
+        bus.subscribe(sms::Sms::new(&cfg.notifications, text));
+    }
+    if cfg.mqtt.enabled() {
+        bus.subscribe(mqtt::Mqtt::new(&cfg.mqtt));
+    }
+    if !cfg.webhooks.is_empty() {
+        let text = webhook::WebhookText {
+            lost: t.remote_lost.clone(),
+            sleeping: t.no_light_sleep.clone(),
+            woke: t.remote_woke.clone(),
+        };
+        bus.subscribe(webhook::Webhooks::new(&cfg.webhooks, text, tz.clone()));
+    }
+    if cfg.email.enabled() {
+        let text = email::EmailText {
+            lost: t.remote_lost.clone(),
+            outage: t.email_outage.clone(),
+        };
+        bus.subscribe(email::Email::new(&cfg.email, text, tz.clone()));
+    }
+    if !cfg.wake_on_lan.is_empty() {
+        bus.subscribe(wol::WakeOnLan::new(&cfg.wake_on_lan));
+    }
+    if cfg.dbus_service
+        && let Some(signals) = dbus::serve()
+    {
+        bus.subscribe(signals);
+    }
+    // После обновления на месте канал уже проверен прошлым процессом
+    // После выключения демоном о подъеме и так придет Woke
+    if cfg.self_test_on_start && resumed.is_none() && powered_off.is_none() {
+        channels::self_test(&cfg, &t.selftest_online);
+    }
+    if let Some(issue) = config_issue {
+        bus.emit(events::Event::ConfigInvalid {
+            message: issue.message,
+            restored: issue.restored,
+        });
+    }
+    tui::enable();
+    tui::listen_sigusr1();
+    handoff::listen_sigusr2();
+    let mut daemon = Daemon {
+        resume_grace: resumed.as_ref().and_then(handoff::Handoff::grace_left),
+        dark_wakes: 0,
+        router_rebooting: false,
+        cadence: timers::Adaptive::default(),
+        last_interval: cfg.scan_interval_sec,
+        cfg,
+        t,
+        tz,
+        bus,
+        quiet,
+        outages,
+        profiles,
+        prober: system::Real,
+        power: system::Real,
+        clock: system::Real,
+        net: system::Real,
+    };
+    if let Some(m) = powered_off {
+        daemon.woke_from_poweroff(m);
+    }
+    loop {
+        daemon.step();
+    }
+}
+
+// --- ЦИКЛ ДЕМОНА ---
+// Одна итерация - step(). К машине - только через system::*, поэтому цикл
+// (grace, пауза, перепроверки, сон) можно гонять в тестах
+struct Daemon<P, M, C, N> {
+    cfg: PortalConfig,
+    t: Locales,
+    tz: schedule::TimeZone,
+    bus: events::Bus,
+    quiet: Vec<schedule::TimeWindow>,
+    outages: outages::OutageSchedule,
+    profiles: Option<profiles::Switcher>,
+    // Grace, начатый старым процессом до обновления на месте
+    resume_grace: Option<u64>,
+    // Снов подряд без света между ними: ступень resleep_minutes
+    dark_wakes: u32,
+    // Последняя неудачная проба: роутер перезагружается (snmp)
+    router_rebooting: bool,
+    cadence: timers::Adaptive,
+    last_interval: u64,
+    prober: P,
+    power: M,
+    clock: C,
+    net: N,
+}
+
+impl<P, M, C, N> Daemon<P, M, C, N>
+where
+    P: system::Prober,
+    M: system::PowerManager,
+    C: system::Clock,
+    N: system::NetworkScanner,
+{
+    fn step(&mut self) {
+        watchdog::pet();
+        if handoff::requested() {
+            handoff::exec(None);
+        }
+        let sleep_seconds = self.resleep_minutes() * 60;
+        // SleepNow по D-Bus или из Telegram: пользователь решил сам, защиты и
+        // пауза не мешают
+        if SLEEP_NOW.swap(false, Ordering::Relaxed) {
+            self.bus
+                .emit(state_changed(self.bus.phase(), "sleep requested remotely"));
+            let sleep_for = self.wake_target().unwrap_or(sleep_seconds);
+            self.sleep_cycle(sleep_for);
+            return;
+        }
+        if self.power.paused() {
+            if self.bus.phase() != state::Phase::Paused {
+                self.bus.emit(state_changed(
+                    state::Phase::Paused,
+                    "paused: sleep disabled by user",
+                ));
+            }
+            self.idle(self.cfg.scan_interval_sec, &self.t.tui_paused.clone());
+            return;
+        }
+        if let Some(sw) = &mut self.profiles {
+            let ssid = self.net.ssid(self.cfg.network_backend);
+            let t = &self.t;
+            match sw.update(&mut self.cfg, ssid) {
+                Some(profiles::Active::Known(ssid)) => {
+                    log::info!(
+                        "{} {} -> {}",
+                        t.profile_applied,
+                        ssid,
+                        self.cfg.lighthouse_ip
+                    );
+                    self.bus.emit(state_changed(
+                        state::Phase::Monitoring,
+                        format!("profile '{}': lighthouse {}", ssid, self.cfg.lighthouse_ip),
+                    ));
+                }
+                Some(profiles::Active::Unknown(ssid)) => {
+                    log::warn!("{} {}", t.profile_unknown, ssid);
+                    self.bus.emit(state_changed(
+                        state::Phase::Monitoring,
+                        format!("unknown network '{}': sleep disabled", ssid),
+                    ));
+                }
+                None => {}
+            }
+            if sw.unknown() {
+                self.idle(self.cfg.scan_interval_sec, &self.t.tui_unknown_net.clone());
+                return;
+            }
+        }
+
+        // Grace, начатый старым процессом: ConnectionLost и уведомления уже были
+        let resumed_grace = self.resume_grace.take();
+        if resumed_grace.is_none()
+            && (self.probe_once() || self.gateway_moved() || self.lighthouse_moved())
+        {
+            if self.bus.phase() != state::Phase::Monitoring {
+                self.bus.emit(state_changed(
+                    state::Phase::Monitoring,
+                    "monitoring: lighthouse reachable",
+                ));
+            }
+            self.outages.refresh_if_due();
+            let secs = self.cadence.next(
+                self.cfg.scan_interval_sec,
+                self.cfg.scan_interval_min_sec,
+                self.cfg.scan_interval_max_sec,
+                probe::healthy(),
+            );
+            if secs != self.last_interval {
+                log::debug!(interval_sec = secs; "scan interval changed");
+                self.last_interval = secs;
+            }
+            let jitter = timers::jitter(
+                Duration::from_secs(self.cfg.scan_jitter_min_sec),
+                Duration::from_secs(self.cfg.scan_jitter_max_sec),
+            );
+            self.idle_for(
+                timers::next_cycle(Duration::from_secs(secs)) + jitter,
+                &self.t.tui_next_check.clone(),
+            );
+            return;
+        }
+
+        self.cadence.shorten();
+        let grace = match resumed_grace {
+            Some(left) => {
+                self.bus.emit(state_changed(
+                    state::Phase::Grace,
+                    "grace resumed after in-place upgrade",
+                ));
+                left
+            }
+            None => {
+                let extra = self.outages.extra_grace(&self.tz, self.clock.now() as i64);
+                // Роутер после просадки поднимается минутами: свет, скорее всего, есть
+                let base = if self.router_rebooting {
+                    log::info!(ip = self.cfg.lighthouse_ip; "{} {} sec", self.t.router_rebooting, self.cfg.snmp.reboot_grace_sec);
+                    self.cfg.snmp.reboot_grace_sec
+                } else {
+                    self.cfg.grace_period_sec
+                };
+                let grace = base + extra;
+                if extra > 0 {
+                    log::info!("{} +{} sec", self.t.outage_unscheduled, extra);
+                }
+                log::warn!(ip = self.cfg.lighthouse_ip, grace_sec = grace; "{} {} sec...", self.t.conn_lost, grace);
+                self.bus
+                    .emit(events::Event::ConnectionLost { grace_sec: grace });
+                grace
+            }
+        };
+        let light_back = self.grace_wait(grace);
+        if self.power.paused() {
+            return;
+        }
+
+        // Вернулся посреди grace - не переспрашиваем: второй, неудачный
+        // ответ уложил бы машину спать до конца grace
+        if light_back || self.probe_once() {
+            log::info!("{}", self.t.conn_restored);
+            self.bus.emit(events::Event::ConnectionRestored);
+            self.bus.emit(state_changed(
+                state::Phase::Monitoring,
+                "monitoring: connection restored during grace",
+            ));
+        } else if let Some(w) =
+            schedule::active_window(&self.quiet, &self.tz, self.clock.now() as i64)
+        {
+            log::info!(guard = "quiet_hours"; "{}: {}", self.t.sleep_skipped_quiet, w.source);
+            self.bus.emit(events::Event::SleepBlocked {
+                guard: "quiet_hours",
+                reason: w.source.clone(),
+            });
+            self.bus.emit(state_changed(
+                state::Phase::Monitoring,
+                format!("sleep skipped: quiet hours {}", w.source),
+            ));
+        } else {
+            if !self.wait_for_guards() {
+                return;
+            }
+            let give_up = self.cfg.give_up_after_wakes;
+            if give_up > 0 && self.dark_wakes >= give_up {
+                log::warn!(dark_wakes = self.dark_wakes; "{} {}", self.t.give_up, self.dark_wakes);
+                self.bus.emit(events::Event::GaveUp {
+                    dark_wakes: self.dark_wakes,
+                });
+                // Не выключились - спим как обычно
+                if self.power.shut_down() {
+                    self.dark_wakes = 0;
+                    return;
+                }
+            }
+            let now = self.clock.now() as i64;
+            let sleep_for = match self.outages.sleep_until_restoration(&self.tz, now) {
+                Some(secs) => {
+                    log::info!(
+                        "{} {}",
+                        self.t.outage_scheduled,
+                        self.tz.to_local(now + secs as i64)
+                    );
+                    secs
+                }
+                None => match self.wake_target() {
+                    Some(secs) => {
+                        log::info!(
+                            "{} {}",
+                            self.t.wake_target,
+                            self.tz.to_local(now + secs as i64)
+                        );
+                        secs
+                    }
+                    None => self.follower_sleep(sleep_seconds),
+                },
+            };
+            self.sleep_cycle(sleep_for);
+        }
+    }
+
+    // Ожидание grace с напоминаниями на середине и за минуту до сна.
+    // [c] / SIGUSR1 - проверить сейчас: свет есть - выходим, нет - ждем дальше.
+    // true - свет вернулся посреди grace
+    fn grace_wait(&mut self, grace: u64) -> bool {
+        let end = self.clock.instant() + Duration::from_secs(grace);
+        let mut marks = Vec::new();
+        for mark in [grace / 2, 60] {
+            if mark > 0 && mark < marks.last().copied().unwrap_or(grace) {
+                marks.push(mark);
+            }
+        }
+        let reminders = marks.clone();
+        // Проб по ходу grace нет - предупреждение о возрасте пробы ставим отдельной отметкой
+        if let Some(secs) = probe::stale_in(self.cfg.probe_age_alert_sec)
+            && secs < grace
+        {
+            marks.push(grace - secs);
+            marks.sort_unstable_by(|a, b| b.cmp(a));
+            marks.dedup();
+        }
+        marks.push(0);
+        let label = self.t.tui_sleep_check.clone();
+        for mark in marks {
+            loop {
+                let left = end
+                    .saturating_duration_since(self.clock.instant())
+                    .as_secs();
+                // Ровно до отметки, а не по сетке next_cycle: та может
+                // проснуться на полпериода раньше и укоротить grace
+                let d = Duration::from_secs(left - mark.min(left));
+                if left <= mark || !self.idle_for(d, &label) {
+                    break;
+                }
+                if handoff::requested() {
+                    handoff::exec(Some(
+                        end.saturating_duration_since(self.clock.instant())
+                            .as_secs(),
+                    ));
+                    continue;
+                }
+                if self.power.paused() {
+                    return false;
+                }
+                if self.probe_once() {
+                    return true;
+                }
+            }
+            if self.power.paused() {
+                return false;
+            }
+            self.check_stale();
+            if reminders.contains(&mark) {
+                self.bus.emit(events::Event::GraceReminder {
+                    remaining_sec: mark,
+                });
+            }
+        }
+        false
+    }
+
+    // Перед сном системные часы еще сверены по NTP - есть с чем сравнить RTC
+    fn check_rtc_offset(&mut self) {
+        if self.cfg.rtc_drift_alert_sec == 0 {
+            return;
+        }
+        let offset = self.tz.offset_at(self.clock.now() as i64) as i64;
+        let dev = rtc::device(&self.cfg.rtcwake_args);
+        if let Some(drift) = rtc::offset(&dev, self.cfg.rtc_clock, offset) {
+            self.report_rtc_drift(drift, false);
+        }
+    }
+
+    fn report_rtc_drift(&mut self, drift_sec: i64, after_wake: bool) {
+        let cfg = &self.cfg;
+        if cfg.rtc_drift_alert_sec == 0 || drift_sec.unsigned_abs() <= cfg.rtc_drift_alert_sec {
+            return;
+        }
+        log::warn!(drift_sec = drift_sec, after_wake = after_wake; "{} {:+} sec", self.t.rtc_drift_warn, drift_sec);
+        self.bus.emit(events::Event::RtcDrift {
+            drift_sec,
+            after_wake,
+        });
+    }
+
+    // Первый сон - sleep_minutes, каждый следующий без света - ступенью выше
+    fn resleep_minutes(&self) -> u64 {
+        self.resleep_minutes_at(self.dark_wakes)
+    }
+
+    fn resleep_minutes_at(&self, dark_wakes: u32) -> u64 {
+        let ladder = &self.cfg.resleep_minutes;
+        match dark_wakes {
+            0 => self.cfg.sleep_minutes,
+            _ if ladder.is_empty() => self.cfg.sleep_minutes,
+            n => ladder[(n as usize - 1).min(ladder.len() - 1)],
+        }
+    }
+
+    // Период сетки для фолловеров: сон сейчас и следующий без света равны,
+    // и их не сдвинут ни wake_times, ни график отключений. Иначе 0 -
+    // фолловер целится только в ближайший подъем лидера
+    fn fleet_period(&self, sleep_for: u64) -> u64 {
+        let next = self.resleep_minutes_at(self.dark_wakes + 1) * 60;
+        let periodic = self.cfg.wake_times.is_empty() && !self.outages.enabled();
+        if periodic && sleep_for == next {
+            next
+        } else {
+            0
+        }
+    }
+
+    // Секунды до ближайшей цели из wake_times; не задано - None
+    fn wake_target(&self) -> Option<u64> {
+        let times: Vec<_> = self
+            .cfg
+            .wake_times
+            .iter()
+            .filter_map(|w| schedule::WakeTime::parse(w))
+            .collect();
+        let now = self.clock.now() as i64;
+        let at = schedule::next_wake(&times, &self.tz, now, MIN_WAKE_AHEAD_SEC)?;
+        Some((at - now) as u64)
+    }
+
+    // Фолловер спит до слота лидера; сетки нет - обычные sleep_minutes
+    fn follower_sleep(&self, own: u64) -> u64 {
+        if self.cfg.fleet_role != fleet::Role::Follower {
+            return own;
+        }
+        let now = self.clock.now();
+        match fleet::aligned_sleep(now, self.cfg.fleet_wake_offset_sec) {
+            Some(secs) => {
+                log::info!(sleep_sec = secs; "{} {}", self.t.fleet_aligned, self.tz.to_local((now + secs) as i64));
+                secs
+            }
+            None => own,
+        }
+    }
+
+    // Хуки, сон, пробуждение. Упавший pre-sleep хук может отменить сон.
+    fn sleep_cycle(&mut self, sleep_for: u64) {
+        let hooks_ok = hooks::run_hooks(
+            hooks::HookStage::PreSleep,
+            &self.cfg.pre_sleep_hooks,
+            self.cfg.hook_timeout_sec,
+            sleep_for,
+        );
+        if !hooks_ok && self.cfg.abort_sleep_on_hook_failure {
+            log::warn!("{}", self.t.hook_abort);
+            self.bus.emit(events::Event::SleepBlocked {
+                guard: "pre_sleep_hook",
+                reason: "pre-sleep hook failed".into(),
+            });
+            self.bus.emit(state_changed(
+                state::Phase::Monitoring,
+                "sleep aborted: pre-sleep hook failed",
+            ));
+            self.clock
+                .sleep(Duration::from_secs(self.cfg.scan_interval_sec));
+            return;
+        }
+        self.check_rtc_offset();
+        let period = self.fleet_period(sleep_for);
+        let mut remaining = sleep_for;
+        let mut rearms = 0;
+        loop {
+            let mode = self.sleep_mode_for();
+            log::info!(
+                sleep_sec = remaining, mode = mode;
+                "{} {} min.", self.t.no_light_sleep, remaining.div_ceil(60)
+            );
+            self.bus.emit(events::Event::SleepRequested {
+                seconds: remaining,
+                mode: mode.clone(),
+            });
+            if self.cfg.fleet_role == fleet::Role::Leader && !dry_run() {
+                fleet::announce_wake(&self.cfg, self.clock.now() + remaining, period);
+            }
+            let started = self.clock.now();
+            let ok = self.power.sleep(&self.cfg, remaining, &mode);
+            let slept_sec = self.clock.now().saturating_sub(started);
+            let early_by_sec = remaining.saturating_sub(slept_sec);
+            // Без будильника (--user, FreeBSD) будят вручную - любой подъем "вовремя"
+            if !ok || !wake_alarm() || early_by_sec <= EARLY_WAKE_TOLERANCE_SEC {
+                // Проснулись по будильнику: на часах должно быть started + remaining
+                let drift = slept_sec as i64 - remaining as i64;
+                if ok && wake_alarm() {
+                    self.report_rtc_drift(drift, true);
+                }
+                self.bus.emit(events::Event::Woke {
+                    slept_sec,
+                    early_by_sec: 0,
+                    cause: None,
+                    rearmed: false,
+                });
+                break;
+            }
+
+            // Ранний подъем: WoL, ACPI, кнопка... Свет вернулся - тогда не спим
+            let light = self.probe_once();
+            let cause = if light {
+                "lighthouse reachable".to_string()
+            } else {
+                power::wakeup_source().unwrap_or_else(|| "unknown cause".into())
+            };
+            let rearmed = self.cfg.rearm_on_early_wake
+                && rearms < MAX_REARMS
+                && !light
+                && early_by_sec >= self.cfg.rearm_min_sec
+                && !self.power.paused();
+            log::warn!(
+                early_by_sec = early_by_sec, rearmed = rearmed;
+                "{} {} min ({})",
+                self.t.early_wake,
+                early_by_sec.div_ceil(60),
+                cause
+            );
+            self.bus.emit(events::Event::Woke {
+                slept_sec,
+                early_by_sec,
+                cause: Some(cause),
+                rearmed,
+            });
+            if !rearmed {
+                break;
+            }
+            rearms += 1;
+            remaining = early_by_sec;
+        }
+        self.dark_wakes += 1;
+        hooks::run_hooks(
+            hooks::HookStage::PostWake,
+            &self.cfg.post_wake_hooks,
+            self.cfg.hook_timeout_sec,
+            sleep_for,
+        );
+        self.settle_after_wake();
+    }
+
+    // Сеть после подъема оживает не сразу - ждем wakeup_wait_sec. Маяк
+    // ответил первой же пробой - ждать нечего, сразу к мониторингу.
+    // true - свет уже есть
+    fn settle_after_wake(&mut self) -> bool {
+        if self.probe_once() {
+            log::info!("{}", self.t.wake_light_back);
+            return true;
+        }
+        log::info!("{} {} sec...", self.t.waking_up, self.cfg.wakeup_wait_sec);
+        self.clock
+            .sleep(Duration::from_secs(self.cfg.wakeup_wait_sec));
+        false
+    }
+
+    // Загрузка после sleep_mode "off" - тот же подъем, только процесс новый:
+    // хуки, ожидание сети, Woke. Раньше будильника - включили кнопкой или
+    // плата сама стартовала, когда вернулось питание
+    fn woke_from_poweroff(&mut self, m: state::PowerOff) {
+        let now = self.clock.now();
+        let slept_sec = now.saturating_sub(m.at);
+        let early_by_sec = m.wake_at.saturating_sub(now);
+        log::info!(slept_sec = slept_sec; "{} {} min", self.t.booted_after_off, slept_sec / 60);
+        hooks::run_hooks(
+            hooks::HookStage::PostWake,
+            &self.cfg.post_wake_hooks,
+            self.cfg.hook_timeout_sec,
+            m.wake_at.saturating_sub(m.at),
+        );
+        let light = self.settle_after_wake();
+        let (early_by_sec, cause) = if early_by_sec <= EARLY_WAKE_TOLERANCE_SEC {
+            (0, None)
+        } else if light || self.probe_once() {
+            (early_by_sec, Some("lighthouse reachable".to_string()))
+        } else {
+            (early_by_sec, Some("booted before the alarm".to_string()))
+        };
+        self.bus.emit(events::Event::Woke {
+            slept_sec,
+            early_by_sec,
+            cause,
+            rearmed: false,
+        });
+    }
+
+    // Откладывает сон, пока guards находят причину. false - сон отменен
+    // (свет вернулся или поставили паузу за время ожидания).
+    fn wait_for_guards(&mut self) -> bool {
+        // Каждая защита считается один раз за попытку уснуть, а не за перепроверку
+        let mut counted: Vec<&str> = Vec::new();
+        while let Some(b) = self.power.blocker(&self.cfg) {
+            log::info!(
+                guard = b.guard;
+                "{} {}, re-check in {} sec",
+                self.t.sleep_postponed, b.reason, self.cfg.inhibit_recheck_sec
+            );
+            let reason = format!("sleep postponed: {}", b.reason);
+            if !counted.contains(&b.guard) {
+                counted.push(b.guard);
+                self.bus.emit(events::Event::SleepBlocked {
+                    guard: b.guard,
+                    reason: b.reason,
+                });
+            }
+            self.bus.emit(state_changed(state::Phase::Grace, reason));
+            self.clock
+                .sleep(Duration::from_secs(self.cfg.inhibit_recheck_sec));
+            if self.power.paused() {
+                return false;
+            }
+            if self.probe_once() {
+                log::info!("{}", self.t.conn_restored);
+                self.bus.emit(events::Event::ConnectionRestored);
+                self.bus.emit(state_changed(
+                    state::Phase::Monitoring,
+                    "monitoring: connection restored while sleep was postponed",
+                ));
+                return false;
+            }
+        }
+        true
+    }
+
+    // Ожидание в цикле демона; в терминале - с отсчетом и горячими клавишами.
+    // true - ожидание прервали ([p], [c], SIGUSR1), пора перепроверить состояние
+    // Ждем до отметки сетки scan_interval: heartbeat и прочие таймеры с кратным
+    // периодом просыпаются вместе с пробой
+    fn idle(&mut self, secs: u64, label: &str) -> bool {
+        self.idle_for(timers::next_cycle(Duration::from_secs(secs)), label)
+    }
+
+    fn idle_for(&mut self, d: Duration, label: &str) -> bool {
+        let t = &self.t;
+        match self.clock.wait(d, label, &t.tui_hint) {
+            Some(tui::Key::Pause) => {
+                let res = if check_pause() {
+                    clear_pause().map(|_| log::info!("{}", t.pause_removed))
+                } else {
+                    set_pause(QUICK_PAUSE_MINUTES)
+                        .map(|_| log::info!("{} {} min.", t.pause_activated, QUICK_PAUSE_MINUTES))
+                };
+                if let Err(e) = res {
+                    log::warn!("{} ({})", t.no_rights, e);
+                }
+                true
+            }
+            Some(tui::Key::Quit) => {
+                tui::restore();
+                println!("{}", t.tui_bye);
+                std::process::exit(0);
+            }
+            Some(tui::Key::Check) => true,
+            None => false,
+        }
+    }
+
+    fn probe_once(&mut self) -> bool {
+        let mut r = self.prober.probe(&self.cfg);
+        r.ok = probe::verdict(&self.cfg, &r);
+        if r.ok {
+            self.dark_wakes = 0;
+        }
+        self.router_rebooting = !r.ok && r.rebooting;
+        self.bus.emit(events::Event::Probe(r));
+        probe::record_ok(r.ok);
+        self.check_stale();
+        r.ok
+    }
+
+    // Удачной пробы нет дольше probe_age_alert_sec - сон тут ни при чем
+    fn check_stale(&mut self) {
+        if let Some(age) = probe::take_stale(self.cfg.probe_age_alert_sec) {
+            log::warn!(age_sec = age; "{} {} min", self.t.probe_stale, age / 60);
+            self.bus.emit(events::Event::ProbeStale { age_sec: age });
+        }
+    }
+
+    // Маяк - роутер, и DHCP (или новый роутер) сменил ему адрес: сеть та же,
+    // а пинг уходит в пустоту. Пока подключены к target_ssid, пробуем текущий
+    // шлюз по умолчанию и, если он отвечает, дальше считаем маяком его
+    fn gateway_moved(&mut self) -> bool {
+        if !self.cfg.probe.network() {
+            return false;
+        }
+        let Some(gateway) = self
+            .net
+            .gateway(self.cfg.network_backend, &self.cfg.target_ssid)
+            .filter(|g| !g.is_empty() && *g != self.cfg.lighthouse_ip)
+        else {
+            return false;
+        };
+        log::warn!(ip = self.cfg.lighthouse_ip, gateway = gateway; "{} {} -> {}", self.t.gateway_probe, self.cfg.lighthouse_ip, gateway);
+        let old = std::mem::replace(&mut self.cfg.lighthouse_ip, gateway);
+        if self.probe_once() {
+            log::info!(ip = self.cfg.lighthouse_ip; "{} {}", self.t.gateway_adopted, self.cfg.lighthouse_ip);
+            true
+        } else {
+            self.cfg.lighthouse_ip = old;
+            false
+        }
+    }
+
+    // Маяк - устройство с `portal_daemon lighthouse`, и DHCP сменил ему адрес:
+    // ищем его по имени в mDNS и, если отвечает там, дальше спрашиваем там
+    fn lighthouse_moved(&mut self) -> bool {
+        let name = self.cfg.lighthouse.name.clone();
+        if name.is_empty() {
+            return false;
+        }
+        let Some(ip) = self
+            .net
+            .lighthouse(&name)
+            .filter(|ip| *ip != self.cfg.lighthouse_ip)
+        else {
+            return false;
+        };
+        log::warn!(ip = self.cfg.lighthouse_ip, found = ip; "{} {} -> {}", self.t.mdns_probe, self.cfg.lighthouse_ip, ip);
+        let old = std::mem::replace(&mut self.cfg.lighthouse_ip, ip);
+        if self.probe_once() {
+            log::info!(ip = self.cfg.lighthouse_ip; "{} {}", self.t.mdns_adopted, self.cfg.lighthouse_ip);
+            true
+        } else {
+            self.cfg.lighthouse_ip = old;
+            false
+        }
+    }
+
+    // Режим сна с учетом заряда батареи и температуры
+    fn sleep_mode_for(&mut self) -> String {
+        let cfg = &self.cfg;
+        if let Some(pct) = power::battery_percent()
+            && pct <= cfg.low_battery_percent
+        {
+            log::warn!("🪫 Battery {}% - switching sleep mode to disk", pct);
+            self.bus.emit(events::Event::BatteryLow { percent: pct });
+            return cold_or_hot(cfg, "disk");
+        }
+        cold_or_hot(cfg, &cfg.sleep_mode)
+    }
+}
+
+// === УТИЛИТЫ ===
+fn load_config_safe() -> Result<PortalConfig, String> {
+    load_config_from(&paths::config(CONFIG_FILE))
+}
+
+fn load_config_from(path: &str) -> Result<PortalConfig, String> {
+    let d = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse_config(&d).map(|l| l.cfg)
+}
+
+struct LoadedConfig {
+    cfg: PortalConfig,
+    // Версия в файле до миграции
+    from_version: u32,
+    // Ключи, которых схема не знает: опечатка или опция из другой версии
+    unknown: Vec<String>,
+}
+
+// Сырой JSON сначала проходит шаги миграции, потом разбирается по типам
+fn parse_config(text: &str) -> Result<LoadedConfig, String> {
+    let mut v: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let obj = v.as_object_mut().ok_or("config must be a JSON object")?;
+    let from_version = obj
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .map_or(0, |n| n.min(u32::MAX as u64) as u32);
+    if from_version < CONFIG_VERSION {
+        for step in &MIGRATIONS[from_version as usize..] {
+            step(obj);
+        }
+        obj.insert("version".into(), CONFIG_VERSION.into());
+    }
+    let known = serde_json::to_value(PortalConfig::default()).map_err(|e| e.to_string())?;
+    let unknown = obj
+        .keys()
+        .filter(|k| known.get(k.as_str()).is_none())
+        .cloned()
+        .collect();
+    let cfg: PortalConfig = serde_json::from_value(v).map_err(|e| e.to_string())?;
+    cfg.validate()?;
+    Ok(LoadedConfig {
+        cfg,
+        from_version,
+        unknown,
+    })
+}
+
+// Старый конфиг после миграции записываем заново, прежний файл - рядом
+fn upgrade_config_file(cfg: &PortalConfig, from_version: u32) -> std::io::Result<String> {
+    let old = format!("{}.v{}", paths::config(CONFIG_FILE), from_version);
+    fs::copy(paths::config(CONFIG_FILE), &old)?;
+    let json = serde_json::to_string_pretty(cfg).map_err(std::io::Error::other)?;
+    fs::write(paths::config(CONFIG_FILE), json)?;
+    Ok(old)
+}
+
+// Конфиг для демона по политике on_invalid_config. Удачно загруженный
+// конфиг сохраняется в config.json.good - из него потом и восстанавливаемся.
+fn startup_config() -> Result<(PortalConfig, Option<ConfigIssue>), PortalError> {
+    let loaded = fs::read_to_string(paths::config(CONFIG_FILE))
+        .map_err(|e| e.to_string())
+        .and_then(|d| parse_config(&d));
+    let err = match loaded {
+        Ok(LoadedConfig {
+            cfg,
+            from_version,
+            unknown,
+        }) => {
+            for key in &unknown {
+                log::warn!(key = key; "⚠️  Unknown config key '{}' ignored (typo or option from another version?)", key);
+            }
+            if from_version > CONFIG_VERSION {
+                log::warn!(
+                    "⚠️  {} is from a newer portal_daemon (version {} > {}), its new options are ignored",
+                    paths::config(CONFIG_FILE),
+                    from_version,
+                    CONFIG_VERSION
+                );
+            } else if from_version < CONFIG_VERSION && !cfg.read_only_root {
+                match upgrade_config_file(&cfg, from_version) {
+                    Ok(old) => log::warn!(
+                        from = from_version, to = CONFIG_VERSION;
+                        "⚙️  Upgraded {} from version {} to {} (old copy: {})",
+                        paths::config(CONFIG_FILE), from_version, CONFIG_VERSION, old
+                    ),
+                    Err(e) => log::warn!(
+                        "⚠️  Config upgraded to version {} in memory only, cannot rewrite {}: {}",
+                        CONFIG_VERSION,
+                        paths::config(CONFIG_FILE),
+                        e
+                    ),
+                }
+            }
+            if !cfg.read_only_root
+                && let Ok(json) = serde_json::to_string_pretty(&cfg)
+            {
+                fs::write(paths::config(CONFIG_BACKUP), json).ok();
+            }
+            return Ok((cfg, None));
+        }
+        Err(e) => e,
+    };
+    let backup = load_config_from(&paths::config(CONFIG_BACKUP)).ok();
+    // Сам конфиг битый: политику берем из него, если JSON хоть как-то читается
+    let policy = fs::read_to_string(paths::config(CONFIG_FILE))
+        .ok()
+        .and_then(|d| serde_json::from_str::<serde_json::Value>(&d).ok())
+        .and_then(|v| serde_json::from_value(v.get("on_invalid_config")?.clone()).ok())
+        .or(backup.as_ref().map(|b| b.on_invalid_config))
+        .unwrap_or_default();
+    log::error!("❌ Invalid config {}: {}", paths::config(CONFIG_FILE), err);
+
+    // Из терминала спрашиваем при любой политике, сервис восстанавливает сам
+    if let Some(cfg) = backup {
+        let restore = if std::io::stdin().is_terminal() {
+            Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!(
+                    "Restore last known good config from {}?",
+                    paths::config(CONFIG_BACKUP)
+                ))
+                .default(true)
+                .interact()
+                .unwrap_or(false)
+        } else {
+            policy == InvalidConfigPolicy::LastGood
+        };
+        if restore {
+            match restore_config_backup() {
+                Ok(broken) => log::warn!(
+                    "♻️  Restored {} (broken copy: {})",
+                    paths::config(CONFIG_FILE),
+                    broken
+                ),
+                Err(e) => log::warn!("⚠️  Cannot restore {}: {}", paths::config(CONFIG_FILE), e),
+            }
+            let message = format!(
+                "invalid config ({}), restored {}",
+                err,
+                paths::config(CONFIG_BACKUP)
+            );
+            return Ok((
+                cfg,
+                Some(ConfigIssue {
+                    message,
+                    restored: true,
+                }),
+            ));
+        }
+    }
+    if policy == InvalidConfigPolicy::Notify {
+        log::warn!("⚠️  Running with DEFAULT settings until the config is fixed!");
+        let message = format!("invalid config ({}), using defaults", err);
+        return Ok((
+            PortalConfig::default(),
+            Some(ConfigIssue {
+                message,
+                restored: false,
+            }),
+        ));
+    }
+    Err(PortalError::Config(
+        "🛑 Refusing to start. Fix the config or run 'portal_daemon --configure'.".into(),
+    ))
+}
+
+// Битый конфиг не затираем, а откладываем рядом для разбора
+fn restore_config_backup() -> std::io::Result<String> {
+    let broken = format!("{}.broken-{}", paths::config(CONFIG_FILE), unix_now());
+    fs::rename(paths::config(CONFIG_FILE), &broken).ok();
+    fs::copy(paths::config(CONFIG_BACKUP), paths::config(CONFIG_FILE))?;
+    Ok(broken)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// Ставится из потока D-Bus или Telegram, забирается циклом демона сразу
+static SLEEP_NOW: AtomicBool = AtomicBool::new(false);
+
+// --dry-run: демон проверяет все как обычно, но не спит, не зовет хуки и
+// не будит соседей; установка и удаление только перечисляют, что тронули бы
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+fn request_sleep_now() {
+    SLEEP_NOW.store(true, Ordering::Relaxed);
+    tui::check_now();
+}
+
+fn set_pause(mins: u64) -> std::io::Result<()> {
+    set_pause_until(unix_now().saturating_add(mins.saturating_mul(60)))
+}
+
+// В файле - UNIX-время конца паузы: не зависит от пояса и переживает рестарт
+fn set_pause_until(end: u64) -> std::io::Result<()> {
+    if !Path::new(paths::run_dir()).exists() {
+        state::prepare_run_dir();
+    }
+    fs::write(paths::run(PAUSE_FILE), end.to_string())?;
+    // Из потока D-Bus, Telegram или fleet: цикл не ждет конца своего ожидания
+    tui::check_now();
+    Ok(())
+}
+
+fn clear_pause() -> std::io::Result<()> {
+    match fs::remove_file(paths::run(PAUSE_FILE)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => {
+            tui::check_now();
+            Ok(())
+        }
+    }
+}
+
+// gethostname(3): /proc/sys/kernel/hostname и /etc/hostname есть не везде
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return "unknown".into();
+    }
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    match String::from_utf8_lossy(&buf[..end]).trim() {
+        "" => "unknown".into(),
+        h => h.to_string(),
+    }
+}
+
+fn pause_until() -> Option<u64> {
+    fs::read_to_string(paths::run(PAUSE_FILE))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn check_pause() -> bool {
+    if Path::new(&paths::run(PAUSE_FILE)).exists() {
+        if let Ok(c) = fs::read_to_string(paths::run(PAUSE_FILE))
+            && let Ok(end) = c.trim().parse::<u64>()
+        {
+            if unix_now() < end {
+                return true;
+            } else {
+                fs::remove_file(paths::run(PAUSE_FILE)).ok();
+                return false;
+            }
+        }
+        fs::remove_file(paths::run(PAUSE_FILE)).ok();
+    }
+    false
+}
+
+fn scan_networks(backend: net::Backend) -> Vec<NetworkInfo> {
+    net::connections(backend)
+        .into_iter()
+        .filter(|c| c.device != "lo" && !c.name.is_empty() && !c.gateway.is_empty())
+        .map(|c| NetworkInfo {
+            ssid: c.name,
+            device: c.device,
+            gateway: c.gateway,
+        })
+        .collect()
+}
+
+// "br0 → enp3s0, enp4s0" для моста/bond/VLAN, просто "eth0" для обычного NIC
+fn link_label(dev: &str) -> String {
+    let carriers = net::carrier_devs(dev);
+    if carriers.len() == 1 && carriers[0] == dev {
+        dev.to_string()
+    } else {
+        format!("{} → {}", dev, carriers.join(", "))
+    }
+}
+
+struct NetworkInfo {
+    ssid: String,
+    device: String,
+    gateway: String,
+}
+
+fn state_changed(phase: state::Phase, reason: impl Into<String>) -> events::Event {
+    events::Event::StateChanged {
+        phase,
+        reason: reason.into(),
+    }
+}
+
+// Вне безопасного диапазона температур - temp_fallback_mode вместо mode
+fn cold_or_hot(cfg: &PortalConfig, mode: &str) -> String {
+    if mode != cfg.temp_fallback_mode
+        && let Some(c) = thermal::out_of_range(
+            cfg.temp_sensor.as_deref(),
+            cfg.suspend_temp_min_c,
+            cfg.suspend_temp_max_c,
+        )
+    {
+        log::warn!(
+            temp_c = format!("{:.1}", c);
+            "🌡️  {:.1}°C is outside the safe range - {} instead of {}", c, cfg.temp_fallback_mode, mode
+        );
+        return cfg.temp_fallback_mode.clone();
+    }
+    mode.to_string()
+}
+
+// true - rtcwake отработал (машина спала и проснулась)
+fn enter_hibernation(cfg: &PortalConfig, seconds: u64, mode: &str) -> bool {
+    if inject::take(inject::Fault::RtcwakeFail) {
+        log::error!("❌ Error: rtcwake failed.");
+        watchdog::sleep(Duration::from_secs(60));
+        return false;
+    }
+    // Пока машина спит, /healthz молчит вместе с ней - после подъема не "завис"
+    watchdog::expect_quiet(Duration::from_secs(seconds));
+    if dry_run() {
+        let action = match mode {
+            "off" => "power off",
+            "disk" => "hibernate",
+            _ => "suspend",
+        };
+        log::info!(
+            sleep_sec = seconds, mode = mode;
+            "🧪 Dry run: would {} for {} min now", action, seconds.div_ceil(60)
+        );
+        // Время идет, как во сне: следующий цикл - тогда же, когда был бы
+        watchdog::sleep(Duration::from_secs(seconds));
+        return true;
+    }
+    if mode == "off" {
+        return power_off(cfg, seconds);
+    }
+    go_to_sleep(cfg, seconds, mode)
+}
+
+// rtcwake -m off и logind только запускают выключение. Ждем, пока система
+// остановит демон: вернуться - значит "проснуться" сразу после сна
+fn power_off(cfg: &PortalConfig, seconds: u64) -> bool {
+    let at = unix_now();
+    state::mark_poweroff(state::PowerOff {
+        at,
+        wake_at: at + seconds,
+    });
+    if !go_to_sleep(cfg, seconds, "off") {
+        state::clear_poweroff();
+        return false;
+    }
+    log::info!(sleep_sec = seconds; "⏻ Powering off, the RTC alarm turns the machine on in {} min", seconds.div_ceil(60));
+    watchdog::sleep(SHUTDOWN_WAIT);
+    // Выключение не началось: его задержал inhibitor или отменили
+    state::clear_poweroff();
+    log::error!(
+        "❌ Error: the machine did not power off within {} sec.",
+        SHUTDOWN_WAIT.as_secs()
+    );
+    false
+}
+
+fn go_to_sleep(cfg: &PortalConfig, seconds: u64, mode: &str) -> bool {
+    if paths::user() {
+        return logind_sleep(mode);
+    }
+    if freebsd::is() {
+        if freebsd::sleep(mode) {
+            log::info!("✅ Sleep OK.");
+            return true;
+        }
+        log::error!("❌ Error: acpiconf failed.");
+        watchdog::sleep(Duration::from_secs(60));
+        return false;
+    }
+    let priv_cmd = if Path::new(DOAS_CONF).exists() {
+        "doas"
+    } else {
+        "sudo"
+    };
+    wol::arm_interfaces(priv_cmd, &cfg.wol_interfaces);
+    let rtcwake = |args: &[String]| {
+        log::debug!(
+            "{} rtcwake {} {}",
+            priv_cmd,
+            args.join(" "),
+            cfg.rtcwake_args.join(" ")
+        );
+        let mut argv = vec!["rtcwake".to_string()];
+        argv.extend(args.iter().chain(&cfg.rtcwake_args).cloned());
+        match privsep::output(Some(priv_cmd), &argv, &[]) {
+            Ok(o) if o.status.success() => true,
+            Ok(o) => {
+                let err = String::from_utf8_lossy(&o.stderr);
+                if !err.trim().is_empty() {
+                    log::warn!("rtcwake: {}", err.trim());
+                }
+                false
+            }
+            Err(e) => {
+                log::warn!("rtcwake: {}", e);
+                false
+            }
+        }
+    };
+
+    let extra = &cfg.rtcwake_args;
+    let has = |opts: &[&str]| {
+        extra.iter().any(|a| {
+            opts.iter()
+                .any(|o| a == o || a.starts_with(&format!("{}=", o)))
+        })
+    };
+    let custom_time = has(&["-s", "--seconds", "-t", "--time", "--date"]);
+    let clock = if has(&["-l", "--local"]) {
+        rtc::RtcClock::Local
+    } else if has(&["-u", "--utc"]) {
+        rtc::RtcClock::Utc
+    } else {
+        cfg.rtc_clock.resolve()
+    };
+
+    let mut args = vec!["-m".to_string(), mode.to_string()];
+    if !has(&["-l", "--local", "-u", "--utc"]) {
+        args.push(clock.flag().into());
+    }
+    if !custom_time {
+        // Сначала только ставим будильник и сверяем его, потом уже спим
+        let target = unix_now() as i64 + seconds as i64;
+        let mut dry = vec!["-m".to_string(), "no".to_string(), clock.flag().into()];
+        dry.extend(["-t".to_string(), target.to_string()]);
+        if rtcwake(&dry) {
+            let tz = schedule::TimeZone::resolve(cfg.timezone.as_deref())
+                .unwrap_or_else(|_| schedule::TimeZone::utc());
+            let offset = tz.offset_at(target) as i64;
+            if let Err(drift) = rtc::verify(&rtc::device(extra), clock, target, offset) {
+                log::warn!(
+                    "⚠️  RTC wakealarm is off by {:+} sec ({:+.1} h): check rtc_clock (utc/local) and /etc/adjtime",
+                    drift,
+                    drift as f64 / 3600.0
+                );
+            }
+        }
+        args.extend(["-t".to_string(), target.to_string()]);
+    }
+
+    if rtcwake(&args) {
+        log::info!("✅ Sleep OK.");
+        return true;
+    }
+    log::error!("❌ Error: rtcwake failed.");
+    watchdog::sleep(Duration::from_secs(60));
+    false
+}
+
+// Будильник RTC ставит только rtcwake от root на Linux
+fn wake_alarm() -> bool {
+    !paths::user() && !freebsd::is()
+}
+
+// --user: ни rtcwake, ни sudo - усыпляет logind (polkit пускает
+// пользователя активной сессии). Будильник RTC без root не поставить:
+// будят крышка, кнопка или WoL
+fn logind_sleep(mode: &str) -> bool {
+    let method = match mode {
+        "disk" => "Hibernate",
+        "off" => "PowerOff",
+        _ => "Suspend",
+    };
+    let before = timers::suspended();
+    let r = dbus::Client::system().and_then(|mut c| {
+        c.call_flag(
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+            method,
+            false,
+        )
+    });
+    if let Err(e) = r {
+        log::error!("❌ Error: logind {} failed: {}", method, e);
+        watchdog::sleep(Duration::from_secs(60));
+        return false;
+    }
+    // logind отвечает сразу, а система засыпает чуть позже
+    for _ in 0..LOGIND_SLEEP_WAIT_SEC {
+        watchdog::sleep(Duration::from_secs(1));
+        if timers::suspended() > before + Duration::from_secs(1) {
+            log::info!("✅ Sleep OK.");
+            return true;
+        }
+    }
+    log::error!(
+        "❌ Error: logind accepted {} but the system did not sleep.",
+        method
+    );
+    false
+}
+
+// Сдались: выключение без будильника - включат кнопкой или сама плата,
+// когда вернется питание. После сброса root logind может и не пустить
+fn shut_down() -> bool {
+    if dry_run() {
+        log::info!("🧪 Dry run: would power off without a wake alarm now");
+        return true;
+    }
+    let r = if freebsd::is() {
+        Command::new("shutdown")
+            .args(["-p", "now"])
+            .status()
+            .map_err(|e| e.to_string())
+            .and_then(|s| s.success().then_some(()).ok_or(s.to_string()))
+    } else {
+        dbus::Client::system().and_then(|mut c| {
+            c.call_flag(
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1",
+                "org.freedesktop.login1.Manager",
+                "PowerOff",
+                false,
+            )
+        })
+    };
+    if let Err(e) = r {
+        log::error!("❌ Error: power off failed: {}", e);
+        return false;
+    }
+    watchdog::sleep(SHUTDOWN_WAIT);
+    log::error!(
+        "❌ Error: the machine did not power off within {} sec.",
+        SHUTDOWN_WAIT.as_secs()
+    );
+    false
 }
 
 fn is_root() -> bool {
-    let out = Command::new("id").arg("-u").output().unwrap();
-    String::from_utf8_lossy(&out.stdout).trim() == "0"
+    unsafe { libc::geteuid() == 0 }
+}
+
+// === УСТАНОВКА СИСТЕМЫ И СЕРВИСОВ ===
+fn run_system_install() -> Result<(), PortalError> {
+    if dry_run() {
+        println!("🔎 Install preview, nothing is changed:");
+    } else {
+        println!("🚀 Starting SYSTEM INSTALL...");
+    }
+    if !is_root() && !dry_run() {
+        return Err(PortalError::NotRoot("Install"));
+    }
+
+    // 1. Копирование бинарника
+    if let Ok(current_exe) = env::current_exe() {
+        println!("📦 Copying binary to {}...", BINARY_DEST);
+        sys_copy(&current_exe, BINARY_DEST)?;
+        // Делаем исполняемым (на всякий случай)
+        sys_chmod(BINARY_DEST, 0o755)?;
+    } else {
+        eprintln!("❌ Cannot find current executable path.");
+    }
+
+    // 2. Настройка прав (sudo/doas)
+    let rtc = find_binary("rtcwake").unwrap_or_else(|| "/usr/sbin/rtcwake".to_string());
+    let net = find_binary("nmcli").unwrap_or_else(|| "/usr/bin/nmcli".to_string());
+
+    println!("👤 Creating group {}...", GROUP_NAME);
+    let user = env::var("SUDO_USER").ok().or(env::var("DOAS_USER").ok());
+    let had_group = group_exists(GROUP_NAME);
+    if freebsd::is() {
+        // pw: группа уже есть - ошибка, это нормально при переустановке
+        sys_run(&["pw", "groupadd", GROUP_NAME])?;
+        if !had_group {
+            remember_created("group", GROUP_NAME);
+        }
+        if let Some(u) = &user {
+            println!("👤 Adding user '{}' to group...", u);
+            sys_run(&["pw", "groupmod", GROUP_NAME, "-m", u])?;
+        }
+    } else {
+        sys_run(&["groupadd", "-f", GROUP_NAME])?;
+        if !had_group {
+            remember_created("group", GROUP_NAME);
+        }
+        if let Some(u) = &user {
+            println!("👤 Adding user '{}' to group...", u);
+            sys_run(&["usermod", "-aG", GROUP_NAME, u])?;
+        }
+        if !privsep::user_exists(RUN_AS_USER) {
+            println!("👤 Creating system user {} (run_as)...", RUN_AS_USER);
+            sys_run(&[
+                "useradd",
+                "--system",
+                "--no-create-home",
+                "--home-dir",
+                "/nonexistent",
+                "--shell",
+                "/usr/sbin/nologin",
+                "--groups",
+                GROUP_NAME,
+                RUN_AS_USER,
+            ])?;
+            remember_created("user", RUN_AS_USER);
+        }
+        // probe = "sensor" читает GPIO и последовательные порты и после run_as
+        let groups = fs::read_to_string("/etc/group").unwrap_or_default();
+        for g in sensor::SENSOR_GROUPS {
+            if groups.lines().any(|l| l.starts_with(&format!("{}:", g))) {
+                try_run(&["usermod", "-aG", g, RUN_AS_USER]);
+            }
+        }
+    }
+
+    // FreeBSD спит через acpiconf от root - sudo ни к чему
+    if !freebsd::is() {
+        if Path::new(DOAS_CONF).exists() {
+            setup_doas(&rtc, &net)?;
+        } else {
+            setup_sudo(&rtc, &net)?;
+        }
+    }
+
+    // 3. Политика D-Bus: без нее системная шина не даст занять имя
+    if Path::new("/etc/dbus-1/system.d").exists() {
+        sys_write(
+            dbus::POLICY_FILE,
+            &dbus::policy_xml(GROUP_NAME, RUN_AS_USER),
+        )?;
+        if !dry_run() {
+            println!("   📄 Created {}", dbus::POLICY_FILE);
+        }
+    }
+
+    // 4. Установка сервиса (Systemd vs OpenRC)
+    install_service()?;
+
+    // 5. Дополнение для установленных оболочек и man
+    install_docs(SHARE_DIR)?;
+
+    if dry_run() {
+        println!("\n👉 Run without --dry-run to apply.");
+        return Ok(());
+    }
+    println!("\n🎉 INSTALLATION COMPLETE!");
+    println!("👉 Run 'portal_daemon --configure' to set up IPs.");
+    Ok(())
+}
+
+// --user: без root, группы и sudoers - бинарник в ~/.local/bin и unit
+// пользовательского systemd
+fn run_user_install() -> Result<(), PortalError> {
+    if dry_run() {
+        println!("🔎 Install preview, nothing is changed:");
+    } else {
+        println!("🚀 Starting USER INSTALL...");
+    }
+    if is_root() {
+        return Err(PortalError::Usage(
+            "--user install is for a regular user, run it without sudo/doas!",
+        ));
+    }
+    let (binary, unit) = user_install_paths();
+
+    if let Ok(current_exe) = env::current_exe() {
+        println!("📦 Copying binary to {}...", binary);
+        if let Some(dir) = Path::new(&binary).parent() {
+            sys_mkdir(dir)?;
+        }
+        sys_copy(&current_exe, &binary)?;
+        sys_chmod(&binary, 0o755)?;
+    } else {
+        eprintln!("❌ Cannot find current executable path.");
+    }
+
+    println!("⚙️  Installing systemd user unit.");
+    let service_content = format!(
+        r#"[Unit]
+Description=Portal Daemon (Network Sleep Manager, user mode)
+
+[Service]
+ExecStart={} --user
+# Обновление на месте: новый бинарник подхватывает состояние (SIGUSR2)
+ExecReload=/bin/kill -USR2 $MAINPID
+Restart=always
+# Invalid config (EX_CONFIG): restarting will not help
+RestartPreventExitStatus=78
+
+[Install]
+WantedBy=default.target
+"#,
+        binary
+    );
+    if let Some(dir) = Path::new(&unit).parent() {
+        sys_mkdir(dir)?;
+    }
+    write_service_file(&unit, &service_content, true)?;
+    install_docs(&user_share_dir())?;
+    sys_run(&["systemctl", "--user", "daemon-reload"])?;
+    sys_run(&["systemctl", "--user", "enable", "--now", "portal"])?;
+    if dry_run() {
+        println!("\n👉 Run without --dry-run to apply.");
+        return Ok(());
+    }
+    println!("   ✅ User service enabled & started.");
+
+    println!("\n🎉 INSTALLATION COMPLETE!");
+    println!("👉 Run 'portal_daemon --user --configure' to set up IPs.");
+    Ok(())
+}
+
+// Скрипты дополнения (только для оболочек, что есть в системе) и man(8)
+fn install_docs(share: &str) -> Result<(), PortalError> {
+    println!("📖 Installing shell completions and man page...");
+    let mut files: Vec<(String, String)> = completions::ALL
+        .into_iter()
+        .filter(|s| find_binary(s.name()).is_some())
+        .map(|s| {
+            let body = completions::generate(s, Args::command());
+            (s.install_path(share), body)
+        })
+        .collect();
+    files.push((
+        completions::man_path(share),
+        completions::man(Args::command()),
+    ));
+    for (path, body) in &files {
+        if let Some(dir) = Path::new(path).parent() {
+            sys_mkdir(dir)?;
+        }
+        sys_write(path, body)?;
+    }
+    Ok(())
+}
+
+fn uninstall_docs(share: &str) {
+    for s in completions::ALL {
+        sys_remove(&s.install_path(share));
+    }
+    sys_remove(&completions::man_path(share));
+}
+
+// $XDG_DATA_HOME, иначе ~/.local/share
+fn user_share_dir() -> String {
+    env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|d| d.starts_with('/'))
+        .unwrap_or_else(|| format!("{}/.local/share", env::var("HOME").unwrap_or_default()))
+}
+
+// Бинарник и unit для --user; $XDG_CONFIG_HOME - родитель каталога конфига
+fn user_install_paths() -> (String, String) {
+    let home = env::var("HOME").unwrap_or_default();
+    let config_home = Path::new(paths::config_dir())
+        .parent()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    (
+        format!("{}/.local/bin/portal_daemon", home),
+        format!("{}/systemd/user/portal.service", config_home),
+    )
+}
+
+// --- СИСТЕМА ИНИЦИАЛИЗАЦИИ ---
+// Кроме systemd и OpenRC: runit (Void, Artix), s6-rc и dinit (Artix).
+// Сначала то, что запущено сейчас, потом то, что просто установлено
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Init {
+    Systemd,
+    RcD,
+    Runit,
+    S6,
+    Dinit,
+    OpenRc,
+}
+
+impl Init {
+    fn detect() -> Self {
+        let exists = |p: &str| Path::new(p).exists();
+        if freebsd::is() {
+            Init::RcD
+        } else if exists("/run/systemd/system") {
+            Init::Systemd
+        } else if exists("/run/runit") || exists("/etc/runit") {
+            Init::Runit
+        } else if exists("/run/s6-rc") || exists(S6_SOURCES) {
+            Init::S6
+        } else if exists("/run/dinitctl") || exists("/etc/dinit.d") {
+            Init::Dinit
+        } else if exists("/usr/lib/systemd") {
+            Init::Systemd
+        } else {
+            // Предполагаем OpenRC (Gentoo/Artix)
+            Init::OpenRc
+        }
+    }
+
+    // Уже стоящая служба: по ее файлам, а не по тому, что запущено
+    fn installed() -> Option<Self> {
+        [
+            (Init::Systemd, SYSTEMD_UNIT.to_string()),
+            (Init::RcD, freebsd::RC_SCRIPT.to_string()),
+            (Init::Runit, runit_dirs().0),
+            (Init::S6, format!("{}/portal", S6_SOURCES)),
+            (Init::Dinit, DINIT_SERVICE.to_string()),
+            (Init::OpenRc, OPENRC_SCRIPT.to_string()),
+        ]
+        .into_iter()
+        .find(|(_, path)| Path::new(path).exists())
+        .map(|(init, _)| init)
+    }
+
+    fn restart_cmd(self) -> &'static str {
+        match self {
+            Init::Systemd => "systemctl restart portal",
+            Init::RcD => "service portal restart",
+            Init::Runit => "sv restart portal",
+            Init::S6 => "s6-svc -r /run/service/portal",
+            Init::Dinit => "dinitctl restart portal",
+            Init::OpenRc => "rc-service portal restart",
+        }
+    }
+}
+
+// runit: Artix держит службы в /etc/runit/sv, Void - в /etc/sv;
+// включение - ссылка в каталог, который сканирует runsvdir
+fn runit_dirs() -> (String, String) {
+    if Path::new("/etc/runit/sv").exists() {
+        ("/etc/runit/sv/portal".into(), "/run/runit/service".into())
+    } else {
+        ("/etc/sv/portal".into(), "/var/service".into())
+    }
+}
+
+fn install_service() -> Result<(), PortalError> {
+    match Init::detect() {
+        Init::Systemd => install_systemd(),
+        Init::RcD => install_rcd(),
+        Init::Runit => install_runit(),
+        Init::S6 => install_s6(),
+        Init::Dinit => install_dinit(),
+        Init::OpenRc => install_openrc(),
+    }
+}
+
+fn install_systemd() -> Result<(), PortalError> {
+    println!("⚙️  Detected Systemd.");
+    let service_content = format!(
+        r#"[Unit]
+Description=Portal Daemon (Network Sleep Manager)
+After=network.target
+
+[Service]
+ExecStart={}
+# Обновление на месте: новый бинарник подхватывает состояние (SIGUSR2)
+ExecReload=/bin/kill -USR2 $MAINPID
+Restart=always
+# Invalid config (EX_CONFIG): restarting will not help
+RestartPreventExitStatus=78
+# Секреты "systemd-creds:portal.<имя>" из /etc/credstore(.encrypted)
+ImportCredential=portal.*
+User=root
+Group=root
+
+# Песочница: root, но без записи в систему и без доступа к /home.
+# С "run_as": "portal" в конфиге root после старта остается только у
+# помощника (rtcwake, ethtool, уведомления), цикл идет от portal.
+# Пишем только конфиг (config.json.good), историю и /run/portal_daemon;
+# /sys открыт - через него засыпаем и ставим будильник RTC.
+# heartbeat_file или хуки пишут куда-то еще - добавьте ReadWritePaths=
+# в drop-in (systemctl edit portal)
+ProtectSystem=strict
+# /home и /root пустые, но /run/user виден: там сессионные шины, через
+# которые идут уведомления на рабочий стол и кнопка "Отменить сон"
+ProtectHome=tmpfs
+BindReadOnlyPaths=-/run/user
+PrivateTmp=yes
+ConfigurationDirectory=portal_daemon
+StateDirectory=portal_daemon
+RuntimeDirectory=portal_daemon
+RuntimeDirectoryPreserve=yes
+ReadWritePaths=/etc/portal_daemon /var/lib/portal_daemon /run/portal_daemon
+# sudo/doas и runuser уже запускаются от root - setuid им не нужен
+NoNewPrivileges=yes
+# NET_RAW - ping и ARP, NET_ADMIN - ethtool (Wake-on-LAN), WAKE_ALARM - rtcwake,
+# SETUID/SETGID/AUDIT_WRITE - sudo/doas и runuser (уведомления),
+# CHOWN - группа portal-admins на /run/portal_daemon,
+# NET_BIND_SERVICE - http_port ниже 1024
+CapabilityBoundingSet=CAP_NET_RAW CAP_NET_ADMIN CAP_NET_BIND_SERVICE CAP_SETUID CAP_SETGID CAP_AUDIT_WRITE CAP_CHOWN CAP_WAKE_ALARM
+
+[Install]
+WantedBy=multi-user.target
+"#,
+        BINARY_DEST
+    );
+
+    write_service_file(SYSTEMD_UNIT, &service_content, true)?;
+
+    sys_run(&["systemctl", "daemon-reload"])?;
+    sys_run(&["systemctl", "enable", "--now", "portal"])?;
+    done("Service enabled & started.");
+    Ok(())
+}
+
+fn install_openrc() -> Result<(), PortalError> {
+    println!("⚙️  Detected OpenRC (or fallback).");
+    let openrc_content = format!(
+        r#"#!/sbin/openrc-run
+
+name="portal"
+description="Portal Daemon"
+command="{}"
+command_background=true
+pidfile="/run/portal.pid"
+
+depend() {{
+    need net
+}}
+
+extra_started_commands="reload"
+
+# Обновление на месте: новый бинарник подхватывает состояние (SIGUSR2)
+reload() {{
+    ebegin "Upgrading ${{name}} in place"
+    start-stop-daemon --signal USR2 --pidfile "${{pidfile}}"
+    eend $?
+}}
+"#,
+        BINARY_DEST
+    );
+
+    if write_service_file(OPENRC_SCRIPT, &openrc_content, false)? {
+        sys_chmod(OPENRC_SCRIPT, 0o755)?;
+    }
+
+    sys_run(&["rc-update", "add", "portal", "default"])?;
+    sys_run(&["rc-service", "portal", "start"])?;
+    done("Service added to default runlevel & started.");
+    Ok(())
+}
+
+// Скрипт запуска для runit и s6: вывод - в лог супервизора
+fn run_script() -> String {
+    format!("#!/bin/sh\nexec {} 2>&1\n", BINARY_DEST)
+}
+
+fn write_script(path: &str, content: &str) -> Result<(), PortalError> {
+    if write_service_file(path, content, false)? {
+        sys_chmod(path, 0o755)?;
+    }
+    Ok(())
+}
+
+// --- ДЕЙСТВИЯ УСТАНОВКИ ---
+// С --dry-run только печатают, что сделали бы
+fn sys_run(cmd: &[&str]) -> Result<(), PortalError> {
+    if dry_run() {
+        println!("   would run: {}", cmd.join(" "));
+        return Ok(());
+    }
+    // Код выхода не проверяем: "группа уже есть" и т.п. - не повод бросать установку
+    Command::new(cmd[0])
+        .args(&cmd[1..])
+        .status()
+        .map(|_| ())
+        .map_err(|source| PortalError::Tool {
+            name: cmd[0].to_string(),
+            source,
+        })
+}
+
+fn sys_write(path: &str, content: &str) -> Result<(), PortalError> {
+    if dry_run() {
+        println!("   would write {} ({} bytes)", path, content.len());
+        return Ok(());
+    }
+    fs::write(path, content).map_err(|e| PortalError::write(path, e))
+}
+
+fn sys_copy(from: &Path, to: &str) -> Result<(), PortalError> {
+    if dry_run() {
+        println!("   would copy {} -> {}", from.display(), to);
+        return Ok(());
+    }
+    fs::copy(from, to)
+        .map(|_| ())
+        .map_err(|e| PortalError::write(to, e))
+}
+
+fn sys_chmod(path: &str, mode: u32) -> Result<(), PortalError> {
+    if dry_run() {
+        println!("   would chmod {:o} {}", mode, path);
+        return Ok(());
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .map_err(|e| PortalError::write(path, e))
+}
+
+fn sys_mkdir(dir: &Path) -> Result<(), PortalError> {
+    if dry_run() {
+        if !dir.exists() {
+            println!("   would create {}/", dir.display());
+        }
+        return Ok(());
+    }
+    fs::create_dir_all(dir).map_err(|e| PortalError::write(dir.display().to_string(), e))
+}
+
+fn sys_symlink(target: &str, link: &str) -> Result<(), PortalError> {
+    if dry_run() {
+        println!("   would link {} -> {}", link, target);
+        return Ok(());
+    }
+    std::os::unix::fs::symlink(target, link).map_err(|e| PortalError::write(link, e))
+}
+
+fn sys_remove(path: &str) {
+    let p = Path::new(path);
+    if !p.exists() {
+        return;
+    }
+    if dry_run() {
+        println!("   would remove {}", path);
+        return;
+    }
+    let r = if p.is_dir() {
+        fs::remove_dir_all(p)
+    } else {
+        fs::remove_file(p)
+    };
+    match r {
+        Ok(()) => println!("   🗑  Removed {}", path),
+        Err(e) => eprintln!("   ❌ Failed to remove {}: {}", path, e),
+    }
 }
 
-// === УСТАНОВКА СИСТЕМЫ И СЕРВИСОВ ===
-fn run_system_install() {
-    println!("🚀 Starting SYSTEM INSTALL...");
-    if !is_root() {
-        eprintln!("❌ Error: Install must be run as root (sudo/doas)!");
-        std::process::exit(1);
+fn done(msg: &str) {
+    if !dry_run() {
+        println!("   ✅ {}", msg);
     }
+}
 
-    // 1. Копирование бинарника
-    if let Ok(current_exe) = env::current_exe() {
-        println!("📦 Copying binary to {}...", BINARY_DEST);
-        if let Err(e) = fs::copy(&current_exe, BINARY_DEST) {
-            eprintln!("❌ Failed to copy binary: {}", e);
-        } else {
-            // Делаем исполняемым (на всякий случай)
-            fs::set_permissions(BINARY_DEST, fs::Permissions::from_mode(0o755)).unwrap();
+fn install_runit() -> Result<(), PortalError> {
+    println!("⚙️  Detected runit.");
+    let (dir, service_dir) = runit_dirs();
+    sys_mkdir(Path::new(&dir))?;
+    write_script(&format!("{}/run", dir), &run_script())?;
+    // finish: после EX_CONFIG служба остается выключенной
+    write_script(
+        &format!("{}/finish", dir),
+        &format!(
+            "#!/bin/sh\n# Invalid config (EX_CONFIG): restarting will not help\n[ \"$1\" = {} ] && exec sv down portal\nexit 0\n",
+            EX_CONFIG
+        ),
+    )?;
+    let link = format!("{}/portal", service_dir);
+    if fs::symlink_metadata(&link).is_err() {
+        sys_symlink(&dir, &link)?;
+        if !dry_run() {
+            println!("   🔗 Linked {} -> {}", link, dir);
         }
-    } else {
-        eprintln!("❌ Cannot find current executable path.");
     }
+    // runsvdir подхватит ссылку сам за несколько секунд
+    done("Service enabled (upgrade in place: sv 2 portal).");
+    Ok(())
+}
 
-    // 2. Настройка прав (sudo/doas)
-    let rtc = find_binary("rtcwake").unwrap_or_else(|| "/usr/sbin/rtcwake".to_string());
-    let net = find_binary("nmcli").unwrap_or_else(|| "/usr/bin/nmcli".to_string());
+fn install_s6() -> Result<(), PortalError> {
+    println!("⚙️  Detected s6-rc.");
+    let dir = format!("{}/portal", S6_SOURCES);
+    sys_mkdir(Path::new(&dir))?;
+    write_service_file(&format!("{}/type", dir), "longrun\n", false)?;
+    write_script(&format!("{}/run", dir), &run_script())?;
+    // finish: выход 125 - s6-supervise больше не перезапускает
+    write_script(
+        &format!("{}/finish", dir),
+        &format!(
+            "#!/bin/sh\n# Invalid config (EX_CONFIG): restarting will not help\n[ \"$1\" = {} ] && exit 125\nexit 0\n",
+            EX_CONFIG
+        ),
+    )?;
+    sys_run(&["s6-service", "add", "default", "portal"])?;
+    sys_run(&["s6-db-reload"])?;
+    sys_run(&["s6-rc", "-u", "change", "portal"])?;
+    done("Service added to the default bundle & started.");
+    Ok(())
+}
 
-    println!("👤 Creating group {}...", GROUP_NAME);
-    Command::new("groupadd")
-        .arg("-f")
-        .arg(GROUP_NAME)
-        .status()
-        .unwrap();
+fn install_rcd() -> Result<(), PortalError> {
+    println!("⚙️  Detected FreeBSD rc.d.");
+    write_script(freebsd::RC_SCRIPT, &freebsd::rc_script(BINARY_DEST))?;
+    sys_run(&["sysrc", "portal_enable=YES"])?;
+    sys_run(&["service", "portal", "start"])?;
+    done("Service enabled & started.");
+    Ok(())
+}
 
-    if let Some(u) = env::var("SUDO_USER").ok().or(env::var("DOAS_USER").ok()) {
-        println!("👤 Adding user '{}' to group...", u);
-        Command::new("usermod")
-            .args(["-aG", GROUP_NAME, &u])
-            .status()
-            .unwrap();
-    }
+fn install_dinit() -> Result<(), PortalError> {
+    println!("⚙️  Detected dinit.");
+    let content = format!(
+        r#"# Portal Daemon (Network Sleep Manager)
+type = process
+command = {}
+restart = true
+# Обновление на месте: dinitctl signal USR2 portal
+"#,
+        BINARY_DEST
+    );
+    write_service_file(DINIT_SERVICE, &content, false)?;
+    sys_run(&["dinitctl", "enable", "portal"])?;
+    done("Service enabled & started.");
+    Ok(())
+}
 
-    if Path::new(DOAS_CONF).exists() {
-        setup_doas(&rtc, &net);
-    } else {
-        setup_sudo(&rtc, &net);
+// Старый файл сервиса мог править пользователь (свой After=, Environment=):
+// показываем разницу и спрашиваем, а не затираем молча. Слияние - только
+// для unit-файлов systemd (init-скрипт - это shell) и только при сохраненной
+// базе: без нее не понять, чья строка устарела. true - файл записан
+fn write_service_file(path: &str, content: &str, mergeable: bool) -> Result<bool, PortalError> {
+    let base = mergeable.then(|| unit_base(path));
+    let Ok(old) = fs::read_to_string(path) else {
+        sys_write(path, content)?;
+        save_unit_base(base.as_deref(), content);
+        if !dry_run() {
+            println!("   📄 Created {}", path);
+        }
+        return Ok(true);
+    };
+    if old == content {
+        save_unit_base(base.as_deref(), content);
+        println!("   📄 {} is up to date", path);
+        return Ok(false);
     }
-
-    // 3. Установка сервиса (Systemd vs OpenRC)
-    install_service();
-
-    println!("\n🎉 INSTALLATION COMPLETE!");
-    println!("👉 Run 'portal_daemon --configure' to set up IPs.");
+    println!(
+        "   ⚠️  {} differs from what this version would write:",
+        path
+    );
+    show_diff(path, content);
+    let was = base.as_ref().and_then(|b| fs::read_to_string(b).ok());
+    if mergeable && was.is_none() {
+        println!("   ℹ️  No copy of the previously generated file, so merging is not offered.");
+    }
+    if dry_run() {
+        println!("   would ask whether to overwrite, merge or keep it");
+        return Ok(false);
+    }
+    if !std::io::stdin().is_terminal() {
+        println!("   ⏭  Kept the existing file (run --install in a terminal to choose).");
+        return Ok(false);
+    }
+    let mut items = vec!["Overwrite with the new version"];
+    if was.is_some() {
+        items.push("Merge: keep lines I changed or added, update the ones I did not touch");
+    }
+    items.push("Keep my file");
+    let choice = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("What to do with it?")
+        .default(items.len() - 1)
+        .items(&items)
+        .interact()
+        .unwrap_or(items.len() - 1);
+    let new = match (choice, &was) {
+        (0, _) => content.to_string(),
+        (1, Some(was)) => {
+            let (merged, conflicts) = merge_unit(was, &old, content);
+            if !conflicts.is_empty() {
+                println!(
+                    "   ⚠️  Changed in both versions, kept yours: {}",
+                    conflicts.join(", ")
+                );
+            }
+            merged
+        }
+        _ => {
+            println!("   ⏭  Kept {}", path);
+            return Ok(false);
+        }
+    };
+    save_unit_base(base.as_deref(), content);
+    if new == old {
+        println!("   📄 {} is up to date", path);
+        return Ok(false);
+    }
+    fs::write(path, new).map_err(|e| PortalError::write(path, e))?;
+    println!("   📄 Updated {}", path);
+    Ok(true)
 }
 
-fn install_service() {
-    // Проверяем Systemd
-    if Path::new("/run/systemd/system").exists() || Path::new("/usr/lib/systemd").exists() {
-        println!("⚙️  Detected Systemd.");
-        let service_content = format!(
-            r#"[Unit]
-Description=Portal Daemon (Network Sleep Manager)
-After=network.target
-
-[Service]
-ExecStart={}
-Restart=always
-User=root
-Group=root
+// Сгенерированный unit в том виде, в каком его записала установка
+fn unit_base(path: &str) -> String {
+    let name = Path::new(path)
+        .file_name()
+        .map_or(path.into(), |n| n.to_string_lossy());
+    paths::state(&format!("{}.generated", name))
+}
 
-[Install]
-WantedBy=multi-user.target
-"#,
-            BINARY_DEST
-        );
+fn save_unit_base(base: Option<&str>, content: &str) {
+    if let Some(base) = base
+        && !dry_run()
+    {
+        fs::create_dir_all(paths::state_dir()).ok();
+        fs::write(base, content).ok();
+    }
+}
 
-        let service_path = "/etc/systemd/system/portal.service";
-        fs::write(service_path, service_content).expect("Failed to write service file");
-        println!("   📄 Created {}", service_path);
+// diff -u (новая версия - на stdin, без временных файлов в /tmp), а если
+// его нет - строки, которых нет в другой версии
+fn show_diff(path: &str, content: &str) {
+    let shown = Command::new("diff")
+        .args(["-u", "--label", path, "--label", "new", path, "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(content.as_bytes()).ok();
+            }
+            child.wait()
+        })
+        .is_ok_and(|s| s.code() == Some(1));
+    if shown {
+        return;
+    }
+    let old = fs::read_to_string(path).unwrap_or_default();
+    for l in old.lines().filter(|l| !content.lines().any(|n| n == *l)) {
+        println!("-{}", l);
+    }
+    for l in content.lines().filter(|l| !old.lines().any(|o| o == *l)) {
+        println!("+{}", l);
+    }
+}
 
-        Command::new("systemctl")
-            .args(["daemon-reload"])
-            .status()
-            .ok();
-        Command::new("systemctl")
-            .args(["enable", "--now", "portal"])
-            .status()
-            .ok();
-        println!("   ✅ Service enabled & started.");
-    } else {
-        // Предполагаем OpenRC (Gentoo/Artix)
-        println!("⚙️  Detected OpenRC (or fallback).");
-        let openrc_content = format!(
-            r#"#!/sbin/openrc-run
+// Трехстороннее слияние unit-файла по ключам внутри секций (все строки
+// ключа вместе - Environment= бывает несколько). База - файл, который
+// записала прошлая установка: ключ, не тронутый пользователем, берется из
+// новой версии, тронутый только им - у него. Изменили обе стороны -
+// остается пользовательский, а ключ попадает в список конфликтов.
+// Добавленные пользователем ключи и секции сохраняются
+fn merge_unit(base: &str, old: &str, new: &str) -> (String, Vec<String>) {
+    let base = unit_sections(base);
+    let old = unit_sections(old);
+    let mut out = Vec::new();
+    let mut conflicts = Vec::new();
+    for (header, ours) in unit_sections(new) {
+        out.extend(header);
+        if !old.iter().any(|(h, _)| *h == header) {
+            out.extend(ours);
+            continue;
+        }
+        let mine = unit_section(&old, header);
+        let was = unit_section(&base, header);
+        let mut seen = Vec::new();
+        for l in &ours {
+            let Some(k) = unit_key(l) else {
+                out.push(l);
+                continue;
+            };
+            if seen.contains(&k) {
+                continue;
+            }
+            seen.push(k);
+            let (o, m, b) = (
+                unit_lines(&ours, k),
+                unit_lines(&mine, k),
+                unit_lines(&was, k),
+            );
+            if m == b {
+                out.extend(o);
+            } else {
+                if o != b {
+                    conflicts.push(k.to_string());
+                }
+                out.extend(m);
+            }
+        }
+        // Ключи пользователя, которых нет у нас, - перед пустыми строками
+        // конца секции; если мы ключ убрали, а он его не менял - убираем
+        let at = out.len() - out.iter().rev().take_while(|l| l.trim().is_empty()).count();
+        let mut extra = Vec::new();
+        for l in mine.iter().copied() {
+            let Some(k) = unit_key(l) else {
+                continue;
+            };
+            if seen.contains(&k) {
+                continue;
+            }
+            seen.push(k);
+            let (m, b) = (unit_lines(&mine, k), unit_lines(&was, k));
+            if m != b {
+                if !b.is_empty() {
+                    conflicts.push(k.to_string());
+                }
+                extra.extend(m);
+            }
+        }
+        out.splice(at..at, extra);
+    }
+    for (header, mine) in &old {
+        let ours = out.contains(&header.unwrap_or_default());
+        // Секцию убрали мы, а пользователь ее не менял
+        let dropped = base.iter().any(|(h, l)| h == header && l == mine);
+        if header.is_some() && !ours && !dropped {
+            if out.last().is_some_and(|l| !l.trim().is_empty()) {
+                out.push("");
+            }
+            out.extend(header);
+            out.extend(mine);
+        }
+    }
+    let mut merged = out.join("\n");
+    merged.push('\n');
+    (merged, conflicts)
+}
 
-name="portal"
-description="Portal Daemon"
-command="{}"
-command_background=true
-pidfile="/run/portal.pid"
+// [Секция] и ее строки; строки до первой секции - с None
+fn unit_sections(text: &str) -> Vec<(Option<&str>, Vec<&str>)> {
+    let mut out: Vec<(Option<&str>, Vec<&str>)> = vec![(None, Vec::new())];
+    for l in text.lines() {
+        if l.trim().starts_with('[') {
+            out.push((Some(l), Vec::new()));
+        } else if let Some((_, lines)) = out.last_mut() {
+            lines.push(l);
+        }
+    }
+    out
+}
 
-depend() {{
-    need net
-}}
-"#,
-            BINARY_DEST
-        );
+fn unit_section<'a>(
+    sections: &[(Option<&str>, Vec<&'a str>)],
+    header: Option<&str>,
+) -> Vec<&'a str> {
+    sections
+        .iter()
+        .find(|(h, _)| *h == header)
+        .map(|(_, l)| l.clone())
+        .unwrap_or_default()
+}
 
-        let init_path = "/etc/init.d/portal";
-        fs::write(init_path, openrc_content).expect("Failed to write init script");
-        fs::set_permissions(init_path, fs::Permissions::from_mode(0o755))
-            .expect("Failed to chmod init script");
-        println!("   📄 Created {} (executable)", init_path);
+fn unit_lines<'a>(lines: &[&'a str], key: &str) -> Vec<&'a str> {
+    lines
+        .iter()
+        .copied()
+        .filter(|l| unit_key(l) == Some(key))
+        .collect()
+}
 
-        Command::new("rc-update")
-            .args(["add", "portal", "default"])
-            .status()
-            .ok();
-        Command::new("rc-service")
-            .args(["portal", "start"])
-            .status()
-            .ok();
-        println!("   ✅ Service added to default runlevel & started.");
+fn unit_key(line: &str) -> Option<&str> {
+    let l = line.trim();
+    if l.starts_with('#') || l.starts_with(';') {
+        return None;
     }
+    l.split_once('=').map(|(k, _)| k.trim())
 }
 
 fn find_binary(bin: &str) -> Option<String> {
@@ -650,7 +4409,7 @@ fn find_binary(bin: &str) -> Option<String> {
     })
 }
 
-fn setup_doas(rtc: &str, net: &str) {
+fn setup_doas(rtc: &str, net: &str) -> Result<(), PortalError> {
     println!("🦅 Configuring Doas...");
     let r1 = format!("permit nopass :{} cmd {}", GROUP_NAME, rtc);
     let r2 = format!("permit nopass :{} cmd {}", GROUP_NAME, net);
@@ -663,22 +4422,743 @@ fn setup_doas(rtc: &str, net: &str) {
         c.push_str(&format!("{}\n", r2));
     }
 
-    fs::write(DOAS_CONF, c).unwrap();
+    sys_write(DOAS_CONF, &c)
 }
 
-fn setup_sudo(rtc: &str, net: &str) {
+fn setup_sudo(rtc: &str, net: &str) -> Result<(), PortalError> {
     println!("🐧 Configuring Sudo...");
     let r = format!("%{} ALL=(root) NOPASSWD: {}, {}\n", GROUP_NAME, rtc, net);
-    let t = "/tmp/portal_check";
-    fs::write(t, r).unwrap();
-
-    if Command::new("visudo")
-        .args(["-c", "-f", t])
+    if dry_run() {
+        print!("   would write {}: {}", SUDOERS_FILE, r);
+        return Ok(());
+    }
+    // Рядом с целью, чтобы переименование было атомарным; файлы с точкой
+    // в имени sudo из sudoers.d не читает
+    let t = format!("{}.tmp", SUDOERS_FILE);
+    fs::write(&t, r).map_err(|e| PortalError::write(&t, e))?;
+    fs::set_permissions(&t, fs::Permissions::from_mode(0o440))
+        .map_err(|e| PortalError::write(&t, e))?;
+    let checked = Command::new("visudo")
+        .args(["-c", "-q", "-f", &t])
         .status()
-        .unwrap()
-        .success()
-    {
-        fs::set_permissions(t, fs::Permissions::from_mode(0o440)).unwrap();
-        Command::new("mv").args([t, SUDOERS_FILE]).status().unwrap();
+        .map_err(|source| PortalError::Tool {
+            name: "visudo".into(),
+            source,
+        });
+    if !checked.as_ref().is_ok_and(|s| s.success()) {
+        fs::remove_file(&t).ok();
+        checked?;
+        return Err(PortalError::Rejected {
+            what: "visudo",
+            detail: format!("generated rule for {}, sudo left unchanged", SUDOERS_FILE),
+        });
+    }
+    fs::rename(&t, SUDOERS_FILE).map_err(|e| PortalError::write(SUDOERS_FILE, e))
+}
+
+// === УДАЛЕНИЕ ===
+// Обратное --install: служба, бинарник, правила sudo/doas, политика D-Bus,
+// группа (с --user - только свои unit и бинарник). Конфиг и история
+// остаются (переустановка их подхватит), если не попросили --purge
+fn run_uninstall(purge: bool) -> Result<(), PortalError> {
+    if dry_run() {
+        println!("🔎 Uninstall preview, nothing is changed:");
+    } else {
+        println!("🧹 Starting UNINSTALL...");
+        if !paths::user() && !is_root() {
+            return Err(PortalError::NotRoot("Uninstall"));
+        }
+    }
+    if paths::user() {
+        let (binary, unit) = user_install_paths();
+        if Path::new(&unit).exists() {
+            try_run(&["systemctl", "--user", "disable", "--now", "portal"]);
+            sys_remove(&unit);
+            try_run(&["systemctl", "--user", "daemon-reload"]);
+        }
+        sys_remove(&binary);
+        uninstall_docs(&user_share_dir());
+    } else {
+        uninstall_system();
+    }
+
+    // 3. Данные
+    if purge {
+        sys_remove(paths::config_dir());
+        sys_remove(paths::state_dir());
+        sys_remove(paths::run_dir());
+    }
+
+    if dry_run() {
+        println!("\n👉 Run without --dry-run to apply.");
+    } else {
+        println!("\n🎉 UNINSTALL COMPLETE!");
+        if !purge {
+            println!(
+                "👉 Config kept in {} (--purge removes it).",
+                paths::config_dir()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn uninstall_system() {
+    // 1. Служба: сначала остановить, потом удалять файлы
+    if Path::new(SYSTEMD_UNIT).exists() {
+        try_run(&["systemctl", "disable", "--now", "portal"]);
+        sys_remove(SYSTEMD_UNIT);
+        try_run(&["systemctl", "daemon-reload"]);
+    }
+    if Path::new(OPENRC_SCRIPT).exists() {
+        try_run(&["rc-service", "portal", "stop"]);
+        try_run(&["rc-update", "del", "portal", "default"]);
+        sys_remove(OPENRC_SCRIPT);
+    }
+    let (runit, service_dir) = runit_dirs();
+    if Path::new(&runit).exists() {
+        try_run(&["sv", "down", "portal"]);
+        sys_remove(&format!("{}/portal", service_dir));
+        sys_remove(&runit);
+    }
+    let s6 = format!("{}/portal", S6_SOURCES);
+    if Path::new(&s6).exists() {
+        try_run(&["s6-rc", "-d", "change", "portal"]);
+        try_run(&["s6-service", "delete", "default", "portal"]);
+        sys_remove(&s6);
+        try_run(&["s6-db-reload"]);
+    }
+    if Path::new(DINIT_SERVICE).exists() {
+        try_run(&["dinitctl", "disable", "portal"]);
+        sys_remove(DINIT_SERVICE);
+    }
+    if Path::new(freebsd::RC_SCRIPT).exists() {
+        try_run(&["service", "portal", "stop"]);
+        try_run(&["sysrc", "-x", "portal_enable"]);
+        sys_remove(freebsd::RC_SCRIPT);
+    }
+
+    // 2. Бинарник, дополнение и права
+    sys_remove(BINARY_DEST);
+    uninstall_docs(SHARE_DIR);
+    sys_remove(SUDOERS_FILE);
+    unset_doas();
+    sys_remove(dbus::POLICY_FILE);
+    // Учетные записи - только созданные нашей установкой: одноименные
+    // чужие (был свой portal до нас) не трогаем
+    let marker = paths::config(CREATED_ACCOUNTS);
+    let created = fs::read_to_string(&marker).unwrap_or_default();
+    let ours = |kind: &str, name: &str| created.lines().any(|l| l == format!("{} {}", kind, name));
+    if !freebsd::is() && privsep::user_exists(RUN_AS_USER) {
+        if ours("user", RUN_AS_USER) {
+            try_run(&["userdel", RUN_AS_USER]);
+        } else {
+            println!(
+                "   ⏭  Kept user {}: it was not created by --install",
+                RUN_AS_USER
+            );
+        }
+    }
+    if group_exists(GROUP_NAME) {
+        if !ours("group", GROUP_NAME) {
+            println!(
+                "   ⏭  Kept group {}: it was not created by --install",
+                GROUP_NAME
+            );
+        } else if freebsd::is() {
+            try_run(&["pw", "groupdel", GROUP_NAME]);
+        } else {
+            try_run(&["groupdel", GROUP_NAME]);
+        }
+    }
+    sys_remove(&marker);
+}
+
+fn group_exists(name: &str) -> bool {
+    Command::new("getent")
+        .args(["group", name])
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+// "user portal" / "group portal-admins" - для uninstall_system
+fn remember_created(kind: &str, name: &str) {
+    if dry_run() {
+        return;
+    }
+    let path = paths::config(CREATED_ACCOUNTS);
+    let mut created = fs::read_to_string(&path).unwrap_or_default();
+    let line = format!("{} {}", kind, name);
+    if created.lines().any(|l| l == line) {
+        return;
+    }
+    created.push_str(&line);
+    created.push('\n');
+    fs::create_dir_all(paths::config_dir()).ok();
+    if let Err(e) = fs::write(&path, created) {
+        eprintln!("   ⚠️  {}: {}", path, e);
+    }
+}
+
+// Удаление идет до конца: чего-то нет - сказать и продолжить
+fn try_run(cmd: &[&str]) {
+    if let Err(e) = sys_run(cmd) {
+        eprintln!("   ⚠️  {}", e);
+    }
+}
+
+// Из doas.conf - только строки, добавленные setup_doas
+fn unset_doas() {
+    let Ok(c) = fs::read_to_string(DOAS_CONF) else {
+        return;
+    };
+    let ours = format!("permit nopass :{} cmd ", GROUP_NAME);
+    let (drop, keep): (Vec<&str>, Vec<&str>) = c.lines().partition(|l| l.starts_with(&ours));
+    if drop.is_empty() {
+        return;
+    }
+    if dry_run() {
+        for l in drop {
+            println!("   would drop from {}: {}", DOAS_CONF, l);
+        }
+        return;
+    }
+    let mut out = keep.join("\n").trim_end().to_string();
+    out.push('\n');
+    match fs::write(DOAS_CONF, out) {
+        Ok(()) => println!("   🦅 Removed portal rules from {}", DOAS_CONF),
+        Err(e) => eprintln!("   ❌ Failed to update {}: {}", DOAS_CONF, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::ops::Range;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    // 14.10.2026 - время подставной машины идет только в ожиданиях и во сне
+    const START: u64 = 1_791_936_000;
+
+    #[derive(Default)]
+    struct World {
+        secs: u64,
+        // Маяк молчит в этот отрезок (секунды от начала теста)
+        dark: Range<u64>,
+        paused_from: Option<u64>,
+        // Сколько проверок guards подряд откладывают сон
+        blocked: u32,
+        probes: u32,
+        sleeps: Vec<u64>,
+        shutdowns: u32,
+        // Маяк переехал сюда: отвечает только по этому адресу, mDNS его находит
+        moved_to: Option<String>,
+        // Молчание маяка - перезагрузка роутера (snmp)
+        rebooting: bool,
+        // Номера проб (с 1), на которые маяк отвечает и в темноте
+        answers: Vec<u32>,
+        // [c] в терминале на этой секунде
+        check_at: Option<u64>,
+    }
+
+    #[derive(Clone)]
+    struct Sim(Rc<RefCell<World>>, Instant);
+
+    impl system::Prober for Sim {
+        fn probe(&mut self, cfg: &PortalConfig) -> probe::ProbeResult {
+            let mut w = self.0.borrow_mut();
+            w.probes += 1;
+            let ok = (!w.dark.contains(&w.secs) || w.answers.contains(&w.probes))
+                && w.moved_to
+                    .as_ref()
+                    .is_none_or(|ip| *ip == cfg.lighthouse_ip);
+            probe::ProbeResult {
+                ok,
+                rebooting: !ok && w.rebooting,
+                ..Default::default()
+            }
+        }
+    }
+
+    impl system::PowerManager for Sim {
+        fn paused(&mut self) -> bool {
+            let w = self.0.borrow();
+            w.paused_from.is_some_and(|p| w.secs >= p)
+        }
+
+        fn blocker(&mut self, _: &PortalConfig) -> Option<guards::Blocker> {
+            let mut w = self.0.borrow_mut();
+            (w.blocked > 0).then(|| {
+                w.blocked -= 1;
+                guards::Blocker {
+                    guard: "test",
+                    reason: "busy".into(),
+                }
+            })
+        }
+
+        fn sleep(&mut self, _: &PortalConfig, seconds: u64, _: &str) -> bool {
+            let mut w = self.0.borrow_mut();
+            w.sleeps.push(seconds);
+            w.secs += seconds;
+            true
+        }
+
+        fn shut_down(&mut self) -> bool {
+            self.0.borrow_mut().shutdowns += 1;
+            true
+        }
+    }
+
+    impl system::Clock for Sim {
+        fn now(&self) -> u64 {
+            START + self.0.borrow().secs
+        }
+
+        fn instant(&self) -> Instant {
+            self.1 + Duration::from_secs(self.0.borrow().secs)
+        }
+
+        fn sleep(&mut self, d: Duration) {
+            self.0.borrow_mut().secs += d.as_secs();
+        }
+
+        fn wait(&mut self, d: Duration, _: &str, _: &str) -> Option<tui::Key> {
+            let mut w = self.0.borrow_mut();
+            let end = w.secs + d.as_secs().max(1);
+            if let Some(at) = w.check_at.filter(|&at| at > w.secs && at <= end) {
+                w.check_at = None;
+                w.secs = at;
+                return Some(tui::Key::Check);
+            }
+            w.secs = end;
+            None
+        }
+    }
+
+    impl system::NetworkScanner for Sim {
+        fn ssid(&mut self, _: net::Backend) -> Option<String> {
+            None
+        }
+
+        fn gateway(&mut self, _: net::Backend, _: &str) -> Option<String> {
+            None
+        }
+
+        fn lighthouse(&mut self, _: &str) -> Option<String> {
+            self.0.borrow().moved_to.clone()
+        }
+    }
+
+    fn daemon(
+        world: World,
+    ) -> (
+        Daemon<Sim, Sim, Sim, Sim>,
+        Sim,
+        mpsc::Receiver<events::Event>,
+    ) {
+        let sim = Sim(Rc::new(RefCell::new(world)), Instant::now());
+        let cfg = PortalConfig {
+            lighthouse_ip: "192.0.2.1".into(),
+            grace_period_sec: 120,
+            sleep_minutes: 30,
+            wakeup_wait_sec: 10,
+            inhibit_recheck_sec: 300,
+            low_battery_percent: 0,
+            rtc_drift_alert_sec: 0,
+            ..Default::default()
+        };
+        let (tx, rx) = mpsc::channel();
+        let mut bus = events::Bus::default();
+        bus.subscribe(tx);
+        let d = Daemon {
+            resume_grace: None,
+            dark_wakes: 0,
+            router_rebooting: false,
+            cadence: timers::Adaptive::default(),
+            last_interval: cfg.scan_interval_sec,
+            outages: outages::OutageSchedule::new(&cfg.outage_schedule),
+            cfg,
+            t: Locales::new(Language::En),
+            tz: schedule::TimeZone::utc(),
+            bus,
+            quiet: Vec::new(),
+            profiles: None,
+            prober: sim.clone(),
+            power: sim.clone(),
+            clock: sim.clone(),
+            net: sim.clone(),
+        };
+        (d, sim, rx)
+    }
+
+    fn always_dark() -> Range<u64> {
+        0..u64::MAX
+    }
+
+    #[test]
+    fn light_keeps_monitoring() {
+        let (mut d, sim, rx) = daemon(World::default());
+        for _ in 0..3 {
+            d.step();
+        }
+        let w = sim.0.borrow();
+        assert_eq!(w.probes, 3);
+        assert!(w.sleeps.is_empty());
+        assert_eq!(d.bus.phase(), state::Phase::Monitoring);
+        assert!(
+            !rx.try_iter()
+                .any(|e| matches!(e, events::Event::ConnectionLost { .. }))
+        );
+    }
+
+    #[test]
+    fn sleeps_after_grace() {
+        let (mut d, sim, rx) = daemon(World {
+            dark: always_dark(),
+            ..Default::default()
+        });
+        d.step();
+        let w = sim.0.borrow();
+        assert_eq!(w.sleeps, [1800]);
+        assert!(w.secs >= 1800 + 10);
+        let events: Vec<_> = rx.try_iter().collect();
+        assert!(
+            events
+                .iter()
+                .any(|e| matches!(e, events::Event::ConnectionLost { grace_sec: 120 }))
+        );
+        assert!(events.iter().any(|e| matches!(
+            e,
+            events::Event::Woke {
+                early_by_sec: 0,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn light_after_wake_skips_the_wait() {
+        let (mut d, sim, _rx) = daemon(World {
+            dark: 0..1000,
+            ..Default::default()
+        });
+        d.cfg.wakeup_wait_sec = 600;
+        d.step();
+        let w = sim.0.borrow();
+        assert_eq!(w.sleeps, [1800]);
+        assert!(w.secs < 1800 + 600);
+        assert_eq!(d.dark_wakes, 0);
+    }
+
+    #[test]
+    fn light_back_during_grace_cancels_sleep() {
+        let (mut d, sim, rx) = daemon(World {
+            dark: 0..20,
+            ..Default::default()
+        });
+        d.step();
+        assert!(sim.0.borrow().sleeps.is_empty());
+        assert_eq!(d.bus.phase(), state::Phase::Monitoring);
+        assert!(
+            rx.try_iter()
+                .any(|e| matches!(e, events::Event::ConnectionRestored))
+        );
+    }
+
+    #[test]
+    fn check_during_grace_decides_once() {
+        // Маяк ответил на [c] посреди grace: свет есть, без второго вопроса
+        let (mut d, sim, rx) = daemon(World {
+            dark: always_dark(),
+            answers: vec![2],
+            check_at: Some(30),
+            ..Default::default()
+        });
+        d.step();
+        assert_eq!(sim.0.borrow().probes, 2);
+        assert!(sim.0.borrow().sleeps.is_empty());
+        assert!(
+            rx.try_iter()
+                .any(|e| matches!(e, events::Event::ConnectionRestored))
+        );
+        // Не ответил - grace идет до конца
+        let (mut d, sim, _rx) = daemon(World {
+            dark: always_dark(),
+            check_at: Some(30),
+            ..Default::default()
+        });
+        d.step();
+        let w = sim.0.borrow();
+        assert_eq!(w.sleeps, [1800]);
+        assert!(w.secs >= 120 + 1800 + 10);
+    }
+
+    #[test]
+    fn pause_stops_probing() {
+        let (mut d, sim, _rx) = daemon(World {
+            dark: always_dark(),
+            paused_from: Some(0),
+            ..Default::default()
+        });
+        d.step();
+        d.step();
+        let w = sim.0.borrow();
+        assert_eq!(w.probes, 0);
+        assert!(w.sleeps.is_empty());
+        assert_eq!(d.bus.phase(), state::Phase::Paused);
+    }
+
+    #[test]
+    fn pause_during_grace_cancels_sleep() {
+        let (mut d, sim, _rx) = daemon(World {
+            dark: always_dark(),
+            paused_from: Some(1),
+            ..Default::default()
+        });
+        d.step();
+        assert!(sim.0.borrow().sleeps.is_empty());
+        d.step();
+        assert_eq!(d.bus.phase(), state::Phase::Paused);
+    }
+
+    #[test]
+    fn guards_postpone_sleep_with_rechecks() {
+        let (mut d, sim, rx) = daemon(World {
+            dark: always_dark(),
+            blocked: 3,
+            ..Default::default()
+        });
+        d.step();
+        let w = sim.0.borrow();
+        assert_eq!(w.sleeps, [1800]);
+        assert!(w.secs >= 3 * 300 + 1800);
+        // Блокировка одной защитой считается один раз за попытку уснуть
+        let blocked = rx
+            .try_iter()
+            .filter(|e| matches!(e, events::Event::SleepBlocked { guard: "test", .. }))
+            .count();
+        assert_eq!(blocked, 1);
+    }
+
+    #[test]
+    fn light_back_while_postponed_cancels_sleep() {
+        let (mut d, sim, rx) = daemon(World {
+            dark: 0..400,
+            blocked: 10,
+            ..Default::default()
+        });
+        d.step();
+        assert!(sim.0.borrow().sleeps.is_empty());
+        assert_eq!(d.bus.phase(), state::Phase::Monitoring);
+        assert!(
+            rx.try_iter()
+                .any(|e| matches!(e, events::Event::ConnectionRestored))
+        );
+    }
+
+    #[test]
+    fn wake_times_replace_sleep_minutes() {
+        let (mut d, sim, _rx) = daemon(World {
+            dark: always_dark(),
+            ..Default::default()
+        });
+        // START - полночь UTC: после grace спим ровно до 00:30, затем wakeup_wait
+        d.cfg.wake_times = vec!["every 30m".into()];
+        d.step();
+        let w = sim.0.borrow();
+        assert_eq!(w.sleeps.len(), 1);
+        assert!(w.sleeps[0] < 1800);
+        assert_eq!(w.secs, 1800 + 10);
+    }
+
+    #[test]
+    fn resleep_climbs_the_ladder_then_gives_up() {
+        let (mut d, sim, rx) = daemon(World {
+            dark: always_dark(),
+            ..Default::default()
+        });
+        d.cfg.resleep_minutes = vec![15, 60];
+        d.cfg.give_up_after_wakes = 4;
+        for _ in 0..5 {
+            d.step();
+        }
+        let w = sim.0.borrow();
+        assert_eq!(w.sleeps, [1800, 900, 3600, 3600]);
+        assert_eq!(w.shutdowns, 1);
+        assert!(
+            rx.try_iter()
+                .any(|e| matches!(e, events::Event::GaveUp { dark_wakes: 4 }))
+        );
+    }
+
+    #[test]
+    fn leader_announces_its_real_period() {
+        let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let announced = |d: &mut Daemon<Sim, Sim, Sim, Sim>| {
+            d.step();
+            let mut buf = [0u8; 512];
+            let n = peer.recv(&mut buf).unwrap();
+            let msg = String::from_utf8_lossy(&buf[..n]).to_string();
+            let f: Vec<&str> = msg.split_whitespace().collect();
+            f[f.iter().position(|w| *w == "WAKE_AT").unwrap() + 2].to_string()
+        };
+        let (mut d, _sim, _rx) = daemon(World {
+            dark: always_dark(),
+            ..Default::default()
+        });
+        d.cfg.fleet_role = fleet::Role::Leader;
+        d.cfg.fleet_peers = vec![peer.local_addr().unwrap().to_string()];
+        // Без лестницы - ровная сетка sleep_minutes
+        assert_eq!(announced(&mut d), "1800");
+        // Лестница 15, 60: 30 мин -> 15 -> 60 -> 60; сетка ровная с третьего сна
+        d.dark_wakes = 0;
+        d.cfg.resleep_minutes = vec![15, 60];
+        assert_eq!(announced(&mut d), "0");
+        assert_eq!(announced(&mut d), "0");
+        assert_eq!(announced(&mut d), "3600");
+    }
+
+    #[test]
+    fn light_resets_the_resleep_ladder() {
+        let (mut d, sim, _rx) = daemon(World {
+            dark: 0..2500,
+            ..Default::default()
+        });
+        d.cfg.resleep_minutes = vec![15];
+        // Сон 30 мин, подъем - света нет: следующий сон на ступень 15 мин
+        d.step();
+        assert_eq!(d.dark_wakes, 1);
+        // После него маяк отвечает: лестница сначала
+        d.step();
+        assert_eq!(d.dark_wakes, 0);
+        assert_eq!(sim.0.borrow().sleeps, [1800, 900]);
+    }
+
+    #[test]
+    fn moved_lighthouse_is_found_by_name() {
+        let (mut d, sim, _rx) = daemon(World {
+            moved_to: Some("192.0.2.7".into()),
+            ..Default::default()
+        });
+        // Без имени искать нечего: теряем маяк и засыпаем
+        d.step();
+        assert_eq!(sim.0.borrow().sleeps.len(), 1);
+        d.cfg.lighthouse.name = "pi".into();
+        d.step();
+        assert_eq!(d.cfg.lighthouse_ip, "192.0.2.7");
+        assert_eq!(sim.0.borrow().sleeps.len(), 1);
+        assert_eq!(d.bus.phase(), state::Phase::Monitoring);
+    }
+
+    #[test]
+    fn router_reboot_gets_its_own_grace() {
+        let lost = |rx: &mpsc::Receiver<events::Event>| {
+            rx.try_iter().find_map(|e| match e {
+                events::Event::ConnectionLost { grace_sec } => Some(grace_sec),
+                _ => None,
+            })
+        };
+        // Роутер перезагружается 5 минут: дольше обычного grace, но не сна
+        let (mut d, sim, rx) = daemon(World {
+            dark: 0..300,
+            rebooting: true,
+            ..Default::default()
+        });
+        d.cfg.snmp.reboot_grace_sec = 600;
+        d.step();
+        assert_eq!(lost(&rx), Some(600));
+        assert!(sim.0.borrow().sleeps.is_empty());
+        // Порт без ИБП погас - света нет, обычный grace и сон
+        let (mut d, sim, rx) = daemon(World {
+            dark: 0..300,
+            ..Default::default()
+        });
+        d.cfg.snmp.reboot_grace_sec = 600;
+        d.step();
+        assert_eq!(lost(&rx), Some(120));
+        assert_eq!(sim.0.borrow().sleeps.len(), 1);
+    }
+
+    #[test]
+    fn boot_after_poweroff_is_a_wake() {
+        let (mut d, sim, rx) = daemon(World {
+            dark: always_dark(),
+            ..Default::default()
+        });
+        // Выключились 10 минут назад с будильником на 30
+        d.woke_from_poweroff(state::PowerOff {
+            at: START - 600,
+            wake_at: START + 1200,
+        });
+        assert!(sim.0.borrow().sleeps.is_empty());
+        let woke = rx.try_iter().find_map(|e| match e {
+            events::Event::Woke {
+                slept_sec,
+                early_by_sec,
+                cause,
+                ..
+            } => Some((slept_sec, early_by_sec, cause)),
+            _ => None,
+        });
+        assert_eq!(
+            woke,
+            Some((600, 1200, Some("booted before the alarm".to_string())))
+        );
+        // Дальше - обычный цикл: grace и снова сон
+        d.step();
+        assert_eq!(sim.0.borrow().sleeps, [1800]);
+    }
+
+    #[test]
+    fn config_migrates_old_versions() {
+        let old = r#"{"lighthouse_ip": "10.0.0.1", "scan_interval_sec": 30, "ping_sise": 64}"#;
+        let l = parse_config(old).unwrap();
+        assert_eq!(l.from_version, 0);
+        assert_eq!(l.cfg.version, CONFIG_VERSION);
+        assert_eq!(l.cfg.lighthouse_ip, "10.0.0.1");
+        assert_eq!(l.unknown, vec!["ping_sise".to_string()]);
+        let current = serde_json::to_string(&PortalConfig::default()).unwrap();
+        let l = parse_config(&current).unwrap();
+        assert_eq!((l.from_version, l.unknown.len()), (CONFIG_VERSION, 0));
+        assert!(parse_config("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn unit_merge_updates_untouched_keys() {
+        let base = "[Unit]\nAfter=network.target\n\n[Service]\nExecStart=/usr/bin/portal_daemon\nCapabilityBoundingSet=CAP_NET_RAW\nNice=5\n";
+        // Пользователь: свой After=, убрал Nice=, добавил Environment= и секцию
+        let old = "[Unit]\nAfter=network-online.target\n\n[Service]\nExecStart=/usr/bin/portal_daemon\nCapabilityBoundingSet=CAP_NET_RAW\nEnvironment=A=1\n\n[Install]\nWantedBy=multi-user.target\n";
+        // Новая версия: другой ExecStart= и CapabilityBoundingSet=, свой After=
+        let new = "[Unit]\nAfter=network.target nss-lookup.target\n\n[Service]\nExecStart=/usr/local/bin/portal_daemon\nCapabilityBoundingSet=CAP_NET_RAW CAP_NET_BIND_SERVICE\nNice=5\n";
+        let (merged, conflicts) = merge_unit(base, old, new);
+        assert_eq!(
+            merged,
+            "[Unit]\nAfter=network-online.target\n\n[Service]\nExecStart=/usr/local/bin/portal_daemon\nCapabilityBoundingSet=CAP_NET_RAW CAP_NET_BIND_SERVICE\nEnvironment=A=1\n\n[Install]\nWantedBy=multi-user.target\n"
+        );
+        assert_eq!(conflicts, ["After"]);
+    }
+
+    #[test]
+    fn adaptive_interval_bounds_are_optional() {
+        let mut cfg = PortalConfig {
+            scan_interval_max_sec: 600,
+            ..Default::default()
+        };
+        assert_eq!(cfg.validate(), Ok(()));
+        cfg.scan_interval_min_sec = 1;
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn language_from_locale() {
+        assert_eq!(Language::from_locale("uk_UA.UTF-8"), Some(Language::Uk));
+        assert_eq!(Language::from_locale("ru_RU@euro"), Some(Language::Ru));
+        assert_eq!(Language::from_locale("en_US.UTF-8"), Some(Language::En));
+        assert_eq!(Language::from_locale("C.UTF-8"), None);
+        assert_eq!(Language::from_locale("de_DE"), None);
     }
 }
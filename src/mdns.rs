@@ -0,0 +1,370 @@
+// === mDNS: ПОИСК МАЯКОВ (_portal._udp.local) ===
+// `portal_daemon lighthouse` отвечает на PTR-запрос своей службы записями
+// PTR + SRV (порт) + TXT + A - хватает и для `avahi-browse -r _portal._udp`.
+// Демон и мастер спрашивают с случайного порта (QU, "legacy unicast"):
+// ответ приходит прямо на этот сокет, порт 5353 у avahi не отнимаем.
+// Маяк по DHCP сменил адрес - демон находит его по имени (lighthouse.name).
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::os::fd::FromRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::net;
+
+pub const SERVICE: &str = "_portal._udp.local";
+const GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const PORT: u16 = 5353;
+const TTL: u32 = 120;
+const A: u16 = 1;
+const PTR: u16 = 12;
+const TXT: u16 = 16;
+const SRV: u16 = 33;
+const ANY: u16 = 255;
+// Уникальные записи (SRV/TXT/A): бит cache-flush в классе
+const IN: u16 = 1;
+const FLUSH: u16 = 0x8000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    // Имя экземпляра: hostname маяка
+    pub name: String,
+    pub ip: String,
+    pub port: u16,
+}
+
+// --- ЗАПРОС ---
+pub fn discover(wait: Duration) -> Vec<Found> {
+    let Ok(sock) = UdpSocket::bind(("0.0.0.0", 0)) else {
+        return Vec::new();
+    };
+    let mut q = header(0, 0, 1, 0);
+    put_name(&mut q, SERVICE);
+    q.extend_from_slice(&PTR.to_be_bytes());
+    q.extend_from_slice(&(IN | FLUSH).to_be_bytes());
+    if sock.send_to(&q, (GROUP, PORT)).is_err() {
+        return Vec::new();
+    }
+    let started = Instant::now();
+    let mut found: Vec<Found> = Vec::new();
+    let mut buf = [0u8; 1500];
+    while let Some(left) = wait.checked_sub(started.elapsed()) {
+        sock.set_read_timeout(Some(left.max(Duration::from_millis(1))))
+            .ok();
+        let Ok((n, from)) = sock.recv_from(&mut buf) else {
+            break;
+        };
+        for f in parse_answer(&buf[..n], from.ip()) {
+            if !found.contains(&f) {
+                found.push(f);
+            }
+        }
+    }
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    found
+}
+
+// Экземпляры из PTR, порт из SRV, адрес из A (нет A - адрес отправителя)
+fn parse_answer(msg: &[u8], from: IpAddr) -> Vec<Found> {
+    let Some(records) = records(msg) else {
+        return Vec::new();
+    };
+    let (mut instances, mut srv, mut addr) = (Vec::new(), HashMap::new(), HashMap::new());
+    for r in records {
+        match r.data {
+            Data::Ptr(target) if r.name.eq_ignore_ascii_case(SERVICE) => instances.push(target),
+            Data::Srv(port, target) => {
+                srv.insert(r.name.to_ascii_lowercase(), (port, target));
+            }
+            Data::A(ip) => {
+                addr.insert(r.name.to_ascii_lowercase(), ip);
+            }
+            _ => {}
+        }
+    }
+    instances
+        .into_iter()
+        .filter_map(|inst| {
+            let (port, target) = srv.get(&inst.to_ascii_lowercase())?;
+            let ip = addr
+                .get(&target.to_ascii_lowercase())
+                .map_or(from, |ip| IpAddr::V4(*ip));
+            let name = inst.strip_suffix(&format!(".{}", SERVICE))?.to_string();
+            Some(Found {
+                name,
+                ip: ip.to_string(),
+                port: *port,
+            })
+        })
+        .collect()
+}
+
+// --- ОТВЕТЧИК НА МАЯКЕ ---
+pub fn spawn_responder(host: &str, port: u16) -> Result<(), String> {
+    let sock = shared_socket().map_err(|e| format!("mDNS UDP {}: {}", PORT, e))?;
+    sock.join_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED)
+        .map_err(|e| format!("mDNS group: {}", e))?;
+    sock.set_multicast_ttl_v4(255).ok();
+    let host = host.to_string();
+    thread::spawn(move || {
+        let mut buf = [0u8; 1500];
+        loop {
+            let Ok((n, from)) = sock.recv_from(&mut buf) else {
+                continue;
+            };
+            let Some(id) = asks_for_service(&buf[..n]) else {
+                continue;
+            };
+            let IpAddr::V4(src) = from.ip() else {
+                continue;
+            };
+            // Адрес того интерфейса, откуда видно спросившего
+            let Some(IpAddr::V4(ip)) = net::route_src(IpAddr::V4(src)) else {
+                continue;
+            };
+            // Спросили не с 5353 - отвечаем ему напрямую, с его id и вопросом
+            let legacy = from.port() != PORT;
+            let reply = answer(&host, port, ip, legacy.then_some(id));
+            let to = if legacy {
+                from
+            } else {
+                SocketAddr::from((GROUP, PORT))
+            };
+            sock.send_to(&reply, to).ok();
+        }
+    });
+    Ok(())
+}
+
+// 5353 обычно уже у avahi: делим порт через SO_REUSEADDR/SO_REUSEPORT
+fn shared_socket() -> std::io::Result<UdpSocket> {
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let sock = UdpSocket::from_raw_fd(fd);
+        let one: libc::c_int = 1;
+        for opt in [libc::SO_REUSEADDR, libc::SO_REUSEPORT] {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                opt,
+                &one as *const _ as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            );
+        }
+        let addr = libc::sockaddr_in {
+            #[cfg(target_os = "freebsd")]
+            sin_len: std::mem::size_of::<libc::sockaddr_in>() as u8,
+            sin_family: libc::AF_INET as libc::sa_family_t,
+            sin_port: PORT.to_be(),
+            sin_addr: libc::in_addr { s_addr: 0 },
+            sin_zero: [0; 8],
+        };
+        if libc::bind(
+            fd,
+            &addr as *const _ as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        ) < 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(sock)
+    }
+}
+
+// Some(id) - запрос с вопросом PTR (или ANY) о нашей службе
+fn asks_for_service(msg: &[u8]) -> Option<u16> {
+    let (id, flags, qd) = (be16(msg, 0)?, be16(msg, 2)?, be16(msg, 4)?);
+    if flags & 0x8000 != 0 {
+        return None;
+    }
+    let mut pos = 12;
+    for _ in 0..qd {
+        let name = read_name(msg, &mut pos)?;
+        let qtype = be16(msg, pos)?;
+        pos += 4;
+        if name.eq_ignore_ascii_case(SERVICE) && (qtype == PTR || qtype == ANY) {
+            return Some(id);
+        }
+    }
+    None
+}
+
+fn answer(host: &str, port: u16, ip: Ipv4Addr, legacy_id: Option<u16>) -> Vec<u8> {
+    let instance = format!("{}.{}", host, SERVICE);
+    let target = format!("{}.local", host);
+    let mut m = header(
+        legacy_id.unwrap_or(0),
+        0x8400,
+        legacy_id.is_some() as u16,
+        4,
+    );
+    if legacy_id.is_some() {
+        put_name(&mut m, SERVICE);
+        m.extend_from_slice(&PTR.to_be_bytes());
+        m.extend_from_slice(&IN.to_be_bytes());
+    }
+    let mut rdata = Vec::new();
+    put_name(&mut rdata, &instance);
+    put_record(&mut m, SERVICE, PTR, IN, &rdata);
+    let mut rdata = vec![0, 0, 0, 0];
+    rdata.extend_from_slice(&port.to_be_bytes());
+    put_name(&mut rdata, &target);
+    put_record(&mut m, &instance, SRV, IN | FLUSH, &rdata);
+    put_record(&mut m, &instance, TXT, IN | FLUSH, b"\x03v=1");
+    put_record(&mut m, &target, A, IN | FLUSH, &ip.octets());
+    m
+}
+
+// --- ФОРМАТ DNS (RFC 1035) ---
+fn header(id: u16, flags: u16, qd: u16, an: u16) -> Vec<u8> {
+    [id, flags, qd, an, 0, 0]
+        .iter()
+        .flat_map(|v| v.to_be_bytes())
+        .collect()
+}
+
+fn put_name(m: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        let l = &label.as_bytes()[..label.len().min(63)];
+        m.push(l.len() as u8);
+        m.extend_from_slice(l);
+    }
+    m.push(0);
+}
+
+fn put_record(m: &mut Vec<u8>, name: &str, kind: u16, class: u16, rdata: &[u8]) {
+    put_name(m, name);
+    m.extend_from_slice(&kind.to_be_bytes());
+    m.extend_from_slice(&class.to_be_bytes());
+    m.extend_from_slice(&TTL.to_be_bytes());
+    m.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    m.extend_from_slice(rdata);
+}
+
+fn be16(m: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*m.get(at)?, *m.get(at + 1)?]))
+}
+
+// Имя со сжатием (ссылки назад); ссылок не больше, чем байтов в пакете
+fn read_name(m: &[u8], pos: &mut usize) -> Option<String> {
+    let (mut labels, mut at, mut jumps) = (Vec::new(), *pos, 0);
+    loop {
+        let len = *m.get(at)? as usize;
+        match len {
+            0 => {
+                if jumps == 0 {
+                    *pos = at + 1;
+                }
+                return Some(labels.join("."));
+            }
+            l if l & 0xc0 == 0xc0 => {
+                let target = (be16(m, at)? & 0x3fff) as usize;
+                if jumps == 0 {
+                    *pos = at + 2;
+                }
+                jumps += 1;
+                if jumps > m.len() {
+                    return None;
+                }
+                at = target;
+            }
+            l => {
+                let label = m.get(at + 1..at + 1 + l)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                at += 1 + l;
+            }
+        }
+    }
+}
+
+enum Data {
+    Ptr(String),
+    Srv(u16, String),
+    A(Ipv4Addr),
+    Other,
+}
+
+struct Record {
+    name: String,
+    data: Data,
+}
+
+// Ответы и дополнительные записи подряд; вопросы пропускаем
+fn records(m: &[u8]) -> Option<Vec<Record>> {
+    if be16(m, 2)? & 0x8000 == 0 {
+        return None;
+    }
+    let qd = be16(m, 4)?;
+    let total = be16(m, 6)? as usize + be16(m, 8)? as usize + be16(m, 10)? as usize;
+    let mut pos = 12;
+    for _ in 0..qd {
+        read_name(m, &mut pos)?;
+        pos += 4;
+    }
+    let mut out = Vec::new();
+    for _ in 0..total {
+        let name = read_name(m, &mut pos)?;
+        let kind = be16(m, pos)?;
+        let len = be16(m, pos + 8)? as usize;
+        let start = pos + 10;
+        m.get(start..start + len)?;
+        let data = match kind {
+            PTR => Data::Ptr(read_name(m, &mut start.clone())?),
+            SRV => Data::Srv(be16(m, start + 4)?, read_name(m, &mut (start + 6))?),
+            A if len == 4 => Data::A(Ipv4Addr::new(
+                m[start],
+                m[start + 1],
+                m[start + 2],
+                m[start + 3],
+            )),
+            _ => Data::Other,
+        };
+        out.push(Record { name, data });
+        pos = start + len;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answer_round_trips_through_the_parser() {
+        let mut q = header(7, 0, 1, 0);
+        put_name(&mut q, SERVICE);
+        q.extend_from_slice(&PTR.to_be_bytes());
+        q.extend_from_slice(&(IN | FLUSH).to_be_bytes());
+        assert_eq!(asks_for_service(&q), Some(7));
+        let reply = answer("pi", 47476, Ipv4Addr::new(192, 168, 1, 50), Some(7));
+        // Ответ на ответ не считается вопросом
+        assert_eq!(asks_for_service(&reply), None);
+        let from = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(
+            parse_answer(&reply, from),
+            [Found {
+                name: "pi".into(),
+                ip: "192.168.1.50".into(),
+                port: 47476
+            }]
+        );
+        // Сжатое имя: PTR ссылается на имя вопроса
+        let mut m = header(0, 0x8400, 0, 1);
+        put_name(&mut m, SERVICE);
+        let mut rdata = vec![2, b'h', b'q', 0xc0, 12];
+        m.extend_from_slice(&PTR.to_be_bytes());
+        m.extend_from_slice(&IN.to_be_bytes());
+        m.extend_from_slice(&TTL.to_be_bytes());
+        m.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        m.append(&mut rdata);
+        let r = records(&m).unwrap();
+        assert!(matches!(&r[0].data, Data::Ptr(n) if n == "hq._portal._udp.local"));
+        // Петля из ссылок не вешает разбор
+        let mut pos = 0;
+        assert_eq!(read_name(&[0xc0, 0], &mut pos), None);
+    }
+}
@@ -0,0 +1,638 @@
+// === MQTT: СОСТОЯНИЕ ДЛЯ УМНОГО ДОМА ===
+// Демон первым узнает о пропаже света - пусть Home Assistant/Node-RED
+// узнают от него. Минимальный клиент MQTT 3.1.1 без крейтов: QoS 0,
+// retained-сообщения, Last Will. Топики под topic_prefix:
+//   availability  online / offline (LWT: брокер публикует сам, если мы пропали)
+//   state         up / down / grace / sleeping / paused
+//   power         ON / OFF - есть ли свет
+//   next_wake     ISO 8601 время будильника rtcwake, пусто - не спим
+//   probe         {"ok":true,"rtt_ms":1.2,"ts":...} на каждую пробу
+// Для Home Assistant - discovery: устройство с binary_sensor (свет) и
+// сенсорами состояния, RTT и времени пробуждения появляется само, без YAML.
+// Публикуем синхронно из цикла (как SMS): "sleeping" должно уйти до сна.
+// Keepalive - таймер цикла демона на сетке timers; спим дольше keepalive -
+// брокер сам объявит offline, после подъема переподключаемся.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::fd::RawFd;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::{Event, Subscriber};
+use crate::schedule::TimeZone;
+use crate::state::Phase;
+use crate::{channels, hostname, log, reactor, timers, unix_now};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(3);
+// Брокер недоступен - не стучимся на каждом событии
+const RETRY: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MqttConfig {
+    // "192.168.1.10:1883"; пусто - MQTT выключен
+    pub broker: String,
+    pub username: String,
+    pub password: String,
+    // Пусто - "portal_daemon-<hostname>"
+    pub client_id: String,
+    // Пусто - "portal_daemon/<hostname>"
+    pub topic_prefix: String,
+    pub keepalive_sec: u16,
+    // Метрики каждой пробы в <prefix>/probe
+    pub publish_probes: bool,
+    // Home Assistant MQTT discovery в <discovery_prefix>/...
+    pub discovery: bool,
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: String::new(),
+            username: String::new(),
+            password: String::new(),
+            client_id: String::new(),
+            topic_prefix: String::new(),
+            keepalive_sec: 120,
+            publish_probes: true,
+            discovery: true,
+            discovery_prefix: "homeassistant".into(),
+        }
+    }
+}
+
+impl MqttConfig {
+    pub fn enabled(&self) -> bool {
+        !self.broker.is_empty()
+    }
+}
+
+struct Client {
+    cfg: MqttConfig,
+    prefix: String,
+    stream: Option<TcpStream>,
+    last_tx: Instant,
+    last_fail: Option<Instant>,
+    // Retained-значения (state, power, next_wake) - для повторной публикации
+    // после переподключения
+    retained: BTreeMap<&'static str, String>,
+}
+
+impl Client {
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.prefix, name)
+    }
+
+    fn connect(&mut self) -> Result<(), String> {
+        let addr = self
+            .cfg
+            .broker
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or("cannot resolve")?;
+        let mut s = TcpStream::connect_timeout(&addr, IO_TIMEOUT).map_err(|e| e.to_string())?;
+        s.set_read_timeout(Some(IO_TIMEOUT)).ok();
+        s.set_write_timeout(Some(IO_TIMEOUT)).ok();
+        s.set_nodelay(true).ok();
+
+        let host = hostname();
+        let client_id = if self.cfg.client_id.is_empty() {
+            format!("portal_daemon-{}", host)
+        } else {
+            self.cfg.client_id.clone()
+        };
+        s.write_all(&self.connect_packet(&client_id))
+            .map_err(|e| e.to_string())?;
+
+        let mut ack = [0u8; 4];
+        s.read_exact(&mut ack).map_err(|e| e.to_string())?;
+        connack(&ack)?;
+        log::info!(broker = self.cfg.broker; "📡 MQTT connected, topics {}/#", self.prefix);
+        self.stream = Some(s);
+        self.last_fail = None;
+        self.send(0x31, "availability", b"online");
+        if self.cfg.discovery {
+            self.discovery(&host);
+        }
+        for (name, value) in self.retained.clone() {
+            self.send(0x31, name, value.as_bytes());
+        }
+        Ok(())
+    }
+
+    fn connect_packet(&self, client_id: &str) -> Vec<u8> {
+        // clean session, will: QoS 0 retained "offline"
+        let mut flags = 0x02 | 0x04 | 0x20;
+        let mut body = Vec::new();
+        put_str(&mut body, "MQTT");
+        body.push(4);
+        if !self.cfg.username.is_empty() {
+            flags |= 0x80;
+            if !self.cfg.password.is_empty() {
+                flags |= 0x40;
+            }
+        }
+        body.push(flags);
+        body.extend_from_slice(&self.cfg.keepalive_sec.to_be_bytes());
+        put_str(&mut body, client_id);
+        put_str(&mut body, &self.topic("availability"));
+        put_str(&mut body, "offline");
+        if flags & 0x80 != 0 {
+            put_str(&mut body, &self.cfg.username);
+        }
+        if flags & 0x40 != 0 {
+            put_str(&mut body, &self.cfg.password);
+        }
+        packet(0x10, &body)
+    }
+
+    fn ensure(&mut self) -> bool {
+        if self.stream.is_some() {
+            return true;
+        }
+        if self.last_fail.is_some_and(|t| t.elapsed() < RETRY) {
+            return false;
+        }
+        let res = self.connect();
+        channels::record("mqtt", &res);
+        if let Err(e) = res {
+            // Предупреждаем один раз за серию неудач
+            if self.last_fail.is_none() {
+                log::warn!(broker = self.cfg.broker; "⚠️  MQTT connect failed: {}", e);
+            }
+            self.stream = None;
+            self.last_fail = Some(Instant::now());
+        }
+        self.stream.is_some()
+    }
+
+    // header: 0x30 - PUBLISH QoS 0, 0x31 - то же с retain
+    fn send(&mut self, header: u8, name: &str, payload: &[u8]) {
+        let mut body = Vec::new();
+        put_str(&mut body, &self.topic(name));
+        body.extend_from_slice(payload);
+        self.write(&packet(header, &body));
+    }
+
+    fn publish(&mut self, name: &str, payload: &[u8], retain: bool) {
+        if self.ensure() {
+            self.send(if retain { 0x31 } else { 0x30 }, name, payload);
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let Some(s) = self.stream.as_mut() else {
+            return;
+        };
+        // PINGRESP и прочее от брокера не нужны - вычитываем, чтобы не копились
+        s.set_nonblocking(true).ok();
+        let mut junk = [0u8; 256];
+        let closed = loop {
+            match s.read(&mut junk) {
+                Ok(0) => break true,
+                Ok(_) => continue,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break false,
+                Err(_) => break true,
+            }
+        };
+        s.set_nonblocking(false).ok();
+        if closed || s.write_all(bytes).is_err() {
+            log::warn!(broker = self.cfg.broker; "⚠️  MQTT connection lost");
+            self.stream = None;
+            return;
+        }
+        self.last_tx = Instant::now();
+    }
+
+    fn keepalive(&mut self) {
+        let half = Duration::from_secs(self.cfg.keepalive_sec as u64 / 2);
+        if self.stream.is_some() && self.last_tx.elapsed() >= half {
+            // PINGREQ
+            self.write(&[0xc0, 0x00]);
+        }
+    }
+
+    fn set(&mut self, name: &'static str, value: &str) {
+        if self.retained.get(name).map(String::as_str) != Some(value) {
+            self.retained.insert(name, value.to_string());
+            // Без соединения ensure подключится и сам разошлет retained
+            if self.stream.is_some() {
+                self.send(0x31, name, value.as_bytes());
+            } else {
+                self.ensure();
+            }
+        }
+    }
+
+    fn set_state(&mut self, st: &str) {
+        self.set("state", st);
+    }
+
+    // <discovery_prefix>/<component>/<node>/<object>/config, retained
+    fn discovery(&mut self, host: &str) {
+        let node: String = host
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let device = json!({
+            "identifiers": [format!("portal_daemon_{}", node)],
+            "name": format!("portal_daemon {}", host),
+            "model": "portal_daemon",
+            "manufacturer": "portal_daemon",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        });
+        let mut entities = vec![
+            (
+                "binary_sensor",
+                "power",
+                json!({
+                    "name": "Grid power",
+                    "device_class": "power",
+                    "state_topic": self.topic("power"),
+                }),
+            ),
+            (
+                "sensor",
+                "state",
+                json!({
+                    "name": "State",
+                    "device_class": "enum",
+                    "options": ["up", "down", "grace", "sleeping", "paused"],
+                    "state_topic": self.topic("state"),
+                    "icon": "mdi:power-sleep",
+                }),
+            ),
+            (
+                "sensor",
+                "next_wake",
+                json!({
+                    "name": "Next wake",
+                    "device_class": "timestamp",
+                    "state_topic": self.topic("next_wake"),
+                    "value_template": "{{ value if value else None }}",
+                }),
+            ),
+        ];
+        if self.cfg.publish_probes {
+            entities.push((
+                "sensor",
+                "rtt",
+                json!({
+                    "name": "Lighthouse RTT",
+                    "unit_of_measurement": "ms",
+                    "state_class": "measurement",
+                    "state_topic": self.topic("probe"),
+                    "value_template": "{{ value_json.rtt_ms | round(1) if value_json.ok else None }}",
+                    "icon": "mdi:timer-outline",
+                }),
+            ));
+        }
+        let root = self.cfg.discovery_prefix.trim_end_matches('/').to_string();
+        for (component, object, mut cfg) in entities {
+            cfg["unique_id"] = json!(format!("portal_daemon_{}_{}", node, object));
+            cfg["object_id"] = json!(format!("portal_daemon_{}_{}", node, object));
+            cfg["availability_topic"] = json!(self.topic("availability"));
+            cfg["device"] = device.clone();
+            let topic = format!("{}/{}/{}/{}/config", root, component, node, object);
+            let mut body = Vec::new();
+            put_str(&mut body, &topic);
+            body.extend_from_slice(cfg.to_string().as_bytes());
+            self.write(&packet(0x31, &body));
+        }
+    }
+}
+
+pub struct Mqtt {
+    client: Arc<Mutex<Client>>,
+    phase: Phase,
+    last_ok: bool,
+}
+
+impl Mqtt {
+    pub fn new(cfg: &MqttConfig) -> Self {
+        let prefix = if cfg.topic_prefix.is_empty() {
+            // + и # в имени топика - подстановки, / - уровень
+            let host: String = hostname()
+                .chars()
+                .map(|c| if matches!(c, '+' | '#' | '/') { '_' } else { c })
+                .collect();
+            format!("portal_daemon/{}", host)
+        } else {
+            cfg.topic_prefix.trim_end_matches('/').to_string()
+        };
+        let client = Arc::new(Mutex::new(Client {
+            cfg: cfg.clone(),
+            prefix,
+            stream: None,
+            last_tx: Instant::now(),
+            last_fail: None,
+            retained: BTreeMap::new(),
+        }));
+        if let Ok(mut c) = client.lock() {
+            c.ensure();
+        }
+        if cfg.keepalive_sec > 0 {
+            let half = Duration::from_secs((cfg.keepalive_sec as u64 / 2).max(1));
+            reactor::add(Keepalive {
+                client: Arc::clone(&client),
+                half,
+                next: Instant::now() + timers::until_tick(half),
+            });
+        }
+        Self {
+            client,
+            phase: Phase::default(),
+            last_ok: true,
+        }
+    }
+}
+
+// Без fd: только deadline, PINGREQ при простое
+struct Keepalive {
+    client: Arc<Mutex<Client>>,
+    half: Duration,
+    next: Instant,
+}
+
+impl reactor::Source for Keepalive {
+    fn fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        Some(self.next)
+    }
+
+    fn ready(&mut self, _: bool, _: &mut reactor::Spawned) -> bool {
+        if let Ok(mut c) = self.client.lock() {
+            c.keepalive();
+        }
+        self.next = Instant::now() + timers::until_tick(self.half);
+        true
+    }
+}
+
+impl Subscriber for Mqtt {
+    fn on_event(&mut self, e: &Event) {
+        let Ok(mut c) = self.client.lock() else {
+            return;
+        };
+        match e {
+            Event::Probe(r) => {
+                self.last_ok = r.ok;
+                if c.cfg.publish_probes {
+                    let m = json!({"ok": r.ok, "rtt_ms": r.rtt_ms, "ts": unix_now()});
+                    c.publish("probe", m.to_string().as_bytes(), false);
+                }
+                // Неудачная проба - это сразу ConnectionLost и "grace"
+                if r.ok && self.phase == Phase::Monitoring {
+                    c.set_state("up");
+                }
+                if r.ok {
+                    c.set("power", "ON");
+                }
+            }
+            Event::StateChanged { phase, .. } => {
+                self.phase = *phase;
+                match phase {
+                    Phase::Paused => c.set_state("paused"),
+                    Phase::Grace => c.set_state("grace"),
+                    Phase::Sleeping => c.set_state("sleeping"),
+                    Phase::Monitoring => c.set_state(if self.last_ok { "up" } else { "down" }),
+                }
+            }
+            Event::ConnectionLost { .. } => {
+                self.phase = Phase::Grace;
+                c.set_state("grace");
+                c.set("power", "OFF");
+            }
+            Event::ConnectionRestored => c.set_state("up"),
+            // Света нет, но уснуть не дали (защита, тихие часы)
+            Event::SleepBlocked { .. } => c.set_state("down"),
+            Event::SleepRequested { seconds, .. } => {
+                self.phase = Phase::Sleeping;
+                c.set_state("sleeping");
+                c.set("next_wake", &iso_utc(unix_now() + seconds));
+            }
+            // Брокер, скорее всего, уже закрыл сессию по keepalive
+            Event::Woke { .. } => {
+                c.stream = None;
+                c.last_fail = None;
+                c.retained.insert("next_wake", String::new());
+            }
+            _ => {}
+        }
+    }
+}
+
+// "2026-10-14T08:28:11+00:00" - так timestamp-сенсор HA его и ждет
+fn iso_utc(ts: u64) -> String {
+    let l = TimeZone::utc().to_local(ts as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}+00:00",
+        l.year, l.month, l.day, l.hour, l.minute, l.second
+    )
+}
+
+// CONNACK: 0x20, длина 2, флаги сессии, код возврата
+fn connack(ack: &[u8; 4]) -> Result<(), String> {
+    if ack[0] != 0x20 {
+        return Err(format!("unexpected reply {:#04x}", ack[0]));
+    }
+    match ack[3] {
+        0 => Ok(()),
+        4 | 5 => Err("not authorized".into()),
+        2 => Err("client id rejected".into()),
+        rc => Err(format!("connection refused ({})", rc)),
+    }
+}
+
+// Фиксированный заголовок + длина остатка (7 бит на байт, старший - "еще")
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![header];
+    let mut len = body.len();
+    loop {
+        let mut b = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            b |= 0x80;
+        }
+        out.push(b);
+        if len == 0 {
+            break;
+        }
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn client(cfg: MqttConfig) -> Client {
+        Client {
+            cfg,
+            prefix: "portal_daemon/test".into(),
+            stream: None,
+            last_tx: Instant::now(),
+            last_fail: None,
+            retained: BTreeMap::new(),
+        }
+    }
+
+    // Пакет целиком: заголовок и тело по длине остатка
+    fn read_packet(s: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let mut b = [0u8; 1];
+        s.read_exact(&mut b).ok()?;
+        let header = b[0];
+        let (mut len, mut shift) = (0usize, 0);
+        loop {
+            s.read_exact(&mut b).ok()?;
+            len |= ((b[0] & 0x7f) as usize) << shift;
+            shift += 7;
+            if b[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0u8; len];
+        s.read_exact(&mut body).ok()?;
+        Some((header, body))
+    }
+
+    fn publish_of(body: &[u8]) -> (String, String) {
+        let n = u16::from_be_bytes([body[0], body[1]]) as usize;
+        (
+            String::from_utf8_lossy(&body[2..2 + n]).into_owned(),
+            String::from_utf8_lossy(&body[2 + n..]).into_owned(),
+        )
+    }
+
+    #[test]
+    fn remaining_length_uses_continuation_bytes() {
+        let len = |n: usize| {
+            let p = packet(0x30, &vec![0; n]);
+            assert_eq!(p[0], 0x30);
+            p[1..p.len() - n].to_vec()
+        };
+        assert_eq!(len(0), [0x00]);
+        assert_eq!(len(127), [0x7f]);
+        assert_eq!(len(128), [0x80, 0x01]);
+        assert_eq!(len(321), [0xc1, 0x02]);
+        assert_eq!(len(16383), [0xff, 0x7f]);
+        assert_eq!(len(16384), [0x80, 0x80, 0x01]);
+
+        let mut buf = Vec::new();
+        put_str(&mut buf, "MQTT");
+        assert_eq!(buf, b"\x00\x04MQTT");
+    }
+
+    #[test]
+    fn connect_carries_will_and_credentials() {
+        let anon = client(MqttConfig::default()).connect_packet("id");
+        let mut body = vec![
+            0, 4, b'M', b'Q', b'T', b'T', 4, 0x26, 0, 120, 0, 2, b'i', b'd',
+        ];
+        body.extend_from_slice(b"\x00\x1fportal_daemon/test/availability\x00\x07offline");
+        assert_eq!(anon, packet(0x10, &body));
+
+        let cfg = MqttConfig {
+            username: "ha".into(),
+            password: "pw".into(),
+            keepalive_sec: 300,
+            ..MqttConfig::default()
+        };
+        let auth = client(cfg).connect_packet("id");
+        // Флаги: пароль, имя, will retain, will, clean session; keepalive 300
+        assert_eq!(&auth[9..12], [0xe6, 0x01, 0x2c]);
+        assert!(auth.ends_with(b"\x00\x02ha\x00\x02pw"));
+
+        // Пароль без имени протокол не разрешает
+        let cfg = MqttConfig {
+            password: "pw".into(),
+            ..MqttConfig::default()
+        };
+        let no_user = client(cfg).connect_packet("id");
+        assert_eq!(no_user[9], 0x26);
+        assert!(no_user.ends_with(b"offline"));
+    }
+
+    #[test]
+    fn connack_return_codes() {
+        assert_eq!(connack(&[0x20, 2, 0, 0]), Ok(()));
+        assert_eq!(connack(&[0x20, 2, 0, 5]), Err("not authorized".into()));
+        assert_eq!(connack(&[0x20, 2, 0, 2]), Err("client id rejected".into()));
+        assert_eq!(
+            connack(&[0x20, 2, 0, 3]),
+            Err("connection refused (3)".into())
+        );
+        assert_eq!(
+            connack(&[0x90, 3, 0, 0]),
+            Err("unexpected reply 0x90".into())
+        );
+    }
+
+    #[test]
+    fn publishes_retained_state_once() {
+        let broker = TcpListener::bind("127.0.0.1:0").unwrap();
+        let cfg = MqttConfig {
+            broker: broker.local_addr().unwrap().to_string(),
+            client_id: "test".into(),
+            discovery: false,
+            ..MqttConfig::default()
+        };
+        let seen = std::thread::spawn(move || {
+            let (mut s, _) = broker.accept().unwrap();
+            let (header, _) = read_packet(&mut s).unwrap();
+            assert_eq!(header, 0x10);
+            s.write_all(&[0x20, 2, 0, 0]).unwrap();
+            let mut seen = Vec::new();
+            while let Some((header, body)) = read_packet(&mut s) {
+                seen.push((header, publish_of(&body)));
+            }
+            seen
+        });
+
+        let mut c = client(cfg);
+        c.set_state("up");
+        c.set_state("up");
+        c.publish("probe", b"{}", false);
+        c.set_state("grace");
+        c.stream = None;
+
+        let topic = |t: &str| format!("portal_daemon/test/{}", t);
+        assert_eq!(
+            seen.join().unwrap(),
+            [
+                (0x31, (topic("availability"), "online".into())),
+                (0x31, (topic("state"), "up".into())),
+                (0x30, (topic("probe"), "{}".into())),
+                (0x31, (topic("state"), "grace".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn next_wake_is_iso_utc() {
+        assert_eq!(iso_utc(0), "1970-01-01T00:00:00+00:00");
+        assert_eq!(iso_utc(951_782_400 + 3723), "2000-02-29T01:02:03+00:00");
+    }
+}
@@ -0,0 +1,459 @@
+// === МАРШРУТ ДО МАЯКА ===
+// Если трафик к маяку уходит в WireGuard/OpenVPN/Tailscale, ответ придет от
+// выходного узла VPN где-то далеко - о свете дома он ничего не говорит.
+// Такие интерфейсы распознаем по sysfs, а пробу можно прибить к физическому
+// интерфейсу маршрута по умолчанию (SO_BINDTODEVICE, как ping -I).
+// Мост, bond и VLAN сами по себе "up", пока жив хоть один порт (хоть veth
+// контейнера), поэтому состояние линка смотрим на физических носителях под ними.
+// Раздача с телефона (USB-модем, Bluetooth PAN, Wi-Fi hotspot) питается от
+// батареи телефона: шлюз отвечает и без света дома, пинг по нему ни о чем.
+
+use serde::{Deserialize, Serialize};
+use std::ffi::{CStr, CString};
+use std::fs::{self, File};
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{freebsd, log};
+
+// Адрес, с которого ядро отправит пакет на ip. connect() у UDP-сокета только
+// выбирает маршрут (с учетом правил policy routing, как у wg-quick) и ничего
+// не шлет: три syscall'а вместо запуска `ip route get`
+pub fn route_src(ip: IpAddr) -> Option<IpAddr> {
+    let any = match ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let s = UdpSocket::bind(SocketAddr::new(any, 0)).ok()?;
+    s.connect(SocketAddr::new(ip, 9)).ok()?;
+    s.local_addr().ok().map(|a| a.ip())
+}
+
+// Интерфейс, через который ядро отправит пакет на ip
+pub fn route_dev(ip: &str) -> Option<String> {
+    let addr: IpAddr = ip.parse().ok()?;
+    route_src(addr)
+        .and_then(dev_with_addr)
+        .or_else(|| ip_route_get(ip))
+}
+
+// (интерфейс, адрес) всех адресов системы
+fn addresses() -> Vec<(String, IpAddr)> {
+    let mut list: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut list) } != 0 {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    let mut cur = list;
+    while !cur.is_null() {
+        let ifa = unsafe { &*cur };
+        if let Some(ip) = sockaddr_ip(ifa.ifa_addr) {
+            let name = unsafe { CStr::from_ptr(ifa.ifa_name) };
+            out.push((name.to_string_lossy().to_string(), ip));
+        }
+        cur = ifa.ifa_next;
+    }
+    unsafe { libc::freeifaddrs(list) };
+    out
+}
+
+// Интерфейс, на котором висит адрес
+fn dev_with_addr(addr: IpAddr) -> Option<String> {
+    addresses()
+        .into_iter()
+        .find_map(|(dev, ip)| (ip == addr).then_some(dev))
+}
+
+// Адрес интерфейса того же семейства, что и to: источник вместо
+// SO_BINDTODEVICE там, где его нет (FreeBSD)
+pub fn dev_addr(dev: &str, to: IpAddr) -> Option<IpAddr> {
+    addresses()
+        .into_iter()
+        .find_map(|(d, ip)| (d == dev && ip.is_ipv6() == to.is_ipv6()).then_some(ip))
+}
+
+pub fn sockaddr_ip(sa: *const libc::sockaddr) -> Option<IpAddr> {
+    if sa.is_null() {
+        return None;
+    }
+    match unsafe { (*sa).sa_family } as i32 {
+        libc::AF_INET => {
+            let sin = unsafe { &*(sa as *const libc::sockaddr_in) };
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                sin.sin_addr.s_addr,
+            ))))
+        }
+        libc::AF_INET6 => {
+            let sin6 = unsafe { &*(sa as *const libc::sockaddr_in6) };
+            Some(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)))
+        }
+        _ => None,
+    }
+}
+
+// Адрес маршрута ни на одном интерфейсе (безадресный туннель) - спросим ip
+fn ip_route_get(ip: &str) -> Option<String> {
+    let out = Command::new("ip")
+        .args(["-o", "route", "get", ip])
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    dev_of(&String::from_utf8_lossy(&out.stdout))
+}
+
+// "... dev eth0 src ..." -> "eth0"
+fn dev_of(line: &str) -> Option<String> {
+    let mut it = line.split_whitespace();
+    it.find(|w| *w == "dev")?;
+    it.next().map(str::to_string)
+}
+
+// tun/tap (OpenVPN, ZeroTier) - есть tun_flags; WireGuard - DEVTYPE=wireguard;
+// ARPHRD_NONE (65534) - безадресные L3-туннели вообще
+pub fn is_vpn(dev: &str) -> bool {
+    let sys = Path::new("/sys/class/net").join(dev);
+    if sys.join("tun_flags").exists() {
+        return true;
+    }
+    let uevent = fs::read_to_string(sys.join("uevent")).unwrap_or_default();
+    if uevent.lines().any(|l| l == "DEVTYPE=wireguard") {
+        return true;
+    }
+    if fs::read_to_string(sys.join("type")).is_ok_and(|t| t.trim() == "65534") {
+        return true;
+    }
+    ["wg", "tun", "tap", "tailscale", "zt"]
+        .iter()
+        .any(|p| dev.starts_with(p))
+}
+
+// Wi-Fi сам или под мостом: у беспроводных в sysfs есть wireless/ или phy80211
+pub fn is_wireless(dev: &str) -> bool {
+    carrier_devs(dev).iter().any(|d| {
+        let sys = Path::new("/sys/class/net").join(d);
+        sys.join("wireless").exists() || sys.join("phy80211").exists()
+    })
+}
+
+// ip в подсети, подключенной к dev напрямую (маршрут без шлюза): до него
+// доходит ARP. Только IPv4 - /proc/net/route
+pub fn on_link(ip: Ipv4Addr, dev: &str) -> bool {
+    let Ok(routes) = fs::read_to_string("/proc/net/route") else {
+        return false;
+    };
+    // В /proc адреса - hex в порядке байт хоста (little-endian на x86/ARM)
+    let ip = u32::from_ne_bytes(ip.octets());
+    let hex = |v: &str| u32::from_str_radix(v, 16).ok();
+    routes.lines().skip(1).any(|l| {
+        let f: Vec<&str> = l.split_whitespace().collect();
+        f.len() > 7
+            && f[0] == dev
+            && f[2] == "00000000"
+            && matches!((hex(f[1]), hex(f[7])), (Some(d), Some(m)) if m != 0 && ip & m == d)
+    })
+}
+
+// Первый (по метрике) маршрут по умолчанию в main, который идет не через VPN.
+// /proc/net/route - это и есть IPv4-таблица main
+pub fn physical_dev() -> Option<String> {
+    if freebsd::is() {
+        return freebsd::default_routes()
+            .into_iter()
+            .map(|(dev, _)| dev)
+            .find(|d| !is_vpn(d));
+    }
+    let routes = fs::read_to_string("/proc/net/route").ok()?;
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|l| {
+            let f: Vec<&str> = l.split_whitespace().collect();
+            // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+            let default = f.len() > 7 && f[1] == "00000000" && f[7] == "00000000";
+            let up = f.get(3).and_then(|v| u16::from_str_radix(v, 16).ok())? & 1 != 0;
+            (default && up).then(|| (f[6].parse::<u32>().unwrap_or(u32::MAX), f[0]))
+        })
+        .filter(|(_, d)| !is_vpn(d))
+        .min_by_key(|(metric, _)| *metric)
+        .map(|(_, d)| d.to_string())
+}
+
+// Маршруты по умолчанию через шлюз: (интерфейс, шлюз, метрика). IPv4 - из
+// /proc/net/route, IPv6 - netlink'ом. Шлюз IPv6 почти всегда fe80::
+// из RA, а link-local без %dev маяком не задать - такие пропускаем
+fn default_routes() -> Vec<(String, IpAddr, u32)> {
+    // Метрик у route get нет: один маршрут на семейство
+    if freebsd::is() {
+        return freebsd::default_routes()
+            .into_iter()
+            .map(|(dev, gw)| (dev, gw, 0))
+            .collect();
+    }
+    let routes = fs::read_to_string("/proc/net/route").unwrap_or_default();
+    let mut out: Vec<(String, IpAddr, u32)> = routes
+        .lines()
+        .skip(1)
+        .filter_map(|l| {
+            let f: Vec<&str> = l.split_whitespace().collect();
+            let default = f.len() > 7 && f[1] == "00000000" && f[7] == "00000000";
+            let gw = u32::from_str_radix(f.get(2)?, 16)
+                .ok()
+                .filter(|g| *g != 0)?;
+            let gw = IpAddr::V4(Ipv4Addr::from(gw.to_ne_bytes()));
+            default.then(|| (f[0].to_string(), gw, f[6].parse().unwrap_or(u32::MAX)))
+        })
+        .collect();
+    out.extend(default_routes_v6());
+    out
+}
+
+#[cfg(target_os = "linux")]
+fn default_routes_v6() -> Vec<(String, IpAddr, u32)> {
+    match crate::netlink::default_routes_v6() {
+        Ok(v6) => v6
+            .into_iter()
+            .filter(|r| !r.gateway.is_unicast_link_local())
+            .map(|r| (r.dev, IpAddr::V6(r.gateway), r.metric))
+            .collect(),
+        Err(e) => {
+            log::debug!("netlink route dump failed: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+// Без netlink: IPv6 дает freebsd::default_routes
+#[cfg(not(target_os = "linux"))]
+fn default_routes_v6() -> Vec<(String, IpAddr, u32)> {
+    Vec::new()
+}
+
+// Шлюз маршрута по умолчанию через dev: IPv4, если есть, иначе IPv6;
+// из нескольких - с наименьшей метрикой
+pub fn default_gateway(dev: &str) -> Option<String> {
+    default_routes()
+        .into_iter()
+        .filter(|(d, _, _)| d == dev)
+        .min_by_key(|(_, gw, metric)| (gw.is_ipv6(), *metric))
+        .map(|(_, gw, _)| gw.to_string())
+}
+
+// Физические носители под интерфейсом: br0 -> порты моста, bond0 -> slaves,
+// eth0.10 -> eth0. Ядро связывает их ссылками lower_* в sysfs; виртуальные
+// порты (veth, tap виртуалок) отбрасываем, если есть хоть один настоящий.
+pub fn carrier_devs(dev: &str) -> Vec<String> {
+    let mut leaves = Vec::new();
+    collect_lower(dev, &mut leaves, 0);
+    let physical: Vec<String> = leaves
+        .iter()
+        .filter(|d| Path::new("/sys/class/net").join(d).join("device").exists())
+        .cloned()
+        .collect();
+    if physical.is_empty() {
+        leaves
+    } else {
+        physical
+    }
+}
+
+fn collect_lower(dev: &str, out: &mut Vec<String>, depth: u8) {
+    let sys = Path::new("/sys/class/net").join(dev);
+    let mut lower: Vec<String> = fs::read_dir(&sys)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            name.strip_prefix("lower_").map(str::to_string)
+        })
+        .collect();
+    // Старые ядра: без lower_*, но с brif/ и bonding/slaves
+    if lower.is_empty() {
+        lower.extend(
+            fs::read_dir(sys.join("brif"))
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().to_string()),
+        );
+        let slaves = fs::read_to_string(sys.join("bonding/slaves")).unwrap_or_default();
+        lower.extend(slaves.split_whitespace().map(str::to_string));
+    }
+    // depth - страховка от петель (bond в мосту во VLAN - это уже 3)
+    if lower.is_empty() || depth > 4 {
+        if !out.iter().any(|d| d == dev) {
+            out.push(dev.to_string());
+        }
+        return;
+    }
+    lower.sort();
+    for l in lower {
+        collect_lower(&l, out, depth + 1);
+    }
+}
+
+// Файлы состояния линка носителя: собираем один раз, читаем каждую пробу
+pub struct Carrier {
+    carrier: PathBuf,
+    operstate: PathBuf,
+}
+
+pub fn carriers(dev: &str) -> Vec<Carrier> {
+    carrier_devs(dev)
+        .iter()
+        .map(|d| {
+            let sys = Path::new("/sys/class/net").join(d);
+            Carrier {
+                carrier: sys.join("carrier"),
+                operstate: sys.join("operstate"),
+            }
+        })
+        .collect()
+}
+
+// Есть ли линк хотя бы на одном носителе; None - судить не по чему
+pub fn link_up(carriers: &[Carrier]) -> Option<bool> {
+    let mut seen = false;
+    for c in carriers {
+        let mut buf = [0u8; 16];
+        // carrier у опущенного интерфейса не читается (EINVAL) - это тоже "нет линка"
+        let state = match read_small(&c.carrier, &mut buf) {
+            Some(v) => Some(v == "1"),
+            None => (read_small(&c.operstate, &mut buf) == Some("down")).then_some(false),
+        };
+        match state {
+            Some(true) => return Some(true),
+            Some(false) => seen = true,
+            None => {}
+        }
+    }
+    seen.then_some(false)
+}
+
+pub fn if_index(dev: &str) -> Option<u32> {
+    let name = CString::new(dev).ok()?;
+    let i = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    (i != 0).then_some(i)
+}
+
+// Короткий файл sysfs в буфер на стеке
+fn read_small<'a>(path: &Path, buf: &'a mut [u8; 16]) -> Option<&'a str> {
+    let n = File::open(path).ok()?.read(buf).ok()?;
+    std::str::from_utf8(&buf[..n]).ok().map(str::trim)
+}
+
+// Драйверы USB-модемов и раздачи с телефона (Android RNDIS/NCM, iPhone)
+const TETHER_DRIVERS: &[&str] = &[
+    "rndis_host",
+    "cdc_ether",
+    "cdc_ncm",
+    "cdc_mbim",
+    "ipheth",
+    "qmi_wwan",
+    "huawei_cdc_ncm",
+];
+
+// Причина считать интерфейс раздачей с телефона или None
+pub fn tethered(dev: &str) -> Option<String> {
+    for d in carrier_devs(dev) {
+        let sys = Path::new("/sys/class/net").join(&d);
+        let driver = fs::read_link(sys.join("device/driver"))
+            .ok()
+            .and_then(|p| p.file_name().map(|f| f.to_string_lossy().to_string()));
+        if let Some(drv) = driver.filter(|drv| TETHER_DRIVERS.contains(&drv.as_str())) {
+            return Some(format!("{} is a USB tether ({})", d, drv));
+        }
+        let uevent = fs::read_to_string(sys.join("uevent")).unwrap_or_default();
+        if uevent.lines().any(|l| l == "DEVTYPE=wwan") {
+            return Some(format!("{} is a mobile modem", d));
+        }
+    }
+    crate::nm::tether_hint(dev)
+}
+
+// --- ОБНАРУЖЕНИЕ СЕТЕЙ (мастер настройки) ---
+// Кто ведет сеть: NetworkManager, iwd или никто (systemd-networkd, статика) -
+// тогда таблица маршрутов ядра; "auto" - по очереди, кто ответит первым
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Auto,
+    NetworkManager,
+    Iwd,
+    Kernel,
+}
+
+pub struct Connection {
+    // Имя соединения NetworkManager, SSID или имя интерфейса
+    pub name: String,
+    pub device: String,
+    // Пусто, если шлюза нет
+    pub gateway: String,
+}
+
+pub fn connections(backend: Backend) -> Vec<Connection> {
+    let res = match backend {
+        Backend::NetworkManager => crate::nm::active_connections(),
+        Backend::Iwd => crate::iwd::connections(),
+        Backend::Kernel => Ok(kernel_connections()),
+        Backend::Auto => crate::nm::active_connections()
+            .or_else(|e| {
+                log::debug!("NetworkManager unavailable: {}", e);
+                crate::iwd::connections()
+            })
+            .or_else(|e| {
+                log::debug!("iwd unavailable: {}", e);
+                Ok(kernel_connections())
+            }),
+    };
+    res.unwrap_or_else(|e| {
+        log::debug!(backend = format!("{:?}", backend); "network discovery failed: {}", e);
+        Vec::new()
+    })
+}
+
+// SSID, к которому подключены сейчас; None - не Wi-Fi, не подключены или
+// узнать не у кого (без менеджера сети SSID ядро нам не назовет)
+pub fn current_ssid(backend: Backend) -> Option<String> {
+    let iwd = || crate::iwd::connections().map(|c| c.into_iter().next().map(|c| c.name));
+    let res = match backend {
+        Backend::NetworkManager => crate::nm::wifi_ssid(),
+        Backend::Iwd => iwd(),
+        Backend::Kernel => Ok(None),
+        Backend::Auto => crate::nm::wifi_ssid().or_else(|e| {
+            log::debug!("NetworkManager unavailable: {}", e);
+            iwd()
+        }),
+    };
+    res.unwrap_or_else(|e| {
+        log::debug!(backend = format!("{:?}", backend); "SSID lookup failed: {}", e);
+        None
+    })
+}
+
+// Без менеджера сети имен соединений нет: по интерфейсу с маршрутом по
+// умолчанию, VPN мимо - маяк за туннелем о свете ничего не скажет
+fn kernel_connections() -> Vec<Connection> {
+    let mut devs: Vec<String> = default_routes()
+        .into_iter()
+        .map(|(d, _, _)| d)
+        .filter(|d| d != "lo" && !is_vpn(d))
+        .collect();
+    devs.sort();
+    devs.dedup();
+    devs.into_iter()
+        .filter_map(|device| {
+            let gateway = default_gateway(&device)?;
+            Some(Connection {
+                name: device.clone(),
+                device,
+                gateway,
+            })
+        })
+        .collect()
+}
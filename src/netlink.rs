@@ -0,0 +1,212 @@
+// === NETLINK: МАРШРУТЫ IPv6 И СОБЫТИЯ ЛИНКА ===
+// У IPv4 таблица main видна в /proc/net/route, у IPv6 такого файла с
+// шлюзами нет (/proc/net/ipv6_route без метрик таблиц и без policy) -
+// спрашиваем ядро дампом RTM_GETROUTE по NETLINK_ROUTE.
+// Там же группа RTMGRP_LINK: ядро само сообщает о смене carrier/operstate,
+// и выдернутый кабель виден сразу, а не через scan_interval.
+
+use std::ffi::CStr;
+use std::io;
+use std::mem;
+use std::net::Ipv6Addr;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+const RTM_NEWLINK: u16 = 16;
+const RTM_DELLINK: u16 = 17;
+const RTM_NEWROUTE: u16 = 24;
+const RTM_GETROUTE: u16 = 26;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLM_F_REQUEST: u16 = 1;
+const NLM_F_DUMP: u16 = 0x300;
+const RT_TABLE_MAIN: u8 = 254;
+const RTA_OIF: u16 = 4;
+const RTA_GATEWAY: u16 = 5;
+const RTA_PRIORITY: u16 = 6;
+const RTMGRP_LINK: u32 = 1;
+
+// Заголовок nlmsghdr (16) + rtmsg (12)
+const NLMSG_HDR: usize = 16;
+const RTMSG_LEN: usize = 12;
+
+pub struct Route6 {
+    pub dev: String,
+    pub gateway: Ipv6Addr,
+    pub metric: u32,
+}
+
+// Маршруты по умолчанию (::/0 через шлюз) из таблицы main
+pub fn default_routes_v6() -> io::Result<Vec<Route6>> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let sock = unsafe { OwnedFd::from_raw_fd(fd) };
+    let fd = sock.as_raw_fd();
+    let timeout = libc::timeval {
+        tv_sec: 2,
+        tv_usec: 0,
+    };
+    unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            &timeout as *const libc::timeval as *const libc::c_void,
+            mem::size_of::<libc::timeval>() as libc::socklen_t,
+        );
+    }
+
+    let mut req = [0u8; NLMSG_HDR + RTMSG_LEN];
+    req[0..4].copy_from_slice(&((NLMSG_HDR + RTMSG_LEN) as u32).to_ne_bytes());
+    req[4..6].copy_from_slice(&RTM_GETROUTE.to_ne_bytes());
+    req[6..8].copy_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+    req[8..12].copy_from_slice(&1u32.to_ne_bytes());
+    req[NLMSG_HDR] = libc::AF_INET6 as u8;
+    if unsafe { libc::send(fd, req.as_ptr() as *const libc::c_void, req.len(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut out = Vec::new();
+    let mut buf = vec![0u8; 32 * 1024];
+    loop {
+        let n = unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut msgs = &buf[..n as usize];
+        while msgs.len() >= NLMSG_HDR {
+            let len = u32::from_ne_bytes([msgs[0], msgs[1], msgs[2], msgs[3]]) as usize;
+            let kind = u16::from_ne_bytes([msgs[4], msgs[5]]);
+            if len < NLMSG_HDR || len > msgs.len() {
+                break;
+            }
+            match kind {
+                NLMSG_DONE => return Ok(out),
+                NLMSG_ERROR => {
+                    let errno = i32::from_ne_bytes([msgs[16], msgs[17], msgs[18], msgs[19]]);
+                    return Err(io::Error::from_raw_os_error(-errno));
+                }
+                RTM_NEWROUTE => out.extend(parse_route(&msgs[NLMSG_HDR..len])),
+                _ => {}
+            }
+            msgs = &msgs[align(len).min(msgs.len())..];
+        }
+    }
+}
+
+// Подписка на события линков; читать - link_events()
+pub fn link_monitor() -> io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let sock = unsafe { OwnedFd::from_raw_fd(fd) };
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as u16;
+    addr.nl_groups = RTMGRP_LINK;
+    let r = unsafe {
+        libc::bind(
+            sock.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if r != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(sock)
+}
+
+// Блокируется до следующей пачки событий; индексы интерфейсов, у которых
+// что-то сменилось
+pub fn link_events(sock: &OwnedFd, buf: &mut [u8]) -> io::Result<Vec<u32>> {
+    let n = unsafe {
+        libc::recv(
+            sock.as_raw_fd(),
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+            0,
+        )
+    };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut out = Vec::new();
+    let mut msgs = &buf[..n as usize];
+    while msgs.len() >= NLMSG_HDR {
+        let len = u32::from_ne_bytes([msgs[0], msgs[1], msgs[2], msgs[3]]) as usize;
+        let kind = u16::from_ne_bytes([msgs[4], msgs[5]]);
+        if len < NLMSG_HDR || len > msgs.len() {
+            break;
+        }
+        // ifinfomsg: family, pad, type (2), index (4)
+        if matches!(kind, RTM_NEWLINK | RTM_DELLINK) && len >= NLMSG_HDR + 8 {
+            let m = &msgs[NLMSG_HDR..];
+            out.push(u32::from_ne_bytes([m[4], m[5], m[6], m[7]]));
+        }
+        msgs = &msgs[align(len).min(msgs.len())..];
+    }
+    Ok(out)
+}
+
+fn parse_route(m: &[u8]) -> Option<Route6> {
+    if m.len() < RTMSG_LEN {
+        return None;
+    }
+    // rtm_family, rtm_dst_len, ..., rtm_table
+    if m[0] != libc::AF_INET6 as u8 || m[1] != 0 || m[4] != RT_TABLE_MAIN {
+        return None;
+    }
+    let (mut oif, mut gateway, mut metric) = (None, None, 0);
+    let mut attrs = &m[RTMSG_LEN..];
+    while attrs.len() >= 4 {
+        let len = u16::from_ne_bytes([attrs[0], attrs[1]]) as usize;
+        let kind = u16::from_ne_bytes([attrs[2], attrs[3]]);
+        if len < 4 || len > attrs.len() {
+            break;
+        }
+        let v = &attrs[4..len];
+        match (kind, v.len()) {
+            (RTA_OIF, 4) => oif = Some(u32::from_ne_bytes([v[0], v[1], v[2], v[3]])),
+            (RTA_PRIORITY, 4) => metric = u32::from_ne_bytes([v[0], v[1], v[2], v[3]]),
+            (RTA_GATEWAY, 16) => {
+                let mut ip = [0u8; 16];
+                ip.copy_from_slice(v);
+                gateway = Some(Ipv6Addr::from(ip));
+            }
+            _ => {}
+        }
+        attrs = &attrs[align(len).min(attrs.len())..];
+    }
+    Some(Route6 {
+        dev: if_name(oif?)?,
+        gateway: gateway?,
+        metric,
+    })
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn if_name(index: u32) -> Option<String> {
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    let p = unsafe { libc::if_indextoname(index, name.as_mut_ptr()) };
+    if p.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(p) }.to_string_lossy().to_string())
+}
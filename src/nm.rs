@@ -0,0 +1,273 @@
+// === NETWORKMANAGER (D-BUS) ===
+// Активные соединения, их устройства и шлюзы - свойствами NetworkManager
+// вместо разбора `nmcli -t`: там поля разделены ':', и имя соединения
+// с двоеточием рассыпалось. Обход объектов - поверх Bus, чтобы в тестах
+// подставить NetworkManager из таблицы свойств.
+
+use crate::dbus::{Client, Value};
+use crate::net::Connection;
+
+const NM: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const ACTIVE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+const DEVICE: &str = "org.freedesktop.NetworkManager.Device";
+const IP4: &str = "org.freedesktop.NetworkManager.IP4Config";
+const AP: &str = "org.freedesktop.NetworkManager.AccessPoint";
+
+// NMDeviceType и NMMetered
+const TYPE_BT: u32 = 5;
+const TYPE_MODEM: u32 = 8;
+const METERED_YES: u32 = 1;
+const METERED_GUESS_YES: u32 = 3;
+
+// Свойства объектов NetworkManager и поиск устройства по интерфейсу
+trait Bus {
+    fn property(&mut self, path: &str, interface: &str, name: &str) -> Result<Value, String>;
+    fn device_by_iface(&mut self, dev: &str) -> Result<Value, String>;
+}
+
+impl Bus for Client {
+    fn property(&mut self, path: &str, interface: &str, name: &str) -> Result<Value, String> {
+        Client::property(self, NM, path, interface, name)
+    }
+
+    fn device_by_iface(&mut self, dev: &str) -> Result<Value, String> {
+        self.call(NM, NM_PATH, NM, "GetDeviceByIpIface", &[dev])
+    }
+}
+
+pub fn active_connections() -> Result<Vec<Connection>, String> {
+    connections(&mut Client::system()?)
+}
+
+pub fn wifi_ssid() -> Result<Option<String>, String> {
+    ssid(&mut Client::system()?)
+}
+
+pub fn tether_hint(dev: &str) -> Option<String> {
+    tether(&mut Client::system().ok()?, dev)
+}
+
+fn connections(c: &mut impl Bus) -> Result<Vec<Connection>, String> {
+    let paths = c.property(NM_PATH, NM, "ActiveConnections")?.into_list();
+    let mut out = Vec::new();
+    for path in paths {
+        let name = c
+            .property(&path, ACTIVE, "Id")?
+            .into_str()
+            .unwrap_or_default();
+        let gateway = ip4_gateway(c, &path);
+        for dev in c.property(&path, ACTIVE, "Devices")?.into_list() {
+            let device = c
+                .property(&dev, DEVICE, "Interface")?
+                .into_str()
+                .unwrap_or_default();
+            out.push(Connection {
+                name: name.clone(),
+                device,
+                gateway: gateway.clone(),
+            });
+        }
+    }
+    Ok(out)
+}
+
+// SSID активного Wi-Fi: имя соединения пользователь мог переименовать,
+// поэтому берем Ssid точки доступа (SpecificObject соединения)
+fn ssid(c: &mut impl Bus) -> Result<Option<String>, String> {
+    let paths = c.property(NM_PATH, NM, "ActiveConnections")?.into_list();
+    for path in paths {
+        let Some(ap) = c
+            .property(&path, ACTIVE, "SpecificObject")?
+            .into_str()
+            .filter(|p| p != "/")
+        else {
+            continue;
+        };
+        // У VPN SpecificObject - родительское соединение, не точка доступа
+        if let Some(ssid) = c
+            .property(&ap, AP, "Ssid")
+            .ok()
+            .and_then(|v| v.into_str())
+            .filter(|s| !s.is_empty())
+        {
+            return Ok(Some(ssid));
+        }
+    }
+    Ok(None)
+}
+
+// Ip4Config соединения -> Gateway; "/" - конфига нет
+fn ip4_gateway(c: &mut impl Bus, path: &str) -> String {
+    let Some(cfg) = c
+        .property(path, ACTIVE, "Ip4Config")
+        .ok()
+        .and_then(|v| v.into_str())
+        .filter(|p| p != "/")
+    else {
+        return String::new();
+    };
+    c.property(&cfg, IP4, "Gateway")
+        .ok()
+        .and_then(|v| v.into_str())
+        .unwrap_or_default()
+}
+
+// Догадка NetworkManager о раздаче с телефона: модем, Bluetooth или
+// "metered" (Android сообщает это в DHCP, iPhone узнается по вендору)
+fn tether(c: &mut impl Bus, dev: &str) -> Option<String> {
+    let path = c.device_by_iface(dev).ok()?.into_str()?;
+    let kind = c.property(&path, DEVICE, "DeviceType").ok()?.as_u32();
+    match kind {
+        Some(TYPE_MODEM) => return Some(format!("{} is a mobile broadband connection", dev)),
+        Some(TYPE_BT) => return Some(format!("{} is a Bluetooth connection", dev)),
+        _ => {}
+    }
+    let metered = c.property(&path, DEVICE, "Metered").ok()?.as_u32()?;
+    match metered {
+        METERED_YES => Some(format!("{} is metered, likely a phone hotspot", dev)),
+        METERED_GUESS_YES => Some(format!(
+            "{} is metered (guessed), likely a phone hotspot",
+            dev
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // NetworkManager из таблицы: (путь, свойство) -> значение
+    #[derive(Default)]
+    struct FakeNm {
+        props: BTreeMap<(String, String), Value>,
+        devices: BTreeMap<String, String>,
+    }
+
+    impl FakeNm {
+        fn set(&mut self, path: &str, name: &str, v: Value) {
+            self.props.insert((path.into(), name.into()), v);
+        }
+    }
+
+    impl Bus for FakeNm {
+        fn property(&mut self, path: &str, _: &str, name: &str) -> Result<Value, String> {
+            self.props
+                .get(&(path.into(), name.into()))
+                .cloned()
+                .ok_or_else(|| format!("No such property {}", name))
+        }
+
+        fn device_by_iface(&mut self, dev: &str) -> Result<Value, String> {
+            self.devices
+                .get(dev)
+                .map(|p| Value::Str(p.clone()))
+                .ok_or_else(|| "No device found".into())
+        }
+    }
+
+    fn str(s: &str) -> Value {
+        Value::Str(s.into())
+    }
+
+    fn list(items: &[&str]) -> Value {
+        Value::List(items.iter().map(|s| s.to_string()).collect())
+    }
+
+    // Wi-Fi с двоеточием в имени и шлюзом, VPN поверх него без Ip4Config
+    fn home() -> FakeNm {
+        let mut nm = FakeNm::default();
+        nm.set(NM_PATH, "ActiveConnections", list(&["/A/1", "/A/2"]));
+        nm.set("/A/1", "Id", str("Home: 5G"));
+        nm.set("/A/1", "Devices", list(&["/D/1"]));
+        nm.set("/A/1", "Ip4Config", str("/IP4/1"));
+        nm.set("/A/1", "SpecificObject", str("/AP/7"));
+        nm.set("/IP4/1", "Gateway", str("192.168.1.1"));
+        nm.set("/AP/7", "Ssid", str("HomeNet"));
+        nm.set("/A/2", "Id", str("vpn"));
+        nm.set("/A/2", "Devices", list(&["/D/2"]));
+        nm.set("/A/2", "Ip4Config", str("/"));
+        nm.set("/A/2", "SpecificObject", str("/A/1"));
+        nm.set("/D/1", "Interface", str("wlan0"));
+        nm.set("/D/2", "Interface", str("tun0"));
+        nm
+    }
+
+    #[test]
+    fn walks_active_connections_to_devices_and_gateways() {
+        let got: Vec<_> = connections(&mut home())
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.name, c.device, c.gateway))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("Home: 5G".into(), "wlan0".into(), "192.168.1.1".into()),
+                ("vpn".into(), "tun0".into(), String::new()),
+            ]
+        );
+
+        // Соединение пропало между запросами - ошибка, а не пустое имя
+        let mut gone = home();
+        gone.props.remove(&("/A/2".into(), "Id".into()));
+        assert_eq!(
+            connections(&mut gone).err(),
+            Some("No such property Id".into())
+        );
+    }
+
+    #[test]
+    fn ssid_comes_from_the_access_point() {
+        assert_eq!(ssid(&mut home()), Ok(Some("HomeNet".into())));
+
+        // VPN первым: его SpecificObject - соединение, у него нет Ssid
+        let mut vpn_first = home();
+        vpn_first.set(NM_PATH, "ActiveConnections", list(&["/A/2", "/A/1"]));
+        assert_eq!(ssid(&mut vpn_first), Ok(Some("HomeNet".into())));
+
+        let mut wired = home();
+        wired.set("/A/1", "SpecificObject", str("/"));
+        assert_eq!(ssid(&mut wired), Ok(None));
+    }
+
+    #[test]
+    fn tether_hint_by_type_then_metered() {
+        let mut nm = FakeNm::default();
+        for (dev, kind, metered) in [("usb0", 8, 0), ("bnep0", 5, 0), ("wlan0", 2, 1)] {
+            let path = format!("/D/{}", dev);
+            nm.devices.insert(dev.into(), path.clone());
+            nm.set(&path, "DeviceType", Value::U32(kind));
+            nm.set(&path, "Metered", Value::U32(metered));
+        }
+        nm.devices.insert("eth0".into(), "/D/eth0".into());
+        nm.set("/D/eth0", "DeviceType", Value::U32(1));
+        nm.set("/D/eth0", "Metered", Value::U32(METERED_GUESS_YES));
+        nm.devices.insert("eth1".into(), "/D/eth1".into());
+        nm.set("/D/eth1", "DeviceType", Value::U32(1));
+        nm.set("/D/eth1", "Metered", Value::U32(4));
+
+        let hint = |nm: &mut FakeNm, dev| tether(nm, dev);
+        assert_eq!(
+            hint(&mut nm, "usb0").as_deref(),
+            Some("usb0 is a mobile broadband connection")
+        );
+        assert_eq!(
+            hint(&mut nm, "bnep0").as_deref(),
+            Some("bnep0 is a Bluetooth connection")
+        );
+        assert_eq!(
+            hint(&mut nm, "wlan0").as_deref(),
+            Some("wlan0 is metered, likely a phone hotspot")
+        );
+        assert_eq!(
+            hint(&mut nm, "eth0").as_deref(),
+            Some("eth0 is metered (guessed), likely a phone hotspot")
+        );
+        // GUESS_NO и неизвестное устройство - молчим
+        assert_eq!(hint(&mut nm, "eth1"), None);
+        assert_eq!(hint(&mut nm, "lo"), None);
+    }
+}
@@ -0,0 +1,588 @@
+// === УВЕДОМЛЕНИЯ ===
+// Рабочий стол: демон работает от root, поэтому уведомление отправляется в
+// сессионную шину каждого вошедшего пользователя (/run/user/<uid>/bus) от
+// его имени. Кнопка "Отменить сон" ставит паузу - та срабатывает после grace.
+// Телефон: ntfy и Telegram, с напоминаниями по ходу grace (начало, середина,
+// минута до сна), сообщением перед сном и после подъема и кнопкой паузы.
+// Нажатие ntfy-кнопки публикует "pause" в командный топик <topic>-cmd,
+// нажатие в Telegram приходит callback'ом; оба опрашивает цикл демона.
+// Телеграм-бот понимает и команды: /pause [мин], /resume, /status, /sleepnow -
+// только из telegram_chat_id и telegram_allowed_chats.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fs;
+use std::os::fd::RawFd;
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::events::{Event, Subscriber};
+use crate::state::Phase;
+use crate::{
+    PAUSE_MINUTES_RANGE, QUICK_PAUSE_MINUTES, channels, clear_pause, curl, hostname, log, privsep,
+    reactor, request_sleep_now, set_pause, status_report, timers, unix_now, watchdog,
+};
+
+// Сколько ждать отправки перед сном: после засыпания поток замрет
+const SLEEP_SEND_WAIT: Duration = Duration::from_secs(15);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct NotifyConfig {
+    // "https://ntfy.sh/my-secret-topic"; пусто - ntfy выключен
+    pub ntfy_url: String,
+    pub ntfy_token: String,
+    pub telegram_bot_token: String,
+    pub telegram_chat_id: String,
+    // Кому еще можно командовать ботом (чат уведомлений можно всегда)
+    pub telegram_allowed_chats: Vec<String>,
+    // SMS через LTE-модем, когда интернета нет: номер получателя и модем
+    // ("/dev/ttyUSB2" - AT-команды, иначе индекс ModemManager, пусто - любой)
+    pub sms_number: String,
+    pub sms_modem: String,
+}
+
+impl NotifyConfig {
+    fn ntfy(&self) -> bool {
+        !self.ntfy_url.is_empty()
+    }
+
+    fn telegram(&self) -> bool {
+        !self.telegram_bot_token.is_empty() && !self.telegram_chat_id.is_empty()
+    }
+
+    pub fn sms(&self) -> bool {
+        !self.sms_number.is_empty()
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.ntfy() || self.telegram()
+    }
+
+    fn ntfy_cmd_url(&self) -> String {
+        format!("{}-cmd", self.ntfy_url.trim_end_matches('/'))
+    }
+
+    fn telegram_allowed(&self, chat: &str) -> bool {
+        chat == self.telegram_chat_id || self.telegram_allowed_chats.iter().any(|c| c == chat)
+    }
+
+    // Токен бота - часть URL: отдаем curl только конфигом
+    fn telegram_api(&self, method: &str) -> curl::Config {
+        let mut c = curl::Config::default();
+        c.url(&format!(
+            "https://api.telegram.org/bot{}/{}",
+            self.telegram_bot_token, method
+        ));
+        c
+    }
+
+    fn ntfy_curl(&self, url: &str) -> curl::Config {
+        let mut c = curl::Config::default();
+        c.url(url);
+        if !self.ntfy_token.is_empty() {
+            c.header(&format!("Authorization: Bearer {}", self.ntfy_token));
+        }
+        c
+    }
+}
+
+pub struct DesktopText {
+    pub title: String,
+    pub sleep_at: String,
+    pub cancel: String,
+}
+
+pub struct Desktop {
+    text: DesktopText,
+    tz: crate::schedule::TimeZone,
+}
+
+impl Desktop {
+    pub fn new(text: DesktopText, tz: crate::schedule::TimeZone) -> Self {
+        Self { text, tz }
+    }
+
+    fn notify(&self, grace_sec: u64) {
+        let at = self
+            .tz
+            .to_local((unix_now() + grace_sec) as i64)
+            .time_of_day();
+        let body = format!("{} {} ({} sec)", self.text.sleep_at, at, grace_sec);
+        for (user, bus) in session_buses() {
+            let title = self.text.title.clone();
+            let body = body.clone();
+            let cancel = self.text.cancel.clone();
+            // --wait держит notify-send до закрытия, поэтому каждому свой поток
+            thread::spawn(move || {
+                let mut argv = ["runuser", "-u", &user, "--", "notify-send"]
+                    .map(String::from)
+                    .to_vec();
+                argv.extend(["-u", "critical", "-a", "portal_daemon", "--wait"].map(String::from));
+                argv.push(format!("--expire-time={}", grace_sec * 1000));
+                argv.push(format!("--action=cancel={}", cancel));
+                argv.extend([title, body]);
+                let bus = format!("unix:path={}", bus);
+                let out = privsep::output(None, &argv, &[("DBUS_SESSION_BUS_ADDRESS", bus)]);
+                let Ok(out) = out else {
+                    return;
+                };
+                if String::from_utf8_lossy(&out.stdout).trim() == "cancel" {
+                    match set_pause(QUICK_PAUSE_MINUTES) {
+                        Ok(()) => log::info!("🔔 Sleep cancelled from desktop by {}", user),
+                        Err(e) => log::warn!("⚠️  Cannot pause for {}: {}", user, e),
+                    }
+                }
+            });
+        }
+    }
+}
+
+impl Subscriber for Desktop {
+    fn on_event(&mut self, e: &Event) {
+        if let Event::ConnectionLost { grace_sec } = e {
+            self.notify(*grace_sec);
+        }
+    }
+}
+
+// (пользователь, путь к сокету) для всех, у кого есть сессионная шина
+fn session_buses() -> Vec<(String, String)> {
+    let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+    let Ok(dirs) = fs::read_dir("/run/user") else {
+        return Vec::new();
+    };
+    dirs.flatten()
+        .filter_map(|d| {
+            let uid = d.file_name().to_string_lossy().to_string();
+            let bus = d.path().join("bus");
+            if !bus.exists() {
+                return None;
+            }
+            let user = passwd.lines().find_map(|l| {
+                let f: Vec<&str> = l.split(':').collect();
+                (f.get(2) == Some(&uid.as_str())).then(|| f[0].to_string())
+            })?;
+            Some((user, bus.to_string_lossy().to_string()))
+        })
+        .collect()
+}
+
+// --- ТЕЛЕФОН (ntfy / Telegram) ---
+pub struct RemoteText {
+    pub lost: String,
+    pub sleep_in: String,
+    pub sleeping: String,
+    pub woke: String,
+    pub rtc_drift: String,
+    pub stale: String,
+    pub pause: String,
+}
+
+pub struct Remote {
+    cfg: NotifyConfig,
+    text: RemoteText,
+    // Досыпание после раннего подъема - не новый сон и не подъем
+    rearmed: bool,
+}
+
+impl Remote {
+    pub fn new(cfg: NotifyConfig, text: RemoteText) -> Self {
+        Self {
+            cfg,
+            text,
+            rearmed: false,
+        }
+    }
+
+    // Канал закроется, когда отправка закончится (или не удастся)
+    fn send(&self, message: String, urgent: bool) -> mpsc::Receiver<()> {
+        let (done, rx) = mpsc::channel::<()>();
+        let cfg = self.cfg.clone();
+        let pause = format!("{} {} min", self.text.pause, QUICK_PAUSE_MINUTES);
+        // curl может висеть до таймаута - цикл демона ждать не должен
+        thread::spawn(move || {
+            let _done = done;
+            if cfg.ntfy() {
+                let priority = if urgent { "urgent" } else { "high" };
+                let res = ntfy_send(&cfg, &message, priority, Some(&pause));
+                if let Err(e) = &res {
+                    log::warn!("⚠️  ntfy notification failed: {}", e);
+                }
+                channels::record("ntfy", &res);
+            }
+            if cfg.telegram() {
+                let res = telegram_send(&cfg, &message, Some(&pause), false);
+                if let Err(e) = &res {
+                    log::warn!("⚠️  Telegram notification failed: {}", e);
+                }
+                channels::record("telegram", &res);
+            }
+        });
+        rx
+    }
+}
+
+// Тихое сообщение в каждый канал - проверка токенов при старте
+pub fn self_test(cfg: &NotifyConfig, message: &str) {
+    if cfg.ntfy() {
+        channels::record("ntfy", &ntfy_send(cfg, message, "min", None));
+    }
+    if cfg.telegram() {
+        channels::record("telegram", &telegram_send(cfg, message, None, true));
+    }
+}
+
+fn title() -> String {
+    format!("portal_daemon @ {}", hostname())
+}
+
+// curl -fsS: при ошибке HTTP код и текст - в stderr.
+// URL бота и Authorization - в конфиге curl, не в argv
+fn run_curl(c: &mut Command, cfg: &curl::Config) -> Result<(), String> {
+    let out = curl::output(c, cfg, None).map_err(|e| format!("curl: {}", e))?;
+    if out.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&out.stderr).trim().to_string())
+    }
+}
+
+fn ntfy_send(
+    cfg: &NotifyConfig,
+    message: &str,
+    priority: &str,
+    pause: Option<&str>,
+) -> Result<(), String> {
+    let mut c = Command::new("curl");
+    c.args(["-fsS", "--max-time", "15", "-o", "/dev/null"])
+        .args(["-H", &format!("Title: {}", title())])
+        .args(["-H", &format!("Priority: {}", priority)]);
+    if let Some(pause) = pause {
+        c.args([
+            "-H",
+            &format!(
+                "Actions: http, {}, {}, method=POST, body=pause, clear=true",
+                pause,
+                cfg.ntfy_cmd_url()
+            ),
+        ]);
+    }
+    run_curl(c.args(["-d", message]), &cfg.ntfy_curl(&cfg.ntfy_url))
+}
+
+fn telegram_send(
+    cfg: &NotifyConfig,
+    message: &str,
+    pause: Option<&str>,
+    silent: bool,
+) -> Result<(), String> {
+    let mut c = Command::new("curl");
+    c.args(["-fsS", "--max-time", "15", "-o", "/dev/null"])
+        .args(["-d", &format!("chat_id={}", cfg.telegram_chat_id)])
+        .args([
+            "--data-urlencode",
+            &format!("text={}\n{}", title(), message),
+        ]);
+    if let Some(pause) = pause {
+        let markup = json!({
+            "inline_keyboard": [[{"text": pause, "callback_data": "pause"}]]
+        });
+        c.args(["--data-urlencode", &format!("reply_markup={}", markup)]);
+    }
+    if silent {
+        c.args(["-d", "disable_notification=true"]);
+    }
+    run_curl(&mut c, &cfg.telegram_api("sendMessage"))
+}
+
+impl Subscriber for Remote {
+    fn on_event(&mut self, e: &Event) {
+        match e {
+            Event::ConnectionLost { grace_sec } => {
+                self.send(format!("{} {} sec", self.text.lost, grace_sec), false);
+            }
+            Event::GraceReminder { remaining_sec } => {
+                self.send(
+                    format!("{} {} sec", self.text.sleep_in, remaining_sec),
+                    *remaining_sec <= 60,
+                );
+            }
+            Event::SleepRequested { seconds, mode } if !self.rearmed => {
+                let msg = format!(
+                    "{} {} min ({})",
+                    self.text.sleeping,
+                    seconds.div_ceil(60),
+                    mode
+                );
+                let sent = self.send(msg, false);
+                watchdog::expect_quiet(SLEEP_SEND_WAIT);
+                sent.recv_timeout(SLEEP_SEND_WAIT).ok();
+            }
+            Event::Woke {
+                slept_sec, rearmed, ..
+            } => {
+                self.rearmed = *rearmed;
+                if !*rearmed {
+                    self.send(
+                        format!("{} ({} min)", self.text.woke, slept_sec / 60),
+                        false,
+                    );
+                }
+            }
+            Event::RtcDrift { drift_sec, .. } => {
+                self.send(
+                    format!("{} {:+} sec", self.text.rtc_drift, drift_sec),
+                    false,
+                );
+            }
+            Event::ProbeStale { age_sec } => {
+                self.send(format!("{} {} min", self.text.stale, age_sec / 60), false);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Источники цикла демона, принимающие нажатия кнопки паузы с телефона
+pub fn listen_commands(cfg: &NotifyConfig) {
+    if cfg.ntfy() {
+        reactor::add(NtfyCommands {
+            cfg: cfg.clone(),
+            since: unix_now().to_string(),
+            job: None,
+            next: Instant::now(),
+        });
+    }
+    if cfg.telegram() {
+        reactor::add(TelegramCommands {
+            cfg: cfg.clone(),
+            offset: 0,
+            job: None,
+            next: Instant::now(),
+        });
+    }
+}
+
+fn remote_pause(from: &str) {
+    match set_pause(QUICK_PAUSE_MINUTES) {
+        Ok(()) => log::info!("📱 Sleep paused {} min from {}", QUICK_PAUSE_MINUTES, from),
+        Err(e) => log::warn!("⚠️  Remote pause failed: {}", e),
+    }
+}
+
+fn curl_job(cfg: &curl::Config, args: &[&str]) -> Option<curl::Job> {
+    let mut c = Command::new("curl");
+    c.args(["-fsS", "--max-time", "40"]).args(args);
+    curl::Job::start(&mut c, cfg)
+        .map_err(|e| log::warn!("⚠️  curl: {}", e))
+        .ok()
+}
+
+// Опрос командного топика: по строке JSON на сообщение
+struct NtfyCommands {
+    cfg: NotifyConfig,
+    since: String,
+    job: Option<curl::Job>,
+    next: Instant,
+}
+
+impl reactor::Source for NtfyCommands {
+    fn fd(&self) -> Option<RawFd> {
+        self.job.as_ref().map(curl::Job::fd)
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.job.is_none().then_some(self.next)
+    }
+
+    fn ready(&mut self, _: bool, _: &mut reactor::Spawned) -> bool {
+        let Some(job) = &mut self.job else {
+            let url = format!(
+                "{}/json?poll=1&since={}",
+                self.cfg.ntfy_cmd_url(),
+                self.since
+            );
+            self.job = curl_job(&self.cfg.ntfy_curl(&url), &[]);
+            // На сетке timers: при scan_interval, кратном 15, - вместе с пробой
+            self.next = Instant::now() + timers::until_tick(Duration::from_secs(15));
+            return true;
+        };
+        let Some(body) = job.read() else {
+            return true;
+        };
+        self.job = None;
+        self.next = Instant::now() + timers::until_tick(Duration::from_secs(15));
+        for m in body
+            .iter()
+            .flat_map(|b| b.lines())
+            .filter_map(|l| serde_json::from_str::<Value>(l).ok())
+        {
+            if let Some(id) = m["id"].as_str() {
+                self.since = id.to_string();
+            }
+            if m["event"] == "message" && m["message"].as_str().map(str::trim) == Some("pause") {
+                remote_pause("ntfy");
+            }
+        }
+        true
+    }
+}
+
+// Long polling getUpdates; нажатия и команды - только из разрешенных чатов.
+// Ответы в чат - отдельные Job в цикле, опрос их не ждет
+struct TelegramCommands {
+    cfg: NotifyConfig,
+    offset: i64,
+    job: Option<curl::Job>,
+    next: Instant,
+}
+
+impl reactor::Source for TelegramCommands {
+    fn fd(&self) -> Option<RawFd> {
+        self.job.as_ref().map(curl::Job::fd)
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.job.is_none().then_some(self.next)
+    }
+
+    fn ready(&mut self, _: bool, spawned: &mut reactor::Spawned) -> bool {
+        let cfg = &self.cfg;
+        let Some(job) = &mut self.job else {
+            let params = format!(
+                "offset={}&timeout=30&allowed_updates=[\"callback_query\",\"message\"]",
+                self.offset
+            );
+            self.job = curl_job(&cfg.telegram_api("getUpdates"), &["-G", "--data", &params]);
+            self.next = Instant::now() + Duration::from_secs(30);
+            return true;
+        };
+        let Some(reply) = job.read() else {
+            return true;
+        };
+        self.job = None;
+        let v: Value = reply
+            .and_then(|b| serde_json::from_str(&b).ok())
+            .unwrap_or_default();
+        // Сеть лежит или токен неверный - не долбим API в цикле
+        self.next = if v["ok"] == true {
+            Instant::now()
+        } else {
+            Instant::now() + Duration::from_secs(30)
+        };
+        let mut answer = |method: &str, args: &[&str]| {
+            if let Some(job) = curl_job(&cfg.telegram_api(method), args) {
+                spawned.push(Box::new(job));
+            }
+        };
+        for u in v["result"].as_array().into_iter().flatten() {
+            self.offset = self.offset.max(u["update_id"].as_i64().unwrap_or(0) + 1);
+            if let Some(text) = u["message"]["text"].as_str() {
+                let chat = u["message"]["chat"]["id"].to_string();
+                if cfg.telegram_allowed(&chat) {
+                    answer(
+                        "sendMessage",
+                        &[
+                            "-d",
+                            &format!("chat_id={}", chat),
+                            "--data-urlencode",
+                            &format!("text={}", telegram_command(text)),
+                        ],
+                    );
+                } else {
+                    log::warn!(chat = chat; "⚠️  Telegram command from a chat not allowed");
+                }
+                continue;
+            }
+            let q = &u["callback_query"];
+            let chat = q["message"]["chat"]["id"].to_string();
+            if q["data"] != "pause" || !cfg.telegram_allowed(&chat) {
+                continue;
+            }
+            remote_pause("Telegram");
+            if let Some(id) = q["id"].as_str() {
+                answer(
+                    "answerCallbackQuery",
+                    &[
+                        "-d",
+                        &format!("callback_query_id={}", id),
+                        "-d",
+                        &format!("text=⏸ {} min", QUICK_PAUSE_MINUTES),
+                    ],
+                );
+            }
+        }
+        true
+    }
+}
+
+// "/pause@my_bot 60" -> ответ в чат
+fn telegram_command(text: &str) -> String {
+    let mut words = text.split_whitespace();
+    let cmd = words.next().unwrap_or("");
+    let cmd = cmd.split('@').next().unwrap_or(cmd);
+    let fail = |e: std::io::Error| format!("❌ {}", e);
+    match cmd {
+        "/pause" => {
+            let mins = match words.next().map(str::parse::<u64>) {
+                None => QUICK_PAUSE_MINUTES,
+                Some(Ok(m)) if PAUSE_MINUTES_RANGE.contains(&m) => m,
+                Some(_) => {
+                    return format!(
+                        "❌ /pause {}..{}",
+                        PAUSE_MINUTES_RANGE.start(),
+                        PAUSE_MINUTES_RANGE.end()
+                    );
+                }
+            };
+            log::info!("📱 Sleep paused {} min from Telegram", mins);
+            set_pause(mins).map_or_else(fail, |_| format!("⏸ {} min", mins))
+        }
+        "/resume" => {
+            log::info!("📱 Pause removed from Telegram");
+            clear_pause().map_or_else(fail, |_| "▶️ ok".into())
+        }
+        "/sleepnow" => {
+            log::info!("📱 Sleep now requested from Telegram");
+            request_sleep_now();
+            "💤 ok".into()
+        }
+        "/status" => telegram_status(),
+        _ => "/pause [min] · /resume · /status · /sleepnow".into(),
+    }
+}
+
+fn telegram_status() -> String {
+    let r = status_report();
+    let mut out = format!("📊 {}", hostname());
+    match &r.daemon {
+        Some(st) => {
+            out += &format!(": {:?}", st.phase);
+            if let Some(rtt) = st.last_rtt_ms {
+                out += &format!(", RTT {:.1} ms", rtt);
+            }
+            if st.phase == Phase::Grace && st.sleep_at > 0 {
+                let left = st.sleep_at.saturating_sub(unix_now());
+                out += &format!(", 💤 {} sec", left);
+            }
+            let failing: Vec<&str> = st
+                .channels
+                .iter()
+                .filter(|(_, c)| !c.ok)
+                .map(|(n, _)| n.as_str())
+                .collect();
+            if !failing.is_empty() {
+                out += &format!(", ⚠️ {}", failing.join(", "));
+            }
+        }
+        None => out += ": not running",
+    }
+    if r.pause_until.is_some() {
+        let left = r.pause_remaining_sec;
+        out += &format!(", ⏸ {}h {:02}m", left / 3600, left % 3600 / 60);
+    }
+    out
+}
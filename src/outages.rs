@@ -0,0 +1,254 @@
+// === ГРАФИКИ ОТКЛЮЧЕНИЙ (Yasno/DTEK и подобные) ===
+// Опубликованный график позволяет проснуться ровно к часу включения, а потерю
+// связи вне графика считать скорее сбоем роутера и ждать дольше.
+// Последний удачно скачанный график кладется на диск: во время отключения
+// интернета, как правило, нет.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::process::Command;
+
+use crate::schedule::{TimeWindow, TimeZone, active_window};
+use crate::{log, state, unix_now};
+
+const CACHE_FILE: &str = "outage_schedule.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct OutageScheduleConfig {
+    pub enabled: bool,
+    // Пусто - используются только `windows` ниже
+    pub url: String,
+    // "yasno": schedule[region][group] = 7 дней из {start, end, type};
+    // "windows": JSON-массив строк "mon 18:00-21:00"
+    pub format: String,
+    pub region: String,
+    pub group: String,
+    pub include_possible: bool,
+    pub refresh_minutes: u64,
+    // Ручной график в том же формате, что и quiet_hours
+    pub windows: Vec<String>,
+    // Добавка к грейс-периоду, если связь пропала вне графика
+    pub unscheduled_extra_grace_sec: u64,
+    // Запас после планового включения (роутеру нужно загрузиться)
+    pub wake_margin_sec: u64,
+}
+
+impl Default for OutageScheduleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            format: "yasno".into(),
+            region: "kiev".into(),
+            group: "1".into(),
+            include_possible: false,
+            refresh_minutes: 60,
+            windows: Vec::new(),
+            unscheduled_extra_grace_sec: 300,
+            wake_margin_sec: 60,
+        }
+    }
+}
+
+pub struct OutageSchedule {
+    cfg: OutageScheduleConfig,
+    windows: Vec<TimeWindow>,
+    last_fetch: u64,
+}
+
+impl OutageSchedule {
+    pub fn new(cfg: &OutageScheduleConfig) -> Self {
+        let mut s = Self {
+            cfg: cfg.clone(),
+            windows: Vec::new(),
+            last_fetch: 0,
+        };
+        if !cfg.enabled {
+            return s;
+        }
+        s.windows = cfg
+            .windows
+            .iter()
+            .filter_map(|w| TimeWindow::parse(w))
+            .collect();
+        if !cfg.url.is_empty()
+            && let Ok(body) = fs::read_to_string(state::persistent(CACHE_FILE))
+            && let Some(w) = s.parse(&body)
+        {
+            s.windows.extend(w);
+        }
+        s
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.cfg.enabled
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+
+    // Вызывается, пока связь есть: обновляет график не чаще refresh_minutes
+    pub fn refresh_if_due(&mut self) {
+        if !self.cfg.enabled || self.cfg.url.is_empty() {
+            return;
+        }
+        let now = unix_now();
+        if now < self.last_fetch + self.cfg.refresh_minutes.max(1) * 60 {
+            return;
+        }
+        self.last_fetch = now;
+        let out = Command::new("curl")
+            .args(["-fsS", "--max-time", "15", &self.cfg.url])
+            .output();
+        let body = match out {
+            Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
+            _ => {
+                log::warn!("⚠️  Outage schedule fetch failed, keeping the cached one.");
+                return;
+            }
+        };
+        match self.parse(&body) {
+            Some(w) => {
+                let manual = self.cfg.windows.iter().filter_map(|w| TimeWindow::parse(w));
+                self.windows = manual.chain(w).collect();
+                state::write_persistent(CACHE_FILE, body);
+                log::info!(
+                    "📅 Outage schedule updated: {} window(s).",
+                    self.windows.len()
+                );
+            }
+            None => log::warn!("⚠️  Outage schedule has unexpected format."),
+        }
+    }
+
+    fn parse(&self, body: &str) -> Option<Vec<TimeWindow>> {
+        let v: Value = serde_json::from_str(body).ok()?;
+        match self.cfg.format.as_str() {
+            "windows" => Some(
+                v.as_array()?
+                    .iter()
+                    .filter_map(|w| w.as_str().and_then(TimeWindow::parse))
+                    .collect(),
+            ),
+            _ => parse_yasno(&v, &self.cfg),
+        }
+    }
+
+    // Отключение по графику прямо сейчас
+    pub fn active(&self, tz: &TimeZone, now: i64) -> Option<&TimeWindow> {
+        active_window(&self.windows, tz, now)
+    }
+
+    // Сколько спать до планового включения (с запасом), если сейчас окно графика
+    pub fn sleep_until_restoration(&self, tz: &TimeZone, now: i64) -> Option<u64> {
+        let mut w = self.active(tz, now)?;
+        let mut end = w.end_after(tz, now);
+        // Соседние слоты графика (18-19, 19-20) склеиваем в одно отключение
+        for _ in 0..48 {
+            match self.active(tz, end) {
+                Some(next) if next != w => {
+                    w = next;
+                    end = w.end_after(tz, end);
+                }
+                _ => break,
+            }
+        }
+        let end = end + self.cfg.wake_margin_sec as i64;
+        Some((end - now).max(60) as u64)
+    }
+
+    pub fn extra_grace(&self, tz: &TimeZone, now: i64) -> u64 {
+        if self.cfg.enabled && !self.windows.is_empty() && self.active(tz, now).is_none() {
+            self.cfg.unscheduled_extra_grace_sec
+        } else {
+            0
+        }
+    }
+}
+
+// Ищем schedule[region]["group_<N>"] где угодно в ответе: обертка страницы
+// меняется чаще, чем сам формат графика.
+fn parse_yasno(v: &Value, cfg: &OutageScheduleConfig) -> Option<Vec<TimeWindow>> {
+    let group_key = if cfg.group.starts_with("group_") {
+        cfg.group.clone()
+    } else {
+        format!("group_{}", cfg.group)
+    };
+    let days = find_group(v, &cfg.region, &group_key)?.as_array()?;
+    let mut out = Vec::new();
+    for (day, slots) in days.iter().enumerate().take(7) {
+        for slot in slots.as_array().into_iter().flatten() {
+            let kind = slot.get("type").and_then(Value::as_str).unwrap_or("");
+            let counts =
+                kind == "DEFINITE_OUTAGE" || (cfg.include_possible && kind == "POSSIBLE_OUTAGE");
+            if !counts {
+                continue;
+            }
+            let start = slot.get("start").and_then(Value::as_f64);
+            let end = slot.get("end").and_then(Value::as_f64);
+            if let (Some(s), Some(e)) = (start, end)
+                && let Some(w) = TimeWindow::from_hours(day as u32, s, e)
+            {
+                out.push(w);
+            }
+        }
+    }
+    Some(out)
+}
+
+fn find_group<'a>(v: &'a Value, region: &str, group: &str) -> Option<&'a Value> {
+    match v {
+        Value::Object(m) => {
+            if let Some(g) = m
+                .get("schedule")
+                .and_then(|s| s.get(region))
+                .and_then(|r| r.get(group))
+            {
+                return Some(g);
+            }
+            m.values().find_map(|x| find_group(x, region, group))
+        }
+        Value::Array(a) => a.iter().find_map(|x| find_group(x, region, group)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::days_from_civil;
+
+    #[test]
+    fn yasno_group_schedule_and_restoration() {
+        let body = r#"{"components": [{"template_name": "electricity-outages-schedule",
+            "schedule": {"kiev": {"group_2": [[], [], [
+                {"start": 18, "end": 19, "type": "DEFINITE_OUTAGE"},
+                {"start": 19, "end": 21.5, "type": "DEFINITE_OUTAGE"},
+                {"start": 22, "end": 23, "type": "POSSIBLE_OUTAGE"}
+            ], [], [], [], []]}}}]}"#;
+        let cfg = OutageScheduleConfig {
+            enabled: true,
+            group: "2".into(),
+            wake_margin_sec: 0,
+            ..Default::default()
+        };
+        let mut s = OutageSchedule::new(&cfg);
+        s.windows = s.parse(body).unwrap();
+        assert_eq!(s.window_count(), 2);
+
+        let tz = TimeZone::from_posix("EET-2EEST,M3.5.0/3,M10.5.0/4").unwrap();
+        // Среда 14.10.2026 18:10 по Киеву: спать до 21:30, склеив оба слота
+        let now = days_from_civil(2026, 10, 14) * 86400 + 15 * 3600 + 10 * 60;
+        assert_eq!(
+            s.sleep_until_restoration(&tz, now),
+            Some(3 * 3600 + 20 * 60)
+        );
+        assert_eq!(s.extra_grace(&tz, now), 0);
+        // В 22:10 отключение лишь "возможное" - вне графика
+        assert_eq!(s.sleep_until_restoration(&tz, now + 4 * 3600), None);
+        assert_eq!(s.extra_grace(&tz, now + 4 * 3600), 300);
+    }
+}
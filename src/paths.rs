@@ -0,0 +1,102 @@
+// === ПУТИ ===
+// Системный демон (root): конфиг в /etc, история в /var/lib, состояние и
+// пауза в /run. С --user все то же лежит в каталогах XDG пользователя:
+// ноутбуку root не нужен, а спать отправляет logind. Выбирается один раз
+// в начале main, до первого обращения к файлам. На FreeBSD системные
+// каталоги - по hier(7): /usr/local/etc, /var/db, /var/run.
+
+use std::env;
+use std::sync::OnceLock;
+
+struct Dirs {
+    user: bool,
+    config: String,
+    state: String,
+    run: String,
+}
+
+static DIRS: OnceLock<Dirs> = OnceLock::new();
+
+impl Dirs {
+    fn system() -> Self {
+        if crate::freebsd::is() {
+            return Self {
+                user: false,
+                config: "/usr/local/etc/portal_daemon".into(),
+                state: "/var/db/portal_daemon".into(),
+                run: "/var/run/portal_daemon".into(),
+            };
+        }
+        Self {
+            user: false,
+            config: "/etc/portal_daemon".into(),
+            state: "/var/lib/portal_daemon".into(),
+            run: "/run/portal_daemon".into(),
+        }
+    }
+
+    // $XDG_*_HOME, иначе умолчания спецификации от $HOME
+    fn user() -> Result<Self, String> {
+        let home = env::var("HOME")
+            .ok()
+            .filter(|h| !h.is_empty())
+            .ok_or("$HOME is not set")?;
+        let xdg = |var: &str, fallback: &str| {
+            env::var(var)
+                .ok()
+                .filter(|d| d.starts_with('/'))
+                .unwrap_or_else(|| format!("{}/{}", home, fallback))
+        };
+        let state = format!("{}/portal_daemon", xdg("XDG_STATE_HOME", ".local/state"));
+        // Без $XDG_RUNTIME_DIR (не через логин) - рядом с историей
+        let run = match env::var("XDG_RUNTIME_DIR") {
+            Ok(d) if d.starts_with('/') => format!("{}/portal_daemon", d),
+            _ => format!("{}/run", state),
+        };
+        Ok(Self {
+            user: true,
+            config: format!("{}/portal_daemon", xdg("XDG_CONFIG_HOME", ".config")),
+            state,
+            run,
+        })
+    }
+}
+
+pub fn init(user: bool) -> Result<(), String> {
+    let dirs = if user { Dirs::user()? } else { Dirs::system() };
+    DIRS.set(dirs)
+        .map_err(|_| "paths already chosen".to_string())
+}
+
+fn dirs() -> &'static Dirs {
+    DIRS.get_or_init(Dirs::system)
+}
+
+// Запущены с --user
+pub fn user() -> bool {
+    dirs().user
+}
+
+pub fn config_dir() -> &'static str {
+    &dirs().config
+}
+
+pub fn state_dir() -> &'static str {
+    &dirs().state
+}
+
+pub fn run_dir() -> &'static str {
+    &dirs().run
+}
+
+pub fn config(name: &str) -> String {
+    format!("{}/{}", config_dir(), name)
+}
+
+pub fn state(name: &str) -> String {
+    format!("{}/{}", state_dir(), name)
+}
+
+pub fn run(name: &str) -> String {
+    format!("{}/{}", run_dir(), name)
+}
@@ -0,0 +1,98 @@
+// === ПИТАНИЕ НОУТБУКА (/sys/class/power_supply) ===
+// На ноутбуке пропажа AC - сигнал отключения надежнее пинга роутера,
+// а по заряду батареи выбирается режим сна: при низком заряде suspend
+// в RAM может не дожить до утра, поэтому уходим в disk.
+
+use std::fs;
+use std::path::Path;
+
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+fn read(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn supplies() -> Vec<(String, std::path::PathBuf)> {
+    let Ok(entries) = fs::read_dir(POWER_SUPPLY_DIR) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let p = e.path();
+            read(&p, "type").map(|t| (t, p))
+        })
+        .collect()
+}
+
+// Some(true) - есть сетевое питание; None - адаптеров нет (десктоп/VM)
+pub fn ac_online() -> Option<bool> {
+    if crate::freebsd::is() {
+        return crate::freebsd::acline();
+    }
+    let mains: Vec<bool> = supplies()
+        .iter()
+        .filter(|(t, _)| t == "Mains" || t == "USB")
+        .filter_map(|(_, p)| read(p, "online").map(|v| v == "1"))
+        .collect();
+    if mains.is_empty() {
+        None
+    } else {
+        Some(mains.iter().any(|&o| o))
+    }
+}
+
+// Минимальный заряд среди батарей, %
+pub fn battery_percent() -> Option<u8> {
+    if let Some(p) = crate::inject::battery() {
+        return Some(p);
+    }
+    supplies()
+        .iter()
+        .filter(|(t, _)| t == "Battery")
+        .filter_map(|(_, p)| read(p, "capacity").and_then(|c| c.parse().ok()))
+        .min()
+}
+
+// Сколько продержится батарея при текущем расходе, минут: energy_now/power_now
+// (мкВт·ч, мкВт) или charge_now/current_now; на зарядке расхода нет - None
+pub fn battery_runtime_min() -> Option<u64> {
+    supplies()
+        .iter()
+        .filter(|(t, p)| t == "Battery" && read(p, "status").as_deref() == Some("Discharging"))
+        .filter_map(|(_, p)| {
+            let num = |f: &str| read(p, f).and_then(|v| v.parse::<f64>().ok());
+            let (left, rate) = match (num("energy_now"), num("power_now")) {
+                (Some(e), Some(w)) => (e, w),
+                _ => (num("charge_now")?, num("current_now")?),
+            };
+            (rate > 0.0).then(|| (left / rate * 60.0) as u64)
+        })
+        .min()
+}
+
+// Что разбудило машину: IRQ из /sys/power/pm_wakeup_irq с именем из /proc/interrupts
+pub fn wakeup_source() -> Option<String> {
+    let irq = fs::read_to_string("/sys/power/pm_wakeup_irq").ok()?;
+    let irq = irq.trim();
+    let name = fs::read_to_string("/proc/interrupts")
+        .ok()
+        .and_then(|all| {
+            all.lines().find_map(|l| {
+                let (n, rest) = l.trim_start().split_once(':')?;
+                (n == irq).then(|| rest.split_whitespace().last().map(str::to_string))?
+            })
+        })
+        .unwrap_or_default();
+    Some(format!("irq {} {}", irq, name).trim().to_string())
+}
+
+// Время загрузки системы: btime из /proc/stat
+pub fn boot_time() -> Option<u64> {
+    fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|l| l.strip_prefix("btime ")?.trim().parse().ok())
+}
@@ -0,0 +1,79 @@
+// === ПРЕСЕТЫ МАСТЕРА ===
+// Опций стало много, а типичных машин - три. Пресет заполняет пробу,
+// защиты, режим сна и интервалы разом; мастер потом спрашивает только
+// маяк и показывает значения пресета как ответы по умолчанию.
+
+use serde::Serialize;
+
+use crate::{PortalConfig, power, probe, store};
+
+#[derive(clap::ValueEnum, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    // Ноутбук: свет видно по AC, спим часто и ненадолго
+    Laptop,
+    // Домашний сервер/NAS: не спим посреди бэкапа, роутеру даем загрузиться
+    Homeserver,
+    // Одноплатник на солнце/аккумуляторе: suspend обычно нет - выключаемся,
+    // будит RTC или плата питания; SD-карту бережем
+    SbcOffgrid,
+}
+
+pub const ALL: [Preset; 3] = [Preset::Laptop, Preset::Homeserver, Preset::SbcOffgrid];
+
+impl Preset {
+    pub fn apply(self, cfg: &mut PortalConfig) {
+        match self {
+            Preset::Laptop => {
+                cfg.sleep_minutes = 30;
+                cfg.grace_period_sec = 120;
+                cfg.wakeup_wait_sec = 15;
+                cfg.scan_interval_sec = 30;
+                cfg.sleep_mode = "mem".into();
+                cfg.low_battery_percent = 15;
+                cfg.hotspot_guard = true;
+                cfg.respect_inhibitors = true;
+            }
+            Preset::Homeserver => {
+                cfg.sleep_minutes = 60;
+                cfg.grace_period_sec = 300;
+                // Роутеру и дискам NAS нужно время после включения
+                cfg.wakeup_wait_sec = 90;
+                cfg.scan_interval_sec = 60;
+                cfg.sleep_mode = "mem".into();
+                cfg.inhibit_processes = ["rsync", "borg", "restic", "ffmpeg"]
+                    .map(String::from)
+                    .to_vec();
+                cfg.disk_io_threshold_mbps = 5.0;
+                cfg.respect_inhibitors = true;
+            }
+            Preset::SbcOffgrid => {
+                cfg.sleep_minutes = 120;
+                cfg.grace_period_sec = 600;
+                cfg.wakeup_wait_sec = 120;
+                cfg.scan_interval_sec = 120;
+                cfg.sleep_mode = "off".into();
+                cfg.temp_fallback_mode = "off".into();
+                cfg.history_backend = store::Backend::Memory;
+                cfg.history_sync_sec = 3600;
+                cfg.timer_slack_ms = 1000;
+            }
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Laptop => "laptop",
+            Preset::Homeserver => "homeserver",
+            Preset::SbcOffgrid => "sbc-offgrid",
+        }
+    }
+
+    // Проба, которую пресет предпочитает определению по окружению
+    pub fn probe(self) -> Option<probe::ProbeKind> {
+        match self {
+            Preset::Laptop if power::ac_online().is_some() => Some(probe::ProbeKind::PowerSupply),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,584 @@
+// === СБРОС ПРАВ (run_as) ===
+// Системный демон стартует от root, но root ему нужен ненадолго: создать
+// каталоги, взять блокировку, прочитать секреты. С "run_as": "portal" сразу
+// после этого демон становится пользователем portal и оставляет себе только
+// CAP_NET_RAW (свой ICMP и ARP) и CAP_NET_BIND_SERVICE (http_port < 1024).
+// То, без чего не уснуть и не проснуться, делает помощник: процесс-потомок,
+// который остается root и слушает /run/portal_daemon/helper.sock только для
+// нашего uid. Он запускает короткий список команд - rtcwake, ethtool для
+// Wake-on-LAN, notify-send от имени вошедшего пользователя - и больше ничего.
+// sudo тут не годится: в unit стоит NoNewPrivileges. Помощник живет, пока жив
+// демон, и переживает обновление на месте - PID тот же.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::{log, paths, secrets};
+
+const HELPER_SOCK: &str = "helper.sock";
+// Строка помощника в stdout: сокет открыт, можно сбрасывать права
+const READY: &str = "ready";
+// Как часто помощник проверяет, жив ли демон
+const PARENT_CHECK: Duration = Duration::from_secs(2);
+
+// linux/capability.h
+#[cfg(target_os = "linux")]
+const CAP_VERSION_3: u32 = 0x2008_0522;
+#[cfg(target_os = "linux")]
+const CAP_NET_BIND_SERVICE: u32 = 10;
+#[cfg(target_os = "linux")]
+const CAP_NET_RAW: u32 = 13;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Serialize, Deserialize)]
+struct Request {
+    argv: Vec<String>,
+    env: Vec<(String, String)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Reply {
+    // Статус wait(2) как есть: код выхода или сигнал
+    status: i32,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, PartialEq)]
+struct Account {
+    uid: u32,
+    gid: u32,
+    groups: Vec<u32>,
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: i32,
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+// Права уже сброшены: команды root только через помощника
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+// Ok(false) - root уже нет, а помощник есть: это exec после обновления
+// rtc - устройство будильника из rtcwake_args: другое помощник не тронет
+pub fn drop_to(name: &str, rtc: &str) -> Result<bool, String> {
+    let sock = paths::run(HELPER_SOCK);
+    if unsafe { libc::geteuid() } != 0 {
+        if UnixStream::connect(&sock).is_ok() {
+            ACTIVE.store(true, Ordering::Relaxed);
+            return Ok(false);
+        }
+        return Err("not running as root".into());
+    }
+    if crate::freebsd::is() {
+        return Err("not supported on FreeBSD (acpiconf needs root)".into());
+    }
+    let passwd = fs::read_to_string("/etc/passwd").map_err(|e| format!("/etc/passwd: {}", e))?;
+    let group = fs::read_to_string("/etc/group").unwrap_or_default();
+    let acc = account(name, &passwd, &group).ok_or(format!("no user '{}'", name))?;
+    if acc.uid == 0 {
+        return Err("run_as must not be root".into());
+    }
+    spawn_helper(acc.uid, rtc)?;
+    // Сделанное от root до сброса (блокировка, история) переходит пользователю
+    chown_tree(Path::new(paths::state_dir()), &acc);
+    // Секреты перечитываются и после exec при обновлении - уже без root
+    chown_tree(Path::new(&paths::config(secrets::STORE_DIR)), &acc);
+    if let Ok(entries) = fs::read_dir(paths::run_dir()) {
+        for e in entries.flatten() {
+            std::os::unix::fs::chown(e.path(), Some(acc.uid), None).ok();
+        }
+    }
+    let switched = switch_user(&acc);
+    // Сорвалось на полпути, но root уже нет - дальше тоже только через помощника
+    if unsafe { libc::geteuid() } != 0 {
+        ACTIVE.store(true, Ordering::Relaxed);
+    }
+    switched.map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+// Команда, которой нужен root. После сброса - через помощника, до него -
+// как раньше: напрямую или через sudo/doas (wrapper)
+pub fn output(
+    wrapper: Option<&str>,
+    argv: &[String],
+    env: &[(&str, String)],
+) -> io::Result<Output> {
+    if active() {
+        return ask(argv, env);
+    }
+    let mut cmd = match wrapper {
+        Some(w) => {
+            let mut c = Command::new(w);
+            c.args(argv);
+            c
+        }
+        None => {
+            let mut c = Command::new(&argv[0]);
+            c.args(&argv[1..]);
+            c
+        }
+    };
+    cmd.envs(env.iter().map(|(k, v)| (k, v))).output()
+}
+
+fn ask(argv: &[String], env: &[(&str, String)]) -> io::Result<Output> {
+    let req = Request {
+        argv: argv.to_vec(),
+        env: env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect(),
+    };
+    let mut stream = UnixStream::connect(paths::run(HELPER_SOCK))?;
+    writeln!(stream, "{}", serde_json::to_string(&req)?)?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let reply: Reply = serde_json::from_str(&line)
+        .map_err(|_| io::Error::other("privileged helper closed the connection"))?;
+    if reply.status < 0 {
+        return Err(io::Error::other(reply.stderr));
+    }
+    Ok(Output {
+        status: std::process::ExitStatus::from_raw(reply.status),
+        stdout: reply.stdout.into_bytes(),
+        stderr: reply.stderr.into_bytes(),
+    })
+}
+
+// Тот же бинарник, скрытая подкоманда: fork без exec в процессе с потоками
+// опасен, а Command делает fork+exec
+fn spawn_helper(uid: u32, rtc: &str) -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut child = Command::new(exe)
+        .args(["privhelper", "--uid", &uid.to_string(), "--rtc", rtc])
+        .args(["--parent", &std::process::id().to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot start the helper: {}", e))?;
+    let mut line = String::new();
+    if let Some(out) = child.stdout.take() {
+        BufReader::new(out).read_line(&mut line).ok();
+    }
+    if line.trim() != READY {
+        child.kill().ok();
+        child.wait().ok();
+        return Err("the helper did not start".into());
+    }
+    Ok(())
+}
+
+// --- ПОМОЩНИК (portal_daemon privhelper) ---
+pub fn serve(uid: u32, parent: u32, rtc: String) -> ! {
+    // PDEATHSIG не годится: сигнал шлется с правами уже не-root родителя.
+    // Демон умер - нас подобрал init, PID родителя сменился
+    thread::spawn(move || {
+        loop {
+            if unsafe { libc::getppid() } as u32 != parent {
+                std::process::exit(0);
+            }
+            thread::sleep(PARENT_CHECK);
+        }
+    });
+    let sock = paths::run(HELPER_SOCK);
+    fs::remove_file(&sock).ok();
+    let listener = match UnixListener::bind(&sock) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("❌ privhelper: {}: {}", sock, e);
+            std::process::exit(1);
+        }
+    };
+    std::os::unix::fs::chown(&sock, Some(uid), None).ok();
+    fs::set_permissions(&sock, fs::Permissions::from_mode(0o600)).ok();
+    println!("{}", READY);
+    io::stdout().flush().ok();
+    for stream in listener.incoming().flatten() {
+        // Один клиент может ждать долго (rtcwake спит, notify-send --wait)
+        let rtc = rtc.clone();
+        thread::spawn(move || handle(stream, uid, &rtc));
+    }
+    std::process::exit(0);
+}
+
+fn handle(stream: UnixStream, uid: u32, rtc: &str) {
+    if peer_uid(&stream) != Some(uid) {
+        return;
+    }
+    let mut line = String::new();
+    if BufReader::new(&stream).read_line(&mut line).is_err() {
+        return;
+    }
+    let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+    let reply = match serde_json::from_str::<Request>(&line) {
+        Ok(req) if allowed(&req, rtc, &passwd, socket_owner) => run(&req),
+        Ok(req) => {
+            log::warn!("⚠️  privhelper: refused {:?}", req.argv);
+            failure("not allowed")
+        }
+        Err(e) => failure(&e.to_string()),
+    };
+    if let Ok(json) = serde_json::to_string(&reply) {
+        writeln!(&stream, "{}", json).ok();
+    }
+}
+
+fn run(req: &Request) -> Reply {
+    if let [op, iface] = req.argv.as_slice()
+        && op == "wakeup"
+    {
+        let path = format!("/sys/class/net/{}/device/power/wakeup", iface);
+        return match fs::write(&path, "enabled") {
+            Ok(()) => Reply {
+                status: 0,
+                stdout: String::new(),
+                stderr: String::new(),
+            },
+            Err(e) => failure(&format!("{}: {}", path, e)),
+        };
+    }
+    let out = Command::new(&req.argv[0])
+        .args(&req.argv[1..])
+        .envs(req.env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .output();
+    match out {
+        Ok(o) => Reply {
+            status: o.status.into_raw(),
+            stdout: String::from_utf8_lossy(&o.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&o.stderr).into_owned(),
+        },
+        Err(e) => failure(&format!("{}: {}", req.argv[0], e)),
+    }
+}
+
+fn failure(msg: &str) -> Reply {
+    Reply {
+        status: -1,
+        stdout: String::new(),
+        stderr: msg.to_string(),
+    }
+}
+
+// Ровно то, что демон делает с правами root, - не "любая команда".
+// Помощник - граница прав: демон под portal мог быть взломан
+fn allowed(req: &Request, rtc: &str, passwd: &str, owner: impl Fn(&Path) -> Option<u32>) -> bool {
+    let argv: Vec<&str> = req.argv.iter().map(String::as_str).collect();
+    let iface = |s: &str| !s.is_empty() && !s.contains('/') && !s.starts_with('.');
+    match argv.as_slice() {
+        ["rtcwake", args @ ..] => req.env.is_empty() && rtcwake_args(args, rtc),
+        ["ethtool", "-s", i, "wol", "g"] => req.env.is_empty() && iface(i),
+        ["wakeup", i] => req.env.is_empty() && iface(i),
+        ["runuser", "-u", user, "--", "notify-send", ..] => session_bus(req, user, passwd, owner),
+        _ => false,
+    }
+}
+
+// Только то, что собирает sleep(): -m <режим>, -u|-l, -t <время> и
+// устройство из конфига (в любой записи, что понимает rtc::device)
+fn rtcwake_args(args: &[&str], rtc: &str) -> bool {
+    const MODES: [&str; 9] = [
+        "standby", "freeze", "mem", "disk", "off", "no", "on", "disable", "show",
+    ];
+    let dev = |d: &str| d.trim_start_matches("/dev/") == rtc;
+    let mut it = args.iter();
+    while let Some(&a) = it.next() {
+        let ok = match a {
+            "-u" | "-l" => true,
+            "-m" => it.next().is_some_and(|m| MODES.contains(m)),
+            "-t" => it
+                .next()
+                .is_some_and(|t| !t.is_empty() && t.bytes().all(|b| b.is_ascii_digit())),
+            "-d" | "--device" => it.next().is_some_and(|d| dev(d)),
+            _ => a.strip_prefix("--device=").is_some_and(dev),
+        };
+        if !ok {
+            return false;
+        }
+    }
+    true
+}
+
+// notify-send от user - только в его собственную сессионную шину:
+// DBUS_SESSION_BUS_ADDRESS ровно unix:path=/run/user/<uid>/bus, сокет
+// принадлежит uid, и это uid user по /etc/passwd
+fn session_bus(
+    req: &Request,
+    user: &str,
+    passwd: &str,
+    owner: impl Fn(&Path) -> Option<u32>,
+) -> bool {
+    let [(key, addr)] = req.env.as_slice() else {
+        return false;
+    };
+    let Some(uid) = addr
+        .strip_prefix("unix:path=/run/user/")
+        .and_then(|r| r.strip_suffix("/bus"))
+        .filter(|u| !u.is_empty() && u.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|u| u.parse::<u32>().ok())
+    else {
+        return false;
+    };
+    key == "DBUS_SESSION_BUS_ADDRESS"
+        && uid != 0
+        && account(user, passwd, "").is_some_and(|a| a.uid == uid)
+        && owner(Path::new(&format!("/run/user/{}/bus", uid))) == Some(uid)
+}
+
+// Владелец сокета; симлинк или обычный файл - None
+fn socket_owner(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    let m = fs::symlink_metadata(path).ok()?;
+    m.file_type().is_socket().then_some(m.uid())
+}
+
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let r = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    (r == 0).then_some(cred.uid)
+}
+
+// На BSD SO_PEERCRED нет - то же дает getpeereid
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    let (mut uid, mut gid) = (0, 0);
+    let r = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    (r == 0).then_some(uid)
+}
+
+// --- СМЕНА ПОЛЬЗОВАТЕЛЯ ---
+fn account(name: &str, passwd: &str, group: &str) -> Option<Account> {
+    let (uid, gid) = passwd.lines().find_map(|l| {
+        let f: Vec<&str> = l.split(':').collect();
+        if f.len() < 4 || f[0] != name {
+            return None;
+        }
+        Some((f[2].parse().ok()?, f[3].parse().ok()?))
+    })?;
+    let mut groups = vec![gid];
+    for l in group.lines() {
+        let f: Vec<&str> = l.split(':').collect();
+        if f.len() > 3
+            && f[3].split(',').any(|m| m == name)
+            && let Ok(g) = f[2].parse()
+            && !groups.contains(&g)
+        {
+            groups.push(g);
+        }
+    }
+    Some(Account { uid, gid, groups })
+}
+
+fn chown_tree(dir: &Path, acc: &Account) {
+    std::os::unix::fs::chown(dir, Some(acc.uid), Some(acc.gid)).ok();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for e in entries.flatten() {
+        let path = e.path();
+        if e.file_type().is_ok_and(|t| t.is_dir()) {
+            chown_tree(&path, acc);
+        } else {
+            std::os::unix::fs::chown(&path, Some(acc.uid), Some(acc.gid)).ok();
+        }
+    }
+}
+
+fn switch_user(acc: &Account) -> io::Result<()> {
+    let check = |r: libc::c_int| {
+        if r == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+    unsafe {
+        // Без KEEPCAPS setuid обнулит и разрешенные capabilities
+        #[cfg(target_os = "linux")]
+        check(libc::prctl(
+            libc::PR_SET_KEEPCAPS,
+            1 as libc::c_ulong,
+            0,
+            0,
+            0,
+        ))?;
+        check(libc::setgroups(acc.groups.len() as _, acc.groups.as_ptr()))?;
+        check(libc::setgid(acc.gid))?;
+        check(libc::setuid(acc.uid))?;
+        #[cfg(target_os = "linux")]
+        keep_caps()?;
+    }
+    // setuid(0) обратно не должен проходить
+    if unsafe { libc::setuid(0) } == 0 {
+        return Err(io::Error::other("still able to regain root"));
+    }
+    Ok(())
+}
+
+// Capabilities - только Linux (на FreeBSD drop_to сюда не доходит)
+#[cfg(target_os = "linux")]
+fn keep_caps() -> io::Result<()> {
+    let keep = (1 << CAP_NET_RAW) | (1 << CAP_NET_BIND_SERVICE);
+    let header = CapHeader {
+        version: CAP_VERSION_3,
+        pid: 0,
+    };
+    let data = [
+        CapData {
+            effective: keep,
+            permitted: keep,
+            inheritable: keep,
+        },
+        CapData::default(),
+    ];
+    if unsafe { libc::syscall(libc::SYS_capset, &header, data.as_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Ambient - чтобы и внешний ping получил NET_RAW (file caps не работают
+    // под NoNewPrivileges)
+    for cap in [CAP_NET_RAW, CAP_NET_BIND_SERVICE] {
+        unsafe {
+            libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_RAISE as libc::c_ulong,
+                cap as libc::c_ulong,
+                0,
+                0,
+            )
+        };
+    }
+    unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 0 as libc::c_ulong, 0, 0, 0) };
+    Ok(())
+}
+
+pub fn user_exists(name: &str) -> bool {
+    let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+    account(name, &passwd, "").is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(argv: &[&str], env: &[(&str, &str)]) -> Request {
+        Request {
+            argv: argv.iter().map(|s| s.to_string()).collect(),
+            env: env
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn helper_runs_only_what_the_daemon_needs() {
+        let passwd = "root:x:0:0::/root:/bin/sh\nanna:x:1000:1000::/home/anna:/bin/sh\n\
+                      admin:x:1001:1001::/home/admin:/bin/sh\n";
+        // Сокеты: /run/user/1000/bus у anna, /run/user/1001/bus у admin
+        let owner = |p: &Path| match p.to_str() {
+            Some("/run/user/1000/bus") => Some(1000),
+            Some("/run/user/1001/bus") => Some(1001),
+            _ => None,
+        };
+        let ok =
+            |argv: &[&str], env: &[(&str, &str)]| allowed(&req(argv, env), "rtc0", passwd, owner);
+        assert!(ok(&["rtcwake", "-m", "mem", "-u", "-t", "1"], &[]));
+        assert!(ok(
+            &[
+                "rtcwake",
+                "-m",
+                "no",
+                "-l",
+                "-t",
+                "1791936000",
+                "-d",
+                "rtc0"
+            ],
+            &[]
+        ));
+        assert!(!ok(&["rtcwake", "-m", "mem", "-d", "rtc1"], &[]));
+        assert!(!ok(&["rtcwake", "-m", "sh"], &[]));
+        assert!(!ok(&["rtcwake", "-t", "1e9"], &[]));
+        assert!(!ok(&["rtcwake", "-m", "mem", "--date", "now"], &[]));
+        assert!(ok(&["ethtool", "-s", "eth0", "wol", "g"], &[]));
+        let bus = ("DBUS_SESSION_BUS_ADDRESS", "unix:path=/run/user/1000/bus");
+        assert!(ok(
+            &["runuser", "-u", "anna", "--", "notify-send", "x"],
+            &[bus]
+        ));
+        // Чужая шина, чужой пользователь, шина вне /run/user
+        assert!(!ok(
+            &["runuser", "-u", "admin", "--", "notify-send", "x"],
+            &[bus]
+        ));
+        assert!(!ok(
+            &["runuser", "-u", "anna", "--", "notify-send", "x"],
+            &[("DBUS_SESSION_BUS_ADDRESS", "unix:path=/tmp/evil")]
+        ));
+        assert!(!ok(
+            &["runuser", "-u", "anna", "--", "notify-send", "x"],
+            &[("DBUS_SESSION_BUS_ADDRESS", "unix:path=/run/user/1001/bus")]
+        ));
+        assert!(!ok(
+            &["runuser", "-u", "anna", "--", "notify-send", "x"],
+            &[]
+        ));
+        assert!(!ok(&["sh", "-c", "id"], &[]));
+        assert!(!ok(&["ethtool", "-s", "eth0", "wol", "d"], &[]));
+        assert!(!ok(&["wakeup", "../../power/state"], &[]));
+        assert!(!ok(&["runuser", "-u", "root", "--", "notify-send"], &[]));
+        assert!(!ok(&["rtcwake"], &[("LD_PRELOAD", "/tmp/x.so")]));
+    }
+
+    #[test]
+    fn account_with_supplementary_groups() {
+        let passwd =
+            "root:x:0:0::/root:/bin/sh\nportal:x:990:990::/nonexistent:/usr/sbin/nologin\n";
+        let group = "portal:x:990:\nportal-admins:x:1002:anna,portal\nwheel:x:10:anna\n";
+        assert_eq!(
+            account("portal", passwd, group),
+            Some(Account {
+                uid: 990,
+                gid: 990,
+                groups: vec![990, 1002],
+            })
+        );
+        assert_eq!(account("nobody", passwd, group), None);
+    }
+}
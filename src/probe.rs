@@ -0,0 +1,652 @@
+// === ПРОБЫ "ЕСТЬ ЛИ СВЕТ" ===
+// ping - маяк в сети (эхо своим сокетом, без запуска ping); power_supply - наличие AC у самого ноутбука;
+// nut - статус ИБП из upsd (OB = на батарее = света нет); arp - ARP-запрос
+// маяку в той же проводной сети; sensor - датчик сети на GPIO или UART;
+// beacon - подписанные UDP-маячки от устройства на сети; http/udp - ответ
+// `portal_daemon lighthouse` на lighthouse_ip:lighthouse.port; snmp - роутер
+// по SNMP, с отдельным grace на его перезагрузку; auto - выбрать при старте по окружению
+// (detect): ИБП отвечает - nut, маяк за проводом в своей подсети - arp,
+// Wi-Fi - ping с повторами (одиночные потери там обычны).
+// Маршрут к маяку через VPN по умолчанию обходим пингом с физического интерфейса;
+// нет линка на носителе (порт моста/bond/VLAN) - света нет без всякого пинга.
+// Заданный interface проверяется так же перед любой пробой, а
+// watch_interface будит цикл на смене линка, не дожидаясь scan_interval.
+// С ping_count > 1 за цикл уходит серия пингов, и свет пропадает не по
+// одному молчанию, а по verdict: потери выше max_loss_percent или RTT выше
+// max_rtt_ms bad_cycles циклов подряд (перегруженный роутер на ИБП).
+
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::{PortalConfig, unix_now};
+use crate::{
+    arp, beacon, freebsd, icmp, inject, lighthouse, log, net, netlink, paths, power, reactor,
+    sensor, snmp, tui,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeKind {
+    #[default]
+    Ping,
+    PowerSupply,
+    Nut,
+    Arp,
+    Sensor,
+    Beacon,
+    Http,
+    Udp,
+    Snmp,
+    Auto,
+}
+
+impl ProbeKind {
+    // Проба по сети до маяка: есть RTT, важен маршрут
+    pub fn network(self) -> bool {
+        matches!(self, ProbeKind::Ping | ProbeKind::Arp)
+    }
+}
+
+// Что выбрал auto и почему
+#[derive(Debug, Clone)]
+pub struct Choice {
+    pub kind: ProbeKind,
+    pub attempts: u32,
+    pub reason: String,
+}
+
+// Повторов пинга по Wi-Fi
+const WIFI_ATTEMPTS: u32 = 3;
+
+// Что делать, если маршрут к маяку идет через VPN
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VpnProbe {
+    // Пинговать через физический интерфейс маршрута по умолчанию
+    #[default]
+    Physical,
+    // Пинговать как есть, только предупредить
+    Warn,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProbeResult {
+    pub ok: bool,
+    // Только для сетевых проб; у серии - среднее по ответам
+    pub rtt_ms: Option<f64>,
+    // Эхо-запросов отправлено и ответов получено (0/0 - не сетевая проба
+    // или до пинга не дошло: нет линка)
+    pub sent: u32,
+    pub received: u32,
+    // snmp: маяк-роутер молчит, потому что перезагружается, а не без света
+    pub rebooting: bool,
+}
+
+impl ProbeResult {
+    fn echo(rtt_ms: Option<f64>) -> Self {
+        Self {
+            ok: rtt_ms.is_some(),
+            rtt_ms,
+            sent: 1,
+            received: rtt_ms.is_some() as u32,
+            rebooting: false,
+        }
+    }
+
+    // Серия эхо: sent - все запросы, RTT - среднее по ответам
+    fn series(rtts: &[Option<f64>]) -> Self {
+        let got: Vec<f64> = rtts.iter().flatten().copied().collect();
+        Self {
+            ok: !got.is_empty(),
+            rtt_ms: (!got.is_empty()).then(|| got.iter().sum::<f64>() / got.len() as f64),
+            sent: rtts.len() as u32,
+            received: got.len() as u32,
+            rebooting: false,
+        }
+    }
+
+    fn state(ok: bool) -> Self {
+        Self {
+            ok,
+            ..Default::default()
+        }
+    }
+
+    pub fn loss_percent(&self) -> Option<u32> {
+        (self.sent > 0).then(|| (self.sent - self.received) * 100 / self.sent)
+    }
+}
+
+pub fn run(cfg: &PortalConfig) -> ProbeResult {
+    if inject::take(inject::Fault::ProbeFail) {
+        return ProbeResult::default();
+    }
+    if let Some(iface) = &cfg.interface
+        && interface_down(iface)
+    {
+        return ProbeResult::default();
+    }
+    run_kind(cfg, cfg.probe, cfg.ping_attempts)
+}
+
+// --- ЛИНК ЗАДАННОГО ИНТЕРФЕЙСА ---
+struct Watched {
+    at: Instant,
+    carriers: Vec<net::Carrier>,
+}
+
+static IFACE: Mutex<Option<Watched>> = Mutex::new(None);
+static IFACE_DOWN: AtomicBool = AtomicBool::new(false);
+
+// Носители пересобираем раз в LINK_TTL (порты моста могут смениться)
+fn interface_down(iface: &str) -> bool {
+    let Ok(mut w) = IFACE.lock() else {
+        return false;
+    };
+    if w.as_ref().is_none_or(|w| w.at.elapsed() > LINK_TTL) {
+        *w = Some(Watched {
+            at: Instant::now(),
+            carriers: net::carriers(iface),
+        });
+    }
+    let down = w
+        .as_ref()
+        .is_some_and(|w| net::link_up(&w.carriers) == Some(false));
+    if IFACE_DOWN.swap(down, Ordering::Relaxed) != down {
+        if down {
+            log::warn!(iface = iface; "🔌 Link lost on {}: no light.", iface);
+        } else {
+            log::info!(iface = iface; "🔌 Link back on {}.", iface);
+        }
+    }
+    down
+}
+
+// Источник цикла: события линка от ядра -> проверить свет сейчас. Будим
+// только на смене состояния (смена MTU или адреса - тоже RTM_NEWLINK)
+pub fn watch_interface(iface: &str) {
+    let sock = match netlink::link_monitor() {
+        Ok(s) => s,
+        Err(e) => {
+            log::warn!(iface = iface; "⚠️  Link events unavailable ({}), polling {} every cycle.", e, iface);
+            return;
+        }
+    };
+    reactor::add(LinkWatch {
+        sock,
+        last: net::link_up(&net::carriers(iface)),
+        iface: iface.to_string(),
+    });
+}
+
+struct LinkWatch {
+    sock: OwnedFd,
+    iface: String,
+    last: Option<bool>,
+}
+
+impl reactor::Source for LinkWatch {
+    fn fd(&self) -> Option<RawFd> {
+        Some(self.sock.as_raw_fd())
+    }
+
+    fn ready(&mut self, _: bool, _: &mut reactor::Spawned) -> bool {
+        let mut buf = vec![0u8; 16 * 1024];
+        let changed = match netlink::link_events(&self.sock, &mut buf) {
+            Ok(c) => c,
+            Err(e) => {
+                // ENOBUFS: очередь переполнилась, события потеряны - перечитаем sysfs
+                log::debug!("link events: {}", e);
+                Vec::new()
+            }
+        };
+        let ours = net::carrier_devs(&self.iface)
+            .iter()
+            .chain([&self.iface])
+            .filter_map(|d| net::if_index(d))
+            .any(|i| changed.contains(&i));
+        if !ours && !changed.is_empty() {
+            return true;
+        }
+        let now = net::link_up(&net::carriers(&self.iface));
+        if now != self.last {
+            self.last = now;
+            log::debug!(iface = self.iface; "link changed, checking now");
+            tui::check_now();
+        }
+        true
+    }
+}
+
+// Без демона (`bench`, `status`) auto выбирается один раз на процесс
+static AUTO: OnceLock<Choice> = OnceLock::new();
+
+fn run_kind(cfg: &PortalConfig, kind: ProbeKind, attempts: u32) -> ProbeResult {
+    match kind {
+        ProbeKind::Auto => {
+            let c = AUTO.get_or_init(|| detect(cfg));
+            run_kind(cfg, c.kind, c.attempts.max(attempts))
+        }
+        ProbeKind::Ping if cfg.ping_count > 1 => ping_lighthouse_n(cfg, cfg.ping_count),
+        ProbeKind::Ping => {
+            let mut r = ProbeResult::default();
+            let mut sent = 0;
+            for _ in 0..attempts.max(1) {
+                r = ping_lighthouse(cfg);
+                sent += r.sent;
+                if r.ok {
+                    break;
+                }
+            }
+            r.sent = sent;
+            r
+        }
+        ProbeKind::Arp => arp_lighthouse(cfg),
+        ProbeKind::PowerSupply => match power::ac_online() {
+            Some(ok) => ProbeResult::state(ok),
+            // Адаптера нет - судить не по чему, откатываемся на ping
+            None => ping_lighthouse(cfg),
+        },
+        ProbeKind::Beacon => match beacon::light(&cfg.beacon) {
+            Some(light) => ProbeResult::state(light),
+            // Порт занят - маячков не услышать
+            None => ping_lighthouse(cfg),
+        },
+        ProbeKind::Http | ProbeKind::Udp => {
+            let timeout = PingOpts::of(cfg).timeout;
+            let r = match kind {
+                ProbeKind::Http => lighthouse::http_probe(cfg, timeout),
+                _ => lighthouse::udp_probe(cfg, timeout),
+            };
+            if let Err(e) = &r {
+                log::debug!(ip = cfg.lighthouse_ip, port = cfg.lighthouse.port; "lighthouse probe: {}", e);
+            }
+            ProbeResult::echo(r.ok())
+        }
+        ProbeKind::Snmp => {
+            match snmp::check(&cfg.lighthouse_ip, &cfg.snmp, PingOpts::of(cfg).timeout) {
+                snmp::Router::Up(rtt) => ProbeResult::echo(Some(rtt)),
+                snmp::Router::PowerOut => ProbeResult::echo(None),
+                snmp::Router::Rebooting => ProbeResult {
+                    rebooting: true,
+                    ..ProbeResult::echo(None)
+                },
+            }
+        }
+        ProbeKind::Sensor => match sensor::read(&cfg.sensor) {
+            Ok(light) => ProbeResult::state(light),
+            Err(e) => {
+                log::warn!("⚠️  Sensor read failed ({}), probing with ping.", e);
+                ping_lighthouse(cfg)
+            }
+        },
+        ProbeKind::Nut => match nut_on_battery(&cfg.nut_address, &cfg.nut_ups) {
+            Ok(on_battery) => ProbeResult::state(!on_battery),
+            Err(e) => {
+                log::warn!("⚠️  NUT query failed ({}), probing with ping.", e);
+                ping_lighthouse(cfg)
+            }
+        },
+    }
+}
+
+// --- РЕШЕНИЕ: ЕСТЬ ЛИ СВЕТ ---
+// Плохих циклов подряд (потери, RTT или нет ответа вовсе)
+static BAD_CYCLES: AtomicU32 = AtomicU32::new(0);
+
+// Последний цикл без потерь и задержек (для адаптивного интервала)
+pub fn healthy() -> bool {
+    BAD_CYCLES.load(Ordering::Relaxed) == 0
+}
+
+// Свет есть, пока плохих циклов подряд меньше bad_cycles. Плохой цикл -
+// проба не прошла, потери серии выше max_loss_percent или RTT выше max_rtt_ms
+pub fn verdict(cfg: &PortalConfig, r: &ProbeResult) -> bool {
+    let lossy = r
+        .loss_percent()
+        .filter(|_| r.sent > 1)
+        .is_some_and(|l| l > cfg.max_loss_percent as u32);
+    let slow = cfg.max_rtt_ms > 0.0 && r.rtt_ms.is_some_and(|ms| ms > cfg.max_rtt_ms);
+    if r.ok && !lossy && !slow {
+        BAD_CYCLES.store(0, Ordering::Relaxed);
+        return true;
+    }
+    let bad = BAD_CYCLES.fetch_add(1, Ordering::Relaxed) + 1;
+    // Дальше порога - уже grace и сон, повторять незачем
+    if r.ok && bad <= cfg.bad_cycles {
+        let loss = r.loss_percent().unwrap_or(0);
+        let rtt = r.rtt_ms.unwrap_or_default();
+        log::warn!(loss_pct = loss, rtt_ms = format!("{:.1}", rtt), bad_cycles = bad;
+            "📉 Lighthouse degraded: {}% loss, {:.1} ms ({}/{})", loss, rtt, bad, cfg.bad_cycles);
+    }
+    bad < cfg.bad_cycles
+}
+
+// --- ВОЗРАСТ ПОСЛЕДНЕЙ УДАЧНОЙ ПРОБЫ ---
+// Главное число для внешнего мониторинга: сколько секунд не было света
+// (или ответа маяка). 0 - с запуска удачных еще не было
+static LAST_OK: AtomicU64 = AtomicU64::new(0);
+static STALE_ALERTED: AtomicBool = AtomicBool::new(false);
+
+pub fn record_ok(ok: bool) {
+    let now = unix_now();
+    if ok {
+        LAST_OK.store(now, Ordering::Relaxed);
+        STALE_ALERTED.store(false, Ordering::Relaxed);
+    } else {
+        // Отсчет - с первой пробы, если удачной еще не было
+        LAST_OK
+            .compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed)
+            .ok();
+    }
+}
+
+fn last_ok_age() -> u64 {
+    match LAST_OK.load(Ordering::Relaxed) {
+        0 => 0,
+        last => unix_now().saturating_sub(last),
+    }
+}
+
+// Через сколько секунд пора предупредить; None - выключено или уже предупредили
+pub fn stale_in(alert_sec: u64) -> Option<u64> {
+    (alert_sec > 0 && !STALE_ALERTED.load(Ordering::Relaxed))
+        .then(|| alert_sec.saturating_sub(last_ok_age()))
+}
+
+// Some(возраст) - порог только что пройден; снова - после удачной пробы
+pub fn take_stale(alert_sec: u64) -> Option<u64> {
+    (stale_in(alert_sec) == Some(0) && !STALE_ALERTED.swap(true, Ordering::Relaxed))
+        .then(last_ok_age)
+}
+
+// Маршрут к маяку. Пересчитываем, только когда ядро сменило адрес отправителя
+// (поднялся или упал VPN, DHCP выдал другой адрес) или раз в LINK_TTL (порты
+// моста могли смениться); в обычном цикле - только адрес и файл carrier
+struct Link {
+    src: Option<IpAddr>,
+    at: Instant,
+    // VPN-интерфейс маршрута, если он есть
+    vpn: Option<String>,
+    // Куда прибить пробу мимо VPN
+    bind: Option<String>,
+    // Чей линк проверять, и его носители
+    dev: Option<String>,
+    carriers: Vec<net::Carrier>,
+}
+
+const LINK_TTL: Duration = Duration::from_secs(300);
+
+// Параметры одного эхо-запроса (ping_timeout_ms, ping_size из конфига)
+#[derive(Debug, Clone, Copy)]
+pub struct PingOpts {
+    pub timeout: Duration,
+    pub size: usize,
+}
+
+impl PingOpts {
+    pub fn of(cfg: &PortalConfig) -> Self {
+        Self {
+            timeout: Duration::from_millis(cfg.ping_timeout_ms),
+            size: cfg.ping_size,
+        }
+    }
+}
+
+static LINK: Mutex<Option<Link>> = Mutex::new(None);
+static LINK_DOWN: AtomicBool = AtomicBool::new(false);
+static NO_ICMP_SOCKET: AtomicBool = AtomicBool::new(false);
+static NO_ARP_SOCKET: AtomicBool = AtomicBool::new(false);
+
+// Физический интерфейс, через который на деле уходит трафик к маяку
+pub fn egress_dev(cfg: &PortalConfig) -> Option<String> {
+    let route = net::route_dev(&cfg.lighthouse_ip)?;
+    if net::is_vpn(&route) {
+        net::physical_dev()
+    } else {
+        Some(route)
+    }
+}
+
+impl Link {
+    fn resolve(cfg: &PortalConfig, src: Option<IpAddr>) -> Link {
+        let route = net::route_dev(&cfg.lighthouse_ip);
+        let vpn = route.clone().filter(|d| net::is_vpn(d));
+        let bind = match (&vpn, cfg.vpn_probe) {
+            (Some(_), VpnProbe::Physical) => net::physical_dev(),
+            _ => None,
+        };
+        let dev = bind.clone().or(route.filter(|_| vpn.is_none()));
+        let carriers = dev.as_deref().map(net::carriers).unwrap_or_default();
+        Link {
+            src,
+            at: Instant::now(),
+            vpn,
+            bind,
+            dev,
+            carriers,
+        }
+    }
+}
+
+fn ping_lighthouse(cfg: &PortalConfig) -> ProbeResult {
+    ping_lighthouse_n(cfg, 1)
+}
+
+// Серия из count эхо к маяку. Линка нет - ни одно не уходит (0/0)
+fn ping_lighthouse_n(cfg: &PortalConfig, count: u32) -> ProbeResult {
+    let ip = &cfg.lighthouse_ip;
+    let opts = PingOpts::of(cfg);
+    let Ok(addr) = ip.parse::<IpAddr>() else {
+        return ping_cmd_series(ip, None, opts, count);
+    };
+    let src = net::route_src(addr);
+    let Ok(mut cached) = LINK.lock() else {
+        return ping(addr, None, opts, count);
+    };
+    if cached
+        .as_ref()
+        .is_none_or(|l| l.src != src || l.at.elapsed() > LINK_TTL)
+    {
+        let fresh = Link::resolve(cfg, src);
+        let last = cached.as_ref().and_then(|l| l.vpn.as_ref());
+        if last != fresh.vpn.as_ref() {
+            match (&fresh.vpn, &fresh.bind) {
+                (Some(v), Some(p)) => log::warn!(
+                    "⚠️  Route to {} goes through VPN {}, probing via {} instead.",
+                    ip,
+                    v,
+                    p
+                ),
+                (Some(v), None) => log::warn!(
+                    "⚠️  Route to {} goes through VPN {}: a reply says nothing about local power.",
+                    ip,
+                    v
+                ),
+                (None, _) => log::info!("✅ Route to {} no longer uses VPN.", ip),
+            }
+        }
+        *cached = Some(fresh);
+    }
+    let Some(link) = cached.as_ref() else {
+        return ping(addr, None, opts, count);
+    };
+    // Носитель без линка (кабель, свитч без питания) - пинговать бессмысленно
+    let down = link
+        .dev
+        .as_deref()
+        .filter(|_| net::link_up(&link.carriers) == Some(false));
+    if LINK_DOWN.swap(down.is_some(), Ordering::Relaxed) != down.is_some() {
+        match (down, &link.dev) {
+            (Some(d), _) => log::warn!(
+                "🔌 No carrier on {} ({}): lighthouse unreachable.",
+                d,
+                net::carrier_devs(d).join(", ")
+            ),
+            (None, Some(d)) => log::info!("🔌 Carrier back on {}.", d),
+            _ => {}
+        }
+    }
+    if down.is_some() {
+        return ProbeResult::default();
+    }
+    ping(addr, link.bind.as_deref(), opts, count)
+}
+
+// Нет пакетного сокета (не root) или маяк не IPv4 - обычный ping
+fn arp_lighthouse(cfg: &PortalConfig) -> ProbeResult {
+    let (Ok(IpAddr::V4(ip)), Some(dev)) = (cfg.lighthouse_ip.parse(), egress_dev(cfg)) else {
+        return ping_lighthouse(cfg);
+    };
+    match arp::request(ip, &dev, PingOpts::of(cfg).timeout) {
+        Ok(rtt) => ProbeResult::echo(rtt),
+        Err(e) => {
+            if !NO_ARP_SOCKET.swap(true, Ordering::Relaxed) {
+                log::warn!("⚠️  No ARP socket on {} ({}), probing with ping.", dev, e);
+            }
+            ping_lighthouse(cfg)
+        }
+    }
+}
+
+// Лучшая проба для этой машины: ИБП надежнее всего, ARP по проводу не
+// зависит от файрвола маяка, по Wi-Fi одиночный потерянный пинг - не отключение
+pub fn detect(cfg: &PortalConfig) -> Choice {
+    let choice = |kind, attempts, reason: String| Choice {
+        kind,
+        attempts,
+        reason,
+    };
+    if nut_on_battery(&cfg.nut_address, &cfg.nut_ups).is_ok() {
+        return choice(
+            ProbeKind::Nut,
+            1,
+            format!("UPS answers at {}", cfg.nut_address),
+        );
+    }
+    let Some(dev) = egress_dev(cfg) else {
+        return choice(ProbeKind::Ping, 1, "no route to lighthouse yet".into());
+    };
+    if net::is_wireless(&dev) {
+        return choice(ProbeKind::Ping, WIFI_ATTEMPTS, format!("{} is Wi-Fi", dev));
+    }
+    // --user: пакетный сокет без CAP_NET_RAW не открыть; на FreeBSD его нет
+    if let Ok(IpAddr::V4(ip)) = cfg.lighthouse_ip.parse()
+        && net::on_link(ip, &dev)
+        && !paths::user()
+        && !freebsd::is()
+    {
+        return choice(
+            ProbeKind::Arp,
+            1,
+            format!("{} is wired, lighthouse on the same subnet", dev),
+        );
+    }
+    choice(ProbeKind::Ping, 1, format!("lighthouse routed via {}", dev))
+}
+
+// upsd: "GET VAR <ups> ups.status" -> VAR <ups> ups.status "OB DISCHRG"
+pub fn nut_on_battery(address: &str, ups: &str) -> Result<bool, String> {
+    let status = nut_var(address, ups, "ups.status")?;
+    Ok(status.split_whitespace().any(|f| f == "OB"))
+}
+
+// Одна переменная upsd: `GET VAR <ups> <var>` -> `VAR <ups> <var> "<значение>"`
+pub fn nut_var(address: &str, ups: &str, var: &str) -> Result<String, String> {
+    let addr = address
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("cannot resolve")?;
+    let end = Instant::now() + Duration::from_secs(3);
+    let mut stream = reactor::connect(addr, end).map_err(|e| e.to_string())?;
+    writeln!(stream, "GET VAR {} {}", ups, var).map_err(|e| e.to_string())?;
+    let (mut reply, mut chunk) = (Vec::new(), [0u8; 256]);
+    while !reply.contains(&b'\n') {
+        match reactor::read_with(stream.as_raw_fd(), end, || stream.read(&mut chunk)) {
+            Ok(0) => break,
+            Ok(n) => reply.extend_from_slice(&chunk[..n]),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    writeln!(stream, "LOGOUT").ok();
+    let reply = String::from_utf8_lossy(&reply);
+    let line = reply.lines().next().unwrap_or_default();
+    if let Some(err) = line.strip_prefix("ERR ") {
+        return Err(err.trim().to_string());
+    }
+    line.split_once('"')
+        .map(|(_, rest)| rest.trim_end().trim_end_matches('"').to_string())
+        .ok_or_else(|| format!("unexpected reply: {}", line.trim()))
+}
+
+// Эхо своим сокетом; нет прав ни на какой ICMP-сокет - запускаем ping.
+// dev - прибить пакет к интерфейсу (SO_BINDTODEVICE), мимо маршрута VPN;
+// count > 1 - серия: эхо уходят разом, RTT - среднее по ответам
+pub fn ping(ip: IpAddr, dev: Option<&str>, opts: PingOpts, count: u32) -> ProbeResult {
+    match icmp::echo(ip, dev, opts.timeout, opts.size, count.max(1) as usize) {
+        Ok(rtts) => ProbeResult::series(&rtts),
+        Err(e) => {
+            if !NO_ICMP_SOCKET.swap(true, Ordering::Relaxed) {
+                log::warn!("⚠️  No ICMP socket ({}), probing with the ping command.", e);
+            }
+            ping_cmd_series(&ip.to_string(), dev, opts, count)
+        }
+    }
+}
+
+fn ping_cmd_series(ip: &str, dev: Option<&str>, opts: PingOpts, count: u32) -> ProbeResult {
+    let mut rtts = Vec::new();
+    for _ in 0..count.max(1) {
+        let one = ping_cmd(ip, dev, opts);
+        // ping не запустился - серии не будет
+        if one.sent == 0 {
+            return one;
+        }
+        rtts.push(one.rtt_ms);
+    }
+    ProbeResult::series(&rtts)
+}
+
+// RTT в миллисекундах из вывода ping ("... time=12.3 ms")
+fn ping_cmd(ip: &str, dev: Option<&str>, opts: PingOpts) -> ProbeResult {
+    let mut cmd = Command::new("ping");
+    // -I у ping FreeBSD - только для multicast: там источник -S
+    match (dev, ip.parse()) {
+        (Some(d), Ok(to)) if freebsd::is() => {
+            if let Some(src) = net::dev_addr(d, to) {
+                cmd.args(["-S", &src.to_string()]);
+            }
+        }
+        (Some(d), _) => {
+            cmd.args(["-I", d]);
+        }
+        _ => {}
+    }
+    // -W у busybox - только целые секунды, у FreeBSD - миллисекунды
+    let wait = if freebsd::is() {
+        opts.timeout.as_millis().max(1).to_string()
+    } else {
+        opts.timeout.as_millis().div_ceil(1000).max(1).to_string()
+    };
+    let out = reactor::output(cmd.args(["-c", "1", "-W", &wait, "-s", &opts.size.to_string(), ip]));
+    let Ok(out) = out else {
+        return ProbeResult::default();
+    };
+    if !out.status.success() {
+        return ProbeResult::echo(None);
+    }
+    let text = String::from_utf8_lossy(&out.stdout);
+    let rtt = text
+        .split_once("time=")
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .and_then(|v| v.parse().ok());
+    // Ответ был, но формат вывода незнакомый - считаем связь живой
+    ProbeResult::echo(Some(rtt.unwrap_or(0.0)))
+}
@@ -0,0 +1,156 @@
+// === ПРОФИЛИ ПО SSID ===
+// Ноутбук ездит между домом, офисом и квартирой родителей: у каждой сети
+// свой маяк и свои тайминги. Каждый цикл смотрим, к какому SSID подключены,
+// и подставляем его профиль; верхний уровень конфига - профиль target_ssid.
+// В чужой сети маяка нет, и его "пропажа" о свете ничего не говорит -
+// там не спим вовсе. Wi-Fi пропал совсем (роутер без света) - это не смена
+// сети: остаемся в текущем профиле, иначе сон никогда бы не наступил.
+
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use crate::{
+    GRACE_SEC_RANGE, PortalConfig, SCAN_INTERVAL_RANGE, SLEEP_MINUTES_RANGE, WAKEUP_SEC_RANGE,
+    probe,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Profile {
+    pub ssid: String,
+    pub lighthouse_ip: String,
+    // Не заданы - как на верхнем уровне конфига
+    pub probe: Option<probe::ProbeKind>,
+    pub sleep_minutes: Option<u64>,
+    pub grace_period_sec: Option<u64>,
+    pub wakeup_wait_sec: Option<u64>,
+    pub scan_interval_sec: Option<u64>,
+}
+
+impl Profile {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ssid.is_empty() {
+            return Err("ssid is empty".into());
+        }
+        if self.probe.is_none_or(probe::ProbeKind::network)
+            && self.lighthouse_ip.parse::<IpAddr>().is_err()
+        {
+            return Err(format!(
+                "{}: lighthouse_ip '{}' is not an IP",
+                self.ssid, self.lighthouse_ip
+            ));
+        }
+        // Определение пробы - один раз при старте, на смену сети его не повторить
+        if self.probe == Some(probe::ProbeKind::Auto) {
+            return Err(format!("{}: probe = auto is not supported here", self.ssid));
+        }
+        for (name, v, range) in [
+            ("sleep_minutes", self.sleep_minutes, SLEEP_MINUTES_RANGE),
+            ("grace_period_sec", self.grace_period_sec, GRACE_SEC_RANGE),
+            ("wakeup_wait_sec", self.wakeup_wait_sec, WAKEUP_SEC_RANGE),
+            (
+                "scan_interval_sec",
+                self.scan_interval_sec,
+                SCAN_INTERVAL_RANGE,
+            ),
+        ] {
+            if let Some(v) = v
+                && !range.contains(&v)
+            {
+                return Err(format!(
+                    "{}: {} = {} is outside {}..={}",
+                    self.ssid,
+                    name,
+                    v,
+                    range.start(),
+                    range.end()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// То, что профиль может поменять
+struct Settings {
+    lighthouse_ip: String,
+    probe: probe::ProbeKind,
+    sleep_minutes: u64,
+    grace_period_sec: u64,
+    wakeup_wait_sec: u64,
+    scan_interval_sec: u64,
+}
+
+impl Settings {
+    fn of(cfg: &PortalConfig) -> Self {
+        Self {
+            lighthouse_ip: cfg.lighthouse_ip.clone(),
+            probe: cfg.probe,
+            sleep_minutes: cfg.sleep_minutes,
+            grace_period_sec: cfg.grace_period_sec,
+            wakeup_wait_sec: cfg.wakeup_wait_sec,
+            scan_interval_sec: cfg.scan_interval_sec,
+        }
+    }
+
+    fn apply(&self, cfg: &mut PortalConfig, p: Option<&Profile>) {
+        cfg.lighthouse_ip = p.map_or(&self.lighthouse_ip, |p| &p.lighthouse_ip).clone();
+        cfg.probe = p.and_then(|p| p.probe).unwrap_or(self.probe);
+        cfg.sleep_minutes = p
+            .and_then(|p| p.sleep_minutes)
+            .unwrap_or(self.sleep_minutes);
+        cfg.grace_period_sec = p
+            .and_then(|p| p.grace_period_sec)
+            .unwrap_or(self.grace_period_sec);
+        cfg.wakeup_wait_sec = p
+            .and_then(|p| p.wakeup_wait_sec)
+            .unwrap_or(self.wakeup_wait_sec);
+        cfg.scan_interval_sec = p
+            .and_then(|p| p.scan_interval_sec)
+            .unwrap_or(self.scan_interval_sec);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Active {
+    // SSID, чей профиль применен
+    Known(String),
+    // Чужая сеть: не спим
+    Unknown(String),
+}
+
+pub struct Switcher {
+    base: Settings,
+    active: Option<Active>,
+}
+
+impl Switcher {
+    // После определения пробы: верхний уровень конфига уже окончательный
+    pub fn new(cfg: &PortalConfig) -> Self {
+        Self {
+            base: Settings::of(cfg),
+            active: None,
+        }
+    }
+
+    // Применяет профиль текущей сети; Some - профиль сменился
+    pub fn update(&mut self, cfg: &mut PortalConfig, ssid: Option<String>) -> Option<Active> {
+        let ssid = ssid?;
+        let profile = cfg.profiles.iter().find(|p| p.ssid == ssid).cloned();
+        let next = if profile.is_some() || ssid == cfg.target_ssid {
+            Active::Known(ssid)
+        } else {
+            Active::Unknown(ssid)
+        };
+        if self.active.as_ref() == Some(&next) {
+            return None;
+        }
+        self.base.apply(cfg, profile.as_ref());
+        self.active = Some(next.clone());
+        Some(next)
+    }
+
+    pub fn unknown(&self) -> bool {
+        matches!(self.active, Some(Active::Unknown(_)))
+    }
+}
@@ -0,0 +1,379 @@
+// === ЦИКЛ СОБЫТИЙ: ОДИН poll(2) НА ВСЕ ===
+// Входящее - сокеты fleet, HTTP, маячка, D-Bus, события линка, опрос
+// ntfy/Telegram (stdout curl) и таймеры вроде keepalive MQTT - не держит
+// по потоку на каждое: это источники одного poll() в потоке цикла демона.
+// Цикл обслуживает их, пока ждет (tui::nap, tui::wait, watchdog::sleep),
+// и просыпается от self-pipe: check_now(), SIGUSR1, клавиша в терминале.
+// Проба, хук или замер диска ждут тем же poll (wait, pause, read_with,
+// output): пока ждут ответа маяка или конца команды, источники тоже
+// обслуживаются, а self-pipe остается до следующего run_until.
+// В своих потоках остается только то, что блокирует надолго по природе:
+// исходящие отправки с повторами (curl, почта, вебхуки), notify-send
+// --wait до закрытия уведомления и чтение клавиатуры. Помощник root и
+// маяк `lighthouse` - отдельные процессы со своими потоками.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::net::{SocketAddr, TcpStream};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::process::{Command, Output, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+pub type Spawned = Vec<Box<dyn Source>>;
+
+pub trait Source: Send {
+    // Дескриптор для poll; None - источник ждет только deadline
+    fn fd(&self) -> Option<RawFd>;
+    // Когда позвать ready без событий на fd
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+    // readable - на fd есть данные (одно чтение не заблокирует), иначе
+    // наступил deadline. Новые источники (принятое соединение) - в spawned;
+    // false - источник закончился, снять
+    fn ready(&mut self, readable: bool, spawned: &mut Spawned) -> bool;
+}
+
+static SOURCES: Mutex<Vec<Box<dyn Source>>> = Mutex::new(Vec::new());
+// Self-pipe: запись в него будит poll
+static WAKE: OnceLock<(RawFd, RawFd)> = OnceLock::new();
+
+// Создать self-pipe до того, как его станет будить обработчик сигнала
+pub fn init() {
+    WAKE.get_or_init(|| {
+        let mut fds = [-1; 2];
+        unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) };
+        (fds[0], fds[1])
+    });
+}
+
+pub fn add(source: impl Source + 'static) {
+    init();
+    if let Ok(mut s) = SOURCES.lock() {
+        s.push(Box::new(source));
+    }
+}
+
+// Прервать ожидание. Атомарное чтение и write() - можно из обработчика сигнала
+pub fn wake() {
+    if let Some(&(_, w)) = WAKE.get() {
+        unsafe { libc::write(w, b"!".as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+// Обслуживать источники до end; true - разбудил wake()
+pub fn run_until(end: Instant) -> bool {
+    matches!(serve(end, None), Wait::Woke)
+}
+
+// Ждать события на своих fd (POLLIN/POLLOUT), обслуживая источники.
+// wake() не прерывает: его дождется следующий run_until. false - вышел end
+pub fn wait(own: &[(RawFd, i16)], end: Instant) -> bool {
+    matches!(serve(end, Some(own)), Wait::Ready)
+}
+
+pub fn wait_readable(fd: RawFd, end: Instant) -> bool {
+    wait(&[(fd, libc::POLLIN)], end)
+}
+
+// Пауза посреди пробы или хука, без thread::sleep
+pub fn pause(end: Instant) {
+    wait(&[], end);
+}
+
+// Вызов на неблокирующем fd: WouldBlock - ждем готовности до end.
+// Не дождались - TimedOut
+pub fn read_with<T>(
+    fd: RawFd,
+    end: Instant,
+    mut f: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    loop {
+        match f() {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if !wait_readable(fd, end) {
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            r => return r,
+        }
+    }
+}
+
+// TcpStream::connect_timeout без блокировки. Поток остается
+// неблокирующим: читать через read_with
+pub fn connect(addr: SocketAddr, end: Instant) -> io::Result<TcpStream> {
+    let (domain, mut ss) = (
+        if addr.is_ipv6() {
+            libc::AF_INET6
+        } else {
+            libc::AF_INET
+        },
+        unsafe { std::mem::zeroed::<libc::sockaddr_storage>() },
+    );
+    let len = match addr {
+        SocketAddr::V4(a) => {
+            let sin = unsafe { &mut *(&mut ss as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = a.port().to_be();
+            sin.sin_addr.s_addr = u32::from_ne_bytes(a.ip().octets());
+            std::mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(a) => {
+            let sin6 = unsafe { &mut *(&mut ss as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = a.port().to_be();
+            sin6.sin6_addr.s6_addr = a.ip().octets();
+            sin6.sin6_scope_id = a.scope_id();
+            std::mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    let fd = unsafe {
+        libc::socket(
+            domain,
+            libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            0,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stream = unsafe { TcpStream::from_raw_fd(fd) };
+    let r = unsafe { libc::connect(fd, &ss as *const _ as *const libc::sockaddr, len as _) };
+    if r != 0 {
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::EINPROGRESS) {
+            return Err(e);
+        }
+        if !wait(&[(fd, libc::POLLOUT)], end) {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        if let Some(e) = stream.take_error()? {
+            return Err(e);
+        }
+    }
+    Ok(stream)
+}
+
+// Command::output, но вывод ждем здесь же: пока идет ping или busctl,
+// источники обслуживаются
+pub fn output(cmd: &mut Command) -> io::Result<Output> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut pipes: Vec<(File, Vec<u8>)> = [
+        child.stdout.take().map(OwnedFd::from),
+        child.stderr.take().map(OwnedFd::from),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|fd| {
+        unsafe {
+            let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFL);
+            libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+        (File::from(fd), Vec::new())
+    })
+    .collect();
+    let mut open = vec![true; pipes.len()];
+    let mut chunk = [0u8; 4096];
+    while open.contains(&true) {
+        let fds: Vec<(RawFd, i16)> = pipes
+            .iter()
+            .zip(&open)
+            .filter(|(_, o)| **o)
+            .map(|((f, _), _)| (f.as_raw_fd(), libc::POLLIN))
+            .collect();
+        // Без своего таймаута: его держит сама команда (ping -W)
+        wait(&fds, Instant::now() + Duration::from_secs(3600));
+        for ((f, buf), o) in pipes.iter_mut().zip(open.iter_mut()).filter(|(_, o)| **o) {
+            match f.read(&mut chunk) {
+                Ok(0) => *o = false,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => *o = false,
+            }
+        }
+    }
+    let status = child.wait()?;
+    let mut bufs = pipes.into_iter().map(|(_, b)| b);
+    Ok(Output {
+        status,
+        stdout: bufs.next().unwrap_or_default(),
+        stderr: bufs.next().unwrap_or_default(),
+    })
+}
+
+enum Wait {
+    Woke,
+    Ready,
+    Timeout,
+}
+
+// own: None - ждем self-pipe, Some - свои fd вместо него
+fn serve(end: Instant, own: Option<&[(RawFd, i16)]>) -> Wait {
+    let wake_fd = WAKE.get().map_or(-1, |&(r, _)| r);
+    let head: Vec<libc::pollfd> = match own {
+        Some(fds) => fds
+            .iter()
+            .map(|&(fd, events)| libc::pollfd {
+                fd,
+                events,
+                revents: 0,
+            })
+            .collect(),
+        None => vec![pollfd(wake_fd)],
+    };
+    // Источники - у того, кто ждет: обработчик может звать add() без взаимоблокировки
+    let mut sources = SOURCES
+        .lock()
+        .map(|mut s| std::mem::take(&mut *s))
+        .unwrap_or_default();
+    let outcome = loop {
+        let now = Instant::now();
+        let mut spawned = Vec::new();
+        sources.retain_mut(|s| match s.deadline() {
+            Some(d) if d <= now => s.ready(false, &mut spawned),
+            _ => true,
+        });
+        sources.append(&mut spawned);
+        if let Ok(mut added) = SOURCES.lock() {
+            sources.append(&mut added);
+        }
+
+        let timeout = sources
+            .iter()
+            .filter_map(|s| s.deadline())
+            .fold(end, Instant::min)
+            .saturating_duration_since(Instant::now());
+        let mut pfds = head.clone();
+        let mut owners = Vec::with_capacity(sources.len());
+        for (i, s) in sources.iter().enumerate() {
+            if let Some(fd) = s.fd() {
+                pfds.push(pollfd(fd));
+                owners.push(i);
+            }
+        }
+        let n = unsafe { libc::poll(pfds.as_mut_ptr(), pfds.len() as libc::nfds_t, ms(timeout)) };
+        if n > 0 {
+            let mut done = Vec::new();
+            for (p, &i) in pfds[head.len()..].iter().zip(&owners) {
+                if p.revents != 0 && !sources[i].ready(true, &mut spawned) {
+                    done.push(i);
+                }
+            }
+            for i in done.into_iter().rev() {
+                sources.swap_remove(i);
+            }
+            sources.append(&mut spawned);
+        }
+        if pfds[..head.len()].iter().any(|p| p.revents != 0) {
+            if own.is_some() {
+                break Wait::Ready;
+            }
+            let mut buf = [0u8; 64];
+            while unsafe { libc::read(wake_fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) }
+                > 0
+            {}
+            break Wait::Woke;
+        }
+        if Instant::now() >= end {
+            break Wait::Timeout;
+        }
+    };
+    if let Ok(mut s) = SOURCES.lock() {
+        s.append(&mut sources);
+    }
+    outcome
+}
+
+fn pollfd(fd: RawFd) -> libc::pollfd {
+    libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    }
+}
+
+// Вверх до миллисекунды: иначе остаток в микросекунды крутит poll(0)
+fn ms(d: Duration) -> i32 {
+    d.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Echo {
+        sock: UnixStream,
+        seen: Arc<AtomicUsize>,
+    }
+
+    impl Source for Echo {
+        fn fd(&self) -> Option<RawFd> {
+            Some(self.sock.as_raw_fd())
+        }
+
+        fn ready(&mut self, readable: bool, _: &mut Spawned) -> bool {
+            let mut buf = [0u8; 16];
+            let n = std::io::Read::read(&mut self.sock, &mut buf).unwrap_or(0);
+            self.seen.fetch_add(n, Ordering::Relaxed);
+            readable && n > 0
+        }
+    }
+
+    #[test]
+    fn serves_sources_while_waiting() {
+        let (mut a, b) = UnixStream::pair().unwrap();
+        let seen = Arc::new(AtomicUsize::new(0));
+        add(Echo {
+            sock: b,
+            seen: Arc::clone(&seen),
+        });
+        a.write_all(b"ping").unwrap();
+        let started = Instant::now();
+        run_until(started + Duration::from_millis(50));
+        assert_eq!(seen.load(Ordering::Relaxed), 4);
+        // Закрытый сокет: ready вернул false - источник снят
+        drop(a);
+        run_until(Instant::now() + Duration::from_millis(10));
+        assert!(SOURCES.lock().unwrap().is_empty());
+        wake();
+        assert!(run_until(Instant::now() + Duration::from_secs(5)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn waits_for_own_fds() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let end = Instant::now() + Duration::from_secs(5);
+        let mut s = connect(listener.local_addr().unwrap(), end).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 8];
+        let r = read_with(
+            s.as_raw_fd(),
+            Instant::now() + Duration::from_millis(20),
+            || s.read(&mut buf),
+        );
+        assert_eq!(r.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        peer.write_all(b"up").unwrap();
+        let n = read_with(s.as_raw_fd(), end, || s.read(&mut buf)).unwrap();
+        assert_eq!(&buf[..n], b"up");
+
+        let out =
+            output(Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"])).unwrap();
+        assert_eq!(out.status.code(), Some(3));
+        assert_eq!(out.stdout, b"out\n");
+        assert_eq!(out.stderr, b"err\n");
+    }
+}
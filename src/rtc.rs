@@ -0,0 +1,104 @@
+// === RTC И ПРОВЕРКА БУДИЛЬНИКА ===
+// rtcwake по умолчанию гадает, в UTC или в локальном времени идут часы RTC.
+// Если угадал неверно, машина проснется на пару часов раньше или позже, и
+// никто этого не заметит. Поэтому режим берем из /etc/adjtime явно, а после
+// установки будильника сверяем /sys/class/rtc/<dev>/wakealarm с ожиданием.
+// Севшая батарейка CMOS ломает всю идею будильника тихо: RTC отстает или
+// сбрасывается. Поэтому перед сном сверяем RTC с системными часами (они
+// по NTP), а после подъема - время с ожидаемым временем будильника.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::unix_now;
+
+const ADJTIME: &str = "/etc/adjtime";
+// Допуск на время между расчетом и записью будильника
+const TOLERANCE_SEC: i64 = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RtcClock {
+    #[default]
+    Auto,
+    Utc,
+    Local,
+}
+
+impl RtcClock {
+    // Auto -> третья строка /etc/adjtime ("UTC" | "LOCAL"), без файла - UTC
+    pub fn resolve(self) -> RtcClock {
+        if self != RtcClock::Auto {
+            return self;
+        }
+        let adj = fs::read_to_string(ADJTIME).unwrap_or_default();
+        match adj.lines().nth(2).map(str::trim) {
+            Some("LOCAL") => RtcClock::Local,
+            _ => RtcClock::Utc,
+        }
+    }
+
+    pub fn flag(self) -> &'static str {
+        match self.resolve() {
+            RtcClock::Local => "-l",
+            _ => "-u",
+        }
+    }
+}
+
+// Устройство из пользовательских аргументов ("-d rtc1", "--device=rtc1")
+pub fn device(extra: &[String]) -> String {
+    let mut it = extra.iter();
+    while let Some(a) = it.next() {
+        if a == "-d" || a == "--device" {
+            if let Some(d) = it.next() {
+                return d.trim_start_matches("/dev/").to_string();
+            }
+        } else if let Some(d) = a.strip_prefix("--device=") {
+            return d.trim_start_matches("/dev/").to_string();
+        }
+    }
+    "rtc0".into()
+}
+
+// Значение будильника в sysfs: ядро считает, что RTC идет в UTC
+pub fn wakealarm(dev: &str) -> Option<i64> {
+    fs::read_to_string(format!("/sys/class/rtc/{}/wakealarm", dev))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+// RTC минус системные часы, сек; нет since_epoch - None
+pub fn offset(dev: &str, clock: RtcClock, utc_offset: i64) -> Option<i64> {
+    let rtc: i64 = fs::read_to_string(format!("/sys/class/rtc/{}/since_epoch", dev))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    // Ядро читает регистры RTC как UTC; в локальном времени - поправка на пояс
+    let rtc = match clock.resolve() {
+        RtcClock::Local => rtc - utc_offset,
+        _ => rtc,
+    };
+    Some(rtc - unix_now() as i64)
+}
+
+// Ok - будильник совпал; Err - на сколько секунд он разъехался с ожиданием
+pub fn verify(dev: &str, clock: RtcClock, target: i64, utc_offset: i64) -> Result<(), i64> {
+    let Some(alarm) = wakealarm(dev) else {
+        // Нет sysfs (контейнер, экзотика) - проверить нечем
+        return Ok(());
+    };
+    let expected = match clock.resolve() {
+        RtcClock::Local => target + utc_offset,
+        _ => target,
+    };
+    let drift = alarm - expected;
+    if drift.abs() <= TOLERANCE_SEC {
+        Ok(())
+    } else {
+        Err(drift)
+    }
+}
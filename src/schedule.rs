@@ -0,0 +1,1153 @@
+// === ВРЕМЯ, ЧАСОВЫЕ ПОЯСА И РАСПИСАНИЯ ===
+// Вся логика расписаний (тихие часы, пробуждение к HH:MM, паузы до времени)
+// считается в локальном времени через этот модуль, а не голой арифметикой
+// UNIX-секунд: иначе дважды в год "06:30" съезжает на час.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+const LOCALTIME_FILE: &str = "/etc/localtime";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl TimeOfDay {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (h, m) = s.trim().split_once(':')?;
+        let hour: u32 = h.parse().ok()?;
+        let minute: u32 = m.parse().ok()?;
+        if hour > 23 || minute > 59 {
+            return None;
+        }
+        Some(Self { hour, minute })
+    }
+
+    pub fn seconds(&self) -> i64 {
+        (self.hour * 3600 + self.minute * 60) as i64
+    }
+}
+
+impl std::fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalDateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    // 0 = понедельник ... 6 = воскресенье
+    pub weekday: u32,
+    pub offset: i32,
+}
+
+impl LocalDateTime {
+    pub fn time_of_day(&self) -> TimeOfDay {
+        TimeOfDay {
+            hour: self.hour,
+            minute: self.minute,
+        }
+    }
+
+    pub fn days(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day)
+    }
+}
+
+impl std::fmt::Display for LocalDateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let off = self.offset.unsigned_abs();
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {}{:02}:{:02}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            sign,
+            off / 3600,
+            (off % 3600) / 60
+        )
+    }
+}
+
+// --- POSIX TZ ("EET-2EEST,M3.5.0/3,M10.5.0/4") ---
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleDate {
+    // Jn: день года 1..365 без учета 29 февраля
+    Julian(u32),
+    // n: день года 0..365 с учетом 29 февраля
+    Ordinal(u32),
+    // Mm.w.d: d-й день недели (0 = вс) w-й недели месяца m (5 = последний)
+    MonthWeekDay(u32, u32, u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DstRule {
+    offset: i32,
+    start: RuleDate,
+    start_time: i32,
+    end: RuleDate,
+    end_time: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PosixTz {
+    std_offset: i32,
+    dst: Option<DstRule>,
+}
+
+impl PosixTz {
+    fn parse(s: &str) -> Option<Self> {
+        let mut p = Cursor {
+            s: s.as_bytes(),
+            i: 0,
+        };
+        p.name()?;
+        let std_offset = -p.offset(MAX_OFFSET_HOURS)?;
+        if p.done() {
+            return Some(Self {
+                std_offset,
+                dst: None,
+            });
+        }
+        p.name()?;
+        let offset = if p.peek() != Some(b',') && !p.done() {
+            -p.offset(MAX_OFFSET_HOURS)?
+        } else {
+            std_offset + 3600
+        };
+        if p.done() {
+            // Без правил переходов: по POSIX умолчание - правила США
+            return Some(Self {
+                std_offset,
+                dst: Some(DstRule {
+                    offset,
+                    start: RuleDate::MonthWeekDay(3, 2, 0),
+                    start_time: 7200,
+                    end: RuleDate::MonthWeekDay(11, 1, 0),
+                    end_time: 7200,
+                }),
+            });
+        }
+        p.expect(b',')?;
+        let (start, start_time) = p.rule()?;
+        p.expect(b',')?;
+        let (end, end_time) = p.rule()?;
+        if !p.done() {
+            return None;
+        }
+        Some(Self {
+            std_offset,
+            dst: Some(DstRule {
+                offset,
+                start,
+                start_time,
+                end,
+                end_time,
+            }),
+        })
+    }
+
+    fn offset_at(&self, t: i64) -> i32 {
+        let Some(rule) = &self.dst else {
+            return self.std_offset;
+        };
+        let (year, _, _) = civil_from_days((t + self.std_offset as i64).div_euclid(86400));
+        // Проверяем соседние годы, чтобы правильно обработать границу 31.12/01.01
+        for y in [year - 1, year, year + 1] {
+            let start =
+                rule_day(y, rule.start) * 86400 + rule.start_time as i64 - self.std_offset as i64;
+            let end = rule_day(y, rule.end) * 86400 + rule.end_time as i64 - rule.offset as i64;
+            if start < end {
+                if t >= start && t < end {
+                    return rule.offset;
+                }
+            } else if y == year {
+                // Южное полушарие: летнее время через Новый год
+                return if t >= end && t < start {
+                    self.std_offset
+                } else {
+                    rule.offset
+                };
+            }
+        }
+        self.std_offset
+    }
+}
+
+// POSIX: смещение пояса - до 24 часов, время перехода в правиле - до 167
+const MAX_OFFSET_HOURS: i32 = 24;
+const MAX_RULE_HOURS: i32 = 167;
+
+struct Cursor<'a> {
+    s: &'a [u8],
+    i: usize,
+}
+
+impl Cursor<'_> {
+    fn done(&self) -> bool {
+        self.i >= self.s.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.i).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        if self.peek() == Some(c) {
+            self.i += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn name(&mut self) -> Option<()> {
+        let start = self.i;
+        if self.peek() == Some(b'<') {
+            while self.peek()? != b'>' {
+                self.i += 1;
+            }
+            self.i += 1;
+            return Some(());
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.i += 1;
+        }
+        (self.i - start >= 3).then_some(())
+    }
+
+    fn number(&mut self) -> Option<i32> {
+        let start = self.i;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.i += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.i])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    // [+-]hh[:mm[:ss]] в секундах, часы не больше max_hours. Строка из TZ
+    // или хвоста TZif - чужой ввод: "UTC9999999" - ошибка, не переполнение
+    fn offset(&mut self, max_hours: i32) -> Option<i32> {
+        let sign = match self.peek() {
+            Some(b'-') => {
+                self.i += 1;
+                -1
+            }
+            Some(b'+') => {
+                self.i += 1;
+                1
+            }
+            _ => 1,
+        };
+        let hours = self.number().filter(|h| *h <= max_hours)?;
+        let mut secs = hours.checked_mul(3600)?;
+        if self.expect(b':').is_some() {
+            secs = secs.checked_add(self.number().filter(|m| *m < 60)? * 60)?;
+            if self.expect(b':').is_some() {
+                secs = secs.checked_add(self.number().filter(|s| *s < 60)?)?;
+            }
+        }
+        Some(sign * secs)
+    }
+
+    fn rule(&mut self) -> Option<(RuleDate, i32)> {
+        let date = match self.peek()? {
+            b'J' => {
+                self.i += 1;
+                RuleDate::Julian(self.number().filter(|n| (1..=365).contains(n))? as u32)
+            }
+            b'M' => {
+                self.i += 1;
+                let m = self.number()? as u32;
+                self.expect(b'.')?;
+                let w = self.number()? as u32;
+                self.expect(b'.')?;
+                let d = self.number()? as u32;
+                if !(1..=12).contains(&m) || !(1..=5).contains(&w) || d > 6 {
+                    return None;
+                }
+                RuleDate::MonthWeekDay(m, w, d)
+            }
+            _ => RuleDate::Ordinal(self.number().filter(|n| *n <= 365)? as u32),
+        };
+        let time = if self.expect(b'/').is_some() {
+            self.offset(MAX_RULE_HOURS)?
+        } else {
+            7200
+        };
+        Some((date, time))
+    }
+}
+
+// Номер дня (от эпохи) для правила перехода в году y
+fn rule_day(y: i64, r: RuleDate) -> i64 {
+    let jan1 = days_from_civil(y, 1, 1);
+    match r {
+        RuleDate::Julian(n) => {
+            let mut d = jan1 + n as i64 - 1;
+            if is_leap(y) && n >= 60 {
+                d += 1;
+            }
+            d
+        }
+        RuleDate::Ordinal(n) => jan1 + n as i64,
+        RuleDate::MonthWeekDay(m, w, d) => {
+            let first = days_from_civil(y, m, 1);
+            // 1970-01-01 - четверг (4, если 0 = воскресенье)
+            let first_wd = (first + 4).rem_euclid(7);
+            let mut day = first + (d as i64 - first_wd).rem_euclid(7) + (w as i64 - 1) * 7;
+            let next_month = if m == 12 {
+                days_from_civil(y + 1, 1, 1)
+            } else {
+                days_from_civil(y, m + 1, 1)
+            };
+            while day >= next_month {
+                day -= 7;
+            }
+            day
+        }
+    }
+}
+
+// --- ЧАСОВОЙ ПОЯС ---
+#[derive(Debug, Clone)]
+pub struct TimeZone {
+    pub name: String,
+    // Переходы из TZif: (UTC-момент, смещение после него)
+    transitions: Vec<(i64, i32)>,
+    initial_offset: i32,
+    // Правило для моментов после последнего перехода
+    rule: Option<PosixTz>,
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self {
+            name: "UTC".into(),
+            transitions: Vec::new(),
+            initial_offset: 0,
+            rule: None,
+        }
+    }
+
+    pub fn from_posix(s: &str) -> Option<Self> {
+        let rule = PosixTz::parse(s)?;
+        Some(Self {
+            name: s.to_string(),
+            transitions: Vec::new(),
+            initial_offset: rule.std_offset,
+            rule: Some(rule),
+        })
+    }
+
+    // Порядок: явный override из конфига -> $TZ -> /etc/localtime -> UTC
+    pub fn resolve(name: Option<&str>) -> Result<Self, String> {
+        if let Some(n) = name.filter(|n| !n.trim().is_empty()) {
+            return Self::by_name(n).ok_or_else(|| format!("Unknown timezone '{}'", n));
+        }
+        if let Ok(tz) = env::var("TZ")
+            && let Some(z) = Self::by_name(&tz)
+        {
+            return Ok(z);
+        }
+        if let Ok(data) = fs::read(LOCALTIME_FILE)
+            && let Some(mut z) = Self::from_tzif(&data)
+        {
+            z.name = fs::read_link(LOCALTIME_FILE)
+                .ok()
+                .and_then(|p| {
+                    let s = p.to_string_lossy().to_string();
+                    s.split_once("zoneinfo/").map(|(_, n)| n.to_string())
+                })
+                .unwrap_or_else(|| "localtime".into());
+            return Ok(z);
+        }
+        Ok(Self::utc())
+    }
+
+    fn by_name(name: &str) -> Option<Self> {
+        let name = name.trim().trim_start_matches(':');
+        if name == "UTC" || name == "UTC0" {
+            return Some(Self::utc());
+        }
+        let path = if name.starts_with('/') {
+            Path::new(name).to_path_buf()
+        } else if name.contains("..") {
+            return None;
+        } else {
+            Path::new(ZONEINFO_DIR).join(name)
+        };
+        if let Ok(data) = fs::read(&path)
+            && let Some(mut z) = Self::from_tzif(&data)
+        {
+            z.name = name.to_string();
+            return Some(z);
+        }
+        Self::from_posix(name)
+    }
+
+    pub fn from_tzif(data: &[u8]) -> Option<Self> {
+        let header = |d: &[u8]| -> Option<[usize; 6]> {
+            if d.len() < 44 || &d[0..4] != b"TZif" {
+                return None;
+            }
+            let mut c = [0usize; 6];
+            for (k, v) in c.iter_mut().enumerate() {
+                let o = 20 + k * 4;
+                *v = u32::from_be_bytes(d[o..o + 4].try_into().ok()?) as usize;
+            }
+            Some(c)
+        };
+        let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = header(data)?;
+        let version = data[4];
+        let v1_len = timecnt * 5 + typecnt * 6 + charcnt + leapcnt * 8 + isstdcnt + isutcnt;
+
+        let (body, time_size, counts) = if version >= b'2' {
+            let d2 = data.get(44 + v1_len..)?;
+            (d2, 8, header(d2)?)
+        } else {
+            (
+                data,
+                4,
+                [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt],
+            )
+        };
+        let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = counts;
+        let mut pos = 44;
+        let read = |pos: usize, n: usize| body.get(pos..pos + n);
+
+        let mut times = Vec::with_capacity(timecnt);
+        for k in 0..timecnt {
+            let b = read(pos + k * time_size, time_size)?;
+            times.push(if time_size == 8 {
+                i64::from_be_bytes(b.try_into().ok()?)
+            } else {
+                i32::from_be_bytes(b.try_into().ok()?) as i64
+            });
+        }
+        pos += timecnt * time_size;
+        let idx = read(pos, timecnt)?.to_vec();
+        pos += timecnt;
+        let mut types = Vec::with_capacity(typecnt);
+        for k in 0..typecnt {
+            let b = read(pos + k * 6, 6)?;
+            types.push((i32::from_be_bytes(b[0..4].try_into().ok()?), b[4] != 0));
+        }
+        pos += typecnt * 6 + charcnt + leapcnt * (time_size + 4) + isstdcnt + isutcnt;
+
+        let transitions = times
+            .iter()
+            .zip(idx.iter())
+            .map(|(t, i)| types.get(*i as usize).map(|ty| (*t, ty.0)))
+            .collect::<Option<Vec<_>>>()?;
+        let initial_offset = types
+            .iter()
+            .find(|t| !t.1)
+            .or(types.first())
+            .map(|t| t.0)
+            .unwrap_or(0);
+
+        // Футер v2+: "\n<POSIX TZ>\n"
+        let rule = if time_size == 8 {
+            body.get(pos..)
+                .and_then(|f| std::str::from_utf8(f).ok())
+                .map(|f| f.trim_matches('\n'))
+                .filter(|f| !f.is_empty())
+                .and_then(PosixTz::parse)
+        } else {
+            None
+        };
+
+        Some(Self {
+            name: String::new(),
+            transitions,
+            initial_offset,
+            rule,
+        })
+    }
+
+    pub fn offset_at(&self, t: i64) -> i32 {
+        match self.transitions.binary_search_by(|(tt, _)| tt.cmp(&t)) {
+            Ok(i) => self.transitions[i].1,
+            Err(0) => {
+                if self.transitions.is_empty()
+                    && let Some(r) = &self.rule
+                {
+                    return r.offset_at(t);
+                }
+                self.initial_offset
+            }
+            Err(i) if i == self.transitions.len() => match &self.rule {
+                Some(r) => r.offset_at(t),
+                None => self.transitions[i - 1].1,
+            },
+            Err(i) => self.transitions[i - 1].1,
+        }
+    }
+
+    pub fn to_local(&self, t: i64) -> LocalDateTime {
+        let offset = self.offset_at(t);
+        let local = t + offset as i64;
+        let days = local.div_euclid(86400);
+        let secs = local.rem_euclid(86400) as u32;
+        let (year, month, day) = civil_from_days(days);
+        LocalDateTime {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            minute: (secs % 3600) / 60,
+            second: secs % 60,
+            weekday: (days + 3).rem_euclid(7) as u32,
+            offset,
+        }
+    }
+
+    // Локальное время дня `days` (номер дня от эпохи) + `secs` -> UTC.
+    // Попадание в "дыру" весеннего перехода сдвигается вперед на размер
+    // дыры (02:30 -> 03:30), при осеннем повторе берется первое вхождение.
+    pub fn local_to_utc(&self, days: i64, secs: i64) -> i64 {
+        let naive = days * 86400 + secs;
+        let before = self.offset_at(naive - 86400) as i64;
+        let after = self.offset_at(naive + 86400) as i64;
+        let mut best: Option<i64> = None;
+        for o in [before, after] {
+            let t = naive - o;
+            if self.offset_at(t) as i64 == o {
+                best = Some(best.map_or(t, |b| b.min(t)));
+            }
+        }
+        best.unwrap_or(naive - before)
+    }
+
+    // Ближайший момент строго после `now`, когда на часах будет `tod`
+    pub fn next_time_of_day(&self, now: i64, tod: TimeOfDay) -> i64 {
+        let today = self.to_local(now).days();
+        for d in 0..3 {
+            let t = self.local_to_utc(today + d, tod.seconds());
+            if t > now {
+                return t;
+            }
+        }
+        self.local_to_utc(today + 3, tod.seconds())
+    }
+}
+
+// --- ОКНА ПО ДНЯМ НЕДЕЛИ (тихие часы, графики отключений) ---
+// Формат: "[дни ]HH:MM-HH:MM", дни - "mon-fri", "sat,sun", "daily".
+// Окно через полночь ("22:00-07:00") относится к дню своего начала.
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeWindow {
+    // Бит i = день недели i (0 = понедельник)
+    days: u8,
+    start: TimeOfDay,
+    end: TimeOfDay,
+    pub source: String,
+}
+
+impl TimeWindow {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (days_part, time_part) = match s.rsplit_once(' ') {
+            Some((d, t)) => (Some(d.trim()), t),
+            None => (None, s),
+        };
+        let (a, b) = time_part.split_once('-')?;
+        let start = TimeOfDay::parse(a)?;
+        let end = TimeOfDay::parse(b)?;
+        if start == end {
+            return None;
+        }
+        let days = match days_part {
+            None => 0x7f,
+            Some(d) => parse_days(d)?,
+        };
+        Some(Self {
+            days,
+            start,
+            end,
+            source: s.to_string(),
+        })
+    }
+
+    fn day_on(&self, weekday: u32) -> bool {
+        self.days & (1 << (weekday % 7)) != 0
+    }
+
+    // Окно из дня недели и часов графика (18.5 = 18:30, 24 = полночь)
+    pub fn from_hours(weekday: u32, start: f64, end: f64) -> Option<Self> {
+        let tod = |h: f64| {
+            let mins = (h * 60.0).round() as u32 % (24 * 60);
+            TimeOfDay {
+                hour: mins / 60,
+                minute: mins % 60,
+            }
+        };
+        let (start, end) = (tod(start), tod(end));
+        if start == end || weekday > 6 {
+            return None;
+        }
+        Some(Self {
+            days: 1 << weekday,
+            start,
+            end,
+            source: format!("{} {}-{}", WEEKDAYS[weekday as usize], start, end),
+        })
+    }
+
+    // Ближайший после `now` момент окончания окна (по локальным часам)
+    pub fn end_after(&self, tz: &TimeZone, now: i64) -> i64 {
+        tz.next_time_of_day(now, self.end)
+    }
+
+    pub fn contains(&self, l: &LocalDateTime) -> bool {
+        let now = l.time_of_day();
+        if self.start < self.end {
+            self.day_on(l.weekday) && now >= self.start && now < self.end
+        } else {
+            (self.day_on(l.weekday) && now >= self.start)
+                || (self.day_on(l.weekday + 6) && now < self.end)
+        }
+    }
+}
+
+fn parse_days(s: &str) -> Option<u8> {
+    let day = |d: &str| WEEKDAYS.iter().position(|w| d.eq_ignore_ascii_case(w));
+    let mut mask = 0u8;
+    for part in s.split(',').map(str::trim) {
+        match part.to_ascii_lowercase().as_str() {
+            "daily" | "all" => mask |= 0x7f,
+            "weekdays" => mask |= 0x1f,
+            "weekends" => mask |= 0x60,
+            _ => match part.split_once('-') {
+                Some((a, b)) => {
+                    let (a, b) = (day(a)?, day(b)?);
+                    let mut i = a;
+                    loop {
+                        mask |= 1 << i;
+                        if i == b {
+                            break;
+                        }
+                        i = (i + 1) % 7;
+                    }
+                }
+                None => mask |= 1 << day(part)?,
+            },
+        }
+    }
+    (mask != 0).then_some(mask)
+}
+
+// Первое окно, в которое попадает `now`
+pub fn active_window<'a>(
+    windows: &'a [TimeWindow],
+    tz: &TimeZone,
+    now: i64,
+) -> Option<&'a TimeWindow> {
+    let l = tz.to_local(now);
+    windows.iter().find(|w| w.contains(&l))
+}
+
+// --- ПРОБУЖДЕНИЕ К ВРЕМЕНИ (wake_times) ---
+// "07:00", "mon-fri 06:30" - к времени на часах; "every 30m" - к ближайшей
+// отметке сетки от полуночи (:00 и :30). Шаг должен делить сутки.
+// "cron 30 6 * * 1-5" - пять полей crontab по локальным часам.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WakeTime {
+    At { days: u8, tod: TimeOfDay },
+    Every(i64),
+    Cron(Cron),
+}
+
+// Дней вперед, где ищем срабатывание: 29 февраля бывает и раз в 8 лет
+const CRON_SEARCH_DAYS: i64 = 8 * 366;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    // Бит d - число d (1..=31), бит m - месяц m (1..=12)
+    days: u64,
+    months: u64,
+    // Бит i - день недели i (0 = воскресенье, как в crontab)
+    weekdays: u64,
+    // Поле дня начиналось с "*". Ограничены оба - день подходит по числу
+    // ИЛИ по дню недели, как у cron
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    fn parse(s: &str) -> Option<Self> {
+        let [mi, h, dom, mon, dow] = s.split_whitespace().collect::<Vec<_>>()[..] else {
+            return None;
+        };
+        let weekdays = cron_field(dow, 0, 7)?;
+        let c = Self {
+            minutes: cron_field(mi, 0, 59)?,
+            hours: cron_field(h, 0, 23)?,
+            days: cron_field(dom, 1, 31)?,
+            months: cron_field(mon, 1, 12)?,
+            // 7 - тоже воскресенье
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: dom.starts_with('*'),
+            any_weekday: dow.starts_with('*'),
+        };
+        // "0 0 31 2 *" (31 февраля) не наступит никогда
+        const LAST_DAY: [u32; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+        let possible = (1..=12)
+            .filter(|m| c.months & 1 << m != 0)
+            .any(|m| c.days & ((1 << (LAST_DAY[m - 1] + 1)) - 2) != 0);
+        (possible || !c.any_weekday).then_some(c)
+    }
+
+    fn day_on(&self, day: i64) -> bool {
+        let (_, m, d) = civil_from_days(day);
+        // 1970-01-01 - четверг (4, если 0 = воскресенье)
+        let wd = (day + 4).rem_euclid(7);
+        let by_date = self.days & 1 << d != 0;
+        let by_weekday = self.weekdays & 1 << wd != 0;
+        let hit = if self.any_day || self.any_weekday {
+            by_date && by_weekday
+        } else {
+            by_date || by_weekday
+        };
+        self.months & 1 << m != 0 && hit
+    }
+
+    fn next_after(&self, tz: &TimeZone, now: i64) -> Option<i64> {
+        let today = tz.to_local(now).days();
+        for day in (today..today + CRON_SEARCH_DAYS).filter(|&d| self.day_on(d)) {
+            for h in (0..24).filter(|h| self.hours & 1 << h != 0) {
+                for m in (0..60).filter(|m| self.minutes & 1 << m != 0) {
+                    let t = tz.local_to_utc(day, h * 3600 + m * 60);
+                    if t > now {
+                        return Some(t);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+// Поле crontab: "*", "5", "1-5", "*/15", "0-30/10" и списки через запятую
+fn cron_field(s: &str, lo: u32, hi: u32) -> Option<u64> {
+    let mut mask = 0u64;
+    for part in s.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, st)) => (r, st.parse::<usize>().ok().filter(|&st| st > 0)?),
+            None => (part, 1),
+        };
+        let (a, b) = match range.split_once('-') {
+            _ if range == "*" => (lo, hi),
+            Some((a, b)) => (a.parse().ok()?, b.parse().ok()?),
+            // "5/15" - с 5 до конца шагом 15
+            None if step > 1 => (range.parse().ok()?, hi),
+            None => {
+                let a = range.parse().ok()?;
+                (a, a)
+            }
+        };
+        if a < lo || b > hi || a > b {
+            return None;
+        }
+        for v in (a..=b).step_by(step) {
+            mask |= 1 << v;
+        }
+    }
+    Some(mask)
+}
+
+impl WakeTime {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(expr) = s.strip_prefix("cron ") {
+            return Cron::parse(expr).map(WakeTime::Cron);
+        }
+        if let Some(span) = s.strip_prefix("every ") {
+            let step = parse_span(span)? as i64;
+            return (step >= 60 && 86400 % step == 0).then_some(WakeTime::Every(step));
+        }
+        let (days, time) = match s.rsplit_once(' ') {
+            Some((d, t)) => (parse_days(d.trim())?, t),
+            None => (0x7f, s),
+        };
+        Some(WakeTime::At {
+            days,
+            tod: TimeOfDay::parse(time)?,
+        })
+    }
+
+    // Ближайший момент строго после `now`
+    pub fn next_after(&self, tz: &TimeZone, now: i64) -> i64 {
+        let today = tz.to_local(now).days();
+        match *self {
+            WakeTime::At { days, tod } => (0..8)
+                .map(|d| tz.local_to_utc(today + d, tod.seconds()))
+                .find(|&t| t > now && days & (1 << tz.to_local(t).weekday) != 0)
+                .unwrap_or_else(|| tz.next_time_of_day(now, tod)),
+            WakeTime::Every(step) => (0..2)
+                .flat_map(|d| (0..86400 / step).map(move |i| (today + d, i * step)))
+                .map(|(day, secs)| tz.local_to_utc(day, secs))
+                .find(|&t| t > now)
+                .unwrap_or(now + step),
+            // Невозможную дату отсеял parse: None тут не бывает
+            WakeTime::Cron(ref c) => c.next_after(tz, now).unwrap_or(now + 86400),
+        }
+    }
+}
+
+// Ближайшая из целей, но не раньше чем через `min_sec`
+pub fn next_wake(times: &[WakeTime], tz: &TimeZone, now: i64, min_sec: i64) -> Option<i64> {
+    times
+        .iter()
+        .map(|w| w.next_after(tz, now + min_sec - 1))
+        .min()
+}
+
+// Длительность вида "90s", "30m", "24h", "7d" (без суффикса - секунды)
+pub fn parse_span(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (num, mult) = match s.char_indices().last()? {
+        (i, 's') => (&s[..i], 1),
+        (i, 'm') => (&s[..i], 60),
+        (i, 'h') => (&s[..i], 3600),
+        (i, 'd') => (&s[..i], 86400),
+        (i, 'w') => (&s[..i], 7 * 86400),
+        _ => (s, 1),
+    };
+    // "99999999999999999w" не влезает в u64 - это ошибка ввода, не паника
+    num.parse::<u64>().ok()?.checked_mul(mult)
+}
+
+// Момент в будущем: "23:30" (ближайшее), "today 23:30", "tomorrow 07:00",
+// "2026-03-29 03:30". Прошедшее время - None.
+pub fn parse_moment(s: &str, tz: &TimeZone, now: i64) -> Option<i64> {
+    let s = s.trim().to_ascii_lowercase();
+    let (day, time) = match s.rsplit_once(' ') {
+        Some((d, t)) => (Some(d.trim()), t),
+        None => (None, s.as_str()),
+    };
+    let tod = TimeOfDay::parse(time)?;
+    let today = tz.to_local(now).days();
+    let t = match day {
+        None => return Some(tz.next_time_of_day(now, tod)),
+        Some("today") => tz.local_to_utc(today, tod.seconds()),
+        Some("tomorrow") => tz.local_to_utc(today + 1, tod.seconds()),
+        Some(date) => {
+            let mut p = date.splitn(3, '-');
+            let y = p.next()?.parse().ok()?;
+            let m = p.next()?.parse().ok().filter(|m| (1..=12).contains(m))?;
+            let d = p.next()?.parse().ok().filter(|d| (1..=31).contains(d))?;
+            tz.local_to_utc(days_from_civil(y, m, d), tod.seconds())
+        }
+    };
+    (t > now).then_some(t)
+}
+
+// --- КАЛЕНДАРЬ (алгоритмы Howard Hinnant) ---
+pub fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = m as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+pub fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+fn is_leap(y: i64) -> bool {
+    (y % 4 == 0 && y % 100 != 0) || y % 400 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KYIV: &str = "EET-2EEST,M3.5.0/3,M10.5.0/4";
+    const NEW_YORK: &str = "EST5EDT,M3.2.0,M11.1.0";
+
+    fn utc(y: i64, mo: u32, d: u32, h: i64, mi: i64) -> i64 {
+        days_from_civil(y, mo, d) * 86400 + h * 3600 + mi * 60
+    }
+
+    fn tod(s: &str) -> TimeOfDay {
+        TimeOfDay::parse(s).unwrap()
+    }
+
+    #[test]
+    fn civil_roundtrip() {
+        for days in [-800_000, -1, 0, 1, 11_016, 20_000, 800_000] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+    }
+
+    #[test]
+    fn kyiv_offsets_around_transitions() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        // 2026: переход на летнее 29 марта 01:00 UTC, обратно 25 октября 01:00 UTC
+        assert_eq!(tz.offset_at(utc(2026, 3, 29, 0, 59)), 7200);
+        assert_eq!(tz.offset_at(utc(2026, 3, 29, 1, 0)), 10800);
+        assert_eq!(tz.offset_at(utc(2026, 10, 25, 0, 59)), 10800);
+        assert_eq!(tz.offset_at(utc(2026, 10, 25, 1, 0)), 7200);
+        assert_eq!(tz.offset_at(utc(2026, 1, 1, 0, 0)), 7200);
+    }
+
+    #[test]
+    fn wake_at_0630_across_spring_forward() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        // Суббота 28 марта 23:00 по Киеву (21:00 UTC)
+        let now = utc(2026, 3, 28, 21, 0);
+        let wake = tz.next_time_of_day(now, tod("06:30"));
+        // 29 марта уже летнее время: 06:30 EEST = 03:30 UTC
+        assert_eq!(wake, utc(2026, 3, 29, 3, 30));
+        assert_eq!(tz.to_local(wake).time_of_day(), tod("06:30"));
+        // Наивные +24 часа от вчерашних 06:30 EET дали бы 07:30
+        let prev = tz.local_to_utc(days_from_civil(2026, 3, 28), tod("06:30").seconds());
+        assert_eq!(wake - prev, 23 * 3600);
+    }
+
+    #[test]
+    fn wake_at_0630_across_fall_back() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        let now = utc(2026, 10, 24, 21, 0);
+        let wake = tz.next_time_of_day(now, tod("06:30"));
+        assert_eq!(wake, utc(2026, 10, 25, 4, 30));
+        let prev = tz.local_to_utc(days_from_civil(2026, 10, 24), tod("06:30").seconds());
+        assert_eq!(wake - prev, 25 * 3600);
+    }
+
+    #[test]
+    fn wake_times_pick_the_nearest_target() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        // Среда 14 октября 2026, 21:10 по Киеву (18:10 UTC)
+        let now = utc(2026, 10, 14, 18, 10);
+        let half = WakeTime::parse("every 30m").unwrap();
+        assert_eq!(half.next_after(&tz, now), utc(2026, 10, 14, 18, 30));
+        let weekdays = WakeTime::parse("mon-fri 07:00").unwrap();
+        assert_eq!(weekdays.next_after(&tz, now), utc(2026, 10, 15, 4, 0));
+        // Пятница вечером: будним утром - только в понедельник
+        let friday = utc(2026, 10, 16, 18, 10);
+        assert_eq!(weekdays.next_after(&tz, friday), utc(2026, 10, 19, 4, 0));
+        let both = [weekdays, half];
+        assert_eq!(
+            next_wake(&both, &tz, now, 60),
+            Some(utc(2026, 10, 14, 18, 30))
+        );
+        // Отметка через 30 сек - слишком близко, берем следующую
+        let close = utc(2026, 10, 14, 18, 29) + 30;
+        assert_eq!(
+            next_wake(&both, &tz, close, 60),
+            Some(utc(2026, 10, 14, 19, 0))
+        );
+        assert_eq!(WakeTime::parse("every 7m"), None);
+        assert_eq!(WakeTime::parse("25:00"), None);
+    }
+
+    #[test]
+    fn cron_wake_times() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        // Пятница 27 марта 2026, 07:00 по Киеву; в воскресенье переход на летнее
+        let friday = utc(2026, 3, 27, 5, 0);
+        let workdays = WakeTime::parse("cron 30 6 * * 1-5").unwrap();
+        // Понедельник 30 марта 06:30 EEST = 03:30 UTC
+        assert_eq!(workdays.next_after(&tz, friday), utc(2026, 3, 30, 3, 30));
+        // Среда 14 октября 2026, 21:10 по Киеву
+        let now = utc(2026, 10, 14, 18, 10);
+        let six_hours = WakeTime::parse("cron 0 */6 * * *").unwrap();
+        assert_eq!(six_hours.next_after(&tz, now), utc(2026, 10, 14, 21, 0));
+        // Число и день недели ограничены оба: 1-е число или воскресенье
+        let either = WakeTime::parse("cron 0 7 1 * 7").unwrap();
+        assert_eq!(either.next_after(&tz, now), utc(2026, 10, 18, 4, 0));
+        let leap = WakeTime::parse("cron 0 12 29 2 *").unwrap();
+        assert_eq!(leap.next_after(&tz, now), utc(2028, 2, 29, 10, 0));
+        for bad in [
+            "cron 0 0 31 2 *",
+            "cron 60 * * * *",
+            "cron * * * *",
+            "cron */0 * * * *",
+            "cron 5-1 * * * *",
+        ] {
+            assert_eq!(WakeTime::parse(bad), None, "{}", bad);
+        }
+        assert!(WakeTime::parse("cron 0 0 31 2 1").is_some());
+    }
+
+    #[test]
+    fn offsets_out_of_range_are_rejected() {
+        assert!(TimeZone::from_posix("UTC9999999").is_none());
+        assert!(TimeZone::from_posix("UTC25").is_none());
+        assert!(TimeZone::from_posix("EET-2EEST,M3.5.0/999999999,M10.5.0/4").is_none());
+        assert!(TimeZone::from_posix("EET-2EEST,J366,J300").is_none());
+        // Время перехода до 167 часов - по POSIX можно
+        assert!(TimeZone::from_posix("EET-2EEST,M3.5.0/167,M10.5.0/4").is_some());
+        assert!(TimeZone::from_posix("<+0530>-5:30").is_some());
+    }
+
+    #[test]
+    fn nonexistent_local_time_moves_forward() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        // 29 марта 03:00-04:00 по Киеву не существует
+        let t = tz.local_to_utc(days_from_civil(2026, 3, 29), tod("03:30").seconds());
+        assert_eq!(tz.to_local(t).time_of_day(), tod("04:30"));
+    }
+
+    #[test]
+    fn repeated_local_time_picks_first() {
+        let tz = TimeZone::from_posix(NEW_YORK).unwrap();
+        // 1 ноября 2026 01:30 бывает дважды: EDT (05:30 UTC) и EST (06:30 UTC)
+        let t = tz.local_to_utc(days_from_civil(2026, 11, 1), tod("01:30").seconds());
+        assert_eq!(t, utc(2026, 11, 1, 5, 30));
+        assert_eq!(tz.to_local(t).offset, -4 * 3600);
+    }
+
+    #[test]
+    fn southern_hemisphere_rule() {
+        // Сидней: летнее время с октября по апрель
+        let tz = TimeZone::from_posix("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(tz.offset_at(utc(2026, 1, 15, 0, 0)), 11 * 3600);
+        assert_eq!(tz.offset_at(utc(2026, 7, 15, 0, 0)), 10 * 3600);
+    }
+
+    #[test]
+    fn weekday_and_display() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        let l = tz.to_local(utc(2026, 10, 14, 9, 5));
+        // 14.10.2026 - среда
+        assert_eq!(l.weekday, 2);
+        assert_eq!(l.to_string(), "2026-10-14 12:05:00 +03:00");
+    }
+
+    #[test]
+    fn quiet_hours_weekdays() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        let w = [TimeWindow::parse("mon-fri 08:00-18:00").unwrap()];
+        // Среда 14.10.2026 12:05 по Киеву
+        assert!(active_window(&w, &tz, utc(2026, 10, 14, 9, 5)).is_some());
+        // Та же среда 18:30
+        assert!(active_window(&w, &tz, utc(2026, 10, 14, 15, 30)).is_none());
+        // Суббота 17.10.2026 12:00
+        assert!(active_window(&w, &tz, utc(2026, 10, 17, 9, 0)).is_none());
+    }
+
+    #[test]
+    fn quiet_hours_overnight_and_dst() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        let w = [TimeWindow::parse("fri 22:00-07:00").unwrap()];
+        // Ночь с пятницы 27.03 на субботу 28.03: суббота 06:30 EET ещё тихая
+        assert!(active_window(&w, &tz, utc(2026, 3, 28, 4, 30)).is_some());
+        // А в субботу 22:30 - уже нет
+        assert!(active_window(&w, &tz, utc(2026, 3, 28, 20, 30)).is_none());
+        // Летом границы сдвигаются вместе с локальным временем: 21:59 EEST
+        let summer = [TimeWindow::parse("22:00-23:00").unwrap()];
+        assert!(active_window(&summer, &tz, utc(2026, 7, 1, 18, 59)).is_none());
+        assert!(active_window(&summer, &tz, utc(2026, 7, 1, 19, 0)).is_some());
+    }
+
+    #[test]
+    fn window_from_schedule_hours() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        // Среда (2), отключение 18:30-24:00
+        let w = TimeWindow::from_hours(2, 18.5, 24.0).unwrap();
+        assert_eq!(w.source, "wed 18:30-00:00");
+        let now = utc(2026, 10, 14, 16, 0);
+        assert!(w.contains(&tz.to_local(now)));
+        assert_eq!(w.end_after(&tz, now), utc(2026, 10, 14, 21, 0));
+        // Четверг 00:10 уже вне окна
+        assert!(!w.contains(&tz.to_local(utc(2026, 10, 14, 21, 10))));
+    }
+
+    #[test]
+    fn quiet_window_parsing() {
+        assert!(TimeWindow::parse("sat,sun 10:00-12:00").is_some());
+        assert!(TimeWindow::parse("weekends 10:00-12:00").is_some());
+        assert!(TimeWindow::parse("fri-mon 10:00-12:00").is_some());
+        assert!(TimeWindow::parse("funday 10:00-12:00").is_none());
+        assert!(TimeWindow::parse("10:00-10:00").is_none());
+        assert!(TimeWindow::parse("10:00").is_none());
+    }
+
+    #[test]
+    fn span_parsing() {
+        assert_eq!(parse_span("24h"), Some(86400));
+        assert_eq!(parse_span("30m"), Some(1800));
+        assert_eq!(parse_span("45"), Some(45));
+        assert_eq!(parse_span("h"), None);
+        assert_eq!(parse_span("99999999999999999w"), None);
+    }
+
+    #[test]
+    fn moment_parsing() {
+        let tz = TimeZone::from_posix(KYIV).unwrap();
+        // 14 октября 2026, 22:00 по Киеву
+        let now = utc(2026, 10, 14, 19, 0);
+        assert_eq!(
+            parse_moment("23:30", &tz, now),
+            Some(utc(2026, 10, 14, 20, 30))
+        );
+        assert_eq!(
+            parse_moment("07:00", &tz, now),
+            Some(utc(2026, 10, 15, 4, 0))
+        );
+        assert_eq!(
+            parse_moment("Tomorrow 07:00", &tz, now),
+            Some(utc(2026, 10, 15, 4, 0))
+        );
+        assert_eq!(parse_moment("today 07:00", &tz, now), None);
+        // После перехода на зимнее время смещение уже +02:00
+        assert_eq!(
+            parse_moment("2026-10-26 07:00", &tz, now),
+            Some(utc(2026, 10, 26, 5, 0))
+        );
+        assert_eq!(parse_moment("2026-13-01 07:00", &tz, now), None);
+        assert_eq!(parse_moment("soon", &tz, now), None);
+    }
+
+    #[test]
+    fn time_of_day_parsing() {
+        assert_eq!(
+            tod("6:30"),
+            TimeOfDay {
+                hour: 6,
+                minute: 30
+            }
+        );
+        assert!(TimeOfDay::parse("24:00").is_none());
+        assert!(TimeOfDay::parse("07").is_none());
+    }
+}
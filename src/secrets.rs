@@ -0,0 +1,117 @@
+// === СЕКРЕТЫ ИЗ ВНЕШНИХ ИСТОЧНИКОВ ===
+// config.json читается root'ом и уезжает в бэкапы вместе с /etc, поэтому
+// токены и пароли можно не писать в него открытым текстом, а сослаться:
+//   "env:PORTAL_TG_TOKEN"          - переменная окружения (Environment= в юните)
+//   "file:/etc/portal_daemon/tg"   - файл, концевой перевод строки срезается
+//   "systemd-creds:portal.tg"      - учетные данные systemd (LoadCredential=,
+//                                    ImportCredential= из /etc/credstore)
+// Все остальное - сам секрет: у токена Telegram тоже есть двоеточие.
+// Подставляется только в памяти демона - config.json.good остается со ссылками.
+// `portal_daemon secret NAME` кладет секрет в свое хранилище (файл 0600) и
+// печатает ссылку file:, которую остается вписать в конфиг.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+use crate::error::PortalError;
+use crate::{log, paths};
+
+// Куда systemd кладет учетные данные, если демон запущен не юнитом (CLI)
+const SERVICE_CREDENTIALS: &str = "/run/credentials/portal.service";
+// Свое хранилище рядом с конфигом: каталог 0700, файлы 0600
+pub const STORE_DIR: &str = "secrets";
+
+pub fn resolve(value: &str) -> Result<String, String> {
+    if let Some(var) = value.strip_prefix("env:") {
+        return env::var(var).map_err(|_| format!("environment variable {} is not set", var));
+    }
+    if let Some(path) = value.strip_prefix("file:") {
+        return read(Path::new(path));
+    }
+    if let Some(name) = value.strip_prefix("systemd-creds:") {
+        if name.is_empty() || name.contains('/') {
+            return Err(format!("invalid credential name '{}'", name));
+        }
+        let dir = env::var("CREDENTIALS_DIRECTORY").unwrap_or_else(|_| SERVICE_CREDENTIALS.into());
+        return read(&Path::new(&dir).join(name));
+    }
+    Ok(value.to_string())
+}
+
+// Без префикса - секрет записан в конфиг открытым текстом
+pub fn is_inline(value: &str) -> bool {
+    !value.is_empty()
+        && !["env:", "file:", "systemd-creds:"]
+            .iter()
+            .any(|p| value.starts_with(p))
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    // Читается, но виден не только владельцу - подставляем и предупреждаем
+    if let Ok(meta) = fs::metadata(path)
+        && meta.permissions().mode() & 0o077 != 0
+    {
+        log::warn!(
+            "⚠️  Secret file {} is readable by group/others, chmod 600 it",
+            path.display()
+        );
+    }
+    Ok(s.trim_end_matches(['\r', '\n']).to_string())
+}
+
+// Подставляет секрет на место ссылки; не вышло - поле пустое (канал без
+// токена обычно просто выключен), а ошибка - в errors
+pub fn resolve_in(field: &str, value: &mut String, errors: &mut Vec<String>) {
+    match resolve(value) {
+        Ok(v) => *value = v,
+        Err(e) => {
+            errors.push(format!("{}: {}", field, e));
+            value.clear();
+        }
+    }
+}
+
+// Записывает секрет в хранилище и возвращает ссылку для конфига
+pub fn store(name: &str, value: &str) -> Result<String, PortalError> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(PortalError::Usage("secret name must be a plain file name"));
+    }
+    let dir = paths::config(STORE_DIR);
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .map_err(|e| PortalError::write(&dir, e))?;
+    let path = format!("{}/{}", dir, name);
+    // Права ставим и у старого файла: open с mode их не меняет
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut f| {
+            f.set_permissions(fs::Permissions::from_mode(0o600))?;
+            writeln!(f, "{}", value)
+        })
+        .map_err(|e| PortalError::write(&path, e))?;
+    Ok(format!("file:{}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_are_not_inline() {
+        assert!(is_inline("123456:ABC-telegram"));
+        assert!(!is_inline(""));
+        assert!(!is_inline("env:PORTAL_TG_TOKEN"));
+        assert!(!is_inline("file:/etc/portal_daemon/secrets/tg"));
+        assert!(!is_inline("systemd-creds:portal.tg"));
+    }
+}
@@ -0,0 +1,249 @@
+// === ДАТЧИК СЕТИ (probe = "sensor") ===
+// Шлюз на Raspberry Pi видит сеть сам, а не по пингу роутера: оптопара или
+// реле от 230 В на GPIO, либо датчик на USB/UART, пишущий строку состояния.
+//   "gpio:17"             - /sys/class/gpio (номер глобальный: на ядрах 6.6+
+//                           у Pi это 512 + GPIO), экспорт - сами
+//   "gpiod:gpiochip0:17"  - gpioget из libgpiod (1.x и 2.x)
+//   "serial:/dev/ttyUSB0" - строки от датчика; свет - по serial_on/serial_off
+// После run_as пользователю нужны группы gpio/dialout (--install добавляет).
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::reactor;
+
+const GPIO_DIR: &str = "/sys/class/gpio";
+// Группы, которым udev отдает GPIO и последовательные порты
+pub const SENSOR_GROUPS: [&str; 2] = ["gpio", "dialout"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SensorConfig {
+    pub source: String,
+    // Уровень 0 = свет есть (оптопара тянет линию к земле)
+    pub active_low: bool,
+    pub baud: u32,
+    // serial: подстрока "свет есть" / "света нет" (выключение проверяется первым)
+    pub serial_on: String,
+    pub serial_off: String,
+    // serial: столько ждем свежую строку; дольше - датчик молчит
+    pub serial_timeout_sec: u64,
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        Self {
+            source: String::new(),
+            active_low: false,
+            baud: 9600,
+            serial_on: "1".into(),
+            serial_off: "0".into(),
+            serial_timeout_sec: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source {
+    Gpio(u32),
+    Gpiod { chip: String, line: u32 },
+    Serial(String),
+}
+
+impl Source {
+    fn parse(s: &str) -> Option<Self> {
+        let (kind, rest) = s.trim().split_once(':')?;
+        match kind {
+            "gpio" => rest.parse().ok().map(Source::Gpio),
+            "gpiod" => {
+                let (chip, line) = rest.rsplit_once(':')?;
+                (!chip.is_empty() && !chip.contains('/')).then_some(())?;
+                Some(Source::Gpiod {
+                    chip: chip.to_string(),
+                    line: line.parse().ok()?,
+                })
+            }
+            "serial" if rest.starts_with("/dev/") => Some(Source::Serial(rest.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl SensorConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let source = Source::parse(&self.source)
+            .ok_or_else(|| format!("invalid source '{}'", self.source))?;
+        if let Source::Serial(_) = source {
+            baud(self.baud).ok_or_else(|| format!("unsupported baud {}", self.baud))?;
+            if self.serial_on.is_empty() || self.serial_off.is_empty() {
+                return Err("serial_on and serial_off must not be empty".into());
+            }
+            if self.serial_timeout_sec == 0 {
+                return Err("serial_timeout_sec must be above 0".into());
+            }
+        }
+        Ok(())
+    }
+}
+
+// true - свет есть
+pub fn read(cfg: &SensorConfig) -> Result<bool, String> {
+    match Source::parse(&cfg.source).ok_or("invalid sensor source")? {
+        Source::Gpio(n) => read_sysfs(n).map(|high| high != cfg.active_low),
+        Source::Gpiod { chip, line } => read_gpiod(&chip, line).map(|high| high != cfg.active_low),
+        Source::Serial(dev) => {
+            let line = read_line(&dev, cfg.baud, Duration::from_secs(cfg.serial_timeout_sec))?;
+            classify(cfg, &line)
+        }
+    }
+}
+
+fn level(s: &str) -> Result<bool, String> {
+    match s.trim() {
+        "0" | "inactive" => Ok(false),
+        "1" | "active" => Ok(true),
+        other => Err(format!("unexpected GPIO value '{}'", other)),
+    }
+}
+
+// --- GPIO ---
+fn read_sysfs(n: u32) -> Result<bool, String> {
+    let dir = format!("{}/gpio{}", GPIO_DIR, n);
+    if !Path::new(&dir).exists() {
+        fs::write(format!("{}/export", GPIO_DIR), n.to_string())
+            .map_err(|e| format!("export gpio{}: {}", n, e))?;
+        // udev раздает права на новый каталог не сразу
+        reactor::pause(Instant::now() + Duration::from_millis(100));
+        fs::write(format!("{}/direction", dir), "in").ok();
+    }
+    let value =
+        fs::read_to_string(format!("{}/value", dir)).map_err(|e| format!("{}: {}", dir, e))?;
+    level(&value)
+}
+
+// libgpiod 1.x: `gpioget CHIP LINE` -> "1"; 2.x: `gpioget --numeric -c CHIP LINE`
+fn read_gpiod(chip: &str, line: u32) -> Result<bool, String> {
+    let line = line.to_string();
+    let mut last = String::from("gpioget not found");
+    for args in [
+        vec![chip, line.as_str()],
+        vec!["--numeric", "-c", chip, line.as_str()],
+    ] {
+        match reactor::output(Command::new("gpioget").args(&args)) {
+            Ok(o) if o.status.success() => return level(&String::from_utf8_lossy(&o.stdout)),
+            Ok(o) => last = String::from_utf8_lossy(&o.stderr).trim().to_string(),
+            Err(e) => return Err(format!("gpioget: {}", e)),
+        }
+    }
+    Err(format!("gpioget: {}", last))
+}
+
+// --- ПОСЛЕДОВАТЕЛЬНЫЙ ПОРТ ---
+fn baud(b: u32) -> Option<libc::speed_t> {
+    Some(match b {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        _ => return None,
+    })
+}
+
+fn classify(cfg: &SensorConfig, line: &str) -> Result<bool, String> {
+    if line.contains(&cfg.serial_off) {
+        Ok(false)
+    } else if line.contains(&cfg.serial_on) {
+        Ok(true)
+    } else {
+        Err(format!("unexpected sensor line '{}'", line))
+    }
+}
+
+// Старое из буфера сбрасываем: первая строка после него может быть
+// обрезанной, поэтому берем следующую целую
+fn read_line(dev: &str, speed: u32, timeout: Duration) -> Result<String, String> {
+    let err = |e: std::io::Error| format!("{}: {}", dev, e);
+    let mut f = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(dev)
+        .map_err(err)?;
+    let fd = f.as_raw_fd();
+    // Не терминал (FIFO от эмулятора) - читаем как есть
+    unsafe {
+        let mut tio: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut tio) == 0 {
+            libc::cfmakeraw(&mut tio);
+            libc::cfsetspeed(&mut tio, baud(speed).unwrap_or(libc::B9600));
+            tio.c_cflag |= libc::CLOCAL | libc::CREAD;
+            libc::tcsetattr(fd, libc::TCSANOW, &tio);
+            libc::tcflush(fd, libc::TCIFLUSH);
+        }
+    }
+    let deadline = Instant::now() + timeout;
+    let (mut buf, mut chunk) = (Vec::new(), [0u8; 256]);
+    let mut synced = false;
+    loop {
+        while let Some(end) = buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=end).collect();
+            if synced {
+                return Ok(String::from_utf8_lossy(&line).trim().to_string());
+            }
+            synced = true;
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(format!("{}: no line within {} sec", dev, timeout.as_secs()));
+        }
+        if !reactor::wait_readable(fd, deadline) {
+            continue;
+        }
+        match f.read(&mut chunk) {
+            Ok(0) => reactor::pause(Instant::now() + Duration::from_millis(50)),
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_and_lines() {
+        assert_eq!(Source::parse("gpio:529"), Some(Source::Gpio(529)));
+        assert_eq!(
+            Source::parse("gpiod:gpiochip0:17"),
+            Some(Source::Gpiod {
+                chip: "gpiochip0".into(),
+                line: 17
+            })
+        );
+        assert_eq!(
+            Source::parse("serial:/dev/ttyUSB0"),
+            Some(Source::Serial("/dev/ttyUSB0".into()))
+        );
+        assert_eq!(Source::parse("serial:ttyUSB0"), None);
+        assert_eq!(Source::parse("gpio:x"), None);
+        let cfg = SensorConfig {
+            serial_on: "MAINS ON".into(),
+            serial_off: "MAINS OFF".into(),
+            ..Default::default()
+        };
+        assert_eq!(classify(&cfg, "MAINS ON 229V"), Ok(true));
+        assert_eq!(classify(&cfg, "MAINS OFF"), Ok(false));
+        assert!(classify(&cfg, "boot").is_err());
+    }
+}
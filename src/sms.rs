@@ -0,0 +1,166 @@
+// === SMS ЧЕРЕЗ LTE-МОДЕМ ===
+// Запасной канал для удаленных точек: когда пропал свет, обычно лежит и
+// интернет, так что ntfy/Telegram не дойдут. SMS уходит через ModemManager
+// (mmcli) или, если задан порт модема, AT-командами напрямую.
+// Отправка синхронная: сразу после SleepRequested машина уснет, поток бы
+// просто не успел.
+
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::events::{Event, Subscriber};
+use crate::notify::NotifyConfig;
+use crate::{hostname, log, watchdog};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct SmsText {
+    pub sleeping: String,
+    pub battery: String,
+}
+
+pub struct Sms {
+    number: String,
+    modem: String,
+    text: SmsText,
+    // Досыпание после раннего подъема - не повод слать SMS еще раз
+    rearmed: bool,
+}
+
+impl Sms {
+    pub fn new(cfg: &NotifyConfig, text: SmsText) -> Self {
+        Self {
+            number: cfg.sms_number.clone(),
+            modem: cfg.sms_modem.clone(),
+            text,
+            rearmed: false,
+        }
+    }
+
+    fn send(&self, message: String) {
+        let message = format!("portal_daemon @ {}: {}", hostname(), message);
+        let res = if self.modem.starts_with("/dev/") {
+            send_at(&self.modem, &self.number, &message)
+        } else {
+            send_mmcli(&self.modem, &self.number, &message)
+        };
+        match res {
+            Ok(()) => log::info!(number = self.number; "📨 SMS sent"),
+            Err(e) => log::warn!(number = self.number; "⚠️  SMS failed: {}", e),
+        }
+    }
+}
+
+impl Subscriber for Sms {
+    fn on_event(&mut self, e: &Event) {
+        match e {
+            Event::SleepRequested { seconds, mode } if !self.rearmed => self.send(format!(
+                "{} {} min ({})",
+                self.text.sleeping,
+                seconds.div_ceil(60),
+                mode
+            )),
+            Event::BatteryLow { percent } => {
+                self.send(format!("{} {}%", self.text.battery, percent))
+            }
+            Event::Woke { rearmed, .. } => self.rearmed = *rearmed,
+            _ => {}
+        }
+    }
+}
+
+// mmcli: создать SMS в хранилище модема, отправить, удалить.
+// modem - индекс или путь ModemManager, пусто - первый попавшийся
+fn send_mmcli(modem: &str, number: &str, message: &str) -> Result<(), String> {
+    let modem = if modem.is_empty() { "any" } else { modem };
+    let timeout = format!("--timeout={}", TIMEOUT.as_secs());
+    // В значениях mmcli кавычки - разделители, в тексте их быть не должно
+    let create = format!(
+        "--messaging-create-sms=text='{}',number='{}'",
+        message.replace('\'', ""),
+        number.replace('\'', "")
+    );
+    let out = Command::new("mmcli")
+        .args(["-m", modem, &timeout, &create])
+        .output()
+        .map_err(|e| format!("mmcli: {}", e))?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let path = text
+        .split_whitespace()
+        .find(|w| w.starts_with("/org/freedesktop/ModemManager1/SMS/"))
+        .ok_or_else(|| format!("mmcli: {}", String::from_utf8_lossy(&out.stderr).trim()))?;
+    let sent = Command::new("mmcli")
+        .args(["-s", path, &timeout, "--send"])
+        .status()
+        .is_ok_and(|s| s.success());
+    Command::new("mmcli")
+        .args(["-m", modem, &format!("--messaging-delete-sms={}", path)])
+        .output()
+        .ok();
+    if sent {
+        Ok(())
+    } else {
+        Err(format!("mmcli could not send {}", path))
+    }
+}
+
+// AT-команды в текстовом режиме: AT+CMGF=1, AT+CMGS="<номер>", текст, Ctrl-Z.
+// Не-ASCII (кириллица) - через кодировку UCS2: номер и текст в hex UTF-16BE
+fn send_at(port: &str, number: &str, message: &str) -> Result<(), String> {
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(port)
+        .map_err(|e| format!("{}: {}", port, e))?;
+    raw_mode(tty.as_raw_fd());
+    let deadline = Instant::now() + TIMEOUT;
+    let mut cmd = |send: &[u8], expect: &str| -> Result<(), String> {
+        tty.write_all(send).map_err(|e| e.to_string())?;
+        let mut reply = String::new();
+        let mut buf = [0u8; 256];
+        while Instant::now() < deadline {
+            watchdog::pet();
+            // VTIME = 1 с: read возвращает 0 по таймауту
+            let n = tty.read(&mut buf).map_err(|e| e.to_string())?;
+            reply.push_str(&String::from_utf8_lossy(&buf[..n]));
+            if reply.contains(expect) {
+                return Ok(());
+            }
+            if reply.contains("ERROR") {
+                return Err(format!("modem replied {}", reply.trim()));
+            }
+        }
+        Err(format!("no '{}' from modem", expect))
+    };
+    cmd(b"AT+CMGF=1\r", "OK")?;
+    let (number, body) = if message.is_ascii() {
+        cmd(b"AT+CSCS=\"GSM\"\r", "OK")?;
+        cmd(b"AT+CSMP=17,167,0,0\r", "OK")?;
+        (number.to_string(), message.replace('\x1a', ""))
+    } else {
+        cmd(b"AT+CSCS=\"UCS2\"\r", "OK")?;
+        // DCS 8 - текст в UCS2
+        cmd(b"AT+CSMP=17,167,0,8\r", "OK")?;
+        (ucs2_hex(number), ucs2_hex(message))
+    };
+    cmd(format!("AT+CMGS=\"{}\"\r", number).as_bytes(), ">")?;
+    cmd(format!("{}\x1a", body).as_bytes(), "OK")
+}
+
+fn ucs2_hex(s: &str) -> String {
+    s.encode_utf16().map(|u| format!("{:04X}", u)).collect()
+}
+
+fn raw_mode(fd: i32) {
+    let mut term: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut term) } != 0 {
+        return;
+    }
+    unsafe { libc::cfmakeraw(&mut term) };
+    term.c_cc[libc::VMIN] = 0;
+    term.c_cc[libc::VTIME] = 10;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &term) };
+}
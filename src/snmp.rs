@@ -0,0 +1,288 @@
+// === SNMP: РОУТЕР ПЕРЕЗАГРУЖАЕТСЯ ИЛИ СВЕТА НЕТ (probe = "snmp") ===
+// SNMPv2c GET к lighthouse_ip: sysUpTime.0 и, если задан if_index,
+// ifOperStatus.<if_index> - порт к тому, что без ИБП (ONT, свитч, WAN).
+//   роутер отвечает, порт up (или порт не задан) - свет есть
+//   роутер отвечает, порт down                   - света нет: grace_period_sec
+//   роутер молчит, порт задан                    - роутер на ИБП сам по себе
+//     не гаснет: просадка его перезагрузила, ждем snmp.reboot_grace_sec
+//   роутер молчит, порт не задан                 - света нет
+// sysUpTime меньше прошлого - роутер перезагружался (пишем в журнал).
+// BER вручную: клиенту хватает INTEGER/OCTET STRING/OID/NULL/TimeTicks.
+
+use serde::{Deserialize, Serialize};
+use std::net::UdpSocket;
+use std::os::fd::AsRawFd;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{log, reactor};
+
+const SYS_UPTIME: [u32; 9] = [1, 3, 6, 1, 2, 1, 1, 3, 0];
+const IF_OPER_STATUS: [u32; 10] = [1, 3, 6, 1, 2, 1, 2, 2, 1, 8];
+// ifOperStatus: up(1)
+const IF_UP: i64 = 1;
+static REQUEST_ID: AtomicU32 = AtomicU32::new(1);
+// Последний sysUpTime, сек: по его сбросу видна перезагрузка
+static LAST_UPTIME: Mutex<Option<u64>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SnmpConfig {
+    // Можно ссылкой: "file:..." (secrets.rs)
+    pub community: String,
+    pub port: u16,
+    // ifIndex порта, который гаснет без света; 0 - только sysUpTime
+    pub if_index: u32,
+    // Grace, пока роутер молчит из-за перезагрузки
+    pub reboot_grace_sec: u64,
+}
+
+impl Default for SnmpConfig {
+    fn default() -> Self {
+        Self {
+            community: "public".into(),
+            port: 161,
+            if_index: 0,
+            reboot_grace_sec: 600,
+        }
+    }
+}
+
+impl SnmpConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("port must be set".into());
+        }
+        if self.community.is_empty() {
+            return Err("community must be set".into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Router {
+    // RTT запроса, мс
+    Up(f64),
+    PowerOut,
+    Rebooting,
+}
+
+pub fn check(ip: &str, cfg: &SnmpConfig, timeout: Duration) -> Router {
+    let started = Instant::now();
+    let mut oids = vec![SYS_UPTIME.to_vec()];
+    if cfg.if_index > 0 {
+        oids.push([&IF_OPER_STATUS[..], &[cfg.if_index]].concat());
+    }
+    let values = match get(ip, cfg, &oids, timeout) {
+        Ok(v) => v,
+        Err(e) => {
+            log::debug!(ip = ip; "snmp: {}", e);
+            return if cfg.if_index > 0 {
+                Router::Rebooting
+            } else {
+                Router::PowerOut
+            };
+        }
+    };
+    let rtt = started.elapsed().as_secs_f64() * 1000.0;
+    if let Some(Value::Ticks(t)) = values.first() {
+        let uptime = t / 100;
+        if let Ok(mut last) = LAST_UPTIME.lock() {
+            if last.is_some_and(|l| uptime < l) {
+                log::info!(ip = ip, uptime_sec = uptime; "🔁 Router rebooted {} sec ago", uptime);
+            }
+            *last = Some(uptime);
+        }
+    }
+    match values.get(1) {
+        Some(Value::Int(s)) if *s != IF_UP => Router::PowerOut,
+        _ => Router::Up(rtt),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(i64),
+    Ticks(u64),
+    // noSuchObject / noSuchInstance / прочее
+    Missing,
+}
+
+fn get(
+    ip: &str,
+    cfg: &SnmpConfig,
+    oids: &[Vec<u32>],
+    timeout: Duration,
+) -> Result<Vec<Value>, String> {
+    let sock = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+    sock.connect((ip, cfg.port)).map_err(|e| e.to_string())?;
+    let id = REQUEST_ID.fetch_add(1, Ordering::Relaxed) as i64;
+    let started = Instant::now();
+    sock.send(&request(&cfg.community, id, oids))
+        .map_err(|e| e.to_string())?;
+    sock.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let mut buf = [0u8; 1500];
+    loop {
+        let n = reactor::read_with(sock.as_raw_fd(), started + timeout, || sock.recv(&mut buf))
+            .map_err(|e| e.to_string())?;
+        // Чужой request-id - опоздавший ответ прошлой пробы
+        if let Some(values) = response(&buf[..n], id)? {
+            return Ok(values);
+        }
+    }
+}
+
+// --- BER ---
+fn tlv(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match body.len() {
+        n if n < 0x80 => out.push(n as u8),
+        n if n <= 0xff => out.extend([0x81, n as u8]),
+        n => out.extend([0x82, (n >> 8) as u8, n as u8]),
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+fn int(v: i64) -> Vec<u8> {
+    let bytes = v.to_be_bytes();
+    // Минимум байтов, знак сохраняется старшим битом
+    let skip = (0..7)
+        .take_while(|&i| {
+            (bytes[i] == 0 && bytes[i + 1] & 0x80 == 0)
+                || (bytes[i] == 0xff && bytes[i + 1] & 0x80 != 0)
+        })
+        .count();
+    tlv(0x02, &bytes[skip..])
+}
+
+fn oid(arcs: &[u32]) -> Vec<u8> {
+    let mut body = vec![(arcs[0] * 40 + arcs[1]) as u8];
+    for &a in &arcs[2..] {
+        let mut chunk = vec![(a & 0x7f) as u8];
+        let mut rest = a >> 7;
+        while rest > 0 {
+            chunk.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        body.extend(chunk.iter().rev());
+    }
+    tlv(0x06, &body)
+}
+
+fn request(community: &str, id: i64, oids: &[Vec<u32>]) -> Vec<u8> {
+    let binds: Vec<u8> = oids
+        .iter()
+        .flat_map(|o| tlv(0x30, &[oid(o), vec![0x05, 0x00]].concat()))
+        .collect();
+    // GetRequest-PDU: request-id, error-status, error-index, varbinds
+    let pdu = tlv(0xa0, &[int(id), int(0), int(0), tlv(0x30, &binds)].concat());
+    // version 1 = v2c
+    tlv(
+        0x30,
+        &[int(1), tlv(0x04, community.as_bytes()), pdu].concat(),
+    )
+}
+
+// (тег, тело) и позиция за ними
+fn read(m: &[u8], pos: usize) -> Option<(u8, &[u8], usize)> {
+    let tag = *m.get(pos)?;
+    let first = *m.get(pos + 1)? as usize;
+    let (len, start) = match first {
+        n if n < 0x80 => (n, pos + 2),
+        0x81 => (*m.get(pos + 2)? as usize, pos + 3),
+        0x82 => (
+            (*m.get(pos + 2)? as usize) << 8 | *m.get(pos + 3)? as usize,
+            pos + 4,
+        ),
+        _ => return None,
+    };
+    Some((tag, m.get(start..start + len)?, start + len))
+}
+
+fn uint(body: &[u8]) -> i64 {
+    let neg = body.first().is_some_and(|b| b & 0x80 != 0);
+    body.iter()
+        .fold(if neg { -1 } else { 0 }, |v, &b| (v << 8) | b as i64)
+}
+
+// Ok(None) - не наш request-id; Err - роутер ответил ошибкой
+fn response(m: &[u8], id: i64) -> Result<Option<Vec<Value>>, String> {
+    let malformed = || "malformed response".to_string();
+    let (0x30, msg, _) = read(m, 0).ok_or_else(malformed)? else {
+        return Err(malformed());
+    };
+    let (_, _version, p) = read(msg, 0).ok_or_else(malformed)?;
+    let (_, _community, p) = read(msg, p).ok_or_else(malformed)?;
+    let (0xa2, pdu, _) = read(msg, p).ok_or_else(malformed)? else {
+        return Err(malformed());
+    };
+    let (_, rid, p) = read(pdu, 0).ok_or_else(malformed)?;
+    if uint(rid) != id {
+        return Ok(None);
+    }
+    let (_, status, p) = read(pdu, p).ok_or_else(malformed)?;
+    let (_, _index, p) = read(pdu, p).ok_or_else(malformed)?;
+    match uint(status) {
+        0 => {}
+        // authorizationError и т.п. - неверный community
+        s => return Err(format!("error-status {}", s)),
+    }
+    let (_, binds, _) = read(pdu, p).ok_or_else(malformed)?;
+    let mut values = Vec::new();
+    let mut p = 0;
+    while p < binds.len() {
+        let (_, bind, next) = read(binds, p).ok_or_else(malformed)?;
+        let (_, _oid, vp) = read(bind, 0).ok_or_else(malformed)?;
+        let (tag, body, _) = read(bind, vp).ok_or_else(malformed)?;
+        values.push(match tag {
+            0x02 => Value::Int(uint(body)),
+            0x43 => Value::Ticks(uint(body) as u64 & 0xffff_ffff),
+            _ => Value::Missing,
+        });
+        p = next;
+    }
+    Ok(Some(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_requests_and_parses_responses() {
+        assert_eq!(int(0), [0x02, 0x01, 0x00]);
+        assert_eq!(int(128), [0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(int(-1), [0x02, 0x01, 0xff]);
+        assert_eq!(uint(&[0x00, 0x80]), 128);
+        assert_eq!(
+            oid(&[1, 3, 6, 1, 2, 1, 2, 2, 1, 8, 200]),
+            [0x06, 0x0b, 0x2b, 6, 1, 2, 1, 2, 2, 1, 8, 0x81, 0x48]
+        );
+        // Ответ роутера: sysUpTime 4 ч 12 мин, порт 3 down(2)
+        let bind = |o: &[u32], v: Vec<u8>| tlv(0x30, &[oid(o), v].concat());
+        let binds = [
+            bind(&SYS_UPTIME, tlv(0x43, &[0x17, 0x12, 0x40])),
+            bind(&[1, 3, 6, 1, 2, 1, 2, 2, 1, 8, 3], int(2)),
+            bind(&[1, 3, 6, 1, 2, 1, 2, 2, 1, 8, 9], vec![0x81, 0x00]),
+        ]
+        .concat();
+        let pdu = tlv(0xa2, &[int(42), int(0), int(0), tlv(0x30, &binds)].concat());
+        let msg = tlv(0x30, &[int(1), tlv(0x04, b"public"), pdu].concat());
+        assert_eq!(
+            response(&msg, 42),
+            Ok(Some(vec![
+                Value::Ticks(1_512_000),
+                Value::Int(2),
+                Value::Missing
+            ]))
+        );
+        assert_eq!(response(&msg, 43), Ok(None));
+        assert!(response(&msg[..10], 42).is_err());
+        // Запрос разбирается тем же кодом (PDU другого типа - не ответ)
+        let req = request("public", 42, &[SYS_UPTIME.to_vec()]);
+        assert!(response(&req, 42).is_err());
+    }
+}
@@ -0,0 +1,423 @@
+// === СОСТОЯНИЕ ДЕМОНА ДЛЯ ЧТЕНИЯ СНАРУЖИ ===
+// Демон публикует снимок состояния в /run/portal_daemon/state.json (0644),
+// поэтому команды только для чтения работают от любого пользователя.
+// Сама директория принадлежит root:portal-admins (0775): менять состояние
+// (файл паузы) могут только root и члены группы.
+// read_only_root: для образов с корнем только для чтения все, что обычно
+// копится в /var/lib (счетчики, кэш графика), живет в /run до перезагрузки.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::events::{Event, Subscriber};
+use crate::{GROUP_NAME, STATE_FILE, channels, log, paths, unix_now};
+
+const STATE_TMP: &str = "state.json.tmp";
+
+// Счетчики копятся между перезапусками
+const COUNTERS_FILE: &str = "counters.json";
+
+// flock держит один демон на каталог /run; внутри - его PID
+const LOCK_FILE: &str = "daemon.lock";
+
+// sleep_mode "off": машину выключил демон, будильник RTC ее включит. Метка
+// на диске, а не в /run - иначе после загрузки о сне не узнать
+const POWEROFF_FILE: &str = "poweroff.json";
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+// Открытый файл блокировки живет до конца процесса
+static LOCK: OnceLock<fs::File> = OnceLock::new();
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    #[default]
+    Monitoring,
+    Grace,
+    Paused,
+    Sleeping,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DaemonState {
+    pub pid: u32,
+    pub started_at: u64,
+    pub updated_at: u64,
+    pub phase: Phase,
+    pub lighthouse_ip: String,
+    pub last_probe_at: u64,
+    pub last_probe_ok: bool,
+    pub last_ok_at: u64,
+    pub last_rtt_ms: Option<f64>,
+    // Когда уснем, если свет не вернется (только в фазе grace)
+    pub sleep_at: u64,
+    // Последнее решение и его причина ("почему не уснул")
+    pub decision: String,
+    pub decision_at: u64,
+    // "guard.<имя>" - сколько раз защита не дала уснуть,
+    // "event.<имя>" - сколько раз случилось событие
+    pub counters: BTreeMap<String, u64>,
+    // Последняя защита, не давшая уснуть: "process: process 'rsync' is running"
+    pub last_blocked: Option<String>,
+    // Демон стартовал не с тем конфигом, что лежит в /etc
+    pub config_warning: Option<String>,
+    // probe = "auto": что выбрано при старте и почему
+    pub probe_choice: Option<String>,
+    // Итог последней отправки по каждому каналу уведомлений
+    pub channels: BTreeMap<String, channels::Channel>,
+}
+
+// Подписчик шины: переводит события в снимок состояния и счетчики
+pub struct StateWriter {
+    state: DaemonState,
+    // Пишется на каждую пробу - буфер один на всю жизнь демона
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new(lighthouse_ip: &str) -> Self {
+        prepare_run_dir();
+        let now = unix_now();
+        let mut w = Self {
+            buf: Vec::new(),
+            state: DaemonState {
+                pid: std::process::id(),
+                started_at: now,
+                updated_at: now,
+                lighthouse_ip: lighthouse_ip.to_string(),
+                decision: "started".into(),
+                decision_at: now,
+                counters: load_counters(),
+                ..Default::default()
+            },
+        };
+        w.publish();
+        w
+    }
+
+    // Продолжение после обновления на месте: время старта и конец grace от старого процесса
+    pub fn resume(&mut self, started_at: u64, grace_until: u64) {
+        if started_at > 0 {
+            self.state.started_at = started_at;
+        }
+        if grace_until > unix_now() {
+            self.state.phase = Phase::Grace;
+            self.state.sleep_at = grace_until;
+        }
+        self.publish();
+    }
+
+    fn phase(&mut self, phase: Phase) {
+        if self.state.phase != phase {
+            self.state.phase = phase;
+            if phase != Phase::Grace {
+                self.state.sleep_at = 0;
+            }
+            self.publish();
+        }
+    }
+
+    fn probe(&mut self, ok: bool, rtt_ms: Option<f64>) {
+        let now = unix_now();
+        self.state.last_probe_at = now;
+        self.state.last_probe_ok = ok;
+        if ok {
+            self.state.last_ok_at = now;
+            self.state.last_rtt_ms = rtt_ms;
+        }
+        self.publish();
+    }
+
+    fn grace(&mut self, secs: u64) {
+        self.state.phase = Phase::Grace;
+        self.state.sleep_at = unix_now() + secs;
+        self.publish();
+    }
+
+    fn decide(&mut self, decision: impl Into<String>) {
+        self.state.decision = decision.into();
+        self.state.decision_at = unix_now();
+        self.publish();
+    }
+
+    pub fn probe_choice(&mut self, choice: String) {
+        self.state.probe_choice = Some(choice);
+        self.publish();
+    }
+
+    fn config_warning(&mut self, warning: String) {
+        self.state.decision = format!("started: {}", warning);
+        self.state.config_warning = Some(warning);
+        self.publish();
+    }
+
+    fn count(&mut self, key: &str) {
+        *self.state.counters.entry(key.to_string()).or_default() += 1;
+        if let Ok(json) = serde_json::to_string_pretty(&self.state.counters) {
+            write_persistent(COUNTERS_FILE, json);
+        }
+        self.publish();
+    }
+
+    // Атомарная запись: читатель никогда не увидит полфайла
+    fn publish(&mut self) {
+        self.state.updated_at = unix_now();
+        self.state.channels = channels::snapshot();
+        self.buf.clear();
+        if serde_json::to_writer_pretty(&mut self.buf, &self.state).is_err() {
+            return;
+        }
+        let tmp = paths::run(STATE_TMP);
+        if fs::write(&tmp, &self.buf).is_ok() {
+            fs::set_permissions(&tmp, fs::Permissions::from_mode(0o644)).ok();
+            fs::rename(&tmp, paths::run(STATE_FILE)).ok();
+        }
+    }
+}
+
+impl Subscriber for StateWriter {
+    fn on_event(&mut self, e: &Event) {
+        match e {
+            Event::Probe(r) => self.probe(r.ok, r.rtt_ms),
+            Event::StateChanged { phase, reason } => {
+                self.phase(*phase);
+                self.decide(reason.clone());
+            }
+            Event::ConnectionLost { grace_sec } => {
+                self.grace(*grace_sec);
+                self.count("event.connection_lost");
+                self.decide(format!(
+                    "grace: lighthouse unreachable, waiting {} sec",
+                    grace_sec
+                ));
+            }
+            Event::GraceReminder { .. } => {}
+            Event::ConnectionRestored => self.count("event.connection_restored"),
+            Event::SleepBlocked { guard, reason } => {
+                self.state.last_blocked = Some(format!("{}: {}", guard, reason));
+                self.count(&format!("guard.{}", guard));
+            }
+            Event::SleepRequested { seconds, mode } => {
+                self.phase(Phase::Sleeping);
+                self.count("event.sleep");
+                self.decide(format!(
+                    "sleeping {} min ({}): no light",
+                    seconds.div_ceil(60),
+                    mode
+                ));
+            }
+            Event::BatteryLow { .. } => self.count("event.battery_low"),
+            Event::GaveUp { dark_wakes } => {
+                self.phase(Phase::Sleeping);
+                self.count("event.gave_up");
+                self.decide(format!(
+                    "powering off without a wake alarm: no light after {} wakes",
+                    dark_wakes
+                ));
+            }
+            Event::Woke {
+                slept_sec,
+                early_by_sec,
+                cause,
+                rearmed,
+            } => {
+                self.count("event.wake");
+                if *early_by_sec == 0 {
+                    self.decide(format!("woke up after {} min", slept_sec / 60));
+                    return;
+                }
+                self.count("event.early_wake");
+                self.decide(format!(
+                    "woke up after {} min, {} min early ({}), {}",
+                    slept_sec / 60,
+                    early_by_sec.div_ceil(60),
+                    cause.as_deref().unwrap_or("unknown cause"),
+                    if *rearmed {
+                        "re-arming sleep"
+                    } else {
+                        "staying up"
+                    }
+                ));
+            }
+            Event::RtcDrift { .. } => self.count("event.rtc_drift"),
+            Event::ProbeStale { .. } => self.count("event.probe_stale"),
+            Event::ConfigInvalid { message, restored } => {
+                self.count("event.invalid_config");
+                if *restored {
+                    self.count("event.config_restored");
+                }
+                self.config_warning(message.clone());
+            }
+        }
+    }
+}
+
+fn load_counters() -> BTreeMap<String, u64> {
+    fs::read_to_string(persistent(COUNTERS_FILE))
+        .ok()
+        .and_then(|d| serde_json::from_str(&d).ok())
+        .unwrap_or_default()
+}
+
+pub fn read_state() -> Option<DaemonState> {
+    let data = fs::read_to_string(paths::run(STATE_FILE)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+// Демон жив, если процесс из снимка существует. kill(pid, 0), а не /proc:
+// на FreeBSD procfs обычно не смонтирован. EPERM - процесс есть, но чужой
+pub fn daemon_alive(st: &DaemonState) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(st.pid) else {
+        return false;
+    };
+    pid > 0
+        && (unsafe { libc::kill(pid, 0) } == 0
+            || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+pub fn set_read_only(on: bool) {
+    READ_ONLY.store(on, Ordering::Relaxed);
+}
+
+pub fn read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+// Файл, переживающий перезапуск демона: /var/lib или, без записи на диск, /run
+pub fn persistent(name: &str) -> PathBuf {
+    Path::new(if read_only() {
+        paths::run_dir()
+    } else {
+        paths::state_dir()
+    })
+    .join(name)
+}
+
+pub fn write_persistent(name: &str, body: impl AsRef<[u8]>) {
+    let path = persistent(name);
+    if let Some(dir) = path.parent()
+        && !dir.exists()
+    {
+        fs::create_dir_all(dir).ok();
+    }
+    fs::write(path, body).ok();
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerOff {
+    pub at: u64,
+    // На когда поставлен будильник
+    pub wake_at: u64,
+}
+
+pub fn mark_poweroff(m: PowerOff) {
+    if let Ok(json) = serde_json::to_string(&m) {
+        write_persistent(POWEROFF_FILE, json);
+        // До выключения метка должна быть на диске
+        if let Ok(f) = fs::File::open(persistent(POWEROFF_FILE)) {
+            f.sync_all().ok();
+        }
+    }
+}
+
+pub fn clear_poweroff() {
+    fs::remove_file(persistent(POWEROFF_FILE)).ok();
+}
+
+// Метка читается один раз. Система загрузилась раньше, чем ее поставили, -
+// выключения не было (демон просто перезапустили), метку забываем
+pub fn take_poweroff(boot_time: Option<u64>) -> Option<PowerOff> {
+    let path = persistent(POWEROFF_FILE);
+    let m: Option<PowerOff> = fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok());
+    fs::remove_file(&path).ok();
+    m.filter(|m| boot_time.is_none_or(|b| b >= m.at))
+}
+
+// Проверка при старте в read_only_root: без /run не будет ни состояния, ни паузы
+pub fn check_run_dir() -> Result<(), String> {
+    prepare_run_dir();
+    let probe = Path::new(paths::run_dir()).join(".write_test");
+    fs::write(&probe, b"").map_err(|e| format!("{}: {}", paths::run_dir(), e))?;
+    fs::remove_file(probe).ok();
+    Ok(())
+}
+
+// Второй демон на той же машине пингует дважды и наперегонки зовет rtcwake.
+// Err - блокировку держит другой процесс (его PID, если удалось прочесть).
+// Дескриптор с O_CLOEXEC: при exec обновления блокировка отпускается и тут
+// же берется новым бинарником с тем же PID.
+pub fn lock_instance() -> Result<(), Option<u32>> {
+    prepare_run_dir();
+    let path = paths::run(LOCK_FILE);
+    let mut file = match fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o644)
+        .open(&path)
+    {
+        Ok(f) => f,
+        // /run не записать (read_only_root) - об этом уже сказано, работаем без блокировки
+        Err(e) => {
+            log::warn!(
+                "⚠️  Cannot open {}: {}, not checking for a second instance.",
+                path,
+                e
+            );
+            return Ok(());
+        }
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let mut pid = String::new();
+        file.read_to_string(&mut pid).ok();
+        return Err(pid.trim().parse().ok());
+    }
+    file.set_len(0).ok();
+    file.rewind().ok();
+    write!(file, "{}", std::process::id()).ok();
+    LOCK.set(file).ok();
+    Ok(())
+}
+
+pub fn prepare_run_dir() {
+    let dir = paths::run_dir();
+    if !Path::new(dir).exists() && fs::create_dir_all(dir).is_err() {
+        return;
+    }
+    // Свой каталог пользователя: группа portal-admins тут ни при чем
+    if paths::user() {
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700)).ok();
+        return;
+    }
+    match group_id(GROUP_NAME) {
+        Some(gid) => {
+            std::os::unix::fs::chown(dir, Some(0), Some(gid)).ok();
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o775)).ok();
+        }
+        None => {
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o755)).ok();
+        }
+    }
+}
+
+fn group_id(name: &str) -> Option<u32> {
+    let groups = fs::read_to_string("/etc/group").ok()?;
+    groups.lines().find_map(|l| {
+        let mut p = l.split(':');
+        if p.next()? != name {
+            return None;
+        }
+        p.nth(1)?.parse().ok()
+    })
+}
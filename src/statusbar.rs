@@ -0,0 +1,169 @@
+// === МОДУЛЬ ДЛЯ ПАНЕЛИ (status --waybar) ===
+// Одна JSON-строка на каждое изменение: {"text", "tooltip", "class"} - формат
+// custom-модуля Waybar ("return-type": "json", без "interval"). class - фаза
+// демона (monitoring/grace/paused/sleeping) или stopped: по нему красит CSS.
+// polybar и i3blocks берут .text через jq. Читает /run, как и status, -
+// подходит любому пользователю.
+
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+use crate::{Language, Locales, StatusReport, hm, local_tz, mmss, state, status_report, unix_now};
+
+// Отсчеты в тексте идут по секундам
+const TICK: Duration = Duration::from_secs(1);
+// Без изменений строка все равно повторяется: так замечаем закрытую панель
+const REPEAT_TICKS: u32 = 30;
+
+pub fn run(lang: Language) {
+    let t = Locales::new(lang);
+    let tz = local_tz();
+    let mut out = std::io::stdout();
+    let (mut last, mut unchanged) = (String::new(), 0);
+    loop {
+        let now = unix_now();
+        let line = module(&t, &status_report(), now, |ts| {
+            tz.to_local(ts as i64).to_string()
+        })
+        .to_string();
+        if line != last || unchanged >= REPEAT_TICKS {
+            // Панель закрылась - выходим, а не пишем в пустоту
+            if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+                return;
+            }
+            (last, unchanged) = (line, 0);
+        } else {
+            unchanged += 1;
+        }
+        thread::sleep(TICK);
+    }
+}
+
+fn module(
+    t: &Locales,
+    r: &StatusReport,
+    now: u64,
+    at: impl Fn(u64) -> String,
+) -> serde_json::Value {
+    let Some(st) = &r.daemon else {
+        return serde_json::json!({
+            "text": "⭘",
+            "tooltip": escape(&t.not_running),
+            "class": "stopped",
+        });
+    };
+    let paused = r.pause_until.filter(|&u| u > now);
+    // Пауза важнее фазы: демон мог еще не заметить файл паузы
+    let (text, class) = if let Some(until) = paused {
+        (format!("⏸ {}", mmss(until - now)), "paused")
+    } else {
+        match st.phase {
+            state::Phase::Grace if st.sleep_at > now => {
+                (format!("🌑 {}", mmss(st.sleep_at - now)), "grace")
+            }
+            state::Phase::Grace => ("🌑".to_string(), "grace"),
+            state::Phase::Sleeping => ("💤".to_string(), "sleeping"),
+            state::Phase::Paused => ("⏸".to_string(), "paused"),
+            state::Phase::Monitoring => match st.last_rtt_ms {
+                Some(ms) if st.last_probe_ok => (format!("⚡ {:.0} ms", ms), "monitoring"),
+                _ => ("⚡".to_string(), "monitoring"),
+            },
+        }
+    };
+    let mut tip = vec![format!(
+        "{} (pid {}): {:?}",
+        t.status_running, st.pid, st.phase
+    )];
+    if st.last_ok_at > 0 {
+        tip.push(format!(
+            "{} {} ({} {})",
+            t.why_last_ok,
+            at(st.last_ok_at),
+            hm(now.saturating_sub(st.last_ok_at)),
+            t.status_ago
+        ));
+    }
+    if st.phase == state::Phase::Grace && st.sleep_at > 0 {
+        tip.push(format!("{} {}", t.status_sleep_at, at(st.sleep_at)));
+    }
+    if let Some(until) = paused {
+        tip.push(format!(
+            "{} {} (-> {})",
+            t.status_paused,
+            hm(until - now),
+            at(until)
+        ));
+    }
+    if !st.decision.is_empty() {
+        tip.push(format!("{} {}", t.why_decision, st.decision));
+    }
+    serde_json::json!({
+        "text": text,
+        "tooltip": escape(&tip.join("\n")),
+        "class": class,
+    })
+}
+
+// Подсказку Waybar разбирает как Pango-разметку
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(phase: state::Phase, sleep_at: u64, pause_until: Option<u64>) -> StatusReport {
+        StatusReport {
+            running: true,
+            last_ok_age_sec: None,
+            daemon: Some(state::DaemonState {
+                phase,
+                sleep_at,
+                last_probe_ok: true,
+                last_rtt_ms: Some(2.4),
+                decision: "slept <1 min> & woke".into(),
+                ..Default::default()
+            }),
+            pause_until,
+            pause_remaining_sec: 0,
+            config: None,
+            recent: None,
+        }
+    }
+
+    #[test]
+    fn text_and_class_follow_the_phase() {
+        let t = Locales::new(Language::En);
+        let m = |r: &StatusReport| module(&t, r, 1000, |ts| ts.to_string());
+        let v = m(&report(state::Phase::Monitoring, 0, None));
+        assert_eq!(
+            (v["text"].as_str(), v["class"].as_str()),
+            (Some("⚡ 2 ms"), Some("monitoring"))
+        );
+        assert!(
+            v["tooltip"]
+                .as_str()
+                .unwrap()
+                .contains("slept &lt;1 min&gt; &amp; woke")
+        );
+        let v = m(&report(state::Phase::Grace, 1095, None));
+        assert_eq!(
+            (v["text"].as_str(), v["class"].as_str()),
+            (Some("🌑 01:35"), Some("grace"))
+        );
+        let v = m(&report(state::Phase::Monitoring, 0, Some(1600)));
+        assert_eq!(
+            (v["text"].as_str(), v["class"].as_str()),
+            (Some("⏸ 10:00"), Some("paused"))
+        );
+        let stopped = StatusReport {
+            daemon: None,
+            ..report(state::Phase::Monitoring, 0, None)
+        };
+        assert_eq!(m(&stopped)["class"], "stopped");
+    }
+}
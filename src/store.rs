@@ -0,0 +1,533 @@
+// === ХРАНИЛИЩЕ ИСТОРИИ ===
+// Куда history складывает агрегаты RTT и события - history_backend:
+//   "jsonl"  - построчно в *.jsonl, только дописывание (как было всегда)
+//   "memory" - в памяти, на диск раз в history_sync_sec и перед сном:
+//              для флешки и read-only корня (0 - не писать вовсе).
+//              Несброшенное теряется при kill и не видно `history` из
+//              другого процесса
+//   "sqlite" - history.db; libsqlite3 подгружается при открытии, так что
+//              бинарник от нее не зависит. Нет библиотеки - откат на jsonl
+// Строки везде одни и те же (JSON записи) - бэкенд не знает их полей,
+// кроме ts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use crate::{log, paths, unix_now};
+
+const SQLITE_FILE: &str = "history.db";
+// Потолок для "memory" без сброса на диск: не копить годами
+const MEMORY_MAX_LINES: usize = 50_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Jsonl,
+    Memory,
+    Sqlite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    Latency,
+    Events,
+}
+
+impl Table {
+    fn file_name(self) -> &'static str {
+        match self {
+            Table::Latency => "latency.jsonl",
+            Table::Events => "events.jsonl",
+        }
+    }
+
+    fn file(self) -> String {
+        paths::state(self.file_name())
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Table::Latency => "latency",
+            Table::Events => "events",
+        }
+    }
+}
+
+pub trait Store: Send {
+    fn append(&mut self, t: Table, ts: u64, line: &str);
+    // Строки не старше since в порядке записи (лишние отсеет history по ts)
+    fn read(&mut self, t: Table, since: u64) -> Vec<String>;
+    // Выкинуть записи старше cutoff
+    fn compact(&mut self, t: Table, cutoff: u64);
+    // Дописать отложенное на диск
+    fn sync(&mut self) {}
+}
+
+static STORE: OnceLock<Mutex<Box<dyn Store>>> = OnceLock::new();
+
+// Один раз при старте (демон или `history`); без вызова - jsonl
+pub fn open(backend: Backend, sync_sec: u64) {
+    let store: Box<dyn Store> = match backend {
+        Backend::Jsonl => Box::new(Jsonl::new()),
+        Backend::Memory => Box::new(Memory::new(sync_sec, Jsonl::new())),
+        Backend::Sqlite => match Sqlite::open(&paths::state(SQLITE_FILE)) {
+            Ok(s) => Box::new(s),
+            Err(e) => {
+                log::warn!("⚠️  SQLite history unavailable ({}), using jsonl", e);
+                Box::new(Jsonl::new())
+            }
+        },
+    };
+    STORE.set(Mutex::new(store)).ok();
+}
+
+pub fn with<R>(f: impl FnOnce(&mut dyn Store) -> R) -> R {
+    let m = STORE.get_or_init(|| Mutex::new(Box::new(Jsonl::new())));
+    let mut s = m.lock().unwrap_or_else(|e| e.into_inner());
+    f(s.as_mut())
+}
+
+pub fn sync() {
+    if STORE.get().is_some() {
+        with(|s| s.sync());
+    }
+}
+
+fn ensure_dir(dir: &str) {
+    if !Path::new(dir).exists() {
+        fs::create_dir_all(dir).ok();
+    }
+}
+
+fn line_ts(line: &str) -> Option<u64> {
+    serde_json::from_str::<serde_json::Value>(line)
+        .ok()?
+        .get("ts")?
+        .as_u64()
+}
+
+// --- jsonl ---
+pub struct Jsonl {
+    // Каталог файлов: paths::state_dir()
+    dir: String,
+}
+
+impl Jsonl {
+    fn new() -> Self {
+        Self::in_dir(paths::state_dir())
+    }
+
+    fn in_dir(dir: &str) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn file(&self, t: Table) -> String {
+        format!("{}/{}", self.dir, t.file_name())
+    }
+
+    fn append_all<'a>(&self, t: Table, lines: impl Iterator<Item = &'a str>) {
+        ensure_dir(&self.dir);
+        if let Ok(mut f) = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.file(t))
+        {
+            for l in lines {
+                writeln!(f, "{}", l).ok();
+            }
+        }
+    }
+}
+
+impl Store for Jsonl {
+    fn append(&mut self, t: Table, _ts: u64, line: &str) {
+        self.append_all(t, std::iter::once(line));
+    }
+
+    fn read(&mut self, t: Table, _since: u64) -> Vec<String> {
+        fs::read_to_string(self.file(t))
+            .unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    // Переписывает файл целиком, только если есть что выкинуть
+    fn compact(&mut self, t: Table, cutoff: u64) {
+        let path = self.file(t);
+        let Ok(data) = fs::read_to_string(&path) else {
+            return;
+        };
+        let keep: Vec<&str> = data
+            .lines()
+            .filter(|l| line_ts(l).is_some_and(|ts| ts >= cutoff))
+            .collect();
+        if keep.len() == data.lines().count() {
+            return;
+        }
+        let tmp = format!("{}.tmp", path);
+        let mut body = keep.join("\n");
+        if !body.is_empty() {
+            body.push('\n');
+        }
+        if fs::write(&tmp, body).is_ok() {
+            fs::rename(&tmp, path).ok();
+        }
+    }
+}
+
+// --- memory ---
+pub struct Memory {
+    disk: Jsonl,
+    sync_sec: u64,
+    last_sync: u64,
+    pending: VecDeque<(Table, String)>,
+}
+
+impl Memory {
+    fn new(sync_sec: u64, disk: Jsonl) -> Self {
+        Self {
+            disk,
+            sync_sec,
+            last_sync: unix_now(),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl Store for Memory {
+    fn append(&mut self, t: Table, _ts: u64, line: &str) {
+        self.pending.push_back((t, line.to_string()));
+        if self.pending.len() > MEMORY_MAX_LINES {
+            self.pending.pop_front();
+        }
+        if self.sync_sec > 0 && unix_now() >= self.last_sync + self.sync_sec {
+            self.sync();
+        }
+    }
+
+    fn read(&mut self, t: Table, since: u64) -> Vec<String> {
+        let mut rows = self.disk.read(t, since);
+        rows.extend(
+            self.pending
+                .iter()
+                .filter(|(pt, _)| *pt == t)
+                .map(|(_, l)| l.clone()),
+        );
+        rows
+    }
+
+    fn compact(&mut self, t: Table, cutoff: u64) {
+        if self.sync_sec > 0 {
+            self.disk.compact(t, cutoff);
+        }
+        self.pending
+            .retain(|(pt, l)| *pt != t || line_ts(l).is_some_and(|ts| ts >= cutoff));
+    }
+
+    fn sync(&mut self) {
+        self.last_sync = unix_now();
+        if self.sync_sec == 0 || self.pending.is_empty() {
+            return;
+        }
+        for t in [Table::Latency, Table::Events] {
+            let lines = self.pending.iter().filter(|(pt, _)| *pt == t);
+            self.disk.append_all(t, lines.map(|(_, l)| l.as_str()));
+        }
+        self.pending.clear();
+    }
+}
+
+// --- sqlite ---
+// Только то, что нужно из C API; указатели берутся через dlsym
+type Db = *mut c_void;
+type Stmt = *mut c_void;
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_OPEN_READWRITE: c_int = 0x02;
+const SQLITE_OPEN_CREATE: c_int = 0x04;
+// SQLITE_TRANSIENT: строку библиотека копирует себе
+const SQLITE_TRANSIENT: isize = -1;
+
+// Таблица функций: поле, символ, сигнатура - и структура, и dlsym из одного места
+macro_rules! api {
+    ($($field:ident = $sym:literal: fn($($a:ty),*) -> $r:ty;)+) => {
+        struct Api {
+            $($field: unsafe extern "C" fn($($a),*) -> $r,)+
+        }
+
+        impl Api {
+            fn resolve(lib: *mut c_void) -> Result<Api, String> {
+                Ok(Api {
+                    $($field: {
+                        let name = concat!($sym, "\0").as_ptr() as *const c_char;
+                        let p = unsafe { libc::dlsym(lib, name) };
+                        if p.is_null() {
+                            return Err(format!("{} missing in libsqlite3", $sym));
+                        }
+                        unsafe {
+                            std::mem::transmute::<*mut c_void, unsafe extern "C" fn($($a),*) -> $r>(p)
+                        }
+                    },)+
+                })
+            }
+        }
+    };
+}
+
+api! {
+    open_v2 = "sqlite3_open_v2": fn(*const c_char, *mut Db, c_int, *const c_char) -> c_int;
+    exec = "sqlite3_exec": fn(Db, *const c_char, *const c_void, *mut c_void, *mut c_void) -> c_int;
+    prepare_v2 = "sqlite3_prepare_v2": fn(Db, *const c_char, c_int, *mut Stmt, *mut *const c_char) -> c_int;
+    bind_int64 = "sqlite3_bind_int64": fn(Stmt, c_int, i64) -> c_int;
+    bind_text = "sqlite3_bind_text": fn(Stmt, c_int, *const c_char, c_int, isize) -> c_int;
+    step = "sqlite3_step": fn(Stmt) -> c_int;
+    column_text = "sqlite3_column_text": fn(Stmt, c_int) -> *const c_char;
+    finalize = "sqlite3_finalize": fn(Stmt) -> c_int;
+    errmsg = "sqlite3_errmsg": fn(Db) -> *const c_char;
+    close_v2 = "sqlite3_close_v2": fn(Db) -> c_int;
+}
+
+impl Api {
+    fn load() -> Result<Api, String> {
+        let lib = ["libsqlite3.so.0", "libsqlite3.so"]
+            .iter()
+            .map(|n| {
+                let n = CString::new(*n).unwrap_or_default();
+                unsafe { libc::dlopen(n.as_ptr(), libc::RTLD_NOW) }
+            })
+            .find(|h| !h.is_null())
+            .ok_or("libsqlite3 not found")?;
+        Api::resolve(lib)
+    }
+}
+
+pub struct Sqlite {
+    api: Api,
+    db: Db,
+}
+
+// Соединение используется только под мьютексом STORE
+unsafe impl Send for Sqlite {}
+
+impl Sqlite {
+    fn open(path: &str) -> Result<Sqlite, String> {
+        let api = Api::load()?;
+        ensure_dir(paths::state_dir());
+        let c = CString::new(path).map_err(|e| e.to_string())?;
+        let mut db: Db = std::ptr::null_mut();
+        let flags = SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE;
+        let rc = unsafe { (api.open_v2)(c.as_ptr(), &mut db, flags, std::ptr::null()) };
+        // Handle выдается и при ошибке: его закроет drop
+        let s = Sqlite { api, db };
+        if rc != SQLITE_OK {
+            return Err(format!("cannot open {}: {}", path, s.error()));
+        }
+        s.exec("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL")?;
+        for t in [Table::Latency, Table::Events] {
+            let exists = format!("SELECT 1 FROM sqlite_master WHERE name='{}'", t.name());
+            let fresh = s.query(&exists, None, None)?.is_empty();
+            let create = format!(
+                "CREATE TABLE IF NOT EXISTS {0} (ts INTEGER NOT NULL, json TEXT NOT NULL);
+                 CREATE INDEX IF NOT EXISTS {0}_ts ON {0}(ts)",
+                t.name()
+            );
+            // Переход с jsonl: старая история переезжает в базу, файл остается.
+            // Таблица и переезд - одной транзакцией: сорвался импорт - таблицы
+            // нет, и следующее открытие попробует снова
+            if fresh {
+                s.transaction(|| s.exec(&create).and_then(|_| s.import(t)))?;
+            } else {
+                s.exec(&create)?;
+            }
+        }
+        Ok(s)
+    }
+
+    // Ошибка внутри - ROLLBACK, транзакция не остается открытой
+    fn transaction(&self, f: impl FnOnce() -> Result<(), String>) -> Result<(), String> {
+        self.exec("BEGIN")?;
+        match f().and_then(|_| self.exec("COMMIT")) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                self.exec("ROLLBACK").ok();
+                Err(e)
+            }
+        }
+    }
+
+    fn error(&self) -> String {
+        let msg = unsafe { (self.api.errmsg)(self.db) };
+        if msg.is_null() {
+            return "unknown error".into();
+        }
+        unsafe { CStr::from_ptr(msg) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn exec(&self, sql: &str) -> Result<(), String> {
+        let c = CString::new(sql).map_err(|e| e.to_string())?;
+        let null = std::ptr::null_mut();
+        match unsafe { (self.api.exec)(self.db, c.as_ptr(), null, null, null) } {
+            SQLITE_OK => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    // Параметры: ?1 - ts, ?2 - текст; результат - первый столбец строк
+    fn query(&self, sql: &str, ts: Option<u64>, text: Option<&str>) -> Result<Vec<String>, String> {
+        let c = CString::new(sql).map_err(|e| e.to_string())?;
+        let mut stmt: Stmt = std::ptr::null_mut();
+        let api = &self.api;
+        if unsafe { (api.prepare_v2)(self.db, c.as_ptr(), -1, &mut stmt, std::ptr::null_mut()) }
+            != SQLITE_OK
+        {
+            return Err(self.error());
+        }
+        if let Some(ts) = ts {
+            unsafe { (api.bind_int64)(stmt, 1, ts as i64) };
+        }
+        if let Some(text) = text {
+            let p = text.as_ptr() as *const c_char;
+            unsafe { (api.bind_text)(stmt, 2, p, text.len() as c_int, SQLITE_TRANSIENT) };
+        }
+        let mut rows = Vec::new();
+        loop {
+            match unsafe { (api.step)(stmt) } {
+                SQLITE_ROW => {
+                    let p = unsafe { (api.column_text)(stmt, 0) };
+                    if !p.is_null() {
+                        rows.push(unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned());
+                    }
+                }
+                done => {
+                    unsafe { (api.finalize)(stmt) };
+                    return if done == SQLITE_DONE {
+                        Ok(rows)
+                    } else {
+                        Err(self.error())
+                    };
+                }
+            }
+        }
+    }
+
+    fn insert(&self, t: Table, ts: u64, line: &str) -> Result<(), String> {
+        let sql = format!("INSERT INTO {} (ts, json) VALUES (?1, ?2)", t.name());
+        self.query(&sql, Some(ts), Some(line)).map(|_| ())
+    }
+
+    fn import(&self, t: Table) -> Result<(), String> {
+        let Ok(data) = fs::read_to_string(t.file()) else {
+            return Ok(());
+        };
+        let mut n = 0;
+        for l in data.lines() {
+            if let Some(ts) = line_ts(l) {
+                self.insert(t, ts, l)?;
+                n += 1;
+            }
+        }
+        log::info!(table = t.name(), rows = n; "📦 History imported into SQLite");
+        Ok(())
+    }
+}
+
+impl Drop for Sqlite {
+    fn drop(&mut self) {
+        unsafe { (self.api.close_v2)(self.db) };
+    }
+}
+
+impl Store for Sqlite {
+    fn append(&mut self, t: Table, ts: u64, line: &str) {
+        if let Err(e) = self.insert(t, ts, line) {
+            log::warn!(table = t.name(); "⚠️  SQLite write failed: {}", e);
+        }
+    }
+
+    fn read(&mut self, t: Table, since: u64) -> Vec<String> {
+        let sql = format!(
+            "SELECT json FROM {} WHERE ts >= ?1 ORDER BY rowid",
+            t.name()
+        );
+        self.query(&sql, Some(since), None).unwrap_or_default()
+    }
+
+    fn compact(&mut self, t: Table, cutoff: u64) {
+        let sql = format!("DELETE FROM {} WHERE ts < ?1", t.name());
+        self.query(&sql, Some(cutoff), None).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str) -> String {
+        let d = std::env::temp_dir().join(format!("portal-store-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&d).ok();
+        d.to_string_lossy().into_owned()
+    }
+
+    fn row(ts: u64) -> String {
+        format!(r#"{{"ts":{},"rtt_ms":1.5}}"#, ts)
+    }
+
+    #[test]
+    fn jsonl_compaction_keeps_fresh_rows() {
+        let d = dir("jsonl");
+        let mut j = Jsonl::in_dir(&d);
+        for ts in [100, 200, 300] {
+            j.append(Table::Latency, ts, &row(ts));
+        }
+        j.append(Table::Events, 50, &row(50));
+        j.append_all(Table::Latency, std::iter::once("not json"));
+        j.compact(Table::Latency, 200);
+        assert_eq!(j.read(Table::Latency, 0), [row(200), row(300)]);
+        // Другая таблица - свой файл
+        assert_eq!(j.read(Table::Events, 0), [row(50)]);
+        assert!(!Path::new(&format!("{}/latency.jsonl.tmp", d)).exists());
+        fs::remove_dir_all(&d).ok();
+    }
+
+    #[test]
+    fn memory_syncs_to_disk_and_caps_pending() {
+        let d = dir("memory");
+        let mut m = Memory::new(3600, Jsonl::in_dir(&d));
+        m.append(Table::Latency, 100, &row(100));
+        m.append(Table::Events, 100, &row(100));
+        // До сброса в файле пусто, но read видит несброшенное
+        assert!(Jsonl::in_dir(&d).read(Table::Latency, 0).is_empty());
+        assert_eq!(m.read(Table::Latency, 0), [row(100)]);
+        m.sync();
+        assert!(m.pending.is_empty());
+        assert_eq!(Jsonl::in_dir(&d).read(Table::Latency, 0), [row(100)]);
+        assert_eq!(Jsonl::in_dir(&d).read(Table::Events, 0), [row(100)]);
+        m.append(Table::Latency, 200, &row(200));
+        assert_eq!(m.read(Table::Latency, 0), [row(100), row(200)]);
+        // Компакция и на диске, и в памяти
+        m.compact(Table::Latency, 150);
+        assert_eq!(m.read(Table::Latency, 0), [row(200)]);
+
+        // sync_sec 0: на диск не пишем вовсе, старое вытесняется
+        let mut m = Memory::new(0, Jsonl::in_dir(&format!("{}/off", d)));
+        for ts in 0..MEMORY_MAX_LINES as u64 + 2 {
+            m.append(Table::Latency, ts, &row(ts));
+        }
+        m.sync();
+        assert_eq!(m.pending.len(), MEMORY_MAX_LINES);
+        assert_eq!(m.pending.front().map(|(_, l)| l.clone()), Some(row(2)));
+        assert!(!Path::new(&format!("{}/off", d)).exists());
+        fs::remove_dir_all(&d).ok();
+    }
+}
@@ -0,0 +1,110 @@
+// === СИСТЕМА ===
+// Все, чем цикл демона трогает машину: проба маяка, сон (с паузой и
+// защитами), часы и сеть. Цикл (Daemon в main.rs) generic над этими
+// трейтами; в тестах вместо Real - подставные, без пинга, rtcwake и
+// настоящих минут ожидания.
+
+use std::time::{Duration, Instant};
+
+use crate::{PortalConfig, guards, mdns, net, probe, tui};
+
+// Сколько ждать ответов mDNS, пока маяк молчит
+const MDNS_WAIT: Duration = Duration::from_secs(2);
+
+pub trait Prober {
+    fn probe(&mut self, cfg: &PortalConfig) -> probe::ProbeResult;
+}
+
+pub trait PowerManager {
+    // Сон выключен пользователем (pause)
+    fn paused(&mut self) -> bool;
+    // Причина отложить сон или None
+    fn blocker(&mut self, cfg: &PortalConfig) -> Option<guards::Blocker>;
+    // true - машина спала и проснулась
+    fn sleep(&mut self, cfg: &PortalConfig, seconds: u64, mode: &str) -> bool;
+    // Выключение без будильника; true - только в dry run, иначе не вернемся
+    fn shut_down(&mut self) -> bool;
+}
+
+pub trait Clock {
+    // UNIX-время, сек
+    fn now(&self) -> u64;
+    // Для интервалов: не прыгает вместе с NTP
+    fn instant(&self) -> Instant;
+    fn sleep(&mut self, d: Duration);
+    // Ожидание цикла; в терминале - с отсчетом и горячими клавишами
+    fn wait(&mut self, d: Duration, label: &str, hint: &str) -> Option<tui::Key>;
+}
+
+pub trait NetworkScanner {
+    fn ssid(&mut self, backend: net::Backend) -> Option<String>;
+    // Шлюз активного соединения с этим именем
+    fn gateway(&mut self, backend: net::Backend, name: &str) -> Option<String>;
+    // Адрес маяка с этим именем по mDNS
+    fn lighthouse(&mut self, name: &str) -> Option<String>;
+}
+
+// Настоящая машина
+pub struct Real;
+
+impl Prober for Real {
+    fn probe(&mut self, cfg: &PortalConfig) -> probe::ProbeResult {
+        probe::run(cfg)
+    }
+}
+
+impl PowerManager for Real {
+    fn paused(&mut self) -> bool {
+        crate::check_pause()
+    }
+
+    fn blocker(&mut self, cfg: &PortalConfig) -> Option<guards::Blocker> {
+        guards::sleep_blocker(cfg)
+    }
+
+    fn sleep(&mut self, cfg: &PortalConfig, seconds: u64, mode: &str) -> bool {
+        crate::enter_hibernation(cfg, seconds, mode)
+    }
+
+    fn shut_down(&mut self) -> bool {
+        crate::shut_down()
+    }
+}
+
+impl Clock for Real {
+    fn now(&self) -> u64 {
+        crate::unix_now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&mut self, d: Duration) {
+        tui::nap(d);
+    }
+
+    fn wait(&mut self, d: Duration, label: &str, hint: &str) -> Option<tui::Key> {
+        tui::wait(d, label, hint)
+    }
+}
+
+impl NetworkScanner for Real {
+    fn ssid(&mut self, backend: net::Backend) -> Option<String> {
+        net::current_ssid(backend)
+    }
+
+    fn gateway(&mut self, backend: net::Backend, name: &str) -> Option<String> {
+        net::connections(backend)
+            .into_iter()
+            .find(|c| c.name == name)
+            .map(|c| c.gateway)
+    }
+
+    fn lighthouse(&mut self, name: &str) -> Option<String> {
+        mdns::discover(MDNS_WAIT)
+            .into_iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+            .map(|f| f.ip)
+    }
+}
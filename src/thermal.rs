@@ -0,0 +1,64 @@
+// === ТЕМПЕРАТУРА И РЕЖИМ СНА ===
+// Одноплатник на балконе, в машине, в уличном шкафу: в мороз и в жару
+// suspend в RAM (и даже disk) просыпается через раз - память, eMMC и
+// батарея ведут себя не по паспорту. Вне suspend_temp_min_c..max_c
+// вместо сна выключаемся (rtcwake -m off) - включит RTC или плата питания.
+// Источник - temp_sensor (hwmon temp*_input, 1-wire w1_slave или файл с
+// градусами), иначе все /sys/class/thermal/thermal_zone*.
+
+use std::fs;
+
+const THERMAL_DIR: &str = "/sys/class/thermal";
+// Зоны без датчика отдают 0, -273 или мусор
+const PLAUSIBLE_C: std::ops::RangeInclusive<f64> = -60.0..=150.0;
+
+// Все показания, °C
+pub fn readings(sensor: Option<&str>) -> Vec<f64> {
+    if let Some(path) = sensor {
+        return fs::read_to_string(path)
+            .ok()
+            .and_then(|s| parse(&s))
+            .into_iter()
+            .collect();
+    }
+    let Ok(entries) = fs::read_dir(THERMAL_DIR) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("thermal_zone"))
+        .filter_map(|e| fs::read_to_string(e.path().join("temp")).ok())
+        .filter_map(|s| parse(&s))
+        .filter(|c| *c != 0.0)
+        .collect()
+}
+
+// "23125" (милли-°C, как в sysfs), "23.1" или "23" (°C) или w1_slave:
+// "... t=23125" в конце
+fn parse(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let v: f64 = match s.rsplit_once("t=") {
+        Some((_, t)) => t.trim().parse::<f64>().ok()? / 1000.0,
+        None => {
+            let v: f64 = s.parse().ok()?;
+            if s.contains('.') || v.abs() < 200.0 {
+                v
+            } else {
+                v / 1000.0
+            }
+        }
+    };
+    PLAUSIBLE_C.contains(&v).then_some(v)
+}
+
+// Показание вне min..max (самое холодное или самое горячее);
+// датчиков нет - None, сон как обычно
+pub fn out_of_range(sensor: Option<&str>, min: Option<f64>, max: Option<f64>) -> Option<f64> {
+    let t = readings(sensor);
+    let cold = t.iter().copied().reduce(f64::min)?;
+    let hot = t.iter().copied().reduce(f64::max)?;
+    if min.is_some_and(|m| cold < m) {
+        return Some(cold);
+    }
+    max.is_some_and(|m| hot > m).then_some(hot)
+}
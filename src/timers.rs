@@ -0,0 +1,130 @@
+// === ОБЪЕДИНЕНИЕ ПРОБУЖДЕНИЙ ===
+// Каждое пробуждение вытаскивает CPU из глубокого C-state, а демон, который
+// экономит энергию, сам не должен их плодить. Периодические таймеры (цикл
+// проб, heartbeat, опрос ntfy) ставим на общую сетку от начала эпохи: при
+// кратных периодах они срабатывают в одно мгновение, одним пробуждением.
+// timer_slack_ms (PR_SET_TIMERSLACK) разрешает ядру сдвинуть срабатывание
+// на столько и слить его с чужими таймерами; ответы сокетов не задерживает.
+// Обратная сторона сетки: десятки демонов в одной сети пингуют роутер в
+// одну и ту же секунду - для них цикл проб сдвигается случайным jitter.
+// Adaptive: после сбоя или потерь проверяем чаще (до пола), после долгой
+// стабильности - реже (до потолка), меньше пробуждений на батарее.
+
+use std::time::{Duration, SystemTime};
+
+use crate::log;
+
+// Наследуется потоками, созданными после вызова - зовем до их запуска
+pub fn set_slack(ms: u64) {
+    if ms == 0 {
+        return;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let ns = ms.saturating_mul(1_000_000) as libc::c_ulong;
+        if unsafe { libc::prctl(libc::PR_SET_TIMERSLACK, ns, 0, 0, 0) } != 0 {
+            log::warn!(
+                "⚠️  Cannot set timer slack: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    // У FreeBSD ручки на процесс нет, только sysctl
+    // kern.timecounter.alloweddeviation на всю систему
+    #[cfg(not(target_os = "linux"))]
+    log::debug!("timer_slack_ms = {} is Linux-only, ignored", ms);
+}
+
+fn now_ns() -> u128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+// Номер текущей ячейки сетки с шагом period
+pub fn slot(period: Duration) -> u128 {
+    now_ns() / period.as_nanos().max(1)
+}
+
+// Сколько ждать до следующей отметки сетки с шагом period
+pub fn until_tick(period: Duration) -> Duration {
+    let period_ns = period.as_nanos().max(1);
+    Duration::from_nanos((period_ns - now_ns() % period_ns) as u64)
+}
+
+// Случайная задержка в min..=max (getrandom; без него - по часам)
+pub fn jitter(min: Duration, max: Duration) -> Duration {
+    if max <= min {
+        return min;
+    }
+    let mut r = [0u8; 8];
+    let n = unsafe { libc::getrandom(r.as_mut_ptr() as *mut libc::c_void, r.len(), 0) };
+    let r = if n == r.len() as isize {
+        u64::from_ne_bytes(r)
+    } else {
+        now_ns() as u64
+    };
+    let span = (max - min).as_millis() as u64 + 1;
+    min + Duration::from_millis(r % span)
+}
+
+// Ожидание цикла на сетке: до ближайшей отметки, но не меньше полупериода,
+// чтобы после выхода из grace не проверять второй раз через миг
+pub fn next_cycle(period: Duration) -> Duration {
+    let d = until_tick(period);
+    if d < period / 2 { d + period } else { d }
+}
+
+// --- АДАПТИВНЫЙ ИНТЕРВАЛ ---
+// Удачных циклов подряд, после которых интервал удваивается
+const STABLE_CYCLES: u32 = 10;
+
+#[derive(Default)]
+pub struct Adaptive {
+    // None - базовый интервал; 0 - пол (после сбоя)
+    cur: Option<u64>,
+    stable: u32,
+}
+
+impl Adaptive {
+    // Свет пропадал (grace): следующий раз проверить как можно скорее
+    pub fn shorten(&mut self) {
+        self.cur = Some(0);
+        self.stable = 0;
+    }
+
+    // Интервал до следующей пробы; min и max по 0 - всегда base
+    pub fn next(&mut self, base: u64, min: u64, max: u64, healthy: bool) -> u64 {
+        if min == 0 && max == 0 {
+            return base;
+        }
+        let (min, max) = (min.clamp(1, base), max.max(base));
+        if !healthy {
+            self.shorten();
+        } else {
+            self.stable += 1;
+            if self.stable >= STABLE_CYCLES {
+                self.stable = 0;
+                self.cur = Some(self.cur.unwrap_or(base).max(min).saturating_mul(2));
+            }
+        }
+        let secs = self.cur.unwrap_or(base).clamp(min, max);
+        self.cur = Some(secs);
+        secs
+    }
+}
+
+// Сколько система проспала в suspend с загрузки: BOOTTIME это время
+// считает, MONOTONIC - нет
+pub fn suspended() -> Duration {
+    let read = |clock| {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        unsafe { libc::clock_gettime(clock, &mut ts) };
+        Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+    };
+    read(libc::CLOCK_BOOTTIME).saturating_sub(read(libc::CLOCK_MONOTONIC))
+}
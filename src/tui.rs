@@ -0,0 +1,229 @@
+// === ЖИВОЙ ОТСЧЕТ В ТЕРМИНАЛЕ ===
+// Только когда демон запущен руками в терминале (не сервисом): вместо
+// простыни println - одна строка с отсчетом и горячие клавиши p/c/q.
+// Терминал переводится в посимвольный ввод без эха; OPOST и ISIG не
+// трогаем, так что обычный вывод и Ctrl-C работают как раньше.
+// SIGUSR1 (и ProbeNow по D-Bus) прерывает любое ожидание так же, как [c],
+// в том числе под systemd: `systemctl kill -s USR1 portal_daemon`.
+
+use std::io::{IsTerminal, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{reactor, watchdog};
+
+pub enum Key {
+    Pause,
+    Check,
+    Quit,
+}
+
+static KEYS: OnceLock<Mutex<Receiver<u8>>> = OnceLock::new();
+static SAVED: OnceLock<libc::termios> = OnceLock::new();
+static CHECK_NOW: AtomicBool = AtomicBool::new(false);
+// Панель (tui) заняла альтернативный экран - при Ctrl-C вернуть обычный
+static FULL_SCREEN: AtomicBool = AtomicBool::new(false);
+const LEAVE_SCREEN: &[u8] = b"\x1b[?25h\x1b[?1049l";
+
+// Проверить свет сейчас, не дожидаясь конца ожидания
+pub fn check_now() {
+    CHECK_NOW.store(true, Ordering::Relaxed);
+    reactor::wake();
+}
+
+pub fn listen_sigusr1() {
+    reactor::init();
+    unsafe { libc::signal(libc::SIGUSR1, on_usr1 as *const () as libc::sighandler_t) };
+}
+
+extern "C" fn on_usr1(_: libc::c_int) {
+    // Атомарная запись и write() - async-signal-safe
+    check_now();
+}
+
+// Включает интерактивный режим, если stdin/stdout - терминал и мы не под systemd
+pub fn enable() -> bool {
+    let interactive = std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal()
+        && std::env::var_os("INVOCATION_ID").is_none();
+    if !interactive || KEYS.get().is_some() {
+        return KEYS.get().is_some();
+    }
+
+    let mut term: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut term) } != 0 {
+        return false;
+    }
+    SAVED.set(term).ok();
+    reenter();
+    unsafe {
+        // Ctrl-C и kill не должны оставить терминал без эха
+        libc::signal(libc::SIGINT, on_signal as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_signal as *const () as libc::sighandler_t);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0u8; 1];
+        while let Ok(1) = std::io::stdin().read(&mut buf) {
+            if tx.send(buf[0]).is_err() {
+                break;
+            }
+            // Ожидание с отсчетом сидит в poll цикла событий
+            reactor::wake();
+        }
+    });
+    KEYS.set(Mutex::new(rx)).ok();
+    true
+}
+
+// Посимвольный ввод без эха; и повторно после restore(), если exec не удался
+pub fn reenter() {
+    let Some(saved) = SAVED.get() else {
+        return;
+    };
+    let mut term = *saved;
+    term.c_lflag &= !(libc::ICANON | libc::ECHO);
+    term.c_cc[libc::VMIN] = 1;
+    term.c_cc[libc::VTIME] = 0;
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term) };
+}
+
+// Идет ли сейчас живой отсчет
+pub fn active() -> bool {
+    KEYS.get().is_some()
+}
+
+// Вернуть терминал как был (перед выходом)
+pub fn restore() {
+    if let Some(term) = SAVED.get() {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, term) };
+        print!("\r\x1b[2K");
+        std::io::stdout().flush().ok();
+    }
+}
+
+extern "C" fn on_signal(sig: libc::c_int) {
+    // Только async-signal-safe вызовы
+    if let Some(term) = SAVED.get() {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, term) };
+    }
+    if FULL_SCREEN.load(Ordering::Relaxed) {
+        let (buf, len) = (LEAVE_SCREEN.as_ptr(), LEAVE_SCREEN.len());
+        unsafe { libc::write(libc::STDOUT_FILENO, buf as *const libc::c_void, len) };
+    }
+    unsafe { libc::_exit(128 + sig) };
+}
+
+// Альтернативный экран без курсора: панель не затирает историю терминала
+pub fn full_screen(on: bool) {
+    FULL_SCREEN.store(on, Ordering::Relaxed);
+    let mut out = std::io::stdout();
+    if on {
+        out.write_all(b"\x1b[?1049h\x1b[?25l").ok();
+    } else {
+        out.write_all(LEAVE_SCREEN).ok();
+    }
+    out.flush().ok();
+}
+
+// Следующая клавиша, не дольше timeout
+pub fn key(timeout: Duration) -> Option<u8> {
+    let keys = KEYS.get()?.lock().ok()?;
+    match keys.recv_timeout(timeout) {
+        Ok(k) => Some(k),
+        Err(RecvTimeoutError::Timeout) => None,
+        // stdin закрыт - новых клавиш не будет, но и крутиться вхолостую незачем
+        Err(RecvTimeoutError::Disconnected) => {
+            thread::sleep(timeout);
+            None
+        }
+    }
+}
+
+// Без терминала - спим целиком (или до heartbeat), SIGUSR1 и check_now()
+// разбудят. true - разбудили
+fn wait_quiet(end: Instant) -> bool {
+    loop {
+        if CHECK_NOW.swap(false, Ordering::Relaxed) {
+            return true;
+        }
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        watchdog::pet();
+        reactor::run_until(Instant::now() + watchdog::until_pet().map_or(left, |p| p.min(left)));
+    }
+}
+
+// Пауза цикла без отсчета (сон отложен защитой, ожидание после подъема):
+// команда с D-Bus, из Telegram или SIGUSR1 обрывает ее, не дожидаясь конца
+pub fn nap(d: Duration) {
+    watchdog::expect_quiet(d);
+    wait_quiet(Instant::now() + d);
+}
+
+// Ожидание с отсчетом; None - время вышло (или терминала нет)
+pub fn wait(d: Duration, label: &str, hint: &str) -> Option<Key> {
+    watchdog::expect_quiet(d);
+    let end = Instant::now() + d;
+    let Some(keys) = KEYS.get().and_then(|m| m.lock().ok()) else {
+        return wait_quiet(end).then_some(Key::Check);
+    };
+    let mut out = std::io::stdout();
+    loop {
+        watchdog::pet();
+        if CHECK_NOW.swap(false, Ordering::Relaxed) {
+            print!("\r\x1b[2K");
+            out.flush().ok();
+            return Some(Key::Check);
+        }
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        let secs = left.as_secs_f64().ceil() as u64;
+        print!(
+            "\r\x1b[2K⏳ {} {:02}:{:02}  {}",
+            label,
+            secs / 60,
+            secs % 60,
+            hint
+        );
+        out.flush().ok();
+        // Перерисовка на границе секунды, чтобы отсчет не "прыгал"
+        let tick = Duration::from_nanos(left.subsec_nanos() as u64);
+        let tick = if tick.is_zero() {
+            Duration::from_secs(1)
+        } else {
+            tick
+        };
+        let key = match keys.try_recv() {
+            Ok(k) => k,
+            Err(TryRecvError::Empty) => {
+                reactor::run_until(Instant::now() + tick.min(left));
+                continue;
+            }
+            Err(TryRecvError::Disconnected) => {
+                watchdog::sleep(end.saturating_duration_since(Instant::now()));
+                break;
+            }
+        };
+        let key = match key.to_ascii_lowercase() {
+            b'p' => Key::Pause,
+            b'c' => Key::Check,
+            b'q' => Key::Quit,
+            _ => continue,
+        };
+        print!("\r\x1b[2K");
+        out.flush().ok();
+        return Some(key);
+    }
+    print!("\r\x1b[2K");
+    out.flush().ok();
+    None
+}
@@ -0,0 +1,108 @@
+// === HEARTBEAT И АППАРАТНЫЙ WATCHDOG ===
+// Каждая итерация цикла (и каждые несколько секунд долгих ожиданий) "гладит"
+// heartbeat-файл и /dev/watchdog. Если демон завис, watchdog перезагрузит
+// машину - на удаленной точке без людей это единственный выход.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::{log, reactor, timers, unix_now};
+
+struct Heartbeat {
+    file: Option<String>,
+    device: Option<File>,
+    interval: Duration,
+    // Номер отметки сетки timers, на которой гладили: раз за отметку
+    last: Option<u128>,
+}
+
+static HEARTBEAT: OnceLock<Mutex<Heartbeat>> = OnceLock::new();
+// Живость для /healthz: когда цикл гладил последний раз и до какого момента
+// обещал молчать (долгое ожидание, сон машины)
+static LAST_PET: AtomicU64 = AtomicU64::new(0);
+static QUIET_UNTIL: AtomicU64 = AtomicU64::new(0);
+
+pub fn init(file: Option<&str>, device: Option<&str>, interval_sec: u64) {
+    let device = device.and_then(|d| match OpenOptions::new().write(true).open(d) {
+        Ok(f) => {
+            log::info!("🐕 Watchdog device {} armed", d);
+            Some(f)
+        }
+        Err(e) => {
+            log::warn!("⚠️  Cannot open watchdog {}: {}", d, e);
+            None
+        }
+    });
+    if let Some(f) = file {
+        log::info!("💓 Heartbeat file {}", f);
+    }
+    let hb = Heartbeat {
+        file: file.map(str::to_string),
+        device,
+        interval: Duration::from_secs(interval_sec.max(1)),
+        last: None,
+    };
+    HEARTBEAT.set(Mutex::new(hb)).ok();
+    LAST_PET.store(unix_now(), Ordering::Relaxed);
+}
+
+// Перед долгим ожиданием: молчать d - это нормально
+pub fn expect_quiet(d: Duration) {
+    QUIET_UNTIL.store(unix_now() + d.as_secs(), Ordering::Relaxed);
+}
+
+// На сколько секунд цикл опаздывает с очередным pet()
+pub fn overdue() -> u64 {
+    let since = LAST_PET
+        .load(Ordering::Relaxed)
+        .max(QUIET_UNTIL.load(Ordering::Relaxed));
+    unix_now().saturating_sub(since)
+}
+
+pub fn pet() {
+    LAST_PET.store(unix_now(), Ordering::Relaxed);
+    let Some(m) = HEARTBEAT.get() else {
+        return;
+    };
+    let Ok(mut hb) = m.lock() else {
+        return;
+    };
+    let slot = timers::slot(hb.interval);
+    if hb.last == Some(slot) {
+        return;
+    }
+    hb.last = Some(slot);
+    if let Some(dev) = hb.device.as_mut() {
+        dev.write_all(b"\0").ok();
+        dev.flush().ok();
+    }
+    if let Some(path) = &hb.file {
+        fs::write(path, unix_now().to_string()).ok();
+    }
+}
+
+// Когда гладить в следующий раз (по сетке, вместе с пробами);
+// None - heartbeat не настроен, спать можно сколько угодно
+pub fn until_pet() -> Option<Duration> {
+    HEARTBEAT
+        .get()
+        .and_then(|m| m.lock().ok().map(|hb| timers::until_tick(hb.interval)))
+}
+
+// sleep, который не дает watchdog'у сработать на долгих ожиданиях; цикл
+// событий тем временем обслуживает сокеты. check_now() его не обрывает
+pub fn sleep(d: Duration) {
+    expect_quiet(d);
+    let end = Instant::now() + d;
+    loop {
+        pet();
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        reactor::run_until(Instant::now() + until_pet().map_or(left, |p| p.min(left)));
+    }
+}
@@ -0,0 +1,151 @@
+// === WAKE-ON-LAN ЗАВИСИМЫХ МАШИН ===
+// После отключения стойка поднимается по порядку: сначала мы, потом - когда
+// маяк ответил и свет точно есть - магические пакеты машинам из wake_on_lan
+// (NAS, медиасервер...) в порядке списка, каждой после ее delay_sec.
+// Пакет: 6 x 0xFF и 16 раз MAC, UDP на broadcast:port.
+// И в обратную сторону: перед сном wol_interfaces переводятся в "wol g",
+// чтобы спящую машину саму можно было разбудить пакетом, а не только RTC.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::UdpSocket;
+use std::thread;
+use std::time::Duration;
+
+use crate::events::{Event, Subscriber};
+use crate::{log, privsep};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WolTarget {
+    // "aa:bb:cc:dd:ee:ff" (или через "-")
+    pub mac: String,
+    pub name: String,
+    pub broadcast: String,
+    pub port: u16,
+    // Пауза перед этой машиной, считая от предыдущей
+    pub delay_sec: u64,
+}
+
+impl Default for WolTarget {
+    fn default() -> Self {
+        Self {
+            mac: String::new(),
+            name: String::new(),
+            broadcast: "255.255.255.255".into(),
+            port: 9,
+            delay_sec: 0,
+        }
+    }
+}
+
+impl WolTarget {
+    pub fn validate(&self) -> Result<(), String> {
+        parse_mac(&self.mac).ok_or_else(|| format!("invalid mac '{}'", self.mac))?;
+        Ok(())
+    }
+
+    fn label(&self) -> &str {
+        if self.name.is_empty() {
+            &self.mac
+        } else {
+            &self.name
+        }
+    }
+}
+
+pub struct WakeOnLan {
+    targets: Vec<WolTarget>,
+    // Уснули - после подъема ждем первую удачную пробу
+    pending: bool,
+}
+
+impl WakeOnLan {
+    pub fn new(targets: &[WolTarget]) -> Self {
+        Self {
+            targets: targets.to_vec(),
+            pending: false,
+        }
+    }
+}
+
+impl Subscriber for WakeOnLan {
+    fn on_event(&mut self, e: &Event) {
+        match e {
+            // Досыпание после раннего подъема - тоже сон, пакет все равно один
+            Event::SleepRequested { .. } => self.pending = true,
+            // Маяк ответил после сна - свет вернулся, будим остальных
+            Event::Probe(r) if r.ok && self.pending => {
+                self.pending = false;
+                let targets = self.targets.clone();
+                // Задержки до минут - цикл демона ждать не должен
+                thread::spawn(move || wake_all(&targets));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn wake_all(targets: &[WolTarget]) {
+    for t in targets {
+        if t.delay_sec > 0 {
+            thread::sleep(Duration::from_secs(t.delay_sec));
+        }
+        match send(t) {
+            Ok(()) => log::info!(mac = t.mac; "🔌 Wake-on-LAN sent to {}", t.label()),
+            Err(e) => log::warn!(mac = t.mac; "⚠️  Wake-on-LAN to {} failed: {}", t.label(), e),
+        }
+    }
+}
+
+// ethtool через sudo/doas, плюс разрешение будить в sysfs (PCI/USB часто
+// выключено, и "wol g" без него ничего не дает)
+pub fn arm_interfaces(priv_cmd: &str, ifaces: &[String]) {
+    for iface in ifaces {
+        let argv = ["ethtool", "-s", iface, "wol", "g"].map(String::from);
+        match privsep::output(Some(priv_cmd), &argv, &[]) {
+            Ok(o) if o.status.success() => log::debug!(iface = iface; "wake-on-lan armed"),
+            Ok(o) => {
+                let err = String::from_utf8_lossy(&o.stderr).trim().to_string();
+                log::warn!(iface = iface; "⚠️  Could not enable Wake-on-LAN: {}", err);
+            }
+            Err(e) => log::warn!(iface = iface; "⚠️  Could not enable Wake-on-LAN: {}", e),
+        }
+        let wakeup = format!("/sys/class/net/{}/device/power/wakeup", iface);
+        if fs::read_to_string(&wakeup).is_ok_and(|s| s.trim() == "disabled") {
+            if privsep::active() {
+                privsep::output(None, &["wakeup".to_string(), iface.clone()], &[]).ok();
+            } else {
+                fs::write(&wakeup, "enabled").ok();
+            }
+        }
+    }
+}
+
+fn send(t: &WolTarget) -> Result<(), String> {
+    let mac = parse_mac(&t.mac).ok_or("invalid mac")?;
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+    let sock = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+    sock.set_broadcast(true).map_err(|e| e.to_string())?;
+    sock.send_to(&packet, (t.broadcast.as_str(), t.port))
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn parse_mac(s: &str) -> Option<[u8; 6]> {
+    let parts: Vec<&str> = s.split([':', '-']).collect();
+    if parts.len() != 6 {
+        return None;
+    }
+    let mut mac = [0u8; 6];
+    for (b, p) in mac.iter_mut().zip(parts) {
+        if p.len() != 2 {
+            return None;
+        }
+        *b = u8::from_str_radix(p, 16).ok()?;
+    }
+    Some(mac)
+}